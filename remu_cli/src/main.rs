@@ -1,6 +1,5 @@
 use anyhow::Result;
 use cfonts::{Colors, Fonts, Options, render};
-use clap::Parser;
 use colored::Colorize;
 use nu_ansi_term::{Color, Style};
 use reedline::{
@@ -10,10 +9,11 @@ use reedline::{
 };
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use remu_boot::boot;
 use remu_debugger::{
-    DebuggerError, DebuggerOption, DebuggerRunner, ExitCode, PlatformConfig, RunOutcome,
+    Debugger, DebuggerError, DebuggerOption, DebuggerRunner, ExitCode, PlatformConfig, RunOutcome,
 };
 use remu_types::{Platform, TracerDyn};
 use std::error::Error;
@@ -88,6 +88,56 @@ fn hello() {
     println!("{}", output.text);
 }
 
+/// Process exit statuses of a `--batch` run that did not end with the guest's own status. A
+/// guest failure never takes one of these, nor 1 (see `ExitCode::process_status`).
+const EXIT_WATCHDOG: i32 = 122;
+const EXIT_DIFFTEST: i32 = 123;
+const EXIT_TIMEOUT: i32 = 124;
+/// Any other error: bad option, bus fault, unimplemented CSR, I/O.
+const EXIT_ERROR: i32 = 125;
+/// 128 + SIGINT, as shells report it.
+const EXIT_INTERRUPTED: i32 = 130;
+
+fn print_exit(exit_code: ExitCode) {
+    match exit_code {
+        ExitCode::Good => println!("{}", "GOOD EXIT".green()),
+        ExitCode::Bad(status) => println!("{}", format!("BAD EXIT (status {status})").red()),
+    }
+}
+
+/// Exit status of a `--batch` run: the guest's (mapped out of the codes below) when the
+/// program exited, else one of the `EXIT_*` codes (1 when the guest output did not match
+/// `--expect-output`).
+fn batch_status<C: PlatformConfig>(
+    debugger: &Debugger<C>,
+    result: Result<RunOutcome, DebuggerError>,
+    timed_out: bool,
+) -> i32 {
+    let e = match result {
+        Ok(RunOutcome::ProgramExit(exit_code)) => {
+            print_exit(exit_code);
+            return exit_code.process_status() as i32;
+        }
+        Ok(RunOutcome::Done) | Err(DebuggerError::ExitRequested) => {
+            println!("{}", "Quiting...".cyan());
+            return 0;
+        }
+        Err(e) => e,
+    };
+    eprintln!("{}", e);
+    if e.is_failure() {
+        debugger.print_iringbuf();
+    }
+    match &e {
+        DebuggerError::Output(_) => 1,
+        e if e.is_difftest_mismatch() => EXIT_DIFFTEST,
+        e if e.is_watchdog_timeout() => EXIT_WATCHDOG,
+        e if e.is_interrupted() && timed_out => EXIT_TIMEOUT,
+        e if e.is_interrupted() => EXIT_INTERRUPTED,
+        _ => EXIT_ERROR,
+    }
+}
+
 struct APPRunner;
 
 impl DebuggerRunner for APPRunner {
@@ -98,20 +148,48 @@ impl DebuggerRunner for APPRunner {
     ) {
         let tracer: TracerDyn = Rc::new(RefCell::new(CLITracer::new(option.isa.clone())));
 
-        let mut debugger = remu_debugger::Debugger::<C>::new(option.clone(), tracer, interrupt);
+        let timed_out = Arc::new(AtomicBool::new(false));
+        if let Some(secs) = option.timeout {
+            let timed_out = Arc::clone(&timed_out);
+            let interrupt = Arc::clone(&interrupt);
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_secs(secs));
+                timed_out.store(true, Ordering::SeqCst);
+                interrupt.store(true, Ordering::SeqCst);
+            });
+        }
 
-        if let Err(e) = debugger.run_startup(&option) {
-            match e {
-                DebuggerError::ExitRequested => {
-                    println!("{}", "Quiting...".cyan());
-                    std::process::exit(0);
-                }
-                _ => {
-                    eprintln!("startup execution error: {}", e);
-                }
+        let mut debugger = Debugger::<C>::new(option.clone(), tracer, interrupt);
+
+        let startup = debugger.run_startup(&option);
+        if option.batch {
+            let timed_out = timed_out.load(Ordering::SeqCst);
+            let status = batch_status(&debugger, startup, timed_out);
+            // `exit` skips destructors: drop the devices first so the terminal is restored.
+            drop(debugger);
+            std::process::exit(status);
+        }
+        if let Err(e) = startup {
+            if matches!(e, DebuggerError::ExitRequested) {
+                println!("{}", "Quiting...".cyan());
+                drop(debugger);
+                std::process::exit(0);
+            }
+            eprintln!("startup execution error: {}", e);
+            if e.is_failure() {
+                debugger.print_iringbuf();
             }
         }
 
+        if let Some(endpoint) = &option.gdb {
+            if let Err(e) = debugger.serve_gdb(endpoint) {
+                eprintln!("gdb server error: {}", e);
+                drop(debugger);
+                std::process::exit(1);
+            }
+            return;
+        }
+
         let mut line_editor = get_editor(option.platform);
         let prompt = get_prompt(option.platform);
 
@@ -136,10 +214,7 @@ impl DebuggerRunner for APPRunner {
                     };
                     if !to_run.trim().is_empty() {
                         match debugger.execute_line(to_run) {
-                            Ok(RunOutcome::ProgramExit(exit_code)) => match exit_code {
-                                ExitCode::Good => println!("{}", "GOOD EXIT".green()),
-                                ExitCode::Bad => println!("{}", "BAD EXIT".red()),
-                            },
+                            Ok(RunOutcome::ProgramExit(exit_code)) => print_exit(exit_code),
                             Ok(RunOutcome::Done) => {}
                             Err(e) => {
                                 if matches!(&e, DebuggerError::ExitRequested) {
@@ -155,6 +230,9 @@ impl DebuggerRunner for APPRunner {
                                 if let Some(bt) = e.backtrace() {
                                     eprintln!("\nStack backtrace:\n{}", bt);
                                 }
+                                if e.is_failure() {
+                                    debugger.print_iringbuf();
+                                }
                             }
                         }
                     }
@@ -172,7 +250,7 @@ impl DebuggerRunner for APPRunner {
 fn main() -> Result<()> {
     let _guard = remu_logger::set_logger("target/logs", "remu.log")?;
 
-    let option = DebuggerOption::parse();
+    let option = DebuggerOption::parse_with_soc();
 
     let interrupt = Arc::new(AtomicBool::new(false));
     let interrupt_clone = Arc::clone(&interrupt);
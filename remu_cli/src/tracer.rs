@@ -1,12 +1,17 @@
+use std::cell::RefCell;
 use std::ops::Range;
+use std::rc::Rc;
 
 use colored::Colorize;
 use remu_fmt::ByteGuesser;
 use remu_isa::isa::{
     IsaSpec,
-    reg::{Fpr, Gpr},
+    reg::{Fpr, Gpr, Mcause},
+};
+use remu_types::{
+    Breakpoint, DynDiagError, IringbufEntry, SourceLine, SourceLookup, StackFrame, Tracer,
+    TrapEvent, WatchTarget,
 };
-use remu_types::{DynDiagError, Tracer};
 use tabled::{
     Table, Tabled,
     settings::{Color, Style, object::Columns},
@@ -14,6 +19,9 @@ use tabled::{
 
 pub struct CLITracer {
     guesser: ByteGuesser,
+    source: Option<Rc<dyn SourceLookup>>,
+    /// Last source line printed by `disasm`, so each line is only shown when it is entered.
+    last_source: RefCell<Option<(String, u32)>>,
 }
 
 fn fmt_range_begin_end(r: &Range<usize>) -> String {
@@ -303,6 +311,13 @@ impl Tracer for CLITracer {
     }
 
     fn disasm(&self, pc: u64, inst: u32) {
+        if let Some(line) = self.source.as_ref().and_then(|s| s.line_at(pc)) {
+            let key = (line.file.clone(), line.line);
+            if self.last_source.borrow().as_ref() != Some(&key) {
+                self.source_line(&line);
+                *self.last_source.borrow_mut() = Some(key);
+            }
+        }
         let result = match self.guesser.disassemble(pc, inst) {
             Ok(disasm) => disasm,
             Err(_) => format!("unknown"),
@@ -315,28 +330,106 @@ impl Tracer for CLITracer {
         );
     }
 
-    fn breakpoint_print(&self, addrs: &[u32]) {
-        if addrs.is_empty() {
+    fn set_source_lookup(&mut self, lookup: Rc<dyn SourceLookup>) {
+        self.source = Some(lookup);
+    }
+
+    fn source_line(&self, line: &SourceLine) {
+        let func = line
+            .function
+            .as_ref()
+            .map(|f| format!("{} at ", f.yellow()))
+            .unwrap_or_default();
+        let text = line.text.as_deref().map(str::trim).unwrap_or_default();
+        println!(
+            "{func}{}  {}",
+            format!("{}:{}", line.file, line.line).green(),
+            text.bright_white()
+        );
+    }
+
+    fn backtrace_print(&self, frames: &[StackFrame]) {
+        for (i, frame) in frames.iter().enumerate() {
+            let symbol = match &frame.symbol {
+                Some((name, offset)) => format!("{}+0x{offset:x}", name.yellow()),
+                None => "??".red().to_string(),
+            };
+            let at = frame
+                .line
+                .as_ref()
+                .map(|l| format!(" at {}", format!("{}:{}", l.file, l.line).green()))
+                .unwrap_or_default();
+            println!(
+                "{} {} in {symbol}{at}",
+                format!("#{i:<2}").bold(),
+                format!("0x{:08x}", frame.pc).blue()
+            );
+        }
+    }
+
+    fn source_list(&self, file: &str, lines: &[(u32, String)], current: Option<u32>) {
+        println!("{}", file.green());
+        for (n, text) in lines {
+            if Some(*n) == current {
+                println!("{} {}  {}", "=>".red().bold(), format!("{n:>5}").yellow(), text.bright_white());
+            } else {
+                println!("   {}  {}", format!("{n:>5}").cyan(), text);
+            }
+        }
+    }
+
+    fn breakpoint_print(&self, breakpoints: &[Breakpoint]) {
+        if breakpoints.is_empty() {
             println!("{}", "no breakpoints".yellow());
             return;
         }
         #[derive(Tabled)]
         struct BreakpointRow {
-            #[tabled(display = "display_index")]
-            index: usize,
-            #[tabled(display = "display_addr")]
-            address: u32,
+            id: u32,
+            address: String,
+            kind: &'static str,
+            enabled: &'static str,
+            hits: u64,
+            ignore: u32,
+            condition: String,
         }
-        fn display_index(i: &usize) -> String {
-            format!("{}", i + 1)
+        let rows: Vec<BreakpointRow> = breakpoints
+            .iter()
+            .map(|bp| BreakpointRow {
+                id: bp.id,
+                address: format!("0x{:08x}", bp.addr),
+                kind: if bp.temporary { "tbreak" } else { "break" },
+                enabled: if bp.enabled { "y" } else { "n" },
+                hits: bp.hits,
+                ignore: bp.ignore,
+                condition: bp.condition.clone().unwrap_or_default(),
+            })
+            .collect();
+        let mut table = Table::new(rows);
+        table.with(Style::rounded());
+        table.modify(Columns::one(0), Color::FG_YELLOW);
+        table.modify(Columns::one(1), Color::FG_CYAN);
+        table.modify(Columns::one(2), Color::FG_GREEN);
+        println!("{table}");
+    }
+
+    fn catch_print(&self, causes: &[Mcause]) {
+        if causes.is_empty() {
+            println!("{}", "no catchpoints".yellow());
+            return;
         }
-        fn display_addr(addr: &u32) -> String {
-            format!("0x{:08x}", addr).to_string()
+        #[derive(Tabled)]
+        struct CatchRow {
+            cause: String,
+            #[tabled(display = "fmt_hex")]
+            mcause: u32,
         }
-        let rows: Vec<BreakpointRow> = addrs
+        let rows: Vec<CatchRow> = causes
             .iter()
-            .enumerate()
-            .map(|(i, &address)| BreakpointRow { index: i, address })
+            .map(|&c| CatchRow {
+                cause: c.to_string(),
+                mcause: c.to_u32(),
+            })
             .collect();
         let mut table = Table::new(rows);
         table.with(Style::rounded());
@@ -345,6 +438,113 @@ impl Tracer for CLITracer {
         println!("{table}");
     }
 
+    fn watch_print(&self, watches: &[(u32, WatchTarget)]) {
+        if watches.is_empty() {
+            println!("{}", "no watchpoints".yellow());
+            return;
+        }
+        #[derive(Tabled)]
+        struct WatchRow {
+            id: u32,
+            kind: &'static str,
+            target: String,
+        }
+        let rows: Vec<WatchRow> = watches
+            .iter()
+            .map(|&(id, target)| WatchRow {
+                id,
+                kind: match target {
+                    WatchTarget::Mem { .. } => "memory",
+                    WatchTarget::Gpr(_) | WatchTarget::Csr(_) => "register",
+                },
+                target: target.to_string(),
+            })
+            .collect();
+        let mut table = Table::new(rows);
+        table.with(Style::rounded());
+        table.modify(Columns::one(0), Color::FG_YELLOW);
+        table.modify(Columns::one(1), Color::FG_GREEN);
+        table.modify(Columns::one(2), Color::FG_CYAN);
+        println!("{table}");
+    }
+
+    fn trap_print(&self, event: &TrapEvent) {
+        let name = |cause: u32| {
+            Mcause::from_u32(cause)
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| format!("unknown(0x{cause:08x})"))
+        };
+        match *event {
+            TrapEvent::Entry {
+                cause,
+                epc,
+                tval,
+                handler,
+            } => println!(
+                "{} {} mepc: {}, mtval: {}, handler: {}",
+                "trap".red(),
+                name(cause).bright_white(),
+                format!("0x{:08x}", epc).blue(),
+                format!("0x{:08x}", tval).cyan(),
+                format!("0x{:08x}", handler).yellow()
+            ),
+            TrapEvent::Return { cause, pc, epc } => println!(
+                "{} {} pc: {}, mepc: {}",
+                "mret".green(),
+                name(cause).bright_white(),
+                format!("0x{:08x}", pc).blue(),
+                format!("0x{:08x}", epc).yellow()
+            ),
+        }
+    }
+
+    fn iringbuf_print(&self, entries: &[IringbufEntry]) {
+        #[derive(Tabled)]
+        struct IringbufRow {
+            #[tabled(rename = "")]
+            marker: &'static str,
+            #[tabled(display = "fmt_hex")]
+            pc: u32,
+            #[tabled(display = "fmt_hex")]
+            inst: u32,
+            disasm: String,
+            writeback: String,
+        }
+        let last = entries.len().saturating_sub(1);
+        let rows: Vec<IringbufRow> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| IringbufRow {
+                marker: if i == last { "-->" } else { "" },
+                pc: e.pc,
+                inst: e.inst,
+                disasm: self
+                    .guesser
+                    .disassemble(e.pc as u64, e.inst)
+                    .unwrap_or_else(|_| "unknown".to_string()),
+                writeback: match (e.retired, e.writeback) {
+                    (false, _) => "(not retired)".to_string(),
+                    (true, Some((rd, val))) => {
+                        let name = Gpr::from_repr(rd as usize)
+                            .map(|g| g.to_string())
+                            .unwrap_or_else(|| format!("x{rd}"));
+                        format!("{name} <- 0x{val:08x}")
+                    }
+                    (true, None) => String::new(),
+                },
+            })
+            .collect();
+        println!("{}", "instruction ring buffer (oldest first):".yellow());
+        let mut table = Table::new(rows);
+        table.with(Style::rounded());
+        table.modify(Columns::one(0), Color::FG_RED);
+        table.modify(Columns::one(1), Color::FG_BLUE);
+        table.modify(Columns::one(2), Color::FG_CYAN);
+        table.modify(Columns::one(3), Color::FG_BRIGHT_WHITE);
+        table.modify(Columns::one(4), Color::FG_YELLOW);
+        println!("{table}");
+    }
+
     fn stat_print(&self, entries: &[(String, String)]) {
         if entries.is_empty() {
             println!("{}", "no statistics".yellow());
@@ -380,6 +580,8 @@ impl CLITracer {
     pub fn new(isa: IsaSpec) -> Self {
         CLITracer {
             guesser: ByteGuesser::new(isa),
+            source: None,
+            last_source: RefCell::new(None),
         }
     }
 }
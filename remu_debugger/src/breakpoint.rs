@@ -0,0 +1,230 @@
+//! Breakpoint objects. The DUT backend only patches addresses; which hit actually stops
+//! (enabled, condition, ignore count, one-shot) is decided here, identically for every backend.
+
+use remu_harness::Breakpoint;
+
+use crate::Expr;
+
+#[derive(Debug, Clone)]
+struct Entry {
+    info: Breakpoint,
+    cond: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Breakpoints {
+    next_id: u32,
+    entries: Vec<Entry>,
+}
+
+impl Breakpoints {
+    pub(crate) fn new() -> Self {
+        Self {
+            next_id: 1,
+            entries: Vec::new(),
+        }
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        addr: u32,
+        cond: Option<Expr>,
+        ignore: u32,
+        temporary: bool,
+    ) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push(Entry {
+            info: Breakpoint {
+                id,
+                addr,
+                enabled: true,
+                temporary,
+                condition: cond.as_ref().map(ToString::to_string),
+                ignore,
+                hits: 0,
+            },
+            cond,
+        });
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: u32) -> Option<Breakpoint> {
+        let pos = self.entries.iter().position(|e| e.info.id == id)?;
+        Some(self.entries.remove(pos).info)
+    }
+
+    pub(crate) fn get_mut(&mut self, id: u32) -> Option<&mut Breakpoint> {
+        self.entry_mut(id).map(|e| &mut e.info)
+    }
+
+    /// Replace (or clear with `None`) the condition; false if `id` does not exist.
+    pub(crate) fn set_condition(&mut self, id: u32, cond: Option<Expr>) -> bool {
+        let Some(entry) = self.entry_mut(id) else {
+            return false;
+        };
+        entry.info.condition = cond.as_ref().map(ToString::to_string);
+        entry.cond = cond;
+        true
+    }
+
+    pub(crate) fn condition(&self, id: u32) -> Option<&Expr> {
+        self.entries
+            .iter()
+            .find(|e| e.info.id == id)
+            .and_then(|e| e.cond.as_ref())
+    }
+
+    /// Whether the backend should keep `addr` patched.
+    pub(crate) fn any_enabled_at(&self, addr: u32) -> bool {
+        self.entries
+            .iter()
+            .any(|e| e.info.enabled && e.info.addr == addr)
+    }
+
+    pub(crate) fn enabled_at(&self, addr: u32) -> Vec<u32> {
+        self.entries
+            .iter()
+            .filter(|e| e.info.enabled && e.info.addr == addr)
+            .map(|e| e.info.id)
+            .collect()
+    }
+
+    /// Count a hit on every enabled breakpoint at `addr` and decide whether to stop. A
+    /// breakpoint whose condition `eval`s to zero is passed over without counting, one with an
+    /// ignore count left uses one up, and a temporary one is removed when it stops. An address
+    /// with no enabled breakpoint always stops.
+    pub(crate) fn hit<E>(
+        &mut self,
+        addr: u32,
+        mut eval: impl FnMut(&Expr) -> Result<u64, E>,
+    ) -> Result<bool, E> {
+        let ids = self.enabled_at(addr);
+        if ids.is_empty() {
+            return Ok(true);
+        }
+        let mut stop = false;
+        for id in ids {
+            let entry = self.entry_mut(id).unwrap();
+            if let Some(cond) = &entry.cond
+                && eval(cond)? == 0
+            {
+                continue;
+            }
+            entry.info.hits += 1;
+            if entry.info.ignore > 0 {
+                entry.info.ignore -= 1;
+                continue;
+            }
+            stop = true;
+            if entry.info.temporary {
+                self.remove(id);
+            }
+        }
+        Ok(stop)
+    }
+
+    /// Every address with a breakpoint, enabled or not.
+    pub(crate) fn addrs(&self) -> impl Iterator<Item = u32> + '_ {
+        self.entries.iter().map(|e| e.info.addr)
+    }
+
+    pub(crate) fn list(&self) -> Vec<Breakpoint> {
+        self.entries.iter().map(|e| e.info.clone()).collect()
+    }
+
+    fn entry_mut(&mut self, id: u32) -> Option<&mut Entry> {
+        self.entries.iter_mut().find(|e| e.info.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use super::*;
+    use crate::{ExprContext, MemWidth, parse_expr};
+
+    const PC: u32 = 0x8000_0010;
+
+    /// Only `a0` is known.
+    struct A0(u64);
+
+    impl ExprContext for A0 {
+        fn reg(&self, name: &str) -> Option<u64> {
+            (name == "a0").then_some(self.0)
+        }
+        fn symbol(&self, _: &str) -> Option<u64> {
+            None
+        }
+        fn read_mem(&mut self, _: u64, _: MemWidth) -> Option<u64> {
+            None
+        }
+    }
+
+    fn hit(bps: &mut Breakpoints, a0: u64) -> bool {
+        bps.hit(PC, |cond| cond.eval(&mut A0(a0))).unwrap()
+    }
+
+    fn info(bps: &Breakpoints, id: u32) -> Breakpoint {
+        bps.list().into_iter().find(|b| b.id == id).unwrap()
+    }
+
+    #[test]
+    fn condition_gates_stop_and_hit_count() {
+        let mut bps = Breakpoints::new();
+        let id = bps.insert(PC, Some(parse_expr("$a0 == 3").unwrap()), 0, false);
+        assert!(!hit(&mut bps, 1));
+        assert_eq!(info(&bps, id).hits, 0);
+        assert!(hit(&mut bps, 3));
+        assert_eq!(info(&bps, id).hits, 1);
+
+        bps.set_condition(id, None);
+        assert!(hit(&mut bps, 1));
+    }
+
+    #[test]
+    fn ignore_count_passes_over_hits() {
+        let mut bps = Breakpoints::new();
+        let id = bps.insert(PC, None, 2, false);
+        assert!(!hit(&mut bps, 0));
+        assert!(!hit(&mut bps, 0));
+        assert!(hit(&mut bps, 0));
+        let bp = info(&bps, id);
+        assert_eq!((bp.hits, bp.ignore), (3, 0));
+    }
+
+    #[test]
+    fn ignore_count_only_counts_true_conditions() {
+        let mut bps = Breakpoints::new();
+        bps.insert(PC, Some(parse_expr("$a0 == 3").unwrap()), 1, false);
+        assert!(!hit(&mut bps, 0));
+        assert!(!hit(&mut bps, 3));
+        assert!(!hit(&mut bps, 0));
+        assert!(hit(&mut bps, 3));
+    }
+
+    #[test]
+    fn temporary_is_removed_when_it_stops() {
+        let mut bps = Breakpoints::new();
+        let tmp = bps.insert(PC, None, 1, true);
+        let keep = bps.insert(PC, None, 0, false);
+        assert!(hit(&mut bps, 0));
+        assert!(bps.list().iter().any(|b| b.id == tmp));
+        assert!(hit(&mut bps, 0));
+        assert_eq!(bps.list().iter().map(|b| b.id).collect::<Vec<_>>(), [keep]);
+        assert!(bps.any_enabled_at(PC));
+    }
+
+    #[test]
+    fn disabled_and_foreign_addresses() {
+        let mut bps = Breakpoints::new();
+        let id = bps.insert(PC, None, 0, true);
+        bps.get_mut(id).unwrap().enabled = false;
+        // Nothing enabled here: the stop is not ours and always stops, and the disabled
+        // temporary breakpoint is neither counted nor removed.
+        assert!(hit(&mut bps, 0));
+        assert_eq!(info(&bps, id).hits, 0);
+        assert!(bps.hit(PC + 4, |_| Ok::<_, Infallible>(0)).unwrap());
+    }
+}
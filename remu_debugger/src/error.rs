@@ -3,6 +3,7 @@ use remu_harness::{ExitCode, HarnessError};
 use thiserror::Error;
 
 use crate::compound_command::ParseError;
+use remu_fmt::ExprError;
 
 #[derive(Error, Debug, Diagnostic)]
 pub enum DebuggerError {
@@ -16,6 +17,30 @@ pub enum DebuggerError {
     #[error("Command execution error: {0}")]
     CommandExec(HarnessError),
 
+    #[error("breakpoint: {0}")]
+    Breakpoint(String),
+
+    #[error("gdb: {0}")]
+    Gdb(#[from] std::io::Error),
+
+    #[error("source: {0}")]
+    Source(String),
+
+    #[error("snapshot: {0}")]
+    Snapshot(String),
+
+    #[error("output: {0}")]
+    Output(String),
+
+    #[error("display: {0}")]
+    Display(String),
+
+    #[error("Expression error: {0}")]
+    Expr(#[from] ExprError),
+
+    #[error("guest panic (panic handler at 0x{0:08x})")]
+    GuestPanic(u32),
+
     #[error("exit requested (run state EXIT)")]
     ExitRequested,
 
@@ -31,4 +56,30 @@ impl DebuggerError {
             _ => None,
         }
     }
+
+    /// Whether this error is a failed run (worth dumping the instruction ring buffer for).
+    #[inline(always)]
+    pub fn is_failure(&self) -> bool {
+        match self {
+            DebuggerError::CommandExec(harness) => harness.is_failure(),
+            DebuggerError::GuestPanic(_) => true,
+            _ => false,
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_difftest_mismatch(&self) -> bool {
+        matches!(self, DebuggerError::CommandExec(harness) if harness.is_difftest_mismatch())
+    }
+
+    #[inline(always)]
+    pub fn is_watchdog_timeout(&self) -> bool {
+        matches!(self, DebuggerError::CommandExec(harness) if harness.is_watchdog_timeout())
+    }
+
+    /// The run was stopped by Ctrl-C (or `--timeout`).
+    #[inline(always)]
+    pub fn is_interrupted(&self) -> bool {
+        matches!(self, DebuggerError::CommandExec(HarnessError::Interrupted))
+    }
 }
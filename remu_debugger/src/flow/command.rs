@@ -1,7 +1,10 @@
 use clap::{CommandFactory, builder::styling};
 use petgraph::graph::{Graph, NodeIndex};
-use remu_fmt::parse_prefixed_uint;
-use remu_harness::{FuncCmd, StateCmd, StatCmd};
+use remu_fmt::NumArg;
+use remu_harness::{FuncCmd, Mcause, StateCmd, StatCmd, WatchAccess};
+use remu_isa::isa::reg::{Csr, Gpr};
+
+use crate::{Expr, Location, parse_expr, parse_location};
 
 fn populate_graph(cmd: &clap::Command, graph: &mut Graph<String, ()>, parent: NodeIndex) {
     let mut has_children = false;
@@ -56,8 +59,56 @@ pub enum Command {
     /// Step
     Step {
         /// Number of steps to take
-        #[arg(default_value_t = 1)]
-        times: usize,
+        #[arg(value_parser = NumArg::<usize>::parse, default_value = "1")]
+        times: NumArg<usize>,
+    },
+
+    /// Step source lines, entering calls (needs an ELF with debug info)
+    StepLine {
+        /// Number of lines to step
+        #[arg(value_parser = NumArg::<usize>::parse, default_value = "1")]
+        times: NumArg<usize>,
+    },
+
+    /// Step source lines, passing over calls and inlined functions
+    NextLine {
+        /// Number of lines to step
+        #[arg(value_parser = NumArg::<usize>::parse, default_value = "1")]
+        times: NumArg<usize>,
+    },
+
+    /// Show source around the PC or LOCATION; a bare repeated `list` continues
+    List {
+        /// file:line or address (e.g. main.rs:42, main, 0x80000000)
+        #[arg(value_parser = parse_location)]
+        location: Option<Location>,
+    },
+
+    /// Print the guest call stack (CFI from the ELF, frame pointers as fallback)
+    Backtrace,
+
+    /// Snapshot Command (registers, memory, devices and breakpoints)
+    Snapshot {
+        #[command(subcommand)]
+        subcmd: SnapshotCmd,
+    },
+
+    /// Run backwards: rewind to an earlier checkpoint and re-execute up to N instructions ago
+    ReverseStep {
+        /// Number of instructions to go back
+        #[arg(value_parser = NumArg::<u64>::parse, default_value = "1")]
+        times: NumArg<u64>,
+    },
+
+    /// Run backwards to the previous breakpoint, watchpoint or catchpoint stop (ignore counts
+    /// and hit counts are left alone)
+    ReverseContinue,
+
+    /// Evaluate an expression, e.g. `print $a0 + 4`, `print *(u32*)$sp`, `print $pc - main`
+    Print {
+        /// Expression (rest of the line)
+        #[arg(required = true, num_args = 1.., allow_hyphen_values = true, trailing_var_arg = true)]
+        expr: Vec<String>,
     },
 
     /// Func Command
@@ -84,6 +135,24 @@ pub enum Command {
         subcmd: BreakpointCmd,
     },
 
+    /// Catchpoint Command (stop on trap entry)
+    Catch {
+        #[command(subcommand)]
+        subcmd: CatchCmd,
+    },
+
+    /// Watchpoint Command (stop on memory access or register change)
+    Watch {
+        #[command(subcommand)]
+        subcmd: WatchCmd,
+    },
+
+    /// Display Command (vga framebuffer)
+    Display {
+        #[command(subcommand)]
+        subcmd: DisplayCmd,
+    },
+
     /// Stat Command
     Stat {
         #[command(subcommand)]
@@ -98,16 +167,159 @@ pub enum Command {
 pub enum BreakpointCmd {
     /// Set breakpoint at address (stop when PC hits this address)
     Set {
-        /// Breakpoint address (0x/0o/0b/0d prefix or decimal, e.g. 0x80000000)
-        #[arg(value_parser = parse_prefixed_uint::<u32>)]
-        addr: u32,
+        #[command(flatten)]
+        spec: BreakpointSpec,
     },
-    /// Delete breakpoint at address
+    /// Set a temporary breakpoint (deleted the first time it stops)
+    Tbreak {
+        #[command(flatten)]
+        spec: BreakpointSpec,
+    },
+    /// Delete breakpoint by id
     Del {
-        /// Breakpoint address (0x/0o/0b/0d prefix or decimal, e.g. 0x80000000)
-        #[arg(value_parser = parse_prefixed_uint::<u32>)]
-        addr: u32,
+        /// Breakpoint id (see `breakpoint print`)
+        id: u32,
+    },
+    /// Enable a disabled breakpoint
+    Enable {
+        /// Breakpoint id
+        id: u32,
+    },
+    /// Disable a breakpoint without deleting it
+    Disable {
+        /// Breakpoint id
+        id: u32,
+    },
+    /// Set the condition of a breakpoint, or make it unconditional when omitted
+    Cond {
+        /// Breakpoint id
+        id: u32,
+        /// Stop only while this is non-zero, e.g. ($a0 == 3)
+        #[arg(value_parser = parse_expr)]
+        cond: Option<Expr>,
+    },
+    /// Pass over the next hits of a breakpoint
+    Ignore {
+        /// Breakpoint id
+        id: u32,
+        /// Number of hits to pass over
+        #[arg(value_parser = NumArg::<u32>::parse)]
+        count: NumArg<u32>,
     },
     /// Print all breakpoints
     Print,
 }
+
+#[derive(Debug, clap::Subcommand)]
+pub enum SnapshotCmd {
+    /// Save the current state under NAME (replacing an older one)
+    Save {
+        /// Snapshot name
+        name: String,
+    },
+    /// Go back (or forward) to the state saved under NAME
+    Restore {
+        /// Snapshot name
+        name: String,
+    },
+    /// Delete a snapshot
+    Del {
+        /// Snapshot name
+        name: String,
+    },
+    /// Print all snapshots
+    Print,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum DisplayCmd {
+    /// Write the current frame to FILE (PPM when it ends in .ppm, PNG otherwise)
+    Dump {
+        /// Output image path
+        path: std::path::PathBuf,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+pub struct BreakpointSpec {
+    /// Breakpoint location: file:line, number, symbol or expression (e.g. main.rs:42, 0x80000000, main, (main + 8))
+    #[arg(value_parser = parse_location)]
+    pub location: Location,
+    /// Stop only while this is non-zero, e.g. ($a0 == 3)
+    #[arg(long = "if", value_parser = parse_expr)]
+    pub cond: Option<Expr>,
+    /// Pass over the first N hits
+    #[arg(long, value_parser = NumArg::<u32>::parse, default_value = "0")]
+    pub ignore: NumArg<u32>,
+}
+
+/// Causes the simulator actually traps on. ECALL only advances the PC and nothing raises
+/// `mip.MTIP` / `mip.MSIP`, so catchpoints on those would be accepted but never fire.
+const CATCHABLE_CAUSES: [Mcause; 5] = [
+    Mcause::IllegalInstruction,
+    Mcause::InstructionAccessFault,
+    Mcause::LoadAccessFault,
+    Mcause::StoreAccessFault,
+    Mcause::MachineExternalInterrupt,
+];
+
+fn parse_catch_cause(s: &str) -> Result<Mcause, String> {
+    let cause: Mcause = s.parse().map_err(|_| format!("unknown trap cause '{s}'"))?;
+    if CATCHABLE_CAUSES.contains(&cause) {
+        Ok(cause)
+    } else {
+        Err(format!("the simulator never raises {cause}"))
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum CatchCmd {
+    /// Stop after entering a trap with this cause
+    Set {
+        /// Trap cause: illegal-instruction, instruction-access-fault, load-access-fault,
+        /// store-access-fault or external
+        #[arg(value_parser = parse_catch_cause)]
+        cause: Mcause,
+    },
+    /// Delete catchpoint for a cause
+    Del {
+        /// Trap cause, as for `catch set`
+        #[arg(value_parser = parse_catch_cause)]
+        cause: Mcause,
+    },
+    /// Print all catchpoints
+    Print,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum WatchCmd {
+    /// Watch a memory range: stop after an instruction accesses it
+    Mem {
+        /// Start address: number, symbol or expression (e.g. 0x80001000, buf, (buf + 8))
+        #[arg(value_parser = NumArg::<usize>::parse)]
+        addr: NumArg<usize>,
+        /// Length in bytes
+        #[arg(value_parser = NumArg::<usize>::parse, default_value = "4")]
+        len: NumArg<usize>,
+        /// Access that triggers it: read, write or access (either)
+        #[arg(long, default_value = "write")]
+        access: WatchAccess,
+    },
+    /// Watch a GPR: stop when its value changes
+    Gpr {
+        /// Register, e.g. a0 or x10
+        reg: Gpr,
+    },
+    /// Watch a CSR: stop when its value changes
+    Csr {
+        /// Register, e.g. mstatus or mepc
+        reg: Csr,
+    },
+    /// Delete watchpoint by id
+    Del {
+        /// Watchpoint id (see `watch print`)
+        id: u32,
+    },
+    /// Print all watchpoints
+    Print,
+}
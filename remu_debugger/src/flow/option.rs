@@ -1,8 +1,13 @@
-use clap::builder::styling;
+use std::path::PathBuf;
+
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, builder::styling};
 use remu_harness::HarnessOption;
 use remu_isa::isa::IsaSpec;
 use remu_types::{DifftestRef, Platform};
 
+use crate::{GdbEndpoint, SocSpec};
+
 #[derive(clap::Parser, Debug, Clone)]
 #[command(
     author,
@@ -23,6 +28,12 @@ pub struct DebuggerOption {
     #[arg(long, default_value = "riscv32i")]
     pub isa: IsaSpec,
 
+    /// SoC description: a preset (remu-default, nemu, qemu-virt) or a TOML file declaring
+    /// `isa`, `init_pc`, `dtb`, `[[mem]]` regions and `[[dev]]` devices. `--isa`, `--init-pc`
+    /// and `--dtb` override it; `--mem` and `--dev` add to it
+    #[arg(long, value_name = "PRESET|PATH", value_parser = SocSpec::load)]
+    pub soc: Option<SocSpec>,
+
     /// Platform (DUT simulator): remu, spike, nzea
     #[arg(long, default_value = "remu")]
     pub platform: Platform,
@@ -31,11 +42,55 @@ pub struct DebuggerOption {
     #[arg(long, value_name = "REF")]
     pub difftest: Option<DifftestRef>,
 
-    /// Batch Mode
+    /// Batch Mode: run to the end and exit with the guest's status (2..=120 as is, any other
+    /// failure as 121; 1 and 122..=130 report the run itself)
     #[arg(long)]
     pub batch: bool,
 
     /// Startup sequence: run this command expression after the debugger is created (tokens joined with spaces; e.g. --startup '{' state reg pc write 0x1000 '}')
     #[arg(long = "startup", value_name = "TOKEN", num_args = 1..)]
     pub startup: Vec<String>,
+
+    /// Before the startup sequence, restore the DUT (and the difftest ref) from this checkpoint
+    /// file
+    #[arg(long, value_name = "FILE")]
+    pub restore_checkpoint: Option<PathBuf>,
+
+    /// Run until `--at-inst` instructions have retired and write a checkpoint file there;
+    /// in batch mode, quit once it is written
+    #[arg(long, value_name = "FILE", requires = "at_inst")]
+    pub save_checkpoint: Option<PathBuf>,
+
+    /// Instruction count at which `--save-checkpoint` is taken
+    #[arg(long, value_name = "N", requires = "save_checkpoint")]
+    pub at_inst: Option<u64>,
+
+    /// In batch mode, compare the output of `out=capture` UARTs with this file once the run
+    /// ends, and fail on a difference
+    #[arg(long, value_name = "FILE", requires = "batch")]
+    pub expect_output: Option<PathBuf>,
+
+    /// In batch mode, stop the run after this many seconds of host time and exit with status
+    /// 124
+    #[arg(long, value_name = "SECONDS", requires = "batch")]
+    pub timeout: Option<u64>,
+
+    /// Serve the DUT to GDB over the remote serial protocol: TCP port (e.g. 1234) or unix socket path
+    #[arg(long, value_name = "PORT|SOCKET")]
+    pub gdb: Option<GdbEndpoint>,
+}
+
+impl DebuggerOption {
+    /// Parse the command line like [`Parser::parse`](clap::Parser::parse), then fill in what
+    /// `--soc` describes.
+    pub fn parse_with_soc() -> Self {
+        let matches = Self::command().get_matches();
+        let mut option = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        if let Some(soc) = option.soc.clone() {
+            soc.apply(&mut option, |id| {
+                matches.value_source(id) == Some(ValueSource::CommandLine)
+            });
+        }
+        option
+    }
 }
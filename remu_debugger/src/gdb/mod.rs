@@ -0,0 +1,672 @@
+//! GDB remote serial protocol server (`--gdb`): exposes the DUT to `riscv32-elf-gdb` and IDE
+//! frontends. All-stop, single thread. Breakpoints go through the debugger's breakpoint table and
+//! watchpoints through the harness, so they behave exactly as the CLI ones.
+
+remu_macro::mod_flat!(packet, target);
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read as _};
+use std::net::TcpListener;
+use std::os::unix::fs::FileTypeExt as _;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use remu_harness::{
+    HarnessError, PlatformConfig, RunOutcome, WatchAccess, WatchEvent, WatchTarget,
+};
+use remu_isa::isa::RvIsa;
+use remu_isa::isa::reg::{Csr, RegAccess, VrState as _};
+use remu_state::StatePolicy;
+
+use crate::{Debugger, DebuggerError};
+
+type Isa<C> = <<C as PlatformConfig>::Policy as StatePolicy>::ISA;
+
+/// Where `--gdb` listens: a TCP port on localhost, or a unix socket path.
+#[derive(Debug, Clone)]
+pub enum GdbEndpoint {
+    Tcp(u16),
+    Unix(PathBuf),
+}
+
+impl FromStr for GdbEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("expected a TCP port or a unix socket path".into());
+        }
+        match s.trim_start_matches(':').parse::<u16>() {
+            Ok(port) => Ok(GdbEndpoint::Tcp(port)),
+            Err(_) => Ok(GdbEndpoint::Unix(PathBuf::from(s))),
+        }
+    }
+}
+
+impl fmt::Display for GdbEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GdbEndpoint::Tcp(port) => write!(f, "localhost:{port}"),
+            GdbEndpoint::Unix(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// How a session ended.
+enum SessionEnd {
+    /// `D` or hang-up: keep listening for the next GDB.
+    Detach,
+    /// `k`: stop serving.
+    Kill,
+}
+
+/// Per-connection state: what GDB inserted, so `z` packets can find it again.
+struct Session {
+    conn: Conn,
+    /// Z0 address -> debugger breakpoint id.
+    breakpoints: HashMap<u32, u32>,
+    /// (Z type, address, length) -> harness watchpoint id.
+    watches: HashMap<(u8, usize, usize), u32>,
+    last_stop: String,
+}
+
+impl<C: PlatformConfig> Debugger<C> {
+    /// Serve GDB on `endpoint` until a client sends `k`. Detached clients may reconnect.
+    pub fn serve_gdb(&mut self, endpoint: &GdbEndpoint) -> Result<(), DebuggerError> {
+        enum Listener {
+            Tcp(TcpListener),
+            Unix(UnixListener),
+        }
+        let listener = match endpoint {
+            GdbEndpoint::Tcp(port) => Listener::Tcp(TcpListener::bind(("127.0.0.1", *port))?),
+            GdbEndpoint::Unix(path) => {
+                // Reuse the path of a stale socket, but never clobber anything else.
+                if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                    std::fs::remove_file(path)?;
+                }
+                Listener::Unix(UnixListener::bind(path)?)
+            }
+        };
+        loop {
+            self.tracer
+                .borrow()
+                .print(&format!("gdb: listening on {endpoint}"));
+            let stream = match &listener {
+                Listener::Tcp(l) => GdbStream::Tcp(l.accept()?.0),
+                Listener::Unix(l) => GdbStream::Unix(l.accept()?.0),
+            };
+            self.tracer.borrow().print("gdb: connected");
+            let mut session = Session {
+                conn: Conn::new(stream)?,
+                breakpoints: HashMap::new(),
+                watches: HashMap::new(),
+                last_stop: "S05".into(),
+            };
+            let end = self.gdb_session(&mut session);
+            self.gdb_cleanup(&mut session);
+            match end? {
+                SessionEnd::Detach => self.tracer.borrow().print("gdb: detached"),
+                SessionEnd::Kill => return Ok(()),
+            }
+        }
+    }
+
+    fn gdb_session(&mut self, session: &mut Session) -> Result<SessionEnd, DebuggerError> {
+        loop {
+            let packet = match session.conn.recv()? {
+                Some(Incoming::Packet(packet)) => packet,
+                // Stray Ctrl-C while already stopped.
+                Some(Incoming::Interrupt) => {
+                    let reply = session.last_stop.clone();
+                    session.conn.send(reply.as_bytes())?;
+                    continue;
+                }
+                None => return Ok(SessionEnd::Detach),
+            };
+            let packet = String::from_utf8_lossy(&packet).into_owned();
+            let reply = match packet.as_bytes().first() {
+                Some(b'D') => {
+                    session.conn.send(b"OK")?;
+                    return Ok(SessionEnd::Detach);
+                }
+                Some(b'k') => return Ok(SessionEnd::Kill),
+                _ => self.gdb_handle(session, &packet)?,
+            };
+            session.conn.send(reply.as_bytes())?;
+            if packet == "QStartNoAckMode" {
+                session.conn.no_ack = true;
+            }
+        }
+    }
+
+    /// Reply to one packet; an empty reply means "not supported".
+    fn gdb_handle(&mut self, session: &mut Session, packet: &str) -> Result<String, DebuggerError> {
+        let (cmd, args) = packet.split_at(1);
+        let reply = match cmd {
+            "?" => session.last_stop.clone(),
+            "q" | "Q" => self.gdb_query(packet),
+            "H" | "T" => "OK".into(),
+            "g" => {
+                let mut out = String::new();
+                for regnum in 0..=PC_REGNUM {
+                    out.push_str(&self.gdb_read_reg(regnum).unwrap_or_default());
+                }
+                out
+            }
+            "G" => match decode_hex(args) {
+                Some(bytes) if bytes.len() >= (PC_REGNUM + 1) * 4 => {
+                    for (regnum, chunk) in bytes.chunks_exact(4).take(PC_REGNUM + 1).enumerate() {
+                        self.gdb_write_reg(regnum, chunk);
+                    }
+                    "OK".into()
+                }
+                _ => "E01".into(),
+            },
+            "p" => usize::from_str_radix(args, 16)
+                .ok()
+                .and_then(|regnum| self.gdb_read_reg(regnum))
+                .unwrap_or_else(|| "E01".into()),
+            "P" => {
+                let parsed = args
+                    .split_once('=')
+                    .and_then(|(n, v)| Some((usize::from_str_radix(n, 16).ok()?, decode_hex(v)?)));
+                match parsed {
+                    Some((regnum, value)) if self.gdb_write_reg(regnum, &value) => "OK".into(),
+                    _ => "E01".into(),
+                }
+            }
+            "m" => match parse_addr_len(args) {
+                // Two hex digits per byte must fit in one reply.
+                Some((addr, len)) if len <= PACKET_SIZE / 2 => self
+                    .gdb_read_mem(addr, len)
+                    .map(|bytes| encode_hex(&bytes))
+                    .unwrap_or_else(|| "E14".into()),
+                _ => "E01".into(),
+            },
+            "M" => {
+                let parsed = args
+                    .split_once(':')
+                    .and_then(|(range, data)| Some((parse_addr_len(range)?, decode_hex(data)?)));
+                match parsed {
+                    Some(((addr, len), data)) if data.len() == len => {
+                        match self.harness.write_dut_memory(addr, &data) {
+                            Ok(()) => "OK".into(),
+                            Err(_) => "E14".into(),
+                        }
+                    }
+                    _ => "E01".into(),
+                }
+            }
+            "c" | "s" => {
+                if let Ok(addr) = u32::from_str_radix(args, 16) {
+                    self.harness.set_pc(addr);
+                }
+                self.gdb_resume(session, cmd == "s")?
+            }
+            "v" => self.gdb_v_packet(session, packet)?,
+            "Z" | "z" => self.gdb_point(session, cmd == "Z", args),
+            _ => String::new(),
+        };
+        Ok(reply)
+    }
+
+    fn gdb_query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!(
+                "PacketSize={PACKET_SIZE:x};qXfer:features:read+;swbreak+;QStartNoAckMode+;vContSupported+"
+            );
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, len)) = parse_addr_len(range) else {
+                return "E01".into();
+            };
+            let xml = target_xml::<Isa<C>>();
+            let chunk = xml.get(offset.min(xml.len())..).unwrap_or_default();
+            let chunk = &chunk[..len.min(chunk.len())];
+            let more = offset + chunk.len() < xml.len();
+            return format!("{}{chunk}", if more { 'm' } else { 'l' });
+        }
+        match packet {
+            "QStartNoAckMode" => "OK".into(),
+            "qAttached" => "1".into(),
+            "qC" => "QC1".into(),
+            "qfThreadInfo" => "m1".into(),
+            "qsThreadInfo" => "l".into(),
+            _ => String::new(),
+        }
+    }
+
+    fn gdb_v_packet(
+        &mut self,
+        session: &mut Session,
+        packet: &str,
+    ) -> Result<String, DebuggerError> {
+        if packet == "vCont?" {
+            return Ok("vCont;c;C;s;S".into());
+        }
+        let Some(actions) = packet.strip_prefix("vCont;") else {
+            return Ok(String::new());
+        };
+        // Single thread: the first action decides.
+        let step = actions.starts_with(['s', 'S']);
+        self.gdb_resume(session, step)
+    }
+
+    /// Step one instruction or continue; the reply is the stop packet.
+    fn gdb_resume(&mut self, session: &mut Session, step: bool) -> Result<String, DebuggerError> {
+        let result = if step {
+            self.run(Some(1))
+        } else {
+            if session.conn.interrupt_pending() {
+                self.interrupt.store(true, Ordering::Relaxed);
+            }
+            let done = Arc::new(AtomicBool::new(false));
+            let watcher = spawn_interrupt_watcher(
+                session.conn.stream()?,
+                Arc::clone(&self.interrupt),
+                Arc::clone(&done),
+            )?;
+            let result = self.run(None);
+            done.store(true, Ordering::Relaxed);
+            let _ = watcher.join();
+            session.conn.stream()?.set_read_timeout(None)?;
+            result
+        };
+        let stop = self.gdb_stop_reply(result);
+        session.last_stop = stop.clone();
+        Ok(stop)
+    }
+
+    fn gdb_stop_reply(&mut self, result: Result<RunOutcome, DebuggerError>) -> String {
+        match result {
+            Ok(RunOutcome::Done) => "S05".into(),
+            Ok(RunOutcome::ProgramExit(code)) => format!("W{:02x}", code.process_status()),
+            Err(DebuggerError::CommandExec(HarnessError::Interrupted)) => "S02".into(),
+            Err(DebuggerError::CommandExec(e)) if e.breakpoint_pc().is_some() => {
+                "T05swbreak:;".into()
+            }
+            Err(DebuggerError::CommandExec(HarnessError::WatchpointHit(hit))) => {
+                let kind = match hit.target {
+                    WatchTarget::Mem { access, .. } => match access {
+                        WatchAccess::Write => "watch",
+                        WatchAccess::Read => "rwatch",
+                        WatchAccess::Access => "awatch",
+                    },
+                    WatchTarget::Gpr(_) | WatchTarget::Csr(_) => return "S05".into(),
+                };
+                let addr = match hit.event {
+                    WatchEvent::Read { addr, .. } | WatchEvent::Write { addr, .. } => addr,
+                    WatchEvent::Changed { .. } => return "S05".into(),
+                };
+                format!("T05{kind}:{addr:x};")
+            }
+            Err(e) => {
+                self.tracer.borrow().print(&format!("gdb: {e}"));
+                if e.is_failure() {
+                    self.print_iringbuf();
+                    "S06".into()
+                } else {
+                    "S05".into()
+                }
+            }
+        }
+    }
+
+    /// `Z`/`z` packets: 0 = software breakpoint, 2/3/4 = write/read/access watchpoint.
+    fn gdb_point(&mut self, session: &mut Session, insert: bool, args: &str) -> String {
+        let mut fields = args.split(',');
+        let (Some(kind), Some(addr), Some(len)) = (
+            fields.next().and_then(|k| k.parse::<u8>().ok()),
+            fields
+                .next()
+                .and_then(|a| usize::from_str_radix(a, 16).ok()),
+            fields
+                .next()
+                .and_then(|l| usize::from_str_radix(l, 16).ok()),
+        ) else {
+            return "E01".into();
+        };
+        let ok = match (kind, insert) {
+            (0, true) => {
+                let addr = addr as u32;
+                session.breakpoints.contains_key(&addr)
+                    || self.harness.set_breakpoint(addr).is_ok() && {
+                        let id = self.breakpoints.insert(addr, None, 0, false);
+                        session.breakpoints.insert(addr, id);
+                        true
+                    }
+            }
+            (0, false) => match session.breakpoints.remove(&(addr as u32)) {
+                Some(id) => {
+                    self.breakpoints.remove(id);
+                    self.sync_breakpoint(addr as u32).is_ok()
+                }
+                None => false,
+            },
+            (2..=4, true) => {
+                let access = match kind {
+                    2 => WatchAccess::Write,
+                    3 => WatchAccess::Read,
+                    _ => WatchAccess::Access,
+                };
+                let target = WatchTarget::Mem {
+                    start: addr,
+                    len,
+                    access,
+                };
+                match self.harness.set_watch(target) {
+                    Ok(id) => {
+                        session.watches.insert((kind, addr, len), id);
+                        true
+                    }
+                    Err(_) => false,
+                }
+            }
+            (2..=4, false) => match session.watches.remove(&(kind, addr, len)) {
+                Some(id) => self.harness.del_watch(id).is_ok(),
+                None => false,
+            },
+            _ => return String::new(),
+        };
+        if ok { "OK".into() } else { "E01".into() }
+    }
+
+    /// Remove whatever the session inserted, so a detached GDB leaves no stray stops behind.
+    fn gdb_cleanup(&mut self, session: &mut Session) {
+        for (addr, id) in std::mem::take(&mut session.breakpoints) {
+            self.breakpoints.remove(id);
+            let _ = self.sync_breakpoint(addr);
+        }
+        for (_, id) in std::mem::take(&mut session.watches) {
+            let _ = self.harness.del_watch(id);
+        }
+    }
+
+    /// Register `regnum` as little-endian hex, `None` if GDB's number is not ours.
+    fn gdb_read_reg(&self, regnum: usize) -> Option<String> {
+        let reg = &self.harness.dut_state().reg;
+        let value = match regnum {
+            0..PC_REGNUM => reg.gpr.raw_read(regnum),
+            PC_REGNUM => *reg.pc,
+            FPR_BASE..CSR_BASE if Isa::<C>::HAS_F => reg.fpr.raw_read(regnum - FPR_BASE),
+            VR_BASE.. if has_vector::<Isa<C>>() && regnum < VR_BASE + 32 => {
+                return Some(encode_hex(reg.vr.raw_read(regnum - VR_BASE)));
+            }
+            _ => reg.read_csr(gdb_csr::<Isa<C>>(regnum)?),
+        };
+        Some(encode_hex(&value.to_le_bytes()))
+    }
+
+    fn gdb_write_reg(&mut self, regnum: usize, bytes: &[u8]) -> bool {
+        let reg = &mut self.harness.dut_state_mut().reg;
+        if has_vector::<Isa<C>>() && (VR_BASE..VR_BASE + 32).contains(&regnum) {
+            if bytes.len() != reg.vr.raw_read(regnum - VR_BASE).len() {
+                return false;
+            }
+            reg.vr.raw_write(regnum - VR_BASE, bytes);
+            return true;
+        }
+        let Ok(value) = <[u8; 4]>::try_from(bytes).map(u32::from_le_bytes) else {
+            return false;
+        };
+        match regnum {
+            0 => {}
+            1..PC_REGNUM => reg.gpr.raw_write(regnum, value),
+            PC_REGNUM => self.harness.set_pc(value),
+            FPR_BASE..CSR_BASE if Isa::<C>::HAS_F => reg.fpr.raw_write(regnum - FPR_BASE, value),
+            _ => match gdb_csr::<Isa<C>>(regnum) {
+                Some(csr) => reg.csr.write(csr, value),
+                None => return false,
+            },
+        }
+        true
+    }
+
+    /// Memory as the program sees it: breakpoint patches are undone.
+    fn gdb_read_mem(&mut self, addr: usize, len: usize) -> Option<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        self.harness
+            .dut_state_mut()
+            .bus
+            .read_bytes(addr, &mut buf)
+            .ok()?;
+        let patched = self
+            .breakpoints
+            .list()
+            .into_iter()
+            .filter(|bp| bp.enabled)
+            .map(|bp| bp.addr);
+        for at in patched.chain(self.panic_hook).collect::<Vec<_>>() {
+            let start = at as usize;
+            if start + 4 <= addr || start >= addr + len {
+                continue;
+            }
+            let Some(orig) = self.harness.fetch_inst(at) else {
+                continue;
+            };
+            for (i, b) in orig.to_le_bytes().into_iter().enumerate() {
+                if let Some(dst) = (start + i).checked_sub(addr).and_then(|o| buf.get_mut(o)) {
+                    *dst = b;
+                }
+            }
+        }
+        Some(buf)
+    }
+}
+
+fn gdb_csr<I: RvIsa>(regnum: usize) -> Option<Csr> {
+    let csr = Csr::from_repr(u16::try_from(regnum.checked_sub(CSR_BASE)?).ok()?)?;
+    csrs::<I>().any(|c| c == csr).then_some(csr)
+}
+
+/// `addr,len` in hex.
+fn parse_addr_len(s: &str) -> Option<(usize, usize)> {
+    let (addr, len) = s.split_once(',')?;
+    Some((
+        usize::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+/// While the DUT runs, turn a `0x03` from GDB (or a hang-up) into a harness interrupt.
+fn spawn_interrupt_watcher(
+    mut stream: GdbStream,
+    interrupt: Arc<AtomicBool>,
+    done: Arc<AtomicBool>,
+) -> io::Result<std::thread::JoinHandle<()>> {
+    stream.set_read_timeout(Some(Duration::from_millis(50)))?;
+    Ok(std::thread::spawn(move || {
+        let mut byte = [0u8; 1];
+        while !done.load(Ordering::Relaxed) {
+            match stream.read(&mut byte) {
+                Ok(1) if byte[0] == 0x03 => interrupt.store(true, Ordering::Relaxed),
+                Ok(0) => {
+                    interrupt.store(true, Ordering::Relaxed);
+                    return;
+                }
+                Ok(_) => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(_) => return,
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::os::unix::net::UnixStream;
+    use std::rc::Rc;
+
+    use clap::Parser;
+    use remu_types::TracerDyn;
+
+    use super::*;
+    use crate::tests::{Args, Quiet, Remu};
+
+    const BASE: u32 = 0x8000_0000;
+
+    /// Bump a0, a1 and a2 and loop.
+    const PROGRAM: [u32; 4] = [
+        0x0015_0513, // addi  a0, a0, 1
+        0x0025_8593, // addi  a1, a1, 2
+        0x0036_0613, // addi  a2, a2, 3
+        0xff5f_f06f, // j     -12
+    ];
+
+    /// A debugger on PROGRAM and a session whose GDB end stays open but silent.
+    fn session() -> (Debugger<Remu>, Session, UnixStream) {
+        let args = Args::parse_from(["remu", "--uart-input", "none"]);
+        let tracer: TracerDyn = Rc::new(RefCell::new(Quiet));
+        let mut debugger = Debugger::new(args.opt, tracer, Arc::new(AtomicBool::new(false)));
+        let program: Vec<u8> = PROGRAM.iter().flat_map(|i| i.to_le_bytes()).collect();
+        debugger
+            .harness
+            .write_dut_memory(BASE as usize, &program)
+            .unwrap();
+        let (ours, gdb) = UnixStream::pair().unwrap();
+        let session = Session {
+            conn: Conn::new(GdbStream::Unix(ours)).unwrap(),
+            breakpoints: HashMap::new(),
+            watches: HashMap::new(),
+            last_stop: "S05".into(),
+        };
+        (debugger, session, gdb)
+    }
+
+    fn handle(debugger: &mut Debugger<Remu>, session: &mut Session, packet: &str) -> String {
+        debugger.gdb_handle(session, packet).unwrap()
+    }
+
+    fn gpr(debugger: &Debugger<Remu>, n: usize) -> u32 {
+        debugger.harness.dut_state().reg.gpr.raw_read(n)
+    }
+
+    fn pc(debugger: &Debugger<Remu>) -> u32 {
+        *debugger.harness.dut_state().reg.pc
+    }
+
+    /// Raw word on the bus, breakpoint patches included.
+    fn raw(debugger: &mut Debugger<Remu>, addr: u32) -> u32 {
+        debugger
+            .harness
+            .dut_state_mut()
+            .bus
+            .fetch_32(addr as usize)
+            .unwrap()
+    }
+
+    #[test]
+    fn g_and_g_round_trip_registers() {
+        let (mut d, mut s, _gdb) = session();
+        let regs = handle(&mut d, &mut s, "g");
+        assert_eq!(regs.len(), (PC_REGNUM + 1) * 8);
+        assert!(regs.ends_with("00000080"));
+
+        let mut bytes = decode_hex(&regs).unwrap();
+        bytes[10 * 4..11 * 4].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+        bytes[PC_REGNUM * 4..].copy_from_slice(&(BASE + 4).to_le_bytes());
+        assert_eq!(handle(&mut d, &mut s, &format!("G{}", encode_hex(&bytes))), "OK");
+        assert_eq!(gpr(&d, 10), 0x1234_5678);
+        assert_eq!(pc(&d), BASE + 4);
+        assert_eq!(handle(&mut d, &mut s, "pa"), "78563412");
+        assert_eq!(handle(&mut d, &mut s, "G1234"), "E01");
+
+        assert_eq!(handle(&mut d, &mut s, "Pb=05000000"), "OK");
+        assert_eq!(gpr(&d, 11), 5);
+        assert_eq!(handle(&mut d, &mut s, "P0=05000000"), "OK");
+        assert_eq!(gpr(&d, 0), 0);
+    }
+
+    #[test]
+    fn z0_patches_and_m_hides_the_patch() {
+        let (mut d, mut s, _gdb) = session();
+        assert_eq!(handle(&mut d, &mut s, "Z0,80000004,4"), "OK");
+        assert_eq!(raw(&mut d, BASE + 4), 0x0010_0073);
+        assert_eq!(handle(&mut d, &mut s, "m80000000,8"), "1305150093852500");
+        assert_eq!(handle(&mut d, &mut s, "z0,80000004,4"), "OK");
+        assert_eq!(raw(&mut d, BASE + 4), PROGRAM[1]);
+        assert_eq!(handle(&mut d, &mut s, "z0,80000004,4"), "E01");
+        assert_eq!(handle(&mut d, &mut s, "Z0,80000002,4"), "E01");
+    }
+
+    #[test]
+    fn m_over_a_breakpoint_keeps_it() {
+        let (mut d, mut s, _gdb) = session();
+        assert_eq!(handle(&mut d, &mut s, "Z0,80000004,4"), "OK");
+        // addi a1, a1, 5, written two bytes at a time across the patch.
+        assert_eq!(handle(&mut d, &mut s, "M80000002,4:00009385"), "OK");
+        assert_eq!(handle(&mut d, &mut s, "M80000006,2:5500"), "OK");
+        assert_eq!(raw(&mut d, BASE + 4), 0x0010_0073);
+        assert_eq!(handle(&mut d, &mut s, "m80000004,4"), "93855500");
+
+        assert!(handle(&mut d, &mut s, "c").starts_with("T05"));
+        assert_eq!(pc(&d), BASE + 4);
+        assert!(handle(&mut d, &mut s, "s").starts_with("S05"));
+        assert_eq!(gpr(&d, 11), 5);
+    }
+
+    #[test]
+    fn c_stops_at_a_breakpoint_with_t05() {
+        let (mut d, mut s, _gdb) = session();
+        assert_eq!(handle(&mut d, &mut s, "Z0,80000008,4"), "OK");
+        assert_eq!(handle(&mut d, &mut s, "c"), "T05swbreak:;");
+        assert_eq!(pc(&d), BASE + 8);
+        assert_eq!((gpr(&d, 10), gpr(&d, 11), gpr(&d, 12)), (1, 2, 0));
+        assert_eq!(handle(&mut d, &mut s, "?"), "T05swbreak:;");
+
+        // Resuming runs the breakpoint's instruction once, then stops there again.
+        assert_eq!(handle(&mut d, &mut s, "c"), "T05swbreak:;");
+        assert_eq!((gpr(&d, 10), gpr(&d, 11), gpr(&d, 12)), (2, 4, 3));
+    }
+
+    #[test]
+    fn moving_the_pc_resumes_over_breakpoints_like_the_cli() {
+        let (mut d, mut s, _gdb) = session();
+        assert_eq!(handle(&mut d, &mut s, "Z0,80000000,4"), "OK");
+        assert_eq!(handle(&mut d, &mut s, "Z0,80000008,4"), "OK");
+        assert_eq!(handle(&mut d, &mut s, "c"), "T05swbreak:;");
+        assert_eq!(pc(&d), BASE);
+
+        // Jumping off a breakpoint leaves it behind: the next one still stops.
+        assert_eq!(handle(&mut d, &mut s, "c80000004"), "T05swbreak:;");
+        assert_eq!(pc(&d), BASE + 8);
+        assert_eq!((gpr(&d, 10), gpr(&d, 11), gpr(&d, 12)), (0, 2, 0));
+
+        // Jumping onto a breakpoint counts it as reported, so resuming runs it.
+        assert_eq!(handle(&mut d, &mut s, "P20=00000080"), "OK");
+        assert_eq!(handle(&mut d, &mut s, "c"), "T05swbreak:;");
+        assert_eq!(pc(&d), BASE + 8);
+        assert_eq!((gpr(&d, 10), gpr(&d, 11), gpr(&d, 12)), (1, 4, 0));
+    }
+
+    #[test]
+    fn m_is_capped_at_the_packet_size() {
+        let (mut d, mut s, _gdb) = session();
+        assert!(handle(&mut d, &mut s, "qSupported").starts_with("PacketSize=4000;"));
+        let max = PACKET_SIZE / 2;
+        assert_eq!(handle(&mut d, &mut s, &format!("m80000000,{max:x}")).len(), PACKET_SIZE);
+        assert_eq!(handle(&mut d, &mut s, &format!("m80000000,{:x}", max + 1)), "E01");
+        assert_eq!(handle(&mut d, &mut s, "m80000000,ffffffffff"), "E01");
+    }
+
+    #[test]
+    fn endpoint_and_hex() {
+        assert!(matches!("1234".parse(), Ok(GdbEndpoint::Tcp(1234))));
+        assert!(matches!(":3333".parse(), Ok(GdbEndpoint::Tcp(3333))));
+        assert!(matches!("/tmp/remu.sock".parse(), Ok(GdbEndpoint::Unix(_))));
+        assert_eq!(encode_hex(&[0x13, 0x05, 0xff]), "1305ff");
+        assert_eq!(decode_hex("1305ff"), Some(vec![0x13, 0x05, 0xff]));
+        assert_eq!(decode_hex("130"), None);
+        assert_eq!(parse_addr_len("80000000,4"), Some((0x8000_0000, 4)));
+    }
+}
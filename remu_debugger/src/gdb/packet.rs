@@ -0,0 +1,187 @@
+//! RSP framing: `$payload#xx` packets, `+` / `-` acks and the bare `0x03` interrupt byte.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+/// Accepted GDB connection.
+#[derive(Debug)]
+pub(crate) enum GdbStream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl GdbStream {
+    pub(crate) fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            GdbStream::Tcp(s) => GdbStream::Tcp(s.try_clone()?),
+            GdbStream::Unix(s) => GdbStream::Unix(s.try_clone()?),
+        })
+    }
+
+    /// Shared by every clone of the stream (it is a socket option).
+    pub(crate) fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            GdbStream::Tcp(s) => s.set_read_timeout(timeout),
+            GdbStream::Unix(s) => s.set_read_timeout(timeout),
+        }
+    }
+}
+
+impl Read for GdbStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            GdbStream::Tcp(s) => s.read(buf),
+            GdbStream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for GdbStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            GdbStream::Tcp(s) => s.write(buf),
+            GdbStream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            GdbStream::Tcp(s) => s.flush(),
+            GdbStream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+/// Largest packet payload we accept and send, advertised in `qSupported`.
+pub(crate) const PACKET_SIZE: usize = 0x4000;
+
+pub(crate) enum Incoming {
+    Packet(Vec<u8>),
+    /// `0x03` outside a packet (Ctrl-C in GDB).
+    Interrupt,
+}
+
+pub(crate) struct Conn {
+    reader: BufReader<GdbStream>,
+    writer: GdbStream,
+    /// Last packet sent, resent on `-`.
+    last: Vec<u8>,
+    pub(crate) no_ack: bool,
+}
+
+impl Conn {
+    pub(crate) fn new(stream: GdbStream) -> io::Result<Self> {
+        Ok(Self {
+            writer: stream.try_clone()?,
+            reader: BufReader::new(stream),
+            last: Vec::new(),
+            no_ack: false,
+        })
+    }
+
+    /// Another handle on the socket, e.g. to watch for `0x03` while the DUT runs.
+    pub(crate) fn stream(&self) -> io::Result<GdbStream> {
+        self.writer.try_clone()
+    }
+
+    /// Whether an interrupt byte already arrived and sits in the read buffer.
+    pub(crate) fn interrupt_pending(&self) -> bool {
+        self.reader.buffer().contains(&0x03)
+    }
+
+    /// Next packet or interrupt; `None` when GDB hung up.
+    pub(crate) fn recv(&mut self) -> io::Result<Option<Incoming>> {
+        loop {
+            let Some(byte) = self.read_byte()? else {
+                return Ok(None);
+            };
+            match byte {
+                b'$' => {
+                    if let Some(packet) = self.read_packet()? {
+                        return Ok(Some(Incoming::Packet(packet)));
+                    }
+                }
+                0x03 => return Ok(Some(Incoming::Interrupt)),
+                b'-' if !self.no_ack => {
+                    let last = std::mem::take(&mut self.last);
+                    self.writer.write_all(&last)?;
+                    self.last = last;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub(crate) fn send(&mut self, payload: &[u8]) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(payload.len() + 4);
+        frame.push(b'$');
+        for &b in payload {
+            if matches!(b, b'$' | b'#' | b'}' | b'*') {
+                frame.extend([b'}', b ^ 0x20]);
+            } else {
+                frame.push(b);
+            }
+        }
+        let sum = frame[1..].iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+        frame.extend(format!("#{sum:02x}").bytes());
+        self.writer.write_all(&frame)?;
+        self.writer.flush()?;
+        self.last = frame;
+        Ok(())
+    }
+
+    /// Body after `$`; `None` (and a `-` ack) on a checksum mismatch.
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut raw = Vec::new();
+        self.reader.read_until(b'#', &mut raw)?;
+        let mut cs = [0u8; 2];
+        self.reader.read_exact(&mut cs)?;
+        if raw.pop() != Some(b'#') {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let sum = raw.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+        let expected = std::str::from_utf8(&cs)
+            .ok()
+            .and_then(|s| u8::from_str_radix(s, 16).ok());
+        if !self.no_ack {
+            let ok = expected == Some(sum);
+            self.writer.write_all(if ok { b"+" } else { b"-" })?;
+            if !ok {
+                return Ok(None);
+            }
+        }
+        let mut body = Vec::with_capacity(raw.len());
+        let mut iter = raw.into_iter();
+        while let Some(b) = iter.next() {
+            match b {
+                b'}' => body.push(iter.next().unwrap_or(0) ^ 0x20),
+                _ => body.push(b),
+            }
+        }
+        Ok(Some(body))
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut b = [0u8; 1];
+        match self.reader.read(&mut b)? {
+            0 => Ok(None),
+            _ => Ok(Some(b[0])),
+        }
+    }
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub(crate) fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
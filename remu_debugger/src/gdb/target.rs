@@ -0,0 +1,97 @@
+//! Target description (`target.xml`) and GDB register numbering for RISC-V.
+
+use std::fmt::Write as _;
+
+use remu_isa::isa::RvIsa;
+use remu_isa::isa::extension_v::VExtensionConfig;
+use remu_isa::isa::reg::Csr;
+use strum::IntoEnumIterator;
+
+/// GDB's fixed RISC-V numbering: x0-x31, pc, f0-f31, CSRs at 65 + address, vectors after that.
+pub(crate) const PC_REGNUM: usize = 32;
+pub(crate) const FPR_BASE: usize = 33;
+pub(crate) const CSR_BASE: usize = 65;
+pub(crate) const VR_BASE: usize = 4162;
+
+pub(crate) fn has_vector<I: RvIsa>() -> bool {
+    <I::VConfig as VExtensionConfig>::VLENB != 0
+}
+
+/// CSRs exposed to GDB: all known to remu, minus vector CSRs without V.
+pub(crate) fn csrs<I: RvIsa>() -> impl Iterator<Item = Csr> {
+    Csr::iter().filter(|csr| has_vector::<I>() || !csr.illegal_when_vs_off())
+}
+
+pub(crate) fn target_xml<I: RvIsa>() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n<target version=\"1.0\">\n<architecture>riscv:rv32</architecture>\n",
+    );
+
+    xml.push_str("<feature name=\"org.gnu.gdb.riscv.cpu\">\n");
+    for i in 0..32 {
+        let ty = match i {
+            1 => "code_ptr",
+            2 | 8 => "data_ptr",
+            _ => "int",
+        };
+        let _ = writeln!(
+            xml,
+            "<reg name=\"x{i}\" bitsize=\"32\" regnum=\"{i}\" type=\"{ty}\"/>"
+        );
+    }
+    let _ = writeln!(
+        xml,
+        "<reg name=\"pc\" bitsize=\"32\" regnum=\"{PC_REGNUM}\" type=\"code_ptr\"/>"
+    );
+    xml.push_str("</feature>\n");
+
+    if I::HAS_F {
+        xml.push_str("<feature name=\"org.gnu.gdb.riscv.fpu\">\n");
+        for i in 0..32 {
+            let _ = writeln!(
+                xml,
+                "<reg name=\"f{i}\" bitsize=\"32\" regnum=\"{}\" type=\"ieee_single\"/>",
+                FPR_BASE + i
+            );
+        }
+        xml.push_str("</feature>\n");
+    }
+
+    xml.push_str("<feature name=\"org.gnu.gdb.riscv.csr\">\n");
+    for csr in csrs::<I>() {
+        let _ = writeln!(
+            xml,
+            "<reg name=\"{csr}\" bitsize=\"32\" regnum=\"{}\" group=\"csr\"/>",
+            CSR_BASE + csr.idx()
+        );
+    }
+    xml.push_str("</feature>\n");
+
+    if has_vector::<I>() {
+        let bits = <I::VConfig as VExtensionConfig>::VLENB * 8;
+        xml.push_str("<feature name=\"org.gnu.gdb.riscv.vector\">\n");
+        for (id, ty, width) in [
+            ("bytes", "uint8", 8),
+            ("shorts", "uint16", 16),
+            ("words", "uint32", 32),
+        ] {
+            let _ = writeln!(
+                xml,
+                "<vector id=\"{id}\" type=\"{ty}\" count=\"{}\"/>",
+                bits / width
+            );
+        }
+        xml.push_str("<union id=\"riscv_vector\"><field name=\"b\" type=\"bytes\"/><field name=\"s\" type=\"shorts\"/><field name=\"w\" type=\"words\"/></union>\n");
+        for i in 0..32 {
+            let _ = writeln!(
+                xml,
+                "<reg name=\"v{i}\" bitsize=\"{bits}\" regnum=\"{}\" type=\"riscv_vector\" group=\"vector\"/>",
+                VR_BASE + i
+            );
+        }
+        xml.push_str("</feature>\n");
+    }
+
+    xml.push_str("</target>\n");
+    xml
+}
@@ -1,15 +1,43 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::Arc;
 
 use clap::Parser;
 
 remu_macro::mod_pub_flat!(prelude);
 remu_macro::mod_pub_flat!(flow);
-remu_macro::mod_flat!(error, compound_command);
+remu_macro::mod_flat!(
+    breakpoint,
+    error,
+    compound_command,
+    gdb,
+    snapshot,
+    soc,
+    source,
+    symbols,
+    unwind
+);
 
+use std::str::FromStr;
+
+pub use remu_fmt::{Expr, ExprContext, ExprError, MemWidth, NumArg, parse_expr};
 use remu_harness::Harness;
+use remu_isa::isa::reg::{Csr, Fpr, Gpr, RegAccess};
 
 pub struct Debugger<C: PlatformConfig> {
     harness: Harness<C>,
+    breakpoints: Breakpoints,
+    symbols: SymbolTable,
+    source: Rc<SourceInfo>,
+    unwinder: Unwinder,
+    /// Address of the guest panic handler while it is hooked (see `install_panic_hook`).
+    panic_hook: Option<u32>,
+    /// Where a bare `list` continues: (file, first line).
+    list_next: Option<(usize, u32)>,
+    snapshots: BTreeMap<String, NamedSnapshot<C>>,
+    tracer: TracerDyn,
+    interrupt: Arc<std::sync::atomic::AtomicBool>,
 }
 
 impl<C: PlatformConfig> Debugger<C> {
@@ -18,20 +46,93 @@ impl<C: PlatformConfig> Debugger<C> {
         tracer: TracerDyn,
         interrupt: Arc<std::sync::atomic::AtomicBool>,
     ) -> Self {
-        Self {
-            harness: Harness::new(opt.sim, tracer, interrupt),
+        let symbols = SymbolTable::load(opt.sim.sim.state.bus.elf.as_deref());
+        let source = Rc::new(SourceInfo::load(opt.sim.sim.state.bus.elf.as_deref()));
+        if !source.is_empty() {
+            tracer.borrow_mut().set_source_lookup(source.clone());
         }
+        let unwinder = Unwinder::load(opt.sim.sim.state.bus.elf.as_deref());
+        let mut debugger = Self {
+            harness: Harness::new(opt.sim, tracer.clone(), interrupt.clone()),
+            breakpoints: Breakpoints::new(),
+            symbols,
+            source,
+            unwinder,
+            panic_hook: None,
+            list_next: None,
+            snapshots: BTreeMap::new(),
+            tracer,
+            interrupt,
+        };
+        debugger.install_panic_hook();
+        debugger
     }
 
-    pub fn run_startup(&mut self, opt: &DebuggerOption) -> Result<(), DebuggerError> {
+    /// Run the startup sequence; in batch mode, `continue` first. The outcome tells a batch
+    /// run how the program ended.
+    pub fn run_startup(&mut self, opt: &DebuggerOption) -> Result<RunOutcome, DebuggerError> {
+        self.checkpoint_files(opt)?;
         let startup_tokens = opt.startup.as_slice();
         let expr = crate::compound_command::startup_to_expr(startup_tokens);
         let startup = if opt.batch {
-            expr.with_continue_prepended().with_quit_appended()
+            expr.with_continue_prepended()
         } else {
             expr
         };
-        self.execute_command_expr(&startup).map(drop)
+        let result = self.execute_command_expr(&startup);
+        if let Some(path) = &opt.expect_output
+            && matches!(result, Ok(_) | Err(DebuggerError::ExitRequested))
+        {
+            self.check_output(path)?;
+        }
+        result
+    }
+
+    /// Write the vga framebuffer to `path`.
+    fn dump_display(&mut self, path: &Path) -> Result<(), DebuggerError> {
+        self.harness
+            .dut_state_mut()
+            .bus
+            .dump_display(path)
+            .ok_or_else(|| DebuggerError::Display("no display device (add a vga --dev)".into()))?
+            .map_err(|e| DebuggerError::Display(format!("{}: {e}", path.display())))?;
+        self.tracer
+            .borrow()
+            .print(&format!("frame written to {}", path.display()));
+        Ok(())
+    }
+
+    /// Compare what the `out=capture` UARTs printed with the contents of `path`.
+    fn check_output(&self, path: &Path) -> Result<(), DebuggerError> {
+        let expected = std::fs::read(path)
+            .map_err(|e| DebuggerError::Output(format!("{}: {e}", path.display())))?;
+        let actual = self
+            .harness
+            .dut_state()
+            .bus
+            .captured_output()
+            .ok_or_else(|| {
+                DebuggerError::Output(
+                    "no UART captures its output (add out=capture to a --dev)".into(),
+                )
+            })?;
+        if let Some(at) = actual
+            .iter()
+            .zip(&expected)
+            .position(|(a, e)| a != e)
+            .or((actual.len() != expected.len()).then(|| actual.len().min(expected.len())))
+        {
+            return Err(DebuggerError::Output(format!(
+                "guest output differs from {} at byte {at} ({} bytes printed, {} expected)",
+                path.display(),
+                actual.len(),
+                expected.len()
+            )));
+        }
+        self.tracer
+            .borrow()
+            .print(&format!("guest output matches {}", path.display()));
+        Ok(())
     }
 
     pub fn execute_line(&mut self, buffer: String) -> Result<RunOutcome, DebuggerError> {
@@ -47,27 +148,188 @@ impl<C: PlatformConfig> Debugger<C> {
 
         let blocks_iter = std::iter::once(first.clone()).chain(tail.iter().map(|(_, b)| b.clone()));
 
-        let mut parsed = Vec::new();
+        // Validate every block before running any; expressions are only syntax-checked here and
+        // evaluated when their own block runs.
+        let mut blocks = Vec::new();
         for block in blocks_iter {
             if block.is_empty() {
                 continue;
             }
-            parsed.push(self.parse_block(block)?);
+            self.parse_block(group_parens(block.clone()))?;
+            blocks.push(block);
         }
 
-        let mut parsed_iter = parsed.into_iter();
-        let first_cmd = match parsed_iter.next() {
-            Some(cmd) => cmd,
+        let mut blocks = blocks.into_iter();
+        let first_block = match blocks.next() {
+            Some(block) => block,
             None => return Ok(RunOutcome::Done),
         };
 
-        let mut outcome = self.execute_parsed(&first_cmd.command)?;
-        for (_, cmd_wrapper) in tail.iter().zip(parsed_iter) {
-            outcome = outcome.or_else(self.execute_parsed(&cmd_wrapper.command)?);
+        let mut outcome = self.execute_block(first_block)?;
+        for block in blocks {
+            outcome = outcome.or_else(self.execute_block(block)?);
         }
         Ok(outcome)
     }
 
+    fn execute_block(&mut self, block: Vec<String>) -> Result<RunOutcome, DebuggerError> {
+        let mut cmd = self.parse_block(group_parens(block))?;
+        self.execute_parsed(&mut cmd.command)
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<u64, ExprError> {
+        expr.eval(&mut DutExprContext {
+            harness: &mut self.harness,
+            symbols: &self.symbols,
+        })
+    }
+
+    fn eval_u32(&mut self, expr: &Expr) -> Result<u32, ExprError> {
+        expr.eval_as(&mut DutExprContext {
+            harness: &mut self.harness,
+            symbols: &self.symbols,
+        })
+    }
+
+    fn eval_arg<T: TryFrom<u64> + Copy>(&mut self, arg: &NumArg<T>) -> Result<T, ExprError> {
+        arg.eval(&mut DutExprContext {
+            harness: &mut self.harness,
+            symbols: &self.symbols,
+        })
+    }
+
+    fn resolve_state_args(&mut self, subcmd: &mut StateCmd) -> Result<(), ExprError> {
+        subcmd.resolve_args(&mut DutExprContext {
+            harness: &mut self.harness,
+            symbols: &self.symbols,
+        })
+    }
+
+    /// Run up to `max_steps` instructions, passing over breakpoint hits that should not stop
+    /// (disabled elsewhere, condition false, ignore count left).
+    fn run(&mut self, max_steps: Option<usize>) -> Result<RunOutcome, DebuggerError> {
+        self.list_next = None;
+        let mut remaining = max_steps;
+        loop {
+            let start = self.harness.total_instructions();
+            let err = match self.harness.run_steps(remaining) {
+                Err(err) if err.breakpoint_pc().is_some() => err,
+                Err(err) if err.is_difftest_mismatch() => {
+                    self.print_backtrace();
+                    return Err(DebuggerError::CommandExec(err));
+                }
+                result => return result.map_err(DebuggerError::CommandExec),
+            };
+            let pc = err.breakpoint_pc().unwrap();
+            self.check_panic_hook(pc)?;
+            if self.breakpoint_stops(pc)? {
+                return Err(DebuggerError::CommandExec(err));
+            }
+            let done = (self.harness.total_instructions() - start) as usize;
+            remaining = remaining.map(|n| n - done);
+        }
+    }
+
+    /// Decide whether a breakpoint stop at `pc` stops (see [`Breakpoints::hit`]). A stop on an
+    /// address with no breakpoint of ours (e.g. the program's own `ebreak`) always stops.
+    fn breakpoint_stops(&mut self, pc: u32) -> Result<bool, DebuggerError> {
+        if !self.breakpoints.any_enabled_at(pc) {
+            return Ok(true);
+        }
+        let stop = self.breakpoints.hit(pc, |cond| {
+            cond.eval(&mut DutExprContext {
+                harness: &mut self.harness,
+                symbols: &self.symbols,
+            })
+        })?;
+        if stop {
+            self.sync_breakpoint(pc)?;
+        }
+        Ok(stop)
+    }
+
+    /// Patch or unpatch `addr` on the DUT to match the enabled breakpoints there.
+    fn sync_breakpoint(&mut self, addr: u32) -> Result<(), DebuggerError> {
+        let result = if self.breakpoints.any_enabled_at(addr) || self.panic_hook == Some(addr) {
+            self.harness.set_breakpoint(addr)
+        } else {
+            // Not patched when every breakpoint here was already disabled.
+            let _ = self.harness.del_breakpoint(addr);
+            Ok(())
+        };
+        result.map_err(DebuggerError::CommandExec)
+    }
+
+    fn set_breakpoint(
+        &mut self,
+        spec: &BreakpointSpec,
+        temporary: bool,
+    ) -> Result<(), DebuggerError> {
+        let ignore = self.eval_arg(&spec.ignore)?;
+        for addr in self.resolve_location(&spec.location)? {
+            self.harness
+                .set_breakpoint(addr)
+                .map_err(DebuggerError::CommandExec)?;
+            let id = self
+                .breakpoints
+                .insert(addr, spec.cond.clone(), ignore, temporary);
+            let at = self
+                .source
+                .line_at(addr as u64)
+                .map(|l| format!(": {}:{}", l.file, l.line))
+                .unwrap_or_default();
+            self.tracer
+                .borrow()
+                .print(&format!("breakpoint {id} at 0x{addr:08x}{at}"));
+        }
+        Ok(())
+    }
+
+    fn breakpoint_exec(&mut self, subcmd: &BreakpointCmd) -> Result<(), DebuggerError> {
+        let not_found = |id: u32| DebuggerError::Breakpoint(format!("breakpoint {id} not found"));
+        match subcmd {
+            BreakpointCmd::Set { spec } => return self.set_breakpoint(spec, false),
+            BreakpointCmd::Tbreak { spec } => return self.set_breakpoint(spec, true),
+            BreakpointCmd::Del { id } => {
+                let bp = self.breakpoints.remove(*id).ok_or_else(|| not_found(*id))?;
+                self.sync_breakpoint(bp.addr)?;
+            }
+            BreakpointCmd::Enable { id } | BreakpointCmd::Disable { id } => {
+                let enabled = matches!(subcmd, BreakpointCmd::Enable { .. });
+                let bp = self
+                    .breakpoints
+                    .get_mut(*id)
+                    .ok_or_else(|| not_found(*id))?;
+                bp.enabled = enabled;
+                let addr = bp.addr;
+                self.sync_breakpoint(addr)?;
+            }
+            BreakpointCmd::Cond { id, cond } => {
+                if !self.breakpoints.set_condition(*id, cond.clone()) {
+                    return Err(not_found(*id));
+                }
+            }
+            BreakpointCmd::Ignore { id, count } => {
+                let count = self.eval_arg(count)?;
+                self.breakpoints
+                    .get_mut(*id)
+                    .ok_or_else(|| not_found(*id))?
+                    .ignore = count;
+            }
+            BreakpointCmd::Print => {
+                self.tracer
+                    .borrow()
+                    .breakpoint_print(&self.breakpoints.list());
+            }
+        }
+        Ok(())
+    }
+
+    /// Dump the harness instruction ring buffer (e.g. after a failed run).
+    pub fn print_iringbuf(&self) {
+        self.harness.print_iringbuf();
+    }
+
     fn parse_block(&self, mut tokens: Vec<String>) -> Result<DebuggerCommand, DebuggerError> {
         let mut commands = Vec::with_capacity(tokens.len() + 1);
         commands.push(env!("CARGO_PKG_NAME").to_string());
@@ -82,46 +344,106 @@ impl<C: PlatformConfig> Debugger<C> {
         }
     }
 
-    fn execute_parsed(&mut self, command: &Command) -> Result<RunOutcome, DebuggerError> {
+    fn execute_parsed(&mut self, command: &mut Command) -> Result<RunOutcome, DebuggerError> {
         match command {
-            Command::Step { times } => self
-                .harness
-                .run_steps(Some(*times))
-                .map_err(DebuggerError::CommandExec),
-            Command::Continue => self
-                .harness
-                .run_steps(None)
-                .map_err(DebuggerError::CommandExec),
+            Command::Step { times } => {
+                let times = self.eval_arg(times)?;
+                self.run(Some(times))
+            }
+            Command::Continue => self.run(None),
+            Command::StepLine { times } => {
+                let times = self.eval_arg(times)?;
+                self.step_lines(times, false)
+            }
+            Command::NextLine { times } => {
+                let times = self.eval_arg(times)?;
+                self.step_lines(times, true)
+            }
+            Command::List { location } => self.list(location.as_ref()).map(|()| RunOutcome::Done),
+            Command::Backtrace => {
+                self.print_backtrace();
+                Ok(RunOutcome::Done)
+            }
+            Command::Snapshot { subcmd } => self.snapshot_exec(subcmd).map(|()| RunOutcome::Done),
+            Command::ReverseStep { times } => {
+                let times = self.eval_arg(times)?;
+                self.reverse_step(times).map(|()| RunOutcome::Done)
+            }
+            Command::ReverseContinue => self.reverse_continue().map(|()| RunOutcome::Done),
+            Command::Print { expr } => {
+                let src = expr.join(" ");
+                let value = self.eval(&parse_expr(&src)?)?;
+                self.tracer
+                    .borrow()
+                    .print(&format!("{src} = 0x{value:x} ({value})"));
+                Ok(RunOutcome::Done)
+            }
             Command::Func { subcmd } => {
                 self.harness.func_exec(subcmd);
                 Ok(RunOutcome::Done)
             }
-            Command::State { subcmd } => self
-                .harness
-                .state_exec(subcmd)
-                .map_err(DebuggerError::CommandExec)
-                .map(|()| RunOutcome::Done),
-            Command::RefState { subcmd } => self
-                .harness
-                .ref_state_exec(subcmd)
-                .map_err(DebuggerError::CommandExec)
-                .map(|()| RunOutcome::Done),
-            Command::Breakpoint { subcmd } => match subcmd {
-                BreakpointCmd::Set { addr } => self
+            Command::State { subcmd } => {
+                self.resolve_state_args(subcmd)?;
+                self.harness
+                    .state_exec(subcmd)
+                    .map_err(DebuggerError::CommandExec)
+                    .map(|()| RunOutcome::Done)
+            }
+            Command::RefState { subcmd } => {
+                self.resolve_state_args(subcmd)?;
+                self.harness
+                    .ref_state_exec(subcmd)
+                    .map_err(DebuggerError::CommandExec)
+                    .map(|()| RunOutcome::Done)
+            }
+            Command::Breakpoint { subcmd } => {
+                self.breakpoint_exec(subcmd).map(|()| RunOutcome::Done)
+            }
+            Command::Catch { subcmd } => match subcmd {
+                CatchCmd::Set { cause } => self
                     .harness
-                    .set_breakpoint(*addr)
+                    .set_catch(*cause)
                     .map_err(DebuggerError::CommandExec)
                     .map(|()| RunOutcome::Done),
-                BreakpointCmd::Del { addr } => self
+                CatchCmd::Del { cause } => self
                     .harness
-                    .del_breakpoint(*addr)
+                    .del_catch(*cause)
                     .map_err(DebuggerError::CommandExec)
                     .map(|()| RunOutcome::Done),
-                BreakpointCmd::Print => {
-                    self.harness.print_breakpoints();
+                CatchCmd::Print => {
+                    self.harness.print_catches();
                     Ok(RunOutcome::Done)
                 }
             },
+            Command::Watch { subcmd } => {
+                let target = match subcmd {
+                    WatchCmd::Mem { addr, len, access } => WatchTarget::Mem {
+                        start: self.eval_arg(addr)?,
+                        len: self.eval_arg(len)?,
+                        access: *access,
+                    },
+                    WatchCmd::Gpr { reg } => WatchTarget::Gpr(*reg),
+                    WatchCmd::Csr { reg } => WatchTarget::Csr(*reg),
+                    WatchCmd::Del { id } => {
+                        return self
+                            .harness
+                            .del_watch(*id)
+                            .map_err(DebuggerError::CommandExec)
+                            .map(|()| RunOutcome::Done);
+                    }
+                    WatchCmd::Print => {
+                        self.harness.print_watches();
+                        return Ok(RunOutcome::Done);
+                    }
+                };
+                self.harness
+                    .set_watch(target)
+                    .map_err(DebuggerError::CommandExec)
+                    .map(|_| RunOutcome::Done)
+            }
+            Command::Display { subcmd } => match subcmd {
+                DisplayCmd::Dump { path } => self.dump_display(path).map(|()| RunOutcome::Done),
+            },
             Command::Stat { subcmd } => {
                 self.harness.stat_exec(subcmd);
                 Ok(RunOutcome::Done)
@@ -130,3 +452,202 @@ impl<C: PlatformConfig> Debugger<C> {
         }
     }
 }
+
+/// Join a token opening an unbalanced `(` with the following tokens up to the matching `)`, so
+/// `(main + 8)` reaches clap as one argument.
+fn group_parens(tokens: Vec<String>) -> Vec<String> {
+    let mut out = Vec::with_capacity(tokens.len());
+    let mut iter = tokens.into_iter();
+    while let Some(mut token) = iter.next() {
+        if token.starts_with('(') {
+            while paren_depth(&token) > 0 {
+                let Some(next) = iter.next() else { break };
+                token.push(' ');
+                token.push_str(&next);
+            }
+        }
+        out.push(token);
+    }
+    out
+}
+
+/// Net number of unclosed `(` in `s`.
+fn paren_depth(s: &str) -> isize {
+    s.chars().fold(0, |depth, c| match c {
+        '(' => depth + 1,
+        ')' => depth - 1,
+        _ => depth,
+    })
+}
+
+/// Expression view of the DUT: `$pc`, GPR / FPR / CSR names, bus memory (RAM only, no side
+/// effects on devices or observers) and ELF symbols.
+struct DutExprContext<'a, C: PlatformConfig> {
+    harness: &'a mut Harness<C>,
+    symbols: &'a SymbolTable,
+}
+
+impl<C: PlatformConfig> ExprContext for DutExprContext<'_, C> {
+    fn reg(&self, name: &str) -> Option<u64> {
+        let reg = &self.harness.dut_state().reg;
+        let value = if name.eq_ignore_ascii_case("pc") {
+            *reg.pc
+        } else if let Ok(gpr) = Gpr::from_str(name) {
+            reg.gpr.raw_read(gpr as usize)
+        } else if let Ok(fpr) = Fpr::from_str(name) {
+            reg.fpr.raw_read(fpr as usize)
+        } else {
+            reg.read_csr(Csr::from_str(name).ok()?)
+        };
+        Some(value as u64)
+    }
+
+    fn symbol(&self, name: &str) -> Option<u64> {
+        self.symbols.lookup(name)
+    }
+
+    fn read_mem(&mut self, addr: u64, width: MemWidth) -> Option<u64> {
+        let mut buf = [0u8; 8];
+        self.harness
+            .dut_state_mut()
+            .bus
+            .read_bytes(usize::try_from(addr).ok()?, &mut buf[..width.bytes()])
+            .ok()?;
+        Some(u64::from_le_bytes(buf))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::cell::RefCell;
+    use std::ops::Range;
+    use std::sync::atomic::AtomicBool;
+
+    use remu_harness::{SimulatorCore, SimulatorOption, SimulatorRemu};
+    use remu_isa::isa::extension_enum::RV32I;
+    use remu_state::StateFastProfile;
+    use remu_types::{AllUsize, DynDiagError, ExitCode, Tracer};
+
+    use super::*;
+
+    pub(crate) struct Remu;
+
+    impl PlatformConfig for Remu {
+        type Policy = StateFastProfile<RV32I>;
+        type Dut = SimulatorRemu<Self::Policy, true>;
+        type Ref = ();
+
+        fn create_dut(opt: &SimulatorOption, tracer: TracerDyn, irq: Arc<AtomicBool>) -> Self::Dut {
+            <Self::Dut as SimulatorCore<Self::Policy>>::new(opt.clone(), tracer, irq)
+        }
+
+        fn create_ref(_: &SimulatorOption, _: TracerDyn, _: Arc<AtomicBool>) {}
+    }
+
+    pub(crate) struct Quiet;
+
+    impl Tracer for Quiet {
+        fn print(&self, _: &str) {}
+        fn mem_print(&self, _: usize, _: &[u8], _: Result<(), Box<dyn DynDiagError>>) {}
+        fn mem_show(&self, _: usize, _: Result<AllUsize, Box<dyn DynDiagError>>) {}
+        fn mem_show_map(&self, _: Vec<(String, Range<usize>)>) {}
+        fn reg_print(&self, _: &[(Gpr, u32); 32], _: Range<usize>) {}
+        fn reg_show(&self, _: Gpr, _: u32) {}
+        fn disasm(&self, _: u64, _: u32) {}
+    }
+
+    #[derive(Parser)]
+    pub(crate) struct Args {
+        #[command(flatten)]
+        pub(crate) opt: DebuggerOption,
+    }
+
+    /// Print "ok" to the UART at 0x1000_0000 and exit through the finisher.
+    const HELLO: [u32; 9] = [
+        0x1000_05b7, // lui   a1, 0x10000
+        0x06f0_0513, // li    a0, 'o'
+        0x00a5_8023, // sb    a0, 0(a1)
+        0x06b0_0513, // li    a0, 'k'
+        0x00a5_8023, // sb    a0, 0(a1)
+        0x0010_0637, // lui   a2, 0x100
+        0x0000_5537, // lui   a0, 0x5
+        0x5555_0513, // addi  a0, a0, 0x555
+        0x00a6_2023, // sw    a0, 0(a2)
+    ];
+
+    /// Expected-output file, removed on drop.
+    struct Expected(std::path::PathBuf);
+
+    impl Drop for Expected {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// Run HELLO in batch mode against `expected`, its UART set to `out`.
+    fn run_hello(name: &str, expected: &[u8], out: &str) -> Result<RunOutcome, DebuggerError> {
+        let path = std::env::temp_dir().join(format!("remu-{}-{name}.txt", std::process::id()));
+        std::fs::write(&path, expected).unwrap();
+        let _expected = Expected(path.clone());
+        let uart = format!("uart16550@0x1000_0000,out={out}");
+        let args = Args::parse_from([
+            "remu",
+            "--batch",
+            "--uart-input",
+            "none",
+            "--expect-output",
+            path.to_str().unwrap(),
+            "--dev",
+            &uart,
+            "--dev",
+            "sifive_test_finisher@0x0010_0000",
+        ]);
+        let tracer: TracerDyn = Rc::new(RefCell::new(Quiet));
+        let opt = args.opt.clone();
+        let mut debugger: Debugger<Remu> =
+            Debugger::new(args.opt, tracer, Arc::new(AtomicBool::new(false)));
+        let program: Vec<u8> = HELLO.iter().flat_map(|i| i.to_le_bytes()).collect();
+        debugger
+            .harness
+            .write_dut_memory(0x8000_0000, &program)
+            .unwrap();
+        debugger.run_startup(&opt)
+    }
+
+    fn output_error(result: Result<RunOutcome, DebuggerError>) -> String {
+        match result {
+            Err(DebuggerError::Output(msg)) => msg,
+            other => panic!("expected an output mismatch, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn expect_output_matches() {
+        let outcome = run_hello("match", b"ok", "capture").unwrap();
+        assert!(matches!(outcome, RunOutcome::ProgramExit(ExitCode::Good)));
+    }
+
+    #[test]
+    fn expect_output_reports_first_differing_byte() {
+        let msg = output_error(run_hello("differ", b"on", "capture"));
+        assert!(
+            msg.contains("at byte 1 (2 bytes printed, 2 expected)"),
+            "{msg}"
+        );
+    }
+
+    #[test]
+    fn expect_output_reports_length_difference() {
+        let msg = output_error(run_hello("longer", b"ok\n", "capture"));
+        assert!(
+            msg.contains("at byte 2 (2 bytes printed, 3 expected)"),
+            "{msg}"
+        );
+    }
+
+    #[test]
+    fn expect_output_needs_a_capture() {
+        let msg = output_error(run_hello("none", b"ok", "none"));
+        assert!(msg.contains("out=capture"), "{msg}");
+    }
+}
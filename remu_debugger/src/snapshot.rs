@@ -0,0 +1,202 @@
+//! Named snapshots and reverse execution. Both sit on the harness checkpoints: going back
+//! restores the closest earlier checkpoint and re-executes forward, so the cost is bounded by
+//! the checkpoint interval rather than the length of the run.
+
+use std::collections::BTreeSet;
+
+use remu_harness::{
+    HarnessError, PlatformConfig, RunOutcome, RunState, SimulatorError, SimulatorInnerError,
+    Snapshot,
+};
+
+use crate::{Breakpoints, Debugger, DebuggerError, DebuggerOption, SnapshotCmd};
+
+pub(crate) struct NamedSnapshot<C: PlatformConfig> {
+    snap: Snapshot<C>,
+    breakpoints: Breakpoints,
+}
+
+impl<C: PlatformConfig> Debugger<C> {
+    pub(crate) fn snapshot_exec(&mut self, subcmd: &SnapshotCmd) -> Result<(), DebuggerError> {
+        let not_found =
+            |name: &str| DebuggerError::Snapshot(format!("snapshot '{name}' not found"));
+        match subcmd {
+            SnapshotCmd::Save { name } => {
+                let snap = self
+                    .harness
+                    .save_snapshot()
+                    .map_err(DebuggerError::CommandExec)?;
+                self.tracer.borrow().print(&format!(
+                    "snapshot '{name}' at instruction {}, pc 0x{:08x}",
+                    snap.position(),
+                    snap.pc()
+                ));
+                self.snapshots.insert(
+                    name.clone(),
+                    NamedSnapshot {
+                        snap,
+                        breakpoints: self.breakpoints.clone(),
+                    },
+                );
+            }
+            SnapshotCmd::Restore { name } => {
+                let saved = self.snapshots.get(name).ok_or_else(|| not_found(name))?;
+                let old = std::mem::replace(&mut self.breakpoints, saved.breakpoints.clone());
+                let addrs: BTreeSet<u32> = old.addrs().chain(self.breakpoints.addrs()).collect();
+                for addr in addrs {
+                    self.sync_breakpoint(addr)?;
+                }
+                // After the patches, so a breakpoint at the restored PC is stepped over.
+                let snap = &self.snapshots[name].snap;
+                self.harness
+                    .restore_snapshot(snap)
+                    .map_err(DebuggerError::CommandExec)?;
+                self.list_next = None;
+                self.tracer.borrow().print(&format!(
+                    "restored snapshot '{name}': instruction {}, pc 0x{:08x}",
+                    snap.position(),
+                    snap.pc()
+                ));
+            }
+            SnapshotCmd::Del { name } => {
+                self.snapshots.remove(name).ok_or_else(|| not_found(name))?;
+            }
+            SnapshotCmd::Print => {
+                let tracer = self.tracer.borrow();
+                for (name, saved) in &self.snapshots {
+                    tracer.print(&format!(
+                        "{name}: instruction {}, pc 0x{:08x}",
+                        saved.snap.position(),
+                        saved.snap.pc()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `--restore-checkpoint` and `--save-checkpoint`, before the startup sequence.
+    pub(crate) fn checkpoint_files(&mut self, opt: &DebuggerOption) -> Result<(), DebuggerError> {
+        if let Some(path) = &opt.restore_checkpoint {
+            self.harness
+                .load_checkpoint_file(path)
+                .map_err(DebuggerError::CommandExec)?;
+            self.tracer.borrow().print(&format!(
+                "restored checkpoint {}: instruction {}, pc 0x{:08x}",
+                path.display(),
+                self.harness.total_instructions(),
+                *self.harness.dut_state().reg.pc
+            ));
+        }
+        let (Some(path), Some(at)) = (&opt.save_checkpoint, opt.at_inst) else {
+            return Ok(());
+        };
+        while self.harness.total_instructions() < at {
+            let steps = (at - self.harness.total_instructions()) as usize;
+            let outcome = self
+                .harness
+                .run_steps(Some(steps))
+                .map_err(DebuggerError::CommandExec)?;
+            if let RunOutcome::ProgramExit(code) = outcome {
+                return Err(DebuggerError::Snapshot(format!(
+                    "program exited ({code}) at instruction {}, before checkpoint at {at}",
+                    self.harness.total_instructions()
+                )));
+            }
+        }
+        self.harness
+            .save_checkpoint_file(path)
+            .map_err(DebuggerError::CommandExec)?;
+        self.tracer.borrow().print(&format!(
+            "checkpoint written to {}: instruction {at}, pc 0x{:08x}",
+            path.display(),
+            *self.harness.dut_state().reg.pc
+        ));
+        if opt.batch {
+            return Err(DebuggerError::ExitRequested);
+        }
+        Ok(())
+    }
+
+    /// Go back `times` instructions (or to the start of the run).
+    pub(crate) fn reverse_step(&mut self, times: u64) -> Result<(), DebuggerError> {
+        self.list_next = None;
+        let target = self.harness.total_instructions().saturating_sub(times);
+        self.harness
+            .rewind_to(target)
+            .map_err(DebuggerError::CommandExec)
+    }
+
+    /// Go back to the latest point before now where a forward run would have stopped. Each
+    /// checkpoint interval is re-executed, newest first, until one contains such a stop.
+    pub(crate) fn reverse_continue(&mut self) -> Result<(), DebuggerError> {
+        self.list_next = None;
+        let now = self.harness.total_instructions();
+        let mut end = now;
+        while let Some(start) = self.harness.checkpoint_before(end) {
+            self.harness
+                .rewind_to(start)
+                .map_err(DebuggerError::CommandExec)?;
+            // The checkpoint itself may sit on a breakpoint, which resuming steps over.
+            let pc = *self.harness.dut_state().reg.pc;
+            let mut last = None;
+            if self.breakpoints.any_enabled_at(pc) && self.would_stop(pc)? {
+                last = Some((start, breakpoint_hit(pc)));
+            }
+            while self.harness.total_instructions() < end
+                && self.harness.run_state() != RunState::Exit
+            {
+                let remaining = (end - self.harness.total_instructions()) as usize;
+                let err = match self.harness.replay(Some(remaining)) {
+                    Ok(_) => continue,
+                    Err(err) if err.is_failure() || matches!(err, HarnessError::Interrupted) => {
+                        return Err(DebuggerError::CommandExec(err));
+                    }
+                    Err(err) => err,
+                };
+                if let Some(pc) = err.breakpoint_pc()
+                    && !self.would_stop(pc)?
+                {
+                    continue;
+                }
+                // A watchpoint or catchpoint stop lands after its instruction: the one that
+                // retired last is where we already are.
+                let position = self.harness.total_instructions();
+                if position < now {
+                    last = Some((position, err));
+                }
+            }
+            if let Some((position, err)) = last {
+                self.harness
+                    .rewind_to(position)
+                    .map_err(DebuggerError::CommandExec)?;
+                return Err(DebuggerError::CommandExec(err));
+            }
+            end = start;
+        }
+        self.harness
+            .rewind_to(0)
+            .map_err(DebuggerError::CommandExec)?;
+        self.tracer.borrow().print("reached the start of the run");
+        Ok(())
+    }
+
+    /// Whether a forward run would stop on the breakpoint hit at `pc`, without counting the hit.
+    fn would_stop(&mut self, pc: u32) -> Result<bool, DebuggerError> {
+        let ids = self.breakpoints.enabled_at(pc);
+        if ids.is_empty() {
+            return Ok(self.panic_hook != Some(pc));
+        }
+        for id in ids {
+            match self.breakpoints.condition(id).cloned() {
+                Some(cond) if self.eval(&cond)? == 0 => {}
+                _ => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn breakpoint_hit(pc: u32) -> HarnessError {
+    HarnessError::Simulator(SimulatorError::Dut(SimulatorInnerError::BreakpointHit(pc)))
+}
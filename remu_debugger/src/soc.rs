@@ -0,0 +1,267 @@
+//! SoC descriptions for `--soc`: the memory map, devices, reset PC and ISA of a platform in
+//! one file, instead of a long run of `--mem`/`--dev` flags.
+//!
+//! The file is TOML:
+//!
+//! ```toml
+//! isa = "riscv32im"
+//! init_pc = 0x8000_0000
+//! dtb = 0x87f0_0000  # as --dtb
+//!
+//! [[mem]]            # one per --mem
+//! name = "ram"
+//! start = 0x8000_0000
+//! end = 0x8800_0000
+//! attrs = "rwx"      # optional, as are the other keys of --mem
+//! image = "ram.bin"
+//!
+//! [[dev]]            # one per --dev; other keys are the device's parameters
+//! kind = "uart16550"
+//! start = 0x1000_0000
+//! out = "capture"
+//! ```
+
+use std::str::FromStr;
+
+use remu_isa::isa::IsaSpec;
+use remu_state::bus::{MemRegionSpec, device::DeviceConfig};
+use serde::Deserialize;
+
+use crate::DebuggerOption;
+
+/// Built-in SoC descriptions, by `--soc` name.
+const PRESETS: &[(&str, &str)] = &[
+    ("remu-default", include_str!("../soc/remu-default.toml")),
+    ("nemu", include_str!("../soc/nemu.toml")),
+    ("qemu-virt", include_str!("../soc/qemu-virt.toml")),
+];
+
+/// A parsed `--soc` description. Everything is optional; what is left out keeps the command
+/// line's defaults.
+#[derive(Debug, Clone, Default)]
+pub struct SocSpec {
+    pub isa: Option<IsaSpec>,
+    pub init_pc: Option<u32>,
+    pub dtb: Option<usize>,
+    pub mem: Vec<MemRegionSpec>,
+    pub devices: Vec<DeviceConfig>,
+}
+
+/// The file as written, before the tables are turned into `--mem`/`--dev` specs.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SocFile {
+    isa: Option<String>,
+    init_pc: Option<u32>,
+    dtb: Option<u64>,
+    #[serde(default)]
+    mem: Vec<MemTable>,
+    #[serde(default)]
+    dev: Vec<DevTable>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MemTable {
+    name: String,
+    start: u64,
+    end: u64,
+    attrs: Option<String>,
+    image: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DevTable {
+    kind: String,
+    start: u64,
+    #[serde(flatten)]
+    params: toml::Table,
+}
+
+impl SocSpec {
+    /// A preset name, or the path of a SoC file.
+    pub fn load(s: &str) -> Result<Self, String> {
+        if let Some((_, text)) = PRESETS.iter().find(|(name, _)| *name == s) {
+            return text.parse();
+        }
+        let text = std::fs::read_to_string(s).map_err(|e| {
+            let presets: Vec<_> = PRESETS.iter().map(|(name, _)| *name).collect();
+            format!(
+                "{s}: {e} (not a file, nor a preset: {})",
+                presets.join(", ")
+            )
+        })?;
+        text.parse().map_err(|e| format!("{s}: {e}"))
+    }
+
+    /// Fill in `option` from the description. `given` tells whether an option was on the
+    /// command line: `--isa`, `--init-pc` and `--dtb` given there win, while `--mem` and
+    /// `--dev` add to the description's regions and devices.
+    pub(crate) fn apply(&self, option: &mut DebuggerOption, given: impl Fn(&str) -> bool) {
+        if let Some(isa) = self.isa
+            && !given("isa")
+        {
+            option.isa = isa;
+        }
+        let state = &mut option.sim.sim.state;
+        if let Some(init_pc) = self.init_pc
+            && !given("init_pc")
+        {
+            state.reg.init_pc = init_pc;
+        }
+        if let Some(dtb) = self.dtb
+            && !given("dtb")
+        {
+            state.bus.dtb = Some(dtb);
+        }
+        if !self.mem.is_empty() {
+            let extra = if given("mem") {
+                std::mem::take(&mut state.bus.mem)
+            } else {
+                Vec::new()
+            };
+            state.bus.mem = self.mem.iter().cloned().chain(extra).collect();
+        }
+        if !self.devices.is_empty() {
+            let extra = if given("devices") {
+                std::mem::take(&mut state.bus.devices)
+            } else {
+                Vec::new()
+            };
+            state.bus.devices = self.devices.iter().cloned().chain(extra).collect();
+        }
+    }
+}
+
+impl MemTable {
+    fn spec(self) -> Result<MemRegionSpec, String> {
+        let mut spec = format!("{}@{:#x}:{:#x}", self.name, self.start, self.end);
+        if let Some(attrs) = self.attrs {
+            spec.push_str(&format!(":{attrs}"));
+        }
+        if let Some(image) = self.image {
+            spec.push_str(&format!(",image={image}"));
+        }
+        spec.parse()
+    }
+}
+
+impl DevTable {
+    fn spec(self) -> Result<DeviceConfig, String> {
+        let mut spec = format!("{}@{:#x}", self.kind, self.start);
+        for (key, value) in self.params {
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(n) => format!("{n:#x}"),
+                toml::Value::Boolean(b) => b.to_string(),
+                value => {
+                    return Err(format!(
+                        "invalid {key} {value}; expected a string, integer or boolean"
+                    ));
+                }
+            };
+            spec.push_str(&format!(",{key}={value}"));
+        }
+        spec.parse()
+    }
+}
+
+impl FromStr for SocSpec {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let file: SocFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let dtb = match file.dtb {
+            Some(addr) if !addr.is_multiple_of(8) => {
+                return Err(format!("invalid dtb {addr:#x}: not 8-byte aligned"));
+            }
+            dtb => dtb.map(|addr| addr as usize),
+        };
+        Ok(SocSpec {
+            isa: file.isa.as_deref().map(str::parse).transpose()?,
+            init_pc: file.init_pc,
+            dtb,
+            mem: file
+                .mem
+                .into_iter()
+                .enumerate()
+                .map(|(i, table)| table.spec().map_err(|e| format!("[[mem]] #{}: {e}", i + 1)))
+                .collect::<Result<_, _>>()?,
+            devices: file
+                .dev
+                .into_iter()
+                .enumerate()
+                .map(|(i, table)| table.spec().map_err(|e| format!("[[dev]] #{}: {e}", i + 1)))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use remu_state::bus::device::{DeviceKind, UartOutput};
+
+    #[test]
+    fn presets_parse() {
+        for (name, _) in PRESETS {
+            let soc = SocSpec::load(name).unwrap_or_else(|e| panic!("{name}: {e}"));
+            assert!(soc.isa.is_some() && !soc.mem.is_empty(), "{name}");
+        }
+    }
+
+    #[test]
+    fn tables_become_regions_and_devices() {
+        let soc: SocSpec = "\
+            init_pc = 0x2000_0000  # boot rom\n\
+            dtb = 0x2000_f000\n\
+            [[mem]]\n\
+            name = 'rom'\n\
+            start = 0x2000_0000\n\
+            end = 0x2001_0000\n\
+            attrs = \"rx\"\n\
+            [[dev]]\n\
+            kind = \"plic\"\n\
+            start = 0x0c00_0000\n\
+            [[dev]]\n\
+            kind = \"uart16550\"\n\
+            start = 0x1000_0000\n\
+            irq = 10\n\
+            out = \"file:uart#0.log\"\n"
+            .parse()
+            .unwrap();
+        assert_eq!(soc.init_pc, Some(0x2000_0000));
+        assert_eq!(soc.dtb, Some(0x2000_f000));
+        assert_eq!(soc.mem[0].region, 0x2000_0000..0x2001_0000);
+        assert_eq!(soc.mem[0].attrs, "rx".parse().unwrap());
+        assert_eq!(soc.devices.len(), 2);
+        assert_eq!(soc.devices[1].kind, DeviceKind::Uart16550);
+        assert_eq!(soc.devices[1].irq, Some(10));
+        assert_eq!(
+            soc.devices[1].out,
+            Some(UartOutput::File("uart#0.log".into()))
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = "isa = \"riscv32i\"\n[[dev]]\nkind = uart\n"
+            .parse::<SocSpec>()
+            .unwrap_err();
+        assert!(err.contains("line 3"), "{err}");
+        let err = "[[mem]]\nname = \"ram\"\nstart = 0\n"
+            .parse::<SocSpec>()
+            .unwrap_err();
+        assert!(err.contains("missing field `end`"), "{err}");
+        let err = "[[mem]]\nname = \"ram\"\nstart = 0\nend = 0x1000\nsize = 4\n"
+            .parse::<SocSpec>()
+            .unwrap_err();
+        assert!(err.contains("unknown field `size`"), "{err}");
+        let err = "[[dev]]\nkind = \"uart16550\"\nstart = 0\nout = [1]\n"
+            .parse::<SocSpec>()
+            .unwrap_err();
+        assert!(err.starts_with("[[dev]] #1: invalid out"), "{err}");
+        assert!("dtb = 0x1004\n".parse::<SocSpec>().is_err());
+        assert!("[cpu]\n".parse::<SocSpec>().is_err());
+    }
+}
@@ -0,0 +1,465 @@
+//! DWARF line info (`.debug_line` plus inlined subroutines) for source-level debugging:
+//! `list`, `step-line` / `next-line`, `breakpoint set file.rs:42` and annotated traces.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use gimli::{EndianRcSlice, Reader as _, RunTimeEndian};
+use object::{Object as _, ObjectSection as _};
+use remu_harness::{PlatformConfig, RunOutcome, SourceLine, SourceLookup};
+
+use crate::{Debugger, DebuggerError, Expr, ExprError, parse_expr};
+
+type Reader = EndianRcSlice<RunTimeEndian>;
+
+/// Where `list` and `breakpoint set` point: `file:line` or an address expression.
+#[derive(Debug, Clone)]
+pub enum Location {
+    /// Source line; `file` matches any compiled file whose path ends with it (e.g. `main.rs`).
+    Line {
+        file: String,
+        line: u32,
+    },
+    Addr(Expr),
+}
+
+impl FromStr for Location {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Expressions never contain ':', so `something:digits` is always a source line.
+        if let Some((file, line)) = s.rsplit_once(':')
+            && !file.is_empty()
+            && let Ok(line) = line.parse()
+        {
+            return Ok(Location::Line {
+                file: file.to_string(),
+                line,
+            });
+        }
+        parse_expr(s).map(Location::Addr)
+    }
+}
+
+pub(crate) fn parse_location(s: &str) -> Result<Location, ExprError> {
+    s.parse()
+}
+
+/// One line-table row: `[addr, end)` belongs to `file:line`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LineRow {
+    pub(crate) addr: u32,
+    pub(crate) end: u32,
+    pub(crate) file: usize,
+    pub(crate) line: u32,
+    /// Recommended breakpoint / step stop location.
+    pub(crate) is_stmt: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct SourceInfo {
+    files: Vec<Rc<Path>>,
+    /// Sorted by address, non-overlapping.
+    rows: Vec<LineRow>,
+    ctx: Option<addr2line::Context<Reader>>,
+    /// Source text per file index; `None` when the file cannot be read.
+    text: RefCell<HashMap<usize, Option<Rc<[String]>>>>,
+}
+
+impl SourceInfo {
+    /// Best-effort load of the line tables of `elf`; empty when there is no ELF or no debug info.
+    pub(crate) fn load(elf: Option<&Path>) -> Self {
+        let Some(path) = elf else {
+            return Self::default();
+        };
+        let buf = match std::fs::read(path) {
+            Ok(b) => b,
+            Err(err) => {
+                tracing::warn!("source: failed to read '{}': {err}", path.display());
+                return Self::default();
+            }
+        };
+        match Self::parse(&buf) {
+            Ok(info) => info,
+            Err(err) => {
+                tracing::warn!("source: bad debug info in '{}': {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    fn parse(buf: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let obj = object::File::parse(buf)?;
+        let endian = if obj.is_little_endian() {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+        let dwarf = gimli::Dwarf::load(|id| -> Result<Reader, gimli::Error> {
+            let data = obj
+                .section_by_name(id.name())
+                .and_then(|s| s.data().ok())
+                .unwrap_or_default();
+            Ok(Reader::new(Rc::from(data), endian))
+        })?;
+
+        let mut info = Self::default();
+        let mut file_ids: HashMap<PathBuf, usize> = HashMap::new();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let Some(program) = unit.line_program.clone() else {
+                continue;
+            };
+            let mut rows = program.rows();
+            let mut prev: Option<LineRow> = None;
+            while let Some((header, row)) = rows.next_row()? {
+                let addr = row.address() as u32;
+                if let Some(mut p) = prev.take()
+                    && addr > p.addr
+                {
+                    p.end = addr;
+                    info.rows.push(p);
+                }
+                if row.end_sequence() {
+                    continue;
+                }
+                let (Some(line), Some(path)) = (
+                    row.line(),
+                    row.file(header)
+                        .and_then(|f| file_path(&dwarf, &unit, header, f)),
+                ) else {
+                    continue;
+                };
+                let next_id = info.files.len();
+                let file = *file_ids.entry(path).or_insert_with_key(|path| {
+                    info.files.push(Rc::from(path.as_path()));
+                    next_id
+                });
+                prev = Some(LineRow {
+                    addr,
+                    end: addr,
+                    file,
+                    line: line.get() as u32,
+                    is_stmt: row.is_stmt(),
+                });
+            }
+        }
+        info.rows.sort_by_key(|r| r.addr);
+        info.rows.dedup_by_key(|r| r.addr);
+        info.ctx = addr2line::Context::from_dwarf(dwarf).ok();
+        Ok(info)
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub(crate) fn row_at(&self, pc: u32) -> Option<&LineRow> {
+        let idx = self.rows.partition_point(|r| r.addr <= pc).checked_sub(1)?;
+        let row = &self.rows[idx];
+        (pc < row.end).then_some(row)
+    }
+
+    /// Address range of the whole line `pc` is on: adjacent rows of the same `file:line`.
+    pub(crate) fn line_range(&self, pc: u32) -> Option<(u32, u32)> {
+        let idx = self.rows.partition_point(|r| r.addr <= pc).checked_sub(1)?;
+        let row = self.rows[idx];
+        if pc >= row.end {
+            return None;
+        }
+        let same = |r: &LineRow| r.file == row.file && r.line == row.line;
+        let mut start = idx;
+        while start > 0
+            && same(&self.rows[start - 1])
+            && self.rows[start - 1].end == self.rows[start].addr
+        {
+            start -= 1;
+        }
+        let mut end = idx;
+        while end + 1 < self.rows.len()
+            && same(&self.rows[end + 1])
+            && self.rows[end].end == self.rows[end + 1].addr
+        {
+            end += 1;
+        }
+        Some((self.rows[start].addr, self.rows[end].end))
+    }
+
+    pub(crate) fn path(&self, file: usize) -> &Path {
+        &self.files[file]
+    }
+
+    /// Function names at `pc`, innermost (inlined) first; one entry when nothing is inlined.
+    pub(crate) fn functions(&self, pc: u32) -> Vec<Option<String>> {
+        let Some(ctx) = &self.ctx else {
+            return Vec::new();
+        };
+        let Ok(mut frames) = ctx.find_frames(pc as u64).skip_all_loads() else {
+            return Vec::new();
+        };
+        let mut out = Vec::new();
+        while let Ok(Some(frame)) = frames.next() {
+            out.push(
+                frame
+                    .function
+                    .and_then(|f| f.demangle().ok().map(|n| n.into_owned())),
+            );
+        }
+        out
+    }
+
+    /// Start addresses of `file:line`, one per place the line was emitted (inlined copies,
+    /// split code). A line without code moves to the next line that has some.
+    pub(crate) fn resolve_line(&self, file: &str, line: u32) -> Vec<u32> {
+        let files: Vec<usize> = (0..self.files.len())
+            .filter(|&i| self.files[i].ends_with(file))
+            .collect();
+        let Some(line) = self
+            .rows
+            .iter()
+            .filter(|r| files.contains(&r.file) && r.line >= line)
+            .map(|r| r.line)
+            .min()
+        else {
+            return Vec::new();
+        };
+        let hit = |r: &LineRow| files.contains(&r.file) && r.line == line;
+        self.rows
+            .iter()
+            .enumerate()
+            .filter(|(i, r)| {
+                hit(r)
+                    && r.is_stmt
+                    && !(*i > 0 && hit(&self.rows[i - 1]) && self.rows[i - 1].end == r.addr)
+            })
+            .map(|(_, r)| r.addr)
+            .collect()
+    }
+
+    /// Index of the compiled file matching `file` (path suffix), if any.
+    pub(crate) fn find_file(&self, file: &str) -> Option<usize> {
+        self.files.iter().position(|p| p.ends_with(file))
+    }
+
+    /// Lines `range` (1-based, clamped) of `file`; `None` when the file cannot be read.
+    pub(crate) fn lines(&self, file: usize, first: u32, count: u32) -> Option<Vec<(u32, String)>> {
+        let text = self.text(file)?;
+        let first = first.max(1);
+        Some(
+            (first..first.saturating_add(count))
+                .filter_map(|n| Some((n, text.get(n as usize - 1)?.clone())))
+                .collect(),
+        )
+    }
+
+    fn text(&self, file: usize) -> Option<Rc<[String]>> {
+        self.text
+            .borrow_mut()
+            .entry(file)
+            .or_insert_with(|| {
+                let src = std::fs::read_to_string(&self.files[file]).ok()?;
+                Some(src.lines().map(str::to_string).collect())
+            })
+            .clone()
+    }
+}
+
+impl SourceLookup for SourceInfo {
+    fn line_at(&self, pc: u64) -> Option<SourceLine> {
+        let row = *self.row_at(u32::try_from(pc).ok()?)?;
+        Some(SourceLine {
+            function: self.functions(row.addr).into_iter().next().flatten(),
+            file: self.files[row.file].display().to_string(),
+            line: row.line,
+            text: self
+                .text(row.file)
+                .and_then(|t| t.get(row.line as usize - 1).cloned()),
+        })
+    }
+}
+
+/// `comp_dir / directory / name`; later absolute parts replace earlier ones.
+fn file_path(
+    dwarf: &gimli::Dwarf<Reader>,
+    unit: &gimli::Unit<Reader>,
+    header: &gimli::LineProgramHeader<Reader>,
+    file: &gimli::FileEntry<Reader>,
+) -> Option<PathBuf> {
+    let attr = |value| -> Option<String> {
+        let s = dwarf.attr_string(unit, value).ok()?;
+        Some(s.to_string_lossy().ok()?.into_owned())
+    };
+    let mut path = PathBuf::new();
+    if let Some(comp_dir) = &unit.comp_dir {
+        path.push(comp_dir.to_string_lossy().ok()?.as_ref());
+    }
+    if let Some(dir) = file.directory(header) {
+        path.push(attr(dir)?);
+    }
+    path.push(attr(file.path_name())?);
+    Some(path)
+}
+
+impl<C: PlatformConfig> Debugger<C> {
+    /// Addresses `location` resolves to: every start of a source line, or the one address.
+    pub(crate) fn resolve_location(
+        &mut self,
+        location: &Location,
+    ) -> Result<Vec<u32>, DebuggerError> {
+        match location {
+            Location::Addr(expr) => Ok(vec![self.eval_u32(expr)?]),
+            Location::Line { file, line } => {
+                let addrs = self.source.resolve_line(file, *line);
+                if addrs.is_empty() {
+                    return Err(DebuggerError::Source(format!("no code at {file}:{line}")));
+                }
+                Ok(addrs)
+            }
+        }
+    }
+
+    /// `step-line` / `next-line`: run until the PC leaves the current line and reaches the start
+    /// of another. `over` passes over calls and inlined functions.
+    pub(crate) fn step_lines(
+        &mut self,
+        times: usize,
+        over: bool,
+    ) -> Result<RunOutcome, DebuggerError> {
+        if self.source.is_empty() {
+            return Err(DebuggerError::Source(
+                "no line info (needs --elf with debug info)".into(),
+            ));
+        }
+        for _ in 0..times {
+            let outcome = self.step_line(over)?;
+            if !matches!(outcome, RunOutcome::Done) {
+                return Ok(outcome);
+            }
+        }
+        let pc = self.pc();
+        match self.source.line_at(pc as u64) {
+            Some(line) => self.tracer.borrow().source_line(&line),
+            None => self
+                .tracer
+                .borrow()
+                .print(&format!("0x{pc:08x} (no line info)")),
+        }
+        Ok(RunOutcome::Done)
+    }
+
+    fn step_line(&mut self, over: bool) -> Result<RunOutcome, DebuggerError> {
+        let start = self.pc();
+        let range = self.source.line_range(start);
+        let depth = self.source.functions(start).len();
+        // Call nesting relative to the start.
+        let mut calls = 0isize;
+        loop {
+            let pc = self.pc();
+            calls += self.harness.fetch_inst(pc).map_or(0, call_delta);
+            let outcome = self.run(Some(1))?;
+            if !matches!(outcome, RunOutcome::Done) {
+                return Ok(outcome);
+            }
+            let pc = self.pc();
+            if range.is_some_and(|(s, e)| (s..e).contains(&pc)) {
+                continue;
+            }
+            if !self
+                .source
+                .row_at(pc)
+                .is_some_and(|r| r.is_stmt && r.addr == pc)
+            {
+                continue;
+            }
+            if over && (calls > 0 || calls == 0 && self.source.functions(pc).len() > depth) {
+                continue;
+            }
+            return Ok(RunOutcome::Done);
+        }
+    }
+
+    /// `list`: ten lines around `location` (default: the PC); without a location a repeated
+    /// `list` continues where the last one stopped.
+    pub(crate) fn list(&mut self, location: Option<&Location>) -> Result<(), DebuggerError> {
+        let no_info = |what: String| DebuggerError::Source(format!("no line info for {what}"));
+        let (file, first) = match (location, self.list_next) {
+            (None, Some(next)) => next,
+            (None, None) => {
+                let pc = self.pc();
+                let row = self
+                    .source
+                    .row_at(pc)
+                    .ok_or_else(|| no_info(format!("0x{pc:08x}")))?;
+                (row.file, row.line.saturating_sub(5))
+            }
+            (Some(Location::Line { file, line }), _) => (
+                self.source
+                    .find_file(file)
+                    .ok_or_else(|| no_info(file.clone()))?,
+                line.saturating_sub(5),
+            ),
+            (Some(Location::Addr(expr)), _) => {
+                let addr = self.eval_u32(expr)?;
+                let row = self
+                    .source
+                    .row_at(addr)
+                    .ok_or_else(|| no_info(format!("0x{addr:08x}")))?;
+                (row.file, row.line.saturating_sub(5))
+            }
+        };
+        let path = self.source.path(file).display().to_string();
+        let lines = self
+            .source
+            .lines(file, first, 10)
+            .ok_or_else(|| DebuggerError::Source(format!("cannot read {path}")))?;
+        let current = self
+            .source
+            .row_at(self.pc())
+            .filter(|r| r.file == file)
+            .map(|r| r.line);
+        self.tracer.borrow().source_list(&path, &lines, current);
+        self.list_next = Some((file, first.max(1) + 10));
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn pc(&self) -> u32 {
+        *self.harness.dut_state().reg.pc
+    }
+}
+
+/// +1 for a call (`jal` / `jalr` linking `ra` or `t0`), -1 for a return (`jalr x0, 0(ra|t0)`).
+fn call_delta(inst: u32) -> isize {
+    let opcode = inst & 0x7f;
+    let rd = (inst >> 7) & 0x1f;
+    let rs1 = (inst >> 15) & 0x1f;
+    let link = |r: u32| r == 1 || r == 5;
+    match opcode {
+        0x6f | 0x67 if link(rd) => 1,
+        0x67 if rd == 0 && link(rs1) => -1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_and_calls() {
+        assert!(matches!(
+            "src/main.rs:42".parse(),
+            Ok(Location::Line { ref file, line: 42 }) if file == "src/main.rs"
+        ));
+        assert!(matches!("main".parse(), Ok(Location::Addr(_))));
+        assert!(matches!("(main + 8)".parse(), Ok(Location::Addr(_))));
+        assert_eq!(call_delta(0x00c0_00ef), 1); // jal ra, +12
+        assert_eq!(call_delta(0x0000_8067), -1); // ret
+        assert_eq!(call_delta(0xffdf_f06f), 0); // j -4
+    }
+}
@@ -0,0 +1,93 @@
+//! ELF symbol table, used to resolve names in debugger expressions and to symbolize addresses.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use object::{Object as _, ObjectSymbol as _, SymbolKind};
+
+#[derive(Debug, Default)]
+pub(crate) struct SymbolTable {
+    by_name: HashMap<String, u64>,
+    /// Function symbols `(addr, size, demangled name)`, sorted by address.
+    by_addr: Vec<(u64, u64, String)>,
+}
+
+impl SymbolTable {
+    /// Best-effort load of defined symbols from `elf`; empty when there is no ELF or it cannot be read.
+    pub(crate) fn load(elf: Option<&Path>) -> Self {
+        let Some(path) = elf else {
+            return Self::default();
+        };
+        let buf = match std::fs::read(path) {
+            Ok(b) => b,
+            Err(err) => {
+                tracing::warn!("symbols: failed to read '{}': {err}", path.display());
+                return Self::default();
+            }
+        };
+        let obj = match object::File::parse(buf.as_slice()) {
+            Ok(o) => o,
+            Err(err) => {
+                tracing::warn!("symbols: failed to parse '{}': {err}", path.display());
+                return Self::default();
+            }
+        };
+        let by_name = obj
+            .symbols()
+            .filter(|s| s.is_definition())
+            .filter_map(|s| Some((s.name().ok()?.to_string(), s.address())))
+            .filter(|(name, _)| !name.is_empty())
+            .collect();
+        let mut by_addr: Vec<_> = obj
+            .symbols()
+            .filter(|s| s.is_definition() && s.kind() == SymbolKind::Text)
+            .filter_map(|s| {
+                let name = s.name().ok().filter(|n| !n.is_empty())?;
+                let name = format!("{:#}", rustc_demangle::demangle(name));
+                Some((s.address(), s.size(), name))
+            })
+            .collect();
+        by_addr.sort_by_key(|&(addr, ..)| addr);
+        Self { by_name, by_addr }
+    }
+
+    #[inline(always)]
+    pub(crate) fn lookup(&self, name: &str) -> Option<u64> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Function containing `addr` and the offset into it. Symbols without a size cover
+    /// everything up to the next one.
+    pub(crate) fn symbolize(&self, addr: u64) -> Option<(&str, u64)> {
+        let idx = self
+            .by_addr
+            .partition_point(|&(a, ..)| a <= addr)
+            .checked_sub(1)?;
+        let (start, size, name) = &self.by_addr[idx];
+        (*size == 0 || addr < start + size).then(|| (name.as_str(), addr - start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbolize_gives_the_enclosing_function_and_offset() {
+        let table = SymbolTable {
+            by_name: HashMap::new(),
+            by_addr: vec![
+                (0x8000_0000, 0x10, "_start".into()),
+                (0x8000_0100, 0x20, "main".into()),
+                (0x8000_0200, 0, "spin".into()),
+            ],
+        };
+        assert_eq!(table.symbolize(0x8000_0000), Some(("_start", 0)));
+        assert_eq!(table.symbolize(0x8000_0108), Some(("main", 8)));
+        // Past the end of a sized symbol, and before the first one.
+        assert_eq!(table.symbolize(0x8000_0120), None);
+        assert_eq!(table.symbolize(0x7fff_fffc), None);
+        // Unsized: runs to the end of the address space.
+        assert_eq!(table.symbolize(0x8000_1000), Some(("spin", 0xe00)));
+    }
+}
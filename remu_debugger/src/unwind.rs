@@ -0,0 +1,344 @@
+//! Guest stack unwinding for `backtrace`: `.eh_frame` / `.debug_frame` CFI from the ELF, with a
+//! frame-pointer walk (`ra` at `fp - 4`, caller `fp` at `fp - 8`) where there is none.
+
+use std::path::Path;
+use std::rc::Rc;
+
+use gimli::{
+    BaseAddresses, CfaRule, DebugFrame, EhFrame, EndianRcSlice, Register, RegisterRule,
+    RunTimeEndian, UnwindContext, UnwindSection,
+};
+use object::{Object as _, ObjectSection as _};
+use remu_harness::{PlatformConfig, SourceLookup as _, StackFrame};
+use remu_isa::isa::reg::RegAccess as _;
+
+use crate::{Debugger, DebuggerError};
+
+type Reader = EndianRcSlice<RunTimeEndian>;
+
+/// GPR values of one frame; `None` where unwinding could not recover the value.
+type Regs = [Option<u32>; 32];
+
+const RA: usize = 1;
+const SP: usize = 2;
+const FP: usize = 8;
+
+/// Frames beyond this are assumed to be a corrupt stack.
+const MAX_FRAMES: usize = 64;
+
+#[derive(Default)]
+pub(crate) struct Unwinder {
+    eh_frame: Option<(EhFrame<Reader>, BaseAddresses)>,
+    debug_frame: Option<(DebugFrame<Reader>, BaseAddresses)>,
+}
+
+impl Unwinder {
+    /// Best-effort load of the CFI sections of `elf`; empty (frame pointers only) on failure.
+    pub(crate) fn load(elf: Option<&Path>) -> Self {
+        let Some(path) = elf else {
+            return Self::default();
+        };
+        let buf = match std::fs::read(path) {
+            Ok(b) => b,
+            Err(err) => {
+                tracing::warn!("unwind: failed to read '{}': {err}", path.display());
+                return Self::default();
+            }
+        };
+        let obj = match object::File::parse(buf.as_slice()) {
+            Ok(o) => o,
+            Err(err) => {
+                tracing::warn!("unwind: failed to parse '{}': {err}", path.display());
+                return Self::default();
+            }
+        };
+        let endian = if obj.is_little_endian() {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+        let address_size = if obj.is_64() { 8 } else { 4 };
+        let text = obj.section_by_name(".text").map(|s| s.address());
+        let section = |name| {
+            let section = obj.section_by_name(name)?;
+            let data = section.data().ok().filter(|d| !d.is_empty())?;
+            Some((Reader::new(Rc::from(data), endian), section.address()))
+        };
+
+        let eh_frame = section(".eh_frame").map(|(data, addr)| {
+            let mut eh_frame = EhFrame::from(data);
+            eh_frame.set_address_size(address_size);
+            let mut bases = BaseAddresses::default().set_eh_frame(addr);
+            if let Some(text) = text {
+                bases = bases.set_text(text);
+            }
+            (eh_frame, bases)
+        });
+        let debug_frame = section(".debug_frame").map(|(data, _)| {
+            let mut debug_frame = DebugFrame::from(data);
+            debug_frame.set_address_size(address_size);
+            (debug_frame, BaseAddresses::default())
+        });
+        Self {
+            eh_frame,
+            debug_frame,
+        }
+    }
+
+    /// Caller PC and registers of the frame executing `pc`, from CFI.
+    fn step(
+        &self,
+        pc: u32,
+        regs: &Regs,
+        read: &mut impl FnMut(u32) -> Option<u32>,
+    ) -> Option<(u32, Regs)> {
+        if let Some((section, bases)) = &self.eh_frame
+            && let Some(caller) = cfi_step(section, bases, pc, regs, read)
+        {
+            return Some(caller);
+        }
+        let (section, bases) = self.debug_frame.as_ref()?;
+        cfi_step(section, bases, pc, regs, read)
+    }
+}
+
+fn cfi_step<S: UnwindSection<Reader>>(
+    section: &S,
+    bases: &BaseAddresses,
+    pc: u32,
+    regs: &Regs,
+    read: &mut impl FnMut(u32) -> Option<u32>,
+) -> Option<(u32, Regs)> {
+    let mut ctx = UnwindContext::new();
+    let row = section
+        .unwind_info_for_address(bases, &mut ctx, pc as u64, S::cie_from_offset)
+        .ok()?;
+    let cfa = match row.cfa() {
+        CfaRule::RegisterAndOffset { register, offset } => {
+            (*regs.get(register.0 as usize)?)?.wrapping_add(*offset as u32)
+        }
+        CfaRule::Expression(_) => return None,
+    };
+    // Registers without a rule keep their value (callee-saved or untouched).
+    let mut caller = *regs;
+    caller[SP] = Some(cfa);
+    for (Register(reg), rule) in row.registers() {
+        let Some(slot) = caller.get_mut(*reg as usize) else {
+            continue;
+        };
+        *slot = match rule {
+            RegisterRule::SameValue => continue,
+            RegisterRule::Offset(offset) => read(cfa.wrapping_add(*offset as u32)),
+            RegisterRule::ValOffset(offset) => Some(cfa.wrapping_add(*offset as u32)),
+            RegisterRule::Register(Register(from)) => *regs.get(*from as usize)?,
+            _ => None,
+        };
+    }
+    Some((caller[RA]?, caller))
+}
+
+/// Standard RISC-V frame-pointer layout: the frame record sits just below `fp`.
+fn fp_step(regs: &Regs, read: &mut impl FnMut(u32) -> Option<u32>) -> Option<(u32, Regs)> {
+    let fp = regs[FP]?;
+    if fp == 0 || fp % 4 != 0 {
+        return None;
+    }
+    let ra = read(fp.wrapping_sub(4))?;
+    let mut caller = *regs;
+    caller[RA] = Some(ra);
+    caller[SP] = Some(fp);
+    caller[FP] = read(fp.wrapping_sub(8));
+    Some((ra, caller))
+}
+
+impl<C: PlatformConfig> Debugger<C> {
+    /// Unwind the DUT's current call stack, innermost frame first.
+    pub(crate) fn backtrace(&mut self) -> Vec<StackFrame> {
+        let reg = &self.harness.dut_state().reg;
+        let mut regs: Regs = std::array::from_fn(|i| Some(reg.gpr.raw_read(i)));
+        let mut pc = *reg.pc;
+        let harness = &mut self.harness;
+        let mut read = |addr: u32| {
+            let mut buf = [0u8; 4];
+            harness
+                .dut_state_mut()
+                .bus
+                .read_bytes(addr as usize, &mut buf)
+                .ok()?;
+            Some(u32::from_le_bytes(buf))
+        };
+
+        let mut frames = Vec::new();
+        for depth in 0..MAX_FRAMES {
+            // A return address points after the call; the call itself is what belongs to the
+            // caller's CFI row and source line.
+            let at = if depth == 0 { pc } else { pc.wrapping_sub(4) };
+            let symbol = self
+                .symbols
+                .symbolize(pc as u64)
+                .map(|(name, offset)| (name.to_string(), offset as u32));
+            let known = symbol.is_some();
+            frames.push(StackFrame {
+                pc,
+                symbol,
+                line: self.source.line_at(at as u64),
+            });
+            if depth > 0 && !known {
+                break;
+            }
+            let Some((caller_pc, caller_regs)) = self
+                .unwinder
+                .step(at, &regs, &mut read)
+                .or_else(|| fp_step(&regs, &mut read))
+            else {
+                break;
+            };
+            // No progress: a corrupt or self-referencing frame.
+            if caller_pc == 0 || (caller_pc == pc && caller_regs[SP] == regs[SP]) {
+                break;
+            }
+            pc = caller_pc;
+            regs = caller_regs;
+        }
+        frames
+    }
+
+    pub(crate) fn print_backtrace(&mut self) {
+        let frames = self.backtrace();
+        self.tracer.borrow().backtrace_print(&frames);
+    }
+
+    /// Stop on the guest's panic handler, so a panicking program (which would otherwise spin in
+    /// `panic-halt`) stops with a backtrace.
+    pub(crate) fn install_panic_hook(&mut self) {
+        self.panic_hook = self
+            .symbols
+            .lookup("rust_begin_unwind")
+            .map(|addr| addr as u32)
+            .filter(|&addr| self.harness.set_breakpoint(addr).is_ok());
+    }
+
+    /// Called on every breakpoint stop: `Err` when it is the guest panic handler.
+    pub(crate) fn check_panic_hook(&mut self, pc: u32) -> Result<(), DebuggerError> {
+        if self.panic_hook != Some(pc) {
+            return Ok(());
+        }
+        self.tracer.borrow().print("guest panic, backtrace:");
+        self.print_backtrace();
+        Err(DebuggerError::GuestPanic(pc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    const CALLER: u32 = 0x8000_0010;
+    const FUNC: u32 = 0x8000_0100;
+
+    /// `.eh_frame` with one FDE for FUNC..FUNC+0x20, whose prologue is
+    /// `addi sp, sp, -16; sw ra, 12(sp); sw s0, 8(sp)`.
+    fn eh_frame() -> Vec<u8> {
+        let mut cie = vec![0, 0, 0, 0, 1, b'z', b'R', 0];
+        cie.extend([
+            1,    // code alignment
+            0x7c, // data alignment -4
+            1,    // return address: ra
+            1,    // augmentation data length
+            0x00, // FDE pointers: absolute
+            0x0c, 2, 0, // DW_CFA_def_cfa sp, 0
+        ]);
+        let mut fde = 24u32.to_le_bytes().to_vec(); // back to the CIE
+        fde.extend(FUNC.to_le_bytes());
+        fde.extend(0x20u32.to_le_bytes());
+        fde.extend([
+            0,    // augmentation data length
+            0x44, // DW_CFA_advance_loc 4
+            0x0e, 16, // DW_CFA_def_cfa_offset 16
+            0x81, 1, // DW_CFA_offset ra, cfa - 4
+            0x88, 2, // DW_CFA_offset s0, cfa - 8
+        ]);
+        let mut out = Vec::new();
+        for entry in [cie, fde] {
+            assert_eq!(entry.len() % 4, 0);
+            out.extend((entry.len() as u32).to_le_bytes());
+            out.extend(entry);
+        }
+        out
+    }
+
+    fn unwinder() -> Unwinder {
+        let data = Reader::new(Rc::from(eh_frame()), RunTimeEndian::Little);
+        let mut eh_frame = EhFrame::from(data);
+        eh_frame.set_address_size(4);
+        Unwinder {
+            eh_frame: Some((eh_frame, BaseAddresses::default().set_eh_frame(0x8000_1000))),
+            debug_frame: None,
+        }
+    }
+
+    fn regs(ra: u32, sp: u32, fp: u32) -> Regs {
+        let mut regs = [Some(0); 32];
+        regs[RA] = Some(ra);
+        regs[SP] = Some(sp);
+        regs[FP] = Some(fp);
+        regs
+    }
+
+    fn stack(words: &[(u32, u32)]) -> impl FnMut(u32) -> Option<u32> + use<> {
+        let words: HashMap<u32, u32> = words.iter().copied().collect();
+        move |addr| words.get(&addr).copied()
+    }
+
+    #[test]
+    fn cfi_recovers_saved_ra_and_fp_after_the_prologue() {
+        let sp = 0x8000_1ff0;
+        let mut read = stack(&[(sp + 12, CALLER), (sp + 8, 0x8000_3000)]);
+        let (pc, caller) = unwinder()
+            .step(FUNC + 8, &regs(0xdead_beef, sp, sp + 16), &mut read)
+            .unwrap();
+        assert_eq!(pc, CALLER);
+        assert_eq!(caller[RA], Some(CALLER));
+        assert_eq!(caller[SP], Some(sp + 16));
+        assert_eq!(caller[FP], Some(0x8000_3000));
+    }
+
+    #[test]
+    fn cfi_before_the_prologue_keeps_ra_and_sp() {
+        let mut read = stack(&[]);
+        let (pc, caller) = unwinder()
+            .step(FUNC, &regs(CALLER, 0x8000_2000, 0x8000_3000), &mut read)
+            .unwrap();
+        assert_eq!(pc, CALLER);
+        assert_eq!(caller[SP], Some(0x8000_2000));
+        assert_eq!(caller[FP], Some(0x8000_3000));
+    }
+
+    #[test]
+    fn cfi_outside_any_fde_or_with_unreadable_slots_gives_up() {
+        let mut read = stack(&[]);
+        let regs = regs(CALLER, 0x8000_1ff0, 0);
+        assert!(unwinder().step(CALLER, &regs, &mut read).is_none());
+        assert!(Unwinder::default().step(FUNC, &regs, &mut read).is_none());
+        // ra is saved on the stack but the stack cannot be read.
+        assert!(unwinder().step(FUNC + 8, &regs, &mut read).is_none());
+    }
+
+    #[test]
+    fn frame_pointer_walk_reads_the_frame_record() {
+        let fp = 0x8000_3000;
+        let mut read = stack(&[(fp - 4, CALLER), (fp - 8, 0x8000_4000)]);
+        let (pc, caller) = fp_step(&regs(0, 0x8000_2ff0, fp), &mut read).unwrap();
+        assert_eq!(pc, CALLER);
+        assert_eq!(caller[RA], Some(CALLER));
+        assert_eq!(caller[SP], Some(fp));
+        assert_eq!(caller[FP], Some(0x8000_4000));
+
+        // The outermost frame: no frame pointer, a misaligned one, or an unreadable record.
+        assert!(fp_step(&regs(0, 0, 0), &mut read).is_none());
+        assert!(fp_step(&regs(0, 0, fp + 2), &mut read).is_none());
+        assert!(fp_step(&regs(0, 0, 0x8000_5000), &mut read).is_none());
+    }
+}
@@ -0,0 +1,470 @@
+//! Debugger expressions: `$a0 + 4`, `*(u32*)0x80000000`, `$pc - main`, `$a0 == 0 && $a1 != 0`.
+//!
+//! Grammar (C precedence, all values are `u64`, comparisons yield 0 / 1):
+//! - primary: number (`parse_prefixed_uint` rules), `$reg` (`pc`, GPR, FPR or CSR name),
+//!   ELF symbol (or a register, when no symbol has that name), `( expr )`
+//! - unary: `-` `!` `~`, and deref `*expr` (word) / `*(u8*|u16*|u32*|u64*)expr`
+//! - binary: `* / %`, `+ -`, `<< >>`, `< <= > >=`, `== !=`, `&`, `^`, `|`, `&&`, `||`
+//!
+//! Command tokens are whitespace-separated, so an expression with spaces must be a single
+//! parenthesized group (`(main + 8)`) or a quoted token, except in `print`, which takes the
+//! rest of the line. Numeric command arguments are [`NumArg`]s, so they all take expressions.
+
+use std::fmt;
+
+use thiserror::Error;
+use winnow::Parser;
+use winnow::ascii::multispace0;
+use winnow::combinator::{alt, delimited, eof, not, opt, preceded, separated_foldl1, terminated};
+use winnow::error::{ContextError, ErrMode};
+use winnow::token::{one_of, take_while};
+
+use crate::parse_prefixed_uint;
+
+/// Width of a memory dereference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemWidth {
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl MemWidth {
+    #[inline(always)]
+    pub fn bytes(self) -> usize {
+        match self {
+            MemWidth::U8 => 1,
+            MemWidth::U16 => 2,
+            MemWidth::U32 => 4,
+            MemWidth::U64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+/// Parsed expression; evaluated later against an [`ExprContext`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Num(u64),
+    Reg(String),
+    Symbol(String),
+    Deref(MemWidth, Box<Expr>),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Error)]
+pub enum ExprError {
+    #[error("invalid expression '{0}'")]
+    Parse(String),
+
+    #[error("unknown register '${0}'")]
+    UnknownRegister(String),
+
+    #[error("unknown symbol '{0}'")]
+    UnknownSymbol(String),
+
+    #[error("cannot read {size} byte(s) at 0x{addr:08x}")]
+    Memory { addr: u64, size: usize },
+
+    #[error("division by zero")]
+    DivisionByZero,
+
+    #[error("value 0x{0:x} does not fit in the argument")]
+    OutOfRange(u64),
+
+    #[error("argument '{0}' was not evaluated")]
+    Unresolved(String),
+}
+
+/// What an expression can see: registers, memory and symbols of the DUT.
+pub trait ExprContext {
+    fn reg(&self, name: &str) -> Option<u64>;
+    fn symbol(&self, name: &str) -> Option<u64>;
+    fn read_mem(&mut self, addr: u64, width: MemWidth) -> Option<u64>;
+}
+
+impl Expr {
+    pub fn eval(&self, ctx: &mut dyn ExprContext) -> Result<u64, ExprError> {
+        Ok(match self {
+            Expr::Num(v) => *v,
+            Expr::Reg(name) => ctx
+                .reg(name)
+                .ok_or_else(|| ExprError::UnknownRegister(name.clone()))?,
+            Expr::Symbol(name) => ctx
+                .symbol(name)
+                .or_else(|| ctx.reg(name))
+                .ok_or_else(|| ExprError::UnknownSymbol(name.clone()))?,
+            Expr::Deref(width, e) => {
+                let addr = e.eval(ctx)?;
+                ctx.read_mem(addr, *width).ok_or(ExprError::Memory {
+                    addr,
+                    size: width.bytes(),
+                })?
+            }
+            Expr::Unary(op, e) => {
+                let v = e.eval(ctx)?;
+                match op {
+                    UnOp::Neg => v.wrapping_neg(),
+                    UnOp::Not => (v == 0) as u64,
+                    UnOp::BitNot => !v,
+                }
+            }
+            // Short-circuit like C, so `$a0 != 0 && *$a0 == 1` is safe.
+            Expr::Binary(BinOp::And, l, r) => (l.eval(ctx)? != 0 && r.eval(ctx)? != 0) as u64,
+            Expr::Binary(BinOp::Or, l, r) => (l.eval(ctx)? != 0 || r.eval(ctx)? != 0) as u64,
+            Expr::Binary(op, l, r) => {
+                let (l, r) = (l.eval(ctx)?, r.eval(ctx)?);
+                match op {
+                    BinOp::Mul => l.wrapping_mul(r),
+                    BinOp::Div => l.checked_div(r).ok_or(ExprError::DivisionByZero)?,
+                    BinOp::Rem => l.checked_rem(r).ok_or(ExprError::DivisionByZero)?,
+                    BinOp::Add => l.wrapping_add(r),
+                    BinOp::Sub => l.wrapping_sub(r),
+                    BinOp::Shl => l.wrapping_shl(r as u32),
+                    BinOp::Shr => l.wrapping_shr(r as u32),
+                    BinOp::Lt => (l < r) as u64,
+                    BinOp::Le => (l <= r) as u64,
+                    BinOp::Gt => (l > r) as u64,
+                    BinOp::Ge => (l >= r) as u64,
+                    BinOp::Eq => (l == r) as u64,
+                    BinOp::Ne => (l != r) as u64,
+                    BinOp::BitAnd => l & r,
+                    BinOp::BitXor => l ^ r,
+                    BinOp::BitOr => l | r,
+                    BinOp::And | BinOp::Or => unreachable!(),
+                }
+            }
+        })
+    }
+
+    /// Evaluate and narrow to `T` (e.g. a `u32` address).
+    pub fn eval_as<T: TryFrom<u64>>(&self, ctx: &mut dyn ExprContext) -> Result<T, ExprError> {
+        let v = self.eval(ctx)?;
+        T::try_from(v).map_err(|_| ExprError::OutOfRange(v))
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Num(v) => write!(f, "0x{v:x}"),
+            Expr::Reg(name) => write!(f, "${name}"),
+            Expr::Symbol(name) => write!(f, "{name}"),
+            Expr::Deref(width, e) => write!(f, "*(u{}*)({e})", width.bytes() * 8),
+            Expr::Unary(op, e) => {
+                let op = match op {
+                    UnOp::Neg => "-",
+                    UnOp::Not => "!",
+                    UnOp::BitNot => "~",
+                };
+                write!(f, "{op}({e})")
+            }
+            Expr::Binary(op, l, r) => {
+                let op = match op {
+                    BinOp::Mul => "*",
+                    BinOp::Div => "/",
+                    BinOp::Rem => "%",
+                    BinOp::Add => "+",
+                    BinOp::Sub => "-",
+                    BinOp::Shl => "<<",
+                    BinOp::Shr => ">>",
+                    BinOp::Lt => "<",
+                    BinOp::Le => "<=",
+                    BinOp::Gt => ">",
+                    BinOp::Ge => ">=",
+                    BinOp::Eq => "==",
+                    BinOp::Ne => "!=",
+                    BinOp::BitAnd => "&",
+                    BinOp::BitXor => "^",
+                    BinOp::BitOr => "|",
+                    BinOp::And => "&&",
+                    BinOp::Or => "||",
+                };
+                write!(f, "({l} {op} {r})")
+            }
+        }
+    }
+}
+
+/// Parse a whole expression. Also used as a clap `value_parser`.
+pub fn parse_expr(input: &str) -> Result<Expr, ExprError> {
+    let mut s = input;
+    delimited(multispace0, parse_or, (multispace0, eof))
+        .parse_next(&mut s)
+        .map_err(|_| ExprError::Parse(input.to_string()))
+}
+
+/// Numeric command argument (`0x1000`, `main + 8`, `$sp`): parsed as an [`Expr`] by clap and
+/// evaluated with [`NumArg::resolve`] right before its command runs. A plain number is its own
+/// value.
+#[derive(Debug, Clone)]
+pub struct NumArg<T> {
+    expr: Expr,
+    value: Option<T>,
+}
+
+impl<T: TryFrom<u64> + Copy> NumArg<T> {
+    /// Clap `value_parser`.
+    pub fn parse(input: &str) -> Result<Self, ExprError> {
+        let expr = parse_expr(input)?;
+        let value = match expr {
+            Expr::Num(v) => Some(T::try_from(v).map_err(|_| ExprError::OutOfRange(v))?),
+            _ => None,
+        };
+        Ok(Self { expr, value })
+    }
+
+    /// Value of the argument in `ctx`.
+    pub fn eval(&self, ctx: &mut dyn ExprContext) -> Result<T, ExprError> {
+        self.expr.eval_as(ctx)
+    }
+
+    /// Evaluate the argument once, for code that reads it with [`NumArg::get`].
+    pub fn resolve(&mut self, ctx: &mut dyn ExprContext) -> Result<(), ExprError> {
+        self.value = Some(self.eval(ctx)?);
+        Ok(())
+    }
+
+    /// Value of the argument; an error if it is an expression that was never resolved.
+    #[inline(always)]
+    pub fn get(&self) -> Result<T, ExprError> {
+        self.value
+            .ok_or_else(|| ExprError::Unresolved(self.expr.to_string()))
+    }
+}
+
+type PResult<T> = winnow::Result<T, ErrMode<ContextError>>;
+
+/// One left-associative precedence level: `next (op next)*`.
+fn binary_level<'i>(
+    input: &mut &'i str,
+    next: fn(&mut &'i str) -> PResult<Expr>,
+    op: impl Parser<&'i str, BinOp, ErrMode<ContextError>>,
+) -> PResult<Expr> {
+    separated_foldl1(next, delimited(multispace0, op, multispace0), |l, op, r| {
+        Expr::Binary(op, Box::new(l), Box::new(r))
+    })
+    .parse_next(input)
+}
+
+fn parse_or(input: &mut &str) -> PResult<Expr> {
+    binary_level(input, parse_and, |i: &mut &str| {
+        "||".value(BinOp::Or).parse_next(i)
+    })
+}
+
+fn parse_and(input: &mut &str) -> PResult<Expr> {
+    binary_level(input, parse_bitor, |i: &mut &str| {
+        "&&".value(BinOp::And).parse_next(i)
+    })
+}
+
+fn parse_bitor(input: &mut &str) -> PResult<Expr> {
+    binary_level(input, parse_bitxor, |i: &mut &str| {
+        terminated("|", not("|")).value(BinOp::BitOr).parse_next(i)
+    })
+}
+
+fn parse_bitxor(input: &mut &str) -> PResult<Expr> {
+    binary_level(input, parse_bitand, |i: &mut &str| {
+        "^".value(BinOp::BitXor).parse_next(i)
+    })
+}
+
+fn parse_bitand(input: &mut &str) -> PResult<Expr> {
+    binary_level(input, parse_equality, |i: &mut &str| {
+        terminated("&", not("&")).value(BinOp::BitAnd).parse_next(i)
+    })
+}
+
+fn parse_equality(input: &mut &str) -> PResult<Expr> {
+    binary_level(input, parse_relational, |i: &mut &str| {
+        alt(("==".value(BinOp::Eq), "!=".value(BinOp::Ne))).parse_next(i)
+    })
+}
+
+fn parse_relational(input: &mut &str) -> PResult<Expr> {
+    binary_level(input, parse_shift, |i: &mut &str| {
+        alt((
+            "<=".value(BinOp::Le),
+            ">=".value(BinOp::Ge),
+            terminated("<", not("<")).value(BinOp::Lt),
+            terminated(">", not(">")).value(BinOp::Gt),
+        ))
+        .parse_next(i)
+    })
+}
+
+fn parse_shift(input: &mut &str) -> PResult<Expr> {
+    binary_level(input, parse_additive, |i: &mut &str| {
+        alt(("<<".value(BinOp::Shl), ">>".value(BinOp::Shr))).parse_next(i)
+    })
+}
+
+fn parse_additive(input: &mut &str) -> PResult<Expr> {
+    binary_level(input, parse_multiplicative, |i: &mut &str| {
+        alt(("+".value(BinOp::Add), "-".value(BinOp::Sub))).parse_next(i)
+    })
+}
+
+fn parse_multiplicative(input: &mut &str) -> PResult<Expr> {
+    binary_level(input, parse_unary, |i: &mut &str| {
+        alt((
+            "*".value(BinOp::Mul),
+            "/".value(BinOp::Div),
+            "%".value(BinOp::Rem),
+        ))
+        .parse_next(i)
+    })
+}
+
+fn parse_unary(input: &mut &str) -> PResult<Expr> {
+    alt((
+        preceded(("*", multispace0), (opt(parse_deref_cast), parse_unary))
+            .map(|(width, e)| Expr::Deref(width.unwrap_or(MemWidth::U32), Box::new(e))),
+        (one_of(['-', '!', '~']), preceded(multispace0, parse_unary)).map(|(op, e)| {
+            let op = match op {
+                '-' => UnOp::Neg,
+                '!' => UnOp::Not,
+                _ => UnOp::BitNot,
+            };
+            Expr::Unary(op, Box::new(e))
+        }),
+        parse_primary,
+    ))
+    .parse_next(input)
+}
+
+/// `(u32*)` after a deref star.
+fn parse_deref_cast(input: &mut &str) -> PResult<MemWidth> {
+    let width = alt((
+        "u8".value(MemWidth::U8),
+        "u16".value(MemWidth::U16),
+        "u32".value(MemWidth::U32),
+        "u64".value(MemWidth::U64),
+    ));
+    delimited(
+        ("(", multispace0),
+        width,
+        (multispace0, "*", multispace0, ")", multispace0),
+    )
+    .parse_next(input)
+}
+
+fn parse_primary(input: &mut &str) -> PResult<Expr> {
+    alt((
+        delimited(("(", multispace0), parse_or, (multispace0, ")")),
+        preceded("$", parse_ident).map(|name| Expr::Reg(name.to_string())),
+        parse_number,
+        parse_ident.map(|name| Expr::Symbol(name.to_string())),
+    ))
+    .parse_next(input)
+}
+
+fn parse_number(input: &mut &str) -> PResult<Expr> {
+    (
+        one_of(|c: char| c.is_ascii_digit()),
+        take_while(0.., |c: char| c.is_ascii_alphanumeric() || c == '_'),
+    )
+        .take()
+        .try_map(|s: &str| parse_prefixed_uint::<u64>(s).map(Expr::Num))
+        .parse_next(input)
+}
+
+fn parse_ident<'a>(input: &mut &'a str) -> PResult<&'a str> {
+    (
+        one_of(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.'),
+        take_while(0.., |c: char| {
+            c.is_ascii_alphanumeric() || c == '_' || c == '.'
+        }),
+    )
+        .take()
+        .parse_next(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ctx;
+
+    impl ExprContext for Ctx {
+        fn reg(&self, name: &str) -> Option<u64> {
+            (name == "a0").then_some(0x8000_0010)
+        }
+        fn symbol(&self, name: &str) -> Option<u64> {
+            (name == "main").then_some(0x8000_0000)
+        }
+        fn read_mem(&mut self, addr: u64, width: MemWidth) -> Option<u64> {
+            let bits = width.bytes() as u32 * 8;
+            (addr == 0x8000_0000).then(|| 0x1122_3344_5566_7788 & (u64::MAX >> (64 - bits)))
+        }
+    }
+
+    fn eval(s: &str) -> Result<u64, ExprError> {
+        parse_expr(s)?.eval(&mut Ctx)
+    }
+
+    #[test]
+    fn precedence_and_operands() {
+        assert_eq!(eval("1 + 2 * 3").unwrap(), 7);
+        assert_eq!(eval("$a0 - main").unwrap(), 0x10);
+        assert_eq!(eval("(1 << 4) | 1 == 1").unwrap(), 0x11);
+        assert_eq!(eval("$a0 > main && !0").unwrap(), 1);
+        assert_eq!(eval("*(u8*)main").unwrap(), 0x88);
+        assert_eq!(eval("*main").unwrap(), 0x5566_7788);
+        assert!(matches!(eval("0 && 1 / 0"), Ok(0)));
+        assert!(matches!(eval("$t9"), Err(ExprError::UnknownRegister(_))));
+        assert!(matches!(eval("1 +"), Err(ExprError::Parse(_))));
+    }
+
+    #[test]
+    fn bare_names_fall_back_to_registers() {
+        assert_eq!(eval("a0 + 4").unwrap(), 0x8000_0014);
+        assert_eq!(eval("main").unwrap(), 0x8000_0000);
+        assert!(matches!(eval("nowhere"), Err(ExprError::UnknownSymbol(_))));
+    }
+
+    #[test]
+    fn num_arg_literals_need_no_resolving() {
+        assert_eq!(NumArg::<u8>::parse("0x10").unwrap().get().unwrap(), 0x10);
+        assert!(matches!(
+            NumArg::<u8>::parse("0x100"),
+            Err(ExprError::OutOfRange(0x100))
+        ));
+        let mut arg = NumArg::<u32>::parse("(main + 8)").unwrap();
+        assert!(matches!(arg.get(), Err(ExprError::Unresolved(_))));
+        arg.resolve(&mut Ctx).unwrap();
+        assert_eq!(arg.get().unwrap(), 0x8000_0008);
+    }
+}
@@ -1 +1 @@
-remu_macro::mod_flat!(number, disasm);
+remu_macro::mod_flat!(number, disasm, expr);
@@ -4,5 +4,5 @@
 pub(crate) const UART16550_BASE: usize = 0x1000_0000;
 
 /// SiFive test finisher base address (default: sifive_test_finisher@0x0010_0000).
-/// Writing 0x5555 = success exit, 0x3333 = fail exit.
+/// Writing 0x5555 = success exit, 0x3333 = fail exit, 0x7777 = reboot.
 pub(crate) const SIFIVE_TEST_FINISHER_BASE: usize = 0x0010_0000;
@@ -7,9 +7,12 @@ use crate::addresses::SIFIVE_TEST_FINISHER_BASE;
 /// Magic value for success exit (remu reports ExitCode::Good).
 pub(crate) const EXIT_SUCCESS: u32 = 0x5555;
 
-/// Magic value for failure exit (remu reports ExitCode::Bad).
+/// Magic value for failure exit (remu reports ExitCode::Bad); the status goes in bits 31:16.
 pub(crate) const EXIT_FAILURE: u32 = 0x3333;
 
+/// Magic value for reboot (remu resets the hart and reloads the program).
+pub(crate) const EXIT_RESET: u32 = 0x7777;
+
 /// Notify remu to exit successfully. Does not return.
 #[inline(never)]
 pub fn exit_success() -> ! {
@@ -23,3 +26,19 @@ pub fn exit_failure() -> ! {
     unsafe { core::ptr::write_volatile(SIFIVE_TEST_FINISHER_BASE as *mut u32, EXIT_FAILURE) };
     loop {}
 }
+
+/// Notify remu to exit with failure status `status` (the process exit code in batch mode).
+/// Does not return.
+#[inline(never)]
+pub fn exit_with_status(status: u16) -> ! {
+    let value = ((status as u32) << 16) | EXIT_FAILURE;
+    unsafe { core::ptr::write_volatile(SIFIVE_TEST_FINISHER_BASE as *mut u32, value) };
+    loop {}
+}
+
+/// Ask remu to reboot: the program starts over from its entry point. Does not return.
+#[inline(never)]
+pub fn reboot() -> ! {
+    unsafe { core::ptr::write_volatile(SIFIVE_TEST_FINISHER_BASE as *mut u32, EXIT_RESET) };
+    loop {}
+}
@@ -0,0 +1,392 @@
+//! DUT snapshots and the periodic checkpoints reverse execution is built on: rewinding restores
+//! the closest earlier checkpoint and re-executes forward from it.
+
+use std::rc::Rc;
+
+use remu_simulator::PlatformConfig;
+use remu_state::{StatePolicy, StateSnapshot};
+
+/// Checkpoints kept before thinning: every other one is dropped and the interval doubles, so
+/// memory stays bounded however long the run.
+const MAX_CHECKPOINTS: usize = 64;
+
+/// DUT state at some point of the run.
+pub struct Snapshot<C: PlatformConfig> {
+    pub(crate) state: StateSnapshot<<C::Policy as StatePolicy>::ISA>,
+    pub(crate) position: u64,
+}
+
+impl<C: PlatformConfig> Snapshot<C> {
+    /// Instructions retired when the snapshot was taken.
+    #[inline(always)]
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    #[inline(always)]
+    pub fn pc(&self) -> u32 {
+        *self.state.pc
+    }
+}
+
+pub(crate) struct Checkpoints<C: PlatformConfig> {
+    /// Instructions between checkpoints; 0 keeps only the one at the start.
+    interval: u64,
+    list: Vec<Rc<Snapshot<C>>>,
+    next: u64,
+    /// The DUT cannot take snapshots: checkpointing is off.
+    unsupported: bool,
+}
+
+impl<C: PlatformConfig> Checkpoints<C> {
+    pub(crate) fn new(interval: u64) -> Self {
+        Self {
+            interval,
+            list: Vec::new(),
+            next: 0,
+            unsupported: false,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn due(&self, position: u64) -> bool {
+        position >= self.next && !self.unsupported
+    }
+
+    pub(crate) fn disable(&mut self) {
+        self.unsupported = true;
+    }
+
+    pub(crate) fn latest(&self) -> Option<&Snapshot<C>> {
+        self.list.last().map(|s| &**s)
+    }
+
+    pub(crate) fn earliest(&self) -> Option<Rc<Snapshot<C>>> {
+        self.list.first().cloned()
+    }
+
+    /// The latest checkpoint at or before `position`.
+    pub(crate) fn at_or_before(&self, position: u64) -> Option<Rc<Snapshot<C>>> {
+        self.list
+            .iter()
+            .rev()
+            .find(|s| s.position <= position)
+            .cloned()
+    }
+
+    pub(crate) fn push(&mut self, snap: Snapshot<C>) {
+        self.list.push(Rc::new(snap));
+        if self.list.len() > MAX_CHECKPOINTS {
+            let mut keep = false;
+            self.list.retain(|_| {
+                keep = !keep;
+                keep
+            });
+            self.interval *= 2;
+        }
+        self.schedule();
+    }
+
+    /// Make `snap` the start of the run, e.g. after loading a checkpoint file.
+    pub(crate) fn reset(&mut self, snap: Snapshot<C>) {
+        self.list.clear();
+        self.push(snap);
+    }
+
+    /// Forget checkpoints past `position`: execution was moved back and may not retrace them.
+    pub(crate) fn truncate_after(&mut self, position: u64) {
+        self.list.retain(|s| s.position <= position);
+        self.schedule();
+    }
+
+    fn schedule(&mut self) {
+        self.next = match (self.list.last(), self.interval) {
+            (None, _) => 0,
+            (Some(_), 0) => u64::MAX,
+            (Some(last), interval) => last.position.saturating_add(interval),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::ops::Range;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use clap::Parser;
+    use remu_isa::isa::extension_enum::RV32I;
+    use remu_isa::isa::reg::{Gpr, Mcause, RegAccess};
+    use remu_simulator::{SimulatorError, SimulatorInnerError};
+    use remu_state::StateFastProfile;
+    use remu_types::{AllUsize, DynDiagError, Tracer, TracerDyn};
+
+    use super::*;
+    use crate::{Harness, HarnessError, HarnessOption, SimulatorOption, SimulatorRemu};
+
+    struct Remu;
+
+    impl PlatformConfig for Remu {
+        type Policy = StateFastProfile<RV32I>;
+        type Dut = SimulatorRemu<Self::Policy, true>;
+        type Ref = ();
+
+        fn create_dut(opt: &SimulatorOption, tracer: TracerDyn, irq: Arc<AtomicBool>) -> Self::Dut {
+            <Self::Dut as remu_simulator::SimulatorCore<Self::Policy>>::new(opt.clone(), tracer, irq)
+        }
+
+        fn create_ref(_: &SimulatorOption, _: TracerDyn, _: Arc<AtomicBool>) {}
+    }
+
+    struct Quiet;
+
+    impl Tracer for Quiet {
+        fn print(&self, _: &str) {}
+        fn mem_print(&self, _: usize, _: &[u8], _: Result<(), Box<dyn DynDiagError>>) {}
+        fn mem_show(&self, _: usize, _: Result<AllUsize, Box<dyn DynDiagError>>) {}
+        fn mem_show_map(&self, _: Vec<(String, Range<usize>)>) {}
+        fn reg_print(&self, _: &[(Gpr, u32); 32], _: Range<usize>) {}
+        fn reg_show(&self, _: Gpr, _: u32) {}
+        fn disasm(&self, _: u64, _: u32) {}
+    }
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        opt: HarnessOption,
+    }
+
+    const UART: &str = "uart16550@0x1000_0000,out=capture";
+    const DATA: usize = 0x8000_1000;
+
+    /// Five instructions a loop: count in a0, store it to the next word from DATA and send its
+    /// low byte to the UART.
+    const PROGRAM: [u32; 7] = [
+        0x8000_15b7, // lui   a1, 0x80001
+        0x1000_0637, // lui   a2, 0x10000
+        0x0015_0513, // addi  a0, a0, 1
+        0x00a5_a023, // sw    a0, 0(a1)
+        0x0045_8593, // addi  a1, a1, 4
+        0x00a6_0023, // sb    a0, 0(a2)
+        0xff1f_f06f, // j     -16
+    ];
+
+    fn harness(checkpoint_interval: u64) -> Harness<Remu> {
+        let interval = checkpoint_interval.to_string();
+        let args = Args::parse_from([
+            "remu",
+            "--uart-input",
+            "none",
+            "--mtime",
+            "instret",
+            "--dev",
+            UART,
+            "--checkpoint-interval",
+            &interval,
+        ]);
+        let tracer: TracerDyn = Rc::new(RefCell::new(Quiet));
+        let mut harness = Harness::new(args.opt, tracer, Arc::new(AtomicBool::new(false)));
+        let program: Vec<u8> = PROGRAM.iter().flat_map(|i| i.to_le_bytes()).collect();
+        harness.write_dut_memory(0x8000_0000, &program).unwrap();
+        harness
+    }
+
+    fn run(harness: &mut Harness<Remu>, steps: usize) {
+        harness.run_steps(Some(steps)).unwrap();
+    }
+
+    fn regs(harness: &Harness<Remu>) -> (u32, Vec<u32>) {
+        let reg = &harness.dut_state().reg;
+        let gpr = (0..32).map(|i| reg.gpr.raw_read(i)).collect();
+        (*reg.pc, gpr)
+    }
+
+    fn data(harness: &mut Harness<Remu>) -> Vec<u8> {
+        let mut buf = vec![0; 0x2000];
+        harness
+            .dut_state_mut()
+            .bus
+            .read_bytes(DATA, &mut buf)
+            .unwrap();
+        buf
+    }
+
+    fn output_len(harness: &Harness<Remu>) -> usize {
+        harness.dut_state().bus.captured_output().unwrap().len()
+    }
+
+    #[test]
+    fn restore_brings_back_registers_and_ram() {
+        let mut h = harness(0);
+        run(&mut h, 1002);
+        let snap = h.save_snapshot().unwrap();
+        let before = (regs(&h), data(&mut h));
+        let printed = output_len(&h);
+
+        run(&mut h, 3000);
+        assert_ne!(regs(&h), before.0);
+        h.restore_snapshot(&snap).unwrap();
+        assert_eq!((regs(&h), data(&mut h)), before);
+        assert_eq!(h.total_instructions(), 1002);
+        // What was printed since is not printed twice.
+        assert_eq!(output_len(&h), printed);
+    }
+
+    #[test]
+    fn unchanged_pages_are_shared() {
+        let mut h = harness(0);
+        run(&mut h, 100);
+        let first = h.save_snapshot().unwrap();
+        run(&mut h, 100);
+        let second = h.save_snapshot().unwrap();
+
+        // Both share every page with the start-of-run checkpoint except the one holding DATA.
+        assert_eq!(second.state.bus.unique_pages(Some(&first.state.bus)), 1);
+        let start = h.checkpoints.earliest().unwrap();
+        assert_eq!(second.state.bus.unique_pages(Some(&start.state.bus)), 1);
+    }
+
+    #[test]
+    fn rewind_lands_on_the_instruction() {
+        let mut h = harness(64);
+        for _ in 0..20 {
+            run(&mut h, 50);
+        }
+        let mut seen = harness(64);
+        run(&mut seen, 333);
+        let at_333 = (regs(&seen), data(&mut seen));
+
+        h.rewind_to(333).unwrap();
+        assert_eq!(h.total_instructions(), 333);
+        assert_eq!((regs(&h), data(&mut h)), at_333);
+        // The capture is cut back to the checkpoint and the muted replay captures the rest again.
+        assert_eq!(output_len(&h), output_len(&seen));
+    }
+
+    #[test]
+    fn checkpoints_thin_out() {
+        let mut h = harness(10);
+        for _ in 0..100 {
+            run(&mut h, 10);
+        }
+        let positions: Vec<u64> = h.checkpoints.list.iter().map(|s| s.position).collect();
+        assert!(positions.len() <= MAX_CHECKPOINTS);
+        assert_eq!(h.checkpoints.interval, 20);
+        assert!(positions.windows(2).all(|w| w[1] - w[0] == 20));
+
+        // A dropped checkpoint (10) is covered by the one before it.
+        h.rewind_to(15).unwrap();
+        assert_eq!(h.total_instructions(), 15);
+        assert_eq!(h.checkpoints.list.len(), 1);
+    }
+
+    /// Count in a0, store it at DATA, then ask the syscon for a reboot.
+    const REBOOT_PROGRAM: [u32; 8] = [
+        0x8000_15b7, // lui   a1, 0x80001
+        0x0015_0513, // addi  a0, a0, 1
+        0x00a5_a023, // sw    a0, 0(a1)
+        0x0010_02b7, // lui   t0, 0x100
+        0x0000_7337, // lui   t1, 0x7
+        0x7773_0313, // addi  t1, t1, 0x777
+        0x0062_a023, // sw    t1, 0(t0)
+        0x0000_006f, // j     0
+    ];
+    const FLASH: usize = 0x2000_0000;
+
+    /// A file in the temp directory, removed when dropped.
+    struct TempFile(std::path::PathBuf);
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn reboot_restores_ram_and_keeps_flash_and_breakpoints() {
+        let image = TempFile(
+            std::env::temp_dir().join(format!("remu-{}-reboot.bin", std::process::id())),
+        );
+        let program: Vec<u8> = REBOOT_PROGRAM.iter().flat_map(|i| i.to_le_bytes()).collect();
+        std::fs::write(&image.0, program).unwrap();
+        let ram = format!("ram@0x8000_0000:0x8001_0000,image={}", image.0.display());
+        let args = Args::parse_from([
+            "remu",
+            "--uart-input",
+            "none",
+            "--mem",
+            &ram,
+            "--dev",
+            "syscon@0x0010_0000",
+            "--dev",
+            "spi_flash@0x1004_0000,xip=0x2000_0000,size=0x1_0000",
+        ]);
+        let tracer: TracerDyn = Rc::new(RefCell::new(Quiet));
+        let mut h: Harness<Remu> = Harness::new(args.opt, tracer, Arc::new(AtomicBool::new(false)));
+        h.write_dut_memory(FLASH, b"kept").unwrap();
+        h.set_breakpoint(0x8000_0004).unwrap();
+
+        let hit = h.run_steps(Some(100)).unwrap_err();
+        assert_eq!(hit.breakpoint_pc(), Some(0x8000_0004));
+        // On through the store and the reboot request.
+        run(&mut h, 6);
+        assert_eq!(h.total_instructions(), 7);
+        let (pc, gpr) = regs(&h);
+        assert_eq!((pc, gpr[10]), (0x8000_0000, 0));
+        assert_eq!(data(&mut h)[..4], [0; 4]);
+        let mut flash = [0; 4];
+        h.dut_state_mut().bus.read_bytes(FLASH, &mut flash).unwrap();
+        assert_eq!(&flash, b"kept");
+
+        let hit = h.run_steps(Some(100)).unwrap_err();
+        assert_eq!(hit.breakpoint_pc(), Some(0x8000_0004));
+        assert_eq!(h.total_instructions(), 8);
+    }
+
+    #[test]
+    fn interrupt_catchpoint_retires_nothing() {
+        // Make the UART's empty transmitter raise PLIC source 1, then enable it.
+        const PROGRAM: [u32; 15] = [
+            0x0c00_0537, // lui   a0, 0xc000
+            0x0010_0293, // li    t0, 1
+            0x0055_2223, // sw    t0, 4(a0)      source 1 priority
+            0x0c00_25b7, // lui   a1, 0xc002
+            0x0020_0293, // li    t0, 2
+            0x0055_a023, // sw    t0, 0(a1)      enable source 1
+            0x1000_0637, // lui   a2, 0x10000
+            0x0056_00a3, // sb    t0, 1(a2)      IER.THRE
+            0x8000_0337, // lui   t1, 0x80000
+            0x3053_1073, // csrw  mtvec, t1
+            0x0000_12b7, // lui   t0, 0x1
+            0x8002_8293, // addi  t0, t0, -2048
+            0x3042_a073, // csrs  mie, t0        MEIE
+            0x3004_6073, // csrsi mstatus, 8     MIE
+            0x0000_006f, // j     .
+        ];
+        let args = Args::parse_from([
+            "remu",
+            "--uart-input",
+            "none",
+            "--dev",
+            "uart16550@0x1000_0000,out=none,irq=1",
+            "--dev",
+            "plic@0x0c00_0000",
+        ]);
+        let tracer: TracerDyn = Rc::new(RefCell::new(Quiet));
+        let mut h: Harness<Remu> = Harness::new(args.opt, tracer, Arc::new(AtomicBool::new(false)));
+        let program: Vec<u8> = PROGRAM.iter().flat_map(|i| i.to_le_bytes()).collect();
+        h.write_dut_memory(0x8000_0000, &program).unwrap();
+        h.set_catch(Mcause::MachineExternalInterrupt).unwrap();
+
+        let hit = h.run_steps(Some(100)).unwrap_err();
+        assert!(matches!(
+            hit,
+            HarnessError::Simulator(SimulatorError::Dut(SimulatorInnerError::CatchpointHit {
+                cause: Mcause::MachineExternalInterrupt,
+                epc: 0x8000_0038
+            }))
+        ));
+        assert_eq!(h.total_instructions(), 14);
+    }
+}
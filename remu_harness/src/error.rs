@@ -1,4 +1,6 @@
-use remu_simulator::SimulatorError;
+use remu_simulator::{SimulatorError, SimulatorInnerError};
+use remu_state::CheckpointError;
+use remu_types::WatchHit;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -8,14 +10,70 @@ pub enum HarnessError {
 
     #[error(transparent)]
     Simulator(#[from] SimulatorError),
+
+    #[error("watchpoint: {0}")]
+    WatchpointError(String),
+
+    #[error("snapshot: {0}")]
+    Snapshot(String),
+
+    #[error("checkpoint file: {0}")]
+    CheckpointFile(#[from] CheckpointError),
+
+    /// A watchpoint fired; the instruction that triggered it has retired.
+    #[error("{0}")]
+    WatchpointHit(WatchHit),
 }
 
 impl HarnessError {
     #[inline(always)]
     pub fn backtrace(&self) -> Option<&std::backtrace::Backtrace> {
         match self {
-            HarnessError::Interrupted => None,
+            HarnessError::Interrupted
+            | HarnessError::WatchpointError(_)
+            | HarnessError::Snapshot(_)
+            | HarnessError::CheckpointFile(_)
+            | HarnessError::WatchpointHit(_) => None,
             HarnessError::Simulator(e) => e.backtrace(),
         }
     }
+
+    /// PC of the breakpoint the DUT stopped on, if this is a breakpoint hit.
+    pub fn breakpoint_pc(&self) -> Option<u32> {
+        match self {
+            HarnessError::Simulator(SimulatorError::Dut(SimulatorInnerError::BreakpointHit(
+                pc,
+            ))) => Some(*pc),
+            _ => None,
+        }
+    }
+
+    pub fn is_difftest_mismatch(&self) -> bool {
+        matches!(self, HarnessError::Simulator(SimulatorError::Difftest(_)))
+    }
+
+    pub fn is_watchdog_timeout(&self) -> bool {
+        matches!(
+            self,
+            HarnessError::Simulator(SimulatorError::Dut(SimulatorInnerError::WatchdogTimeout))
+        )
+    }
+
+    /// True for errors that mean the run went wrong (bus error, difftest mismatch, unimplemented
+    /// CSR, ...), as opposed to a user stop (interrupt, breakpoint, catchpoint, watchpoint).
+    pub fn is_failure(&self) -> bool {
+        !matches!(
+            self,
+            HarnessError::Interrupted
+                | HarnessError::WatchpointError(_)
+                | HarnessError::Snapshot(_)
+                | HarnessError::CheckpointFile(_)
+                | HarnessError::WatchpointHit(_)
+                | HarnessError::Simulator(SimulatorError::Dut(
+                    SimulatorInnerError::Interrupted
+                        | SimulatorInnerError::BreakpointHit(_)
+                        | SimulatorInnerError::CatchpointHit { .. }
+                ))
+        )
+    }
 }
@@ -5,4 +5,13 @@ pub struct HarnessOption {
     /// Simulator Option
     #[command(flatten)]
     pub sim: SimulatorOption,
+
+    /// Instruction ring buffer size (last N instructions, dumped on failure); 0 disables it
+    #[arg(long, value_name = "N", default_value_t = crate::IRINGBUF_DEFAULT_SIZE)]
+    pub iringbuf: usize,
+
+    /// Instructions between the checkpoints reverse execution rewinds to; 0 keeps only the one
+    /// at the start
+    #[arg(long, value_name = "N", default_value_t = 50_000_000)]
+    pub checkpoint_interval: u64,
 }
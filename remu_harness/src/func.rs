@@ -37,6 +37,8 @@ impl Trace {
         match command {
             TraceCmd::Instruction { enable } => self.flags.set_instruction(*enable),
             TraceCmd::WaveForm { enable } => self.flags.set_waveform(*enable),
+            TraceCmd::Exception { enable } => self.flags.set_exception(*enable),
+            TraceCmd::Iringbuf { enable } => self.flags.set_iringbuf(*enable),
         }
     }
 }
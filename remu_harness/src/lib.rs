@@ -1,10 +1,19 @@
 remu_macro::mod_pub_flat!(prelude);
 remu_macro::mod_pub_flat!(flow);
-remu_macro::mod_flat!(error, func, run_state, isa_dispatch);
+remu_macro::mod_flat!(error, func, run_state, isa_dispatch, watch, checkpoint);
 
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 
+use remu_isa::isa::reg::RegAccess;
+use remu_state::State;
+
+/// Default `--iringbuf` size, also used when the ring is switched on at run time after
+/// `--iringbuf 0`.
+const IRINGBUF_DEFAULT_SIZE: usize = 16;
+
 pub struct Harness<C: PlatformConfig> {
     dut_model: <C as PlatformConfig>::Dut,
     ref_model: <C as PlatformConfig>::Ref,
@@ -12,6 +21,12 @@ pub struct Harness<C: PlatformConfig> {
     interrupt: Arc<AtomicBool>,
     run_state: RunState,
     total_instructions: u64,
+    iringbuf: Iringbuf,
+    watches: watch::Watchpoints,
+    checkpoints: Checkpoints<C>,
+    /// DUT state right after loading the program, for guest reboots (`None` when the DUT
+    /// cannot take snapshots).
+    power_on: Option<Rc<Snapshot<C>>>,
     tracer: TracerDyn,
 }
 
@@ -21,13 +36,23 @@ impl<C: PlatformConfig> Harness<C> {
         let mut ref_model = C::create_ref(&opt.sim, tracer.clone(), Arc::clone(&interrupt));
         <C::Dut as remu_simulator::SimulatorCore<C::Policy>>::init(&mut dut_model);
         <C::Ref as remu_simulator::SimulatorCore<C::Policy>>::init(&mut ref_model);
+        let power_on = dut_model
+            .save_state(None)
+            .ok()
+            .map(|state| Rc::new(Snapshot { state, position: 0 }));
+        let mut func = func::Func::new();
+        func.trace.flags.set_iringbuf(opt.iringbuf > 0);
         Self {
             dut_model,
             ref_model,
-            func: func::Func::new(),
+            func,
             interrupt,
             run_state: RunState::Idle,
             total_instructions: 0,
+            iringbuf: Iringbuf::new(opt.iringbuf),
+            watches: watch::Watchpoints::new(),
+            checkpoints: Checkpoints::new(opt.checkpoint_interval),
+            power_on,
             tracer,
         }
     }
@@ -37,11 +62,245 @@ impl<C: PlatformConfig> Harness<C> {
         self.run_state
     }
 
+    /// Instructions retired by the DUT so far.
     #[inline(always)]
-    fn step_once<const TRACE: u64>(&mut self) -> Result<(), SimulatorError> {
+    pub fn total_instructions(&self) -> u64 {
+        self.total_instructions
+    }
+
+    /// DUT architectural state (registers and bus), e.g. for debugger expressions.
+    #[inline(always)]
+    pub fn dut_state(&self) -> &State<C::Policy> {
+        self.dut_model.state()
+    }
+
+    #[inline(always)]
+    pub fn dut_state_mut(&mut self) -> &mut State<C::Policy> {
+        self.dut_model.state_mut()
+    }
+
+    /// Instruction at `pc` as the program sees it (breakpoint patches undone).
+    #[inline(always)]
+    pub fn fetch_inst(&mut self, pc: u32) -> Option<u32> {
+        self.dut_model.fetch_inst(pc)
+    }
+
+    /// Write DUT memory from outside the program (no observers) and drop stale decodes.
+    /// Breakpoints under the written range stay set and run the new instruction.
+    pub fn write_dut_memory(&mut self, addr: usize, data: &[u8]) -> Result<(), HarnessError> {
         self.dut_model
-            .step_once::<TRACE>()
+            .write_memory(addr, data)
+            .map_err(SimulatorError::Dut)?;
+        self.dut_model.flush_icache();
+        Ok(())
+    }
+
+    /// Move the DUT to `pc` from outside the program (debugger, GDB): as after a snapshot
+    /// restore, a breakpoint at `pc` counts as reported and resuming runs it.
+    pub fn set_pc(&mut self, pc: u32) {
+        *self.dut_model.state_mut().reg.pc = pc;
+        self.dut_model.resume_over_breakpoint();
+    }
+
+    /// Snapshot the DUT, e.g. to come back to it with [`restore_snapshot`](Self::restore_snapshot).
+    pub fn save_snapshot(&mut self) -> Result<Snapshot<C>, HarnessError> {
+        let state = self
+            .dut_model
+            .save_state(self.checkpoints.latest().map(|s| &s.state))
+            .map_err(SimulatorError::Dut)?;
+        Ok(Snapshot {
+            state,
+            position: self.total_instructions,
+        })
+    }
+
+    /// Put the DUT (and the difftest ref) back to `snap`. Checkpoints past it are dropped.
+    pub fn restore_snapshot(&mut self, snap: &Snapshot<C>) -> Result<(), HarnessError> {
+        self.dut_model
+            .restore_state(&snap.state)
             .map_err(SimulatorError::Dut)?;
+        if <C::Ref as SimulatorRef<C::Policy>>::ENABLE {
+            self.ref_model
+                .restore_state(&snap.state)
+                .map_err(SimulatorError::Ref)?;
+            self.ref_model.sync_regs_from(&self.dut_model.state().reg);
+        }
+        self.total_instructions = snap.position;
+        self.run_state = RunState::Idle;
+        self.iringbuf.clear();
+        self.checkpoints.truncate_after(snap.position);
+        Ok(())
+    }
+
+    /// Write the current DUT state to a checkpoint file, for
+    /// [`load_checkpoint_file`](Self::load_checkpoint_file) in a later run.
+    pub fn save_checkpoint_file(&mut self, path: &Path) -> Result<(), HarnessError> {
+        let snap = self.save_snapshot()?;
+        self.dut_model
+            .state()
+            .write_checkpoint(&snap.state, snap.position, path)?;
+        Ok(())
+    }
+
+    /// Restore a checkpoint file into the DUT and the difftest ref. It becomes the earliest
+    /// point reverse execution can go back to.
+    pub fn load_checkpoint_file(&mut self, path: &Path) -> Result<(), HarnessError> {
+        let (state, position) = self.dut_model.state().read_checkpoint(path)?;
+        let snap = Snapshot { state, position };
+        self.restore_snapshot(&snap)?;
+        self.checkpoints.reset(snap);
+        Ok(())
+    }
+
+    /// Move the DUT back to `position` (instructions retired): restore the latest checkpoint at
+    /// or before it and re-execute from there. Exact as long as the program is deterministic.
+    /// Stops at the earliest checkpoint when `position` predates it (a loaded checkpoint file).
+    pub fn rewind_to(&mut self, position: u64) -> Result<(), HarnessError> {
+        let snap = self
+            .checkpoints
+            .at_or_before(position)
+            .or_else(|| self.checkpoints.earliest())
+            .ok_or_else(|| HarnessError::Snapshot("no checkpoint to rewind from".into()))?;
+        self.restore_snapshot(&snap)?;
+        self.replay_to(position)
+    }
+
+    /// Position of the latest checkpoint before `position`, where [`rewind_to`](Self::rewind_to)
+    /// is cheapest.
+    pub fn checkpoint_before(&self, position: u64) -> Option<u64> {
+        self.checkpoints
+            .at_or_before(position.checked_sub(1)?)
+            .map(|s| s.position)
+    }
+
+    /// Re-execute up to `position`, passing over breakpoints, watchpoints and catchpoints.
+    pub fn replay_to(&mut self, position: u64) -> Result<(), HarnessError> {
+        while self.total_instructions < position && self.run_state != RunState::Exit {
+            let steps = (position - self.total_instructions) as usize;
+            match self.replay(Some(steps)) {
+                Err(err) if err.is_failure() || matches!(err, HarnessError::Interrupted) => {
+                    return Err(err);
+                }
+                _ => {}
+            }
+        }
+        self.dut_model.resume_over_breakpoint();
+        Ok(())
+    }
+
+    /// [`run_steps`](Self::run_steps) without the instruction trace or device output, for
+    /// re-executing instructions that were already shown.
+    pub fn replay(&mut self, max_steps: Option<usize>) -> Result<RunOutcome, HarnessError> {
+        let flags = self.func.trace.flags;
+        self.func.trace.flags = TraceFlags::from_bits(flags.bits() & !TraceFlags::INSTRUCTION);
+        self.dut_model.state_mut().bus.mute_output(true);
+        let result = self.run_steps(max_steps);
+        self.dut_model.state_mut().bus.mute_output(false);
+        self.func.trace.flags = flags;
+        result
+    }
+
+    /// Guest reboot: registers and RAM of the DUT (and the difftest ref) back to power-on, so
+    /// the program is reloaded, and devices reset. Flash arrays keep their contents,
+    /// breakpoints stay set and the instruction count keeps going.
+    #[inline(never)]
+    fn reboot(&mut self) -> Result<(), HarnessError> {
+        let snap = self.power_on.clone().ok_or_else(|| {
+            HarnessError::Snapshot("reboot needs a DUT that can take snapshots".into())
+        })?;
+        let devices = self.dut_model.state().bus.save_devices();
+        let mut boot = snap.state.clone();
+        if self.dut_model.state().bus.has_nonvolatile() {
+            // Flash contents as the program sees them (breakpoint patches undone).
+            let live = self
+                .dut_model
+                .save_state(Some(&snap.state))
+                .map_err(SimulatorError::Dut)?;
+            self.dut_model
+                .state()
+                .bus
+                .keep_nonvolatile(&live.bus, &mut boot.bus);
+        }
+        self.dut_model
+            .restore_state(&boot)
+            .map_err(SimulatorError::Dut)?;
+        self.dut_model.state_mut().bus.reboot_devices(&devices);
+        self.dut_model.rearm_breakpoint();
+        if <C::Ref as SimulatorRef<C::Policy>>::ENABLE {
+            self.ref_model
+                .restore_state(&boot)
+                .map_err(SimulatorError::Ref)?;
+            self.ref_model.sync_regs_from(&self.dut_model.state().reg);
+        }
+        tracing::info!("reboot after {} instructions", self.total_instructions);
+        Ok(())
+    }
+
+    #[inline(never)]
+    fn checkpoint(&mut self) {
+        match self
+            .dut_model
+            .save_state(self.checkpoints.latest().map(|s| &s.state))
+        {
+            Ok(state) => self.checkpoints.push(Snapshot {
+                state,
+                position: self.total_instructions,
+            }),
+            Err(err) => {
+                tracing::debug!("checkpoints disabled: {err}");
+                self.checkpoints.disable();
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn step_once<const TRACE: u64>(&mut self) -> Result<(), SimulatorError> {
+        let pc = *self.dut_model.state().reg.pc;
+        let result = self.dut_model.step_once::<TRACE>();
+        if TraceFlags::iringbuf(TRACE) {
+            self.iringbuf_record(pc, result.is_ok());
+        }
+        match result {
+            Ok(()) => self.difftest_step(),
+            // The trap was entered on the DUT; keep the ref in lockstep before stopping.
+            Err(e @ SimulatorInnerError::CatchpointHit { .. }) => {
+                self.difftest_step()?;
+                Err(SimulatorError::Dut(e))
+            }
+            Err(e) => Err(SimulatorError::Dut(e)),
+        }
+    }
+
+    #[inline(always)]
+    fn retire(&mut self) {
+        self.total_instructions += 1;
+        self.dut_model.state_mut().bus.retire();
+    }
+
+    #[inline(never)]
+    fn iringbuf_record(&mut self, pc: u32, retired: bool) {
+        let (inst, rd) = self.dut_model.last_inst(pc).unwrap_or((0, None));
+        let writeback = rd
+            .filter(|_| retired)
+            .map(|rd| (rd, self.dut_model.state().reg.gpr.raw_read(rd as usize)));
+        self.iringbuf.push(IringbufEntry {
+            pc,
+            inst,
+            retired,
+            writeback,
+        });
+    }
+
+    /// Dump the instruction ring buffer via the tracer (no-op if disabled or empty).
+    pub fn print_iringbuf(&self) {
+        if self.iringbuf.is_empty() {
+            return;
+        }
+        self.tracer.borrow().iringbuf_print(&self.iringbuf.to_vec());
+    }
+
+    #[inline(always)]
+    fn difftest_step(&mut self) -> Result<(), SimulatorError> {
         if <C::Ref as SimulatorRef<C::Policy>>::ENABLE {
             let events = self.dut_model.take_observer_events();
             let mut need_sync = false;
@@ -52,6 +311,11 @@ impl<C: PlatformConfig> Harness<C> {
                     ObserverEvent::MemoryWrite(addr, data) => {
                         mem_writes.push((*addr, data.clone()));
                     }
+                    ObserverEvent::DmaWrite(addr, data) => {
+                        self.ref_model
+                            .sync_mem_from(*addr, data)
+                            .map_err(SimulatorError::Ref)?;
+                    }
                 }
             }
             if need_sync {
@@ -81,10 +345,19 @@ impl<C: PlatformConfig> Harness<C> {
 
     pub fn func_exec(&mut self, subcmd: &FuncCmd) {
         self.func.execute(subcmd);
+        if let FuncCmd::Trace {
+            subcmd: TraceCmd::Iringbuf { enable: true },
+        } = subcmd
+            && self.iringbuf.capacity() == 0
+        {
+            self.iringbuf = Iringbuf::new(IRINGBUF_DEFAULT_SIZE);
+        }
         if let FuncCmd::Trace { subcmd: trace } = subcmd {
             let (kind, enabled) = match trace {
                 TraceCmd::Instruction { enable } => (TraceKind::Instruction, *enable),
                 TraceCmd::WaveForm { enable } => (TraceKind::Wavetrace, *enable),
+                TraceCmd::Exception { enable } => (TraceKind::Exception, *enable),
+                TraceCmd::Iringbuf { enable } => (TraceKind::Iringbuf, *enable),
             };
             self.dut_model.on_trace_change(kind, enabled);
         }
@@ -104,6 +377,7 @@ impl<C: PlatformConfig> Harness<C> {
             .map_err(HarnessError::from)
     }
 
+    /// Patch a breakpoint at `addr` on the DUT (idempotent).
     #[inline(always)]
     pub fn set_breakpoint(&mut self, addr: u32) -> Result<(), HarnessError> {
         self.dut_model
@@ -121,8 +395,105 @@ impl<C: PlatformConfig> Harness<C> {
     }
 
     #[inline(always)]
-    pub fn print_breakpoints(&self) {
-        self.dut_model.print_breakpoints();
+    pub fn set_catch(&mut self, cause: Mcause) -> Result<(), HarnessError> {
+        self.dut_model
+            .set_catch(cause)
+            .map_err(SimulatorError::Dut)
+            .map_err(HarnessError::from)
+    }
+
+    #[inline(always)]
+    pub fn del_catch(&mut self, cause: Mcause) -> Result<(), HarnessError> {
+        self.dut_model
+            .del_catch(cause)
+            .map_err(SimulatorError::Dut)
+            .map_err(HarnessError::from)
+    }
+
+    #[inline(always)]
+    pub fn print_catches(&self) {
+        self.dut_model.print_catches();
+    }
+
+    /// Set a watchpoint and return its id.
+    pub fn set_watch(&mut self, target: WatchTarget) -> Result<u32, HarnessError> {
+        if let WatchTarget::Mem { len: 0, .. } = target {
+            return Err(HarnessError::WatchpointError(
+                "watched range must not be empty".into(),
+            ));
+        }
+        let last = watch::reg_value(&self.dut_model.state().reg, target).unwrap_or(0);
+        let id = self.watches.insert(target, last);
+        if let WatchTarget::Mem { start, len, access } = target {
+            self.dut_model
+                .state_mut()
+                .bus
+                .set_watch(id, start..start + len, access);
+        }
+        self.func.trace.flags.set_watch(true);
+        self.tracer
+            .borrow()
+            .print(&format!("watchpoint {id}: {target}"));
+        Ok(id)
+    }
+
+    pub fn del_watch(&mut self, id: u32) -> Result<(), HarnessError> {
+        let target = self
+            .watches
+            .remove(id)
+            .ok_or_else(|| HarnessError::WatchpointError(format!("watchpoint {id} not found")))?;
+        if let WatchTarget::Mem { .. } = target {
+            self.dut_model.state_mut().bus.del_watch(id);
+        }
+        self.func.trace.flags.set_watch(!self.watches.is_empty());
+        Ok(())
+    }
+
+    pub fn print_watches(&self) {
+        self.tracer.borrow().watch_print(&self.watches.list());
+    }
+
+    /// Re-read watched registers and drop stale bus hits, so edits made between runs
+    /// (e.g. `state reg ... write`) do not fire on the first step.
+    fn watch_sync(&mut self) {
+        let reg = &self.dut_model.state().reg;
+        for (_, target, last) in self.watches.regs_mut() {
+            if let Some(value) = watch::reg_value(reg, target) {
+                *last = value;
+            }
+        }
+        self.dut_model.state_mut().bus.take_watch_hit();
+    }
+
+    /// Check watchpoints after the instruction at `pc` retired.
+    #[inline(never)]
+    fn watch_check(&mut self, pc: u32) -> Option<WatchHit> {
+        if let Some((id, event)) = self.dut_model.state_mut().bus.take_watch_hit()
+            && let Some(target) = self.watches.target(id)
+        {
+            return Some(WatchHit {
+                id,
+                target,
+                pc,
+                event,
+            });
+        }
+        let reg = &self.dut_model.state().reg;
+        for (id, target, last) in self.watches.regs_mut() {
+            let Some(new) = watch::reg_value(reg, target) else {
+                continue;
+            };
+            if new != *last {
+                let old = std::mem::replace(last, new);
+                return Some(WatchHit {
+                    id,
+                    target,
+                    pc,
+                    event: WatchEvent::Changed { old, new },
+                });
+            }
+        }
+        None
     }
 
     pub fn collect_stats(&self) -> Vec<StatEntry> {
@@ -152,14 +523,31 @@ impl<C: PlatformConfig> Harness<C> {
         if self.run_state == RunState::Exit {
             return Ok(RunOutcome::Done);
         }
-        let trace = self.func.trace.flags.bits();
-        match trace {
+        let trace = self.func.trace.flags.bits() & TraceFlags::STEP_MASK;
+        if TraceFlags::watch(trace) {
+            self.watch_sync();
+        }
+        let result = match trace {
             0 => self.run_steps_impl::<0>(max_steps, BATCH),
             1 => self.run_steps_impl::<1>(max_steps, BATCH),
             2 => self.run_steps_impl::<2>(max_steps, BATCH),
             3 => self.run_steps_impl::<3>(max_steps, BATCH),
+            8 => self.run_steps_impl::<8>(max_steps, BATCH),
+            9 => self.run_steps_impl::<9>(max_steps, BATCH),
+            10 => self.run_steps_impl::<10>(max_steps, BATCH),
+            11 => self.run_steps_impl::<11>(max_steps, BATCH),
+            16 => self.run_steps_impl::<16>(max_steps, BATCH),
+            17 => self.run_steps_impl::<17>(max_steps, BATCH),
+            18 => self.run_steps_impl::<18>(max_steps, BATCH),
+            19 => self.run_steps_impl::<19>(max_steps, BATCH),
+            24 => self.run_steps_impl::<24>(max_steps, BATCH),
+            25 => self.run_steps_impl::<25>(max_steps, BATCH),
+            26 => self.run_steps_impl::<26>(max_steps, BATCH),
+            27 => self.run_steps_impl::<27>(max_steps, BATCH),
             _ => self.run_steps_impl::<0>(max_steps, BATCH),
-        }
+        };
+        self.dut_model.state_mut().bus.flush_output();
+        result
     }
 
     #[inline(always)]
@@ -174,6 +562,9 @@ impl<C: PlatformConfig> Harness<C> {
                 self.interrupt.store(false, Ordering::Relaxed);
                 return Err(HarnessError::Interrupted);
             }
+            if self.checkpoints.due(self.total_instructions) {
+                self.checkpoint();
+            }
             if max_steps.map_or(false, |limit| steps >= limit) {
                 return Ok(RunOutcome::Done);
             }
@@ -181,10 +572,37 @@ impl<C: PlatformConfig> Harness<C> {
                 .map(|limit| (limit - steps).min(batch))
                 .unwrap_or(batch);
             for _ in 0..to_run {
+                let pc = if TraceFlags::watch(TRACE) {
+                    *self.dut_model.state().reg.pc
+                } else {
+                    0
+                };
                 match self.step_once::<TRACE>() {
                     Ok(()) => {
                         steps += 1;
-                        self.total_instructions += 1;
+                        self.retire();
+                        if TraceFlags::watch(TRACE)
+                            && let Some(hit) = self.watch_check(pc)
+                        {
+                            return Err(HarnessError::WatchpointHit(hit));
+                        }
+                    }
+                    Err(
+                        e @ SimulatorError::Dut(SimulatorInnerError::CatchpointHit {
+                            cause, ..
+                        }),
+                    ) => {
+                        // A synchronous trap ends its instruction; an interrupt is taken
+                        // before the next one runs.
+                        if !cause.is_interrupt() {
+                            self.retire();
+                        }
+                        return Err(HarnessError::from(e));
+                    }
+                    Err(SimulatorError::Dut(SimulatorInnerError::Reboot)) => {
+                        steps += 1;
+                        self.retire();
+                        self.reboot()?;
                     }
                     Err(SimulatorError::Dut(SimulatorInnerError::ProgramExit(exit_code))) => {
                         self.run_state = RunState::Exit;
@@ -0,0 +1,73 @@
+//! Data watchpoints. Memory ranges are armed on the DUT bus; GPR / CSR watchpoints keep the last
+//! seen value here and are compared after every step while `TraceFlags::WATCH` is set.
+
+use remu_isa::isa::RvIsa;
+use remu_isa::isa::reg::RegAccess;
+use remu_state::reg::riscv::RiscvReg;
+use remu_types::WatchTarget;
+
+#[derive(Debug, Clone, Copy)]
+struct Watchpoint {
+    id: u32,
+    target: WatchTarget,
+    /// Last seen value (register watchpoints only).
+    last: u32,
+}
+
+#[derive(Debug)]
+pub(crate) struct Watchpoints {
+    next_id: u32,
+    entries: Vec<Watchpoint>,
+}
+
+impl Watchpoints {
+    pub(crate) fn new() -> Self {
+        Self {
+            next_id: 1,
+            entries: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn insert(&mut self, target: WatchTarget, last: u32) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push(Watchpoint { id, target, last });
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: u32) -> Option<WatchTarget> {
+        let pos = self.entries.iter().position(|w| w.id == id)?;
+        Some(self.entries.remove(pos).target)
+    }
+
+    pub(crate) fn target(&self, id: u32) -> Option<WatchTarget> {
+        self.entries.iter().find(|w| w.id == id).map(|w| w.target)
+    }
+
+    pub(crate) fn list(&self) -> Vec<(u32, WatchTarget)> {
+        self.entries.iter().map(|w| (w.id, w.target)).collect()
+    }
+
+    /// Register watchpoints as `(id, target, last value)`.
+    pub(crate) fn regs_mut(&mut self) -> impl Iterator<Item = (u32, WatchTarget, &mut u32)> {
+        self.entries
+            .iter_mut()
+            .filter(|w| !matches!(w.target, WatchTarget::Mem { .. }))
+            .map(|w| (w.id, w.target, &mut w.last))
+    }
+}
+
+/// Current value of a register watch target; `None` for memory targets.
+#[inline(always)]
+pub(crate) fn reg_value<I: RvIsa>(reg: &RiscvReg<I>, target: WatchTarget) -> Option<u32> {
+    match target {
+        WatchTarget::Gpr(gpr) => Some(reg.gpr.raw_read(gpr as usize)),
+        WatchTarget::Csr(csr) => Some(reg.csr.read(csr)),
+        WatchTarget::Mem { .. } => None,
+    }
+}
@@ -3,29 +3,47 @@
 mod vector;
 pub use vector::*;
 
-use strum::{Display, EnumString, FromRepr};
+use strum::{Display, EnumIter, EnumString, FromRepr};
 
-#[derive(Debug, PartialEq, Clone, Copy, Eq, FromRepr)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq, EnumString, Display, FromRepr)]
 #[repr(u32)]
+#[strum(ascii_case_insensitive)]
 pub enum Mcause {
     // Synchronous exceptions (bit 31 = 0)
+    #[strum(to_string = "instruction-address-misaligned", serialize = "instruction-address-misaligned")]
     InstructionAddressMisaligned = 0,
+    #[strum(to_string = "instruction-access-fault", serialize = "instruction-access-fault")]
     InstructionAccessFault = 1,
+    #[strum(to_string = "illegal-instruction", serialize = "illegal-instruction")]
     IllegalInstruction = 2,
+    #[strum(to_string = "breakpoint", serialize = "breakpoint")]
     Breakpoint = 3,
+    #[strum(to_string = "load-address-misaligned", serialize = "load-address-misaligned")]
     LoadAddressMisaligned = 4,
+    #[strum(to_string = "load-access-fault", serialize = "load-access-fault")]
     LoadAccessFault = 5,
+    #[strum(to_string = "store-address-misaligned", serialize = "store-address-misaligned")]
     StoreAddressMisaligned = 6,
+    #[strum(to_string = "store-access-fault", serialize = "store-access-fault")]
     StoreAccessFault = 7,
+    #[strum(to_string = "ecall-u", serialize = "ecall-u")]
     EnvCallFromU = 8,
+    #[strum(to_string = "ecall-s", serialize = "ecall-s")]
     EnvCallFromS = 9,
+    #[strum(to_string = "ecall-m", serialize = "ecall-m", serialize = "ecall")]
     EnvCallFromM = 11,
+    #[strum(to_string = "instruction-page-fault", serialize = "instruction-page-fault")]
     InstructionPageFault = 12,
+    #[strum(to_string = "load-page-fault", serialize = "load-page-fault")]
     LoadPageFault = 13,
+    #[strum(to_string = "store-page-fault", serialize = "store-page-fault")]
     StorePageFault = 15,
     // Interrupts (bit 31 = 1)
+    #[strum(to_string = "software-interrupt", serialize = "software-interrupt", serialize = "software")]
     MachineSoftwareInterrupt = 0x8000_0003,
+    #[strum(to_string = "timer-interrupt", serialize = "timer-interrupt", serialize = "timer")]
     MachineTimerInterrupt = 0x8000_0007,
+    #[strum(to_string = "external-interrupt", serialize = "external-interrupt", serialize = "external")]
     MachineExternalInterrupt = 0x8000_000B,
 }
 
@@ -39,9 +57,15 @@ impl Mcause {
     pub fn from_u32(x: u32) -> Option<Self> {
         Self::from_repr(x)
     }
+
+    /// `mcause` bit 31: interrupt (1) vs synchronous exception (0).
+    #[inline(always)]
+    pub fn is_interrupt(self) -> bool {
+        (self.to_u32() >> 31) != 0
+    }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy, Eq, EnumString, Display, FromRepr)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq, EnumString, Display, FromRepr, EnumIter)]
 #[repr(u16)]
 #[strum(ascii_case_insensitive)]
 pub enum Csr {
@@ -41,9 +41,10 @@ where
     last_commit_mem_count: u32,
     /// is_load of the last applied commit; when true, take_observer_events pops 0 (load needs no diff).
     last_commit_is_load: bool,
-    /// Breakpoint PCs; no duplicates.
+    /// Breakpoint PCs; no duplicates. Ids, conditions and counters live in the debugger.
     breakpoints: Vec<u32>,
-    /// When true: on breakpoint hit, apply normally. When false: return BreakpointHit. Toggles on each hit.
+    /// Set when stopped on a breakpoint: the next step runs the instruction at pc instead of
+    /// stopping again. Cleared once a commit is applied.
     breakpoint_apply_next: bool,
     /// Set when DPI bus_write hits sifive_test_finisher; consumed by step_once.
     pending_exit_code: Option<ExitCode>,
@@ -161,6 +162,14 @@ where
         unsafe {
             dpi::set_nzea(self as *mut Self as *mut dyn NzeaDpi);
         }
+        // Stop with pc at the breakpoint before its instruction commits, as the remu backend does.
+        if IS_DUT {
+            let pc = *self.state.reg.pc;
+            if !self.breakpoint_apply_next && self.breakpoints.contains(&pc) {
+                self.breakpoint_apply_next = true;
+                return Err(SimulatorInnerError::BreakpointHit(pc));
+            }
+        }
         let mut cycle_count: u64 = 0;
         while self.commit_buffer.is_empty() {
             if let Some(ec) = self.pending_exit_code.take() {
@@ -177,17 +186,10 @@ where
             return Err(SimulatorInnerError::ProgramExit(ec));
         }
         let msg = self.commit_buffer.remove(0);
-        if IS_DUT && self.breakpoints.contains(&msg.next_pc) {
-            if !self.breakpoint_apply_next {
-                self.breakpoint_apply_next = true;
-                self.commit_buffer.insert(0, msg);
-                return Err(SimulatorInnerError::BreakpointHit(msg.next_pc));
-            }
-            self.breakpoint_apply_next = false;
-        }
+        self.breakpoint_apply_next = false;
         if TraceFlags::instruction(TRACE) && IS_DUT {
             let pc = *self.state.reg.pc;
-            let inst = self.state.bus.read_32(pc as usize).unwrap_or(0);
+            let inst = self.state.bus.fetch_32(pc as usize).unwrap_or(0);
             self.tracer.borrow().disasm(pc as u64, inst);
         }
         self.apply_commit(msg);
@@ -326,6 +328,10 @@ where
     fn del_breakpoint(&mut self, addr: u32) -> Result<(), SimulatorInnerError> {
         if let Some(pos) = self.breakpoints.iter().position(|&x| x == addr) {
             self.breakpoints.remove(pos);
+            // Stopped on this breakpoint: the held commit no longer needs letting through.
+            if addr == *self.state.reg.pc {
+                self.breakpoint_apply_next = false;
+            }
             Ok(())
         } else {
             Err(SimulatorInnerError::BreakpointError(format!(
@@ -334,10 +340,6 @@ where
         }
     }
 
-    fn print_breakpoints(&self) {
-        self.tracer.borrow().breakpoint_print(&self.breakpoints);
-    }
-
     fn platform_stats(&self, ctx: &StatContext) -> Vec<StatEntry> {
         let mut v = vec![StatEntry::CycleCount(self.cycle_count)];
         if self.cycle_count > 0 {
@@ -14,6 +14,9 @@ pub struct CacheEntry {
 /// No Option: invalid slot is represented by CacheEntry { addr: INVALID_ADDR, .. }.
 pub struct Icache<const SIZE: usize> {
     data: Box<[CacheEntry; SIZE]>,
+    /// Raw instruction word of each slot, kept apart from the entries so that only traces that
+    /// need it (iringbuf) touch it.
+    raw: Box<[u32; SIZE]>,
 }
 
 impl<const SIZE: usize> Icache<SIZE> {
@@ -28,6 +31,7 @@ impl<const SIZE: usize> Icache<SIZE> {
                 addr: INVALID_ADDR,
                 decoded: DecodedInst::default(),
             }; SIZE]),
+            raw: Box::new([0; SIZE]),
         }
     }
 
@@ -43,6 +47,18 @@ impl<const SIZE: usize> Icache<SIZE> {
         unsafe { self.data.get_unchecked_mut(i) }
     }
 
+    /// Raw instruction word the slot for `pc` was filled from. Valid only on a hit.
+    #[inline(always)]
+    pub fn raw(&self, pc: u32) -> u32 {
+        unsafe { *self.raw.get_unchecked(Self::index(pc)) }
+    }
+
+    /// Records the raw instruction word the slot for `pc` is filled from.
+    #[inline(always)]
+    pub fn set_raw(&mut self, pc: u32, inst: u32) {
+        unsafe { *self.raw.get_unchecked_mut(Self::index(pc)) = inst }
+    }
+
     /// Invalidates the cache line for `pc`. Next fetch at this PC will refill from bus.
     #[inline(always)]
     pub fn invalidate(&mut self, pc: u32) {
@@ -5,7 +5,7 @@ use remu_isa::isa::extension_v::VExtensionConfig;
 use remu_state::{StateError, StatePolicy};
 
 remu_macro::mod_pub!(opcode);
-remu_macro::mod_flat!(bytes);
+remu_macro::mod_flat!(bytes, trap);
 
 use crate::riscv::opcode::{
     AUIPC, BRANCH, CUS0, JAL, JALR, LOAD, LOAD_FP, LUI, MISC_MEM, OP, OP_IMM, OP_V, STORE,
@@ -43,6 +43,34 @@ pub struct DecodedInst {
     pub(crate) inst: Inst,
 }
 
+impl DecodedInst {
+    /// GPR this instruction writes (x0 excluded); the iringbuf shows its value after retiring.
+    pub(crate) fn gpr_dest(&self) -> Option<u8> {
+        use OP_V::{OpMvvInst, VInst};
+        use SYSTEM::SystemInst;
+        let writes_rd = match self.inst {
+            Inst::Lui
+            | Inst::Auipc
+            | Inst::Jal
+            | Inst::Jalr
+            | Inst::OpImm(..)
+            | Inst::Op(..)
+            | Inst::Load(..) => true,
+            Inst::System(sys) => !matches!(
+                sys,
+                SystemInst::Ecall | SystemInst::Ebreak | SystemInst::Mret
+            ),
+            Inst::V(VInst::OpCfg(..))
+            | Inst::V(VInst::OpMvv(
+                OpMvvInst::Vmv_x_s | OpMvvInst::Vfirst_m | OpMvvInst::Vcpop_m,
+            )) => true,
+            Inst::Cus0(CUS0::Cus0Inst::NnLoadRd(..)) => true,
+            _ => false,
+        };
+        (writes_rd && self.rd != 0).then_some(self.rd)
+    }
+}
+
 #[inline(always)]
 pub fn decode<P: StatePolicy>(inst: u32) -> DecodedInst {
     let op = opcode(inst);
@@ -79,7 +107,7 @@ pub fn decode<P: StatePolicy>(inst: u32) -> DecodedInst {
 }
 
 #[inline(always)]
-pub(crate) fn execute<P: StatePolicy, C: crate::ExecuteContext<P>>(
+pub(crate) fn execute<P: StatePolicy, C: crate::ExecuteContext<P>, const TRACE: u64>(
     ctx: &mut C,
     decoded: &DecodedInst,
 ) -> Result<(), StateError> {
@@ -91,10 +119,10 @@ pub(crate) fn execute<P: StatePolicy, C: crate::ExecuteContext<P>>(
         Inst::Branch(..) => BRANCH::execute(ctx, decoded),
         Inst::OpImm(..) => OP_IMM::execute(ctx, decoded),
         Inst::Op(..) => OP::execute(ctx, decoded),
-        Inst::Load(..) => LOAD::execute(ctx, decoded),
-        Inst::LoadFp(..) => LOAD_FP::execute(ctx, decoded),
-        Inst::Store(..) => STORE::execute(ctx, decoded),
-        Inst::StoreFp(..) => STORE_FP::execute(ctx, decoded),
+        Inst::Load(..) => LOAD::execute::<P, C, TRACE>(ctx, decoded),
+        Inst::LoadFp(..) => LOAD_FP::execute::<P, C, TRACE>(ctx, decoded),
+        Inst::Store(..) => STORE::execute::<P, C, TRACE>(ctx, decoded),
+        Inst::StoreFp(..) => STORE_FP::execute::<P, C, TRACE>(ctx, decoded),
         Inst::MiscMem(..) => MISC_MEM::execute(ctx, decoded),
         Inst::System(..) => SYSTEM::execute(ctx, decoded),
         Inst::V(..) => {
@@ -133,3 +161,37 @@ pub const RV32_INSTRUCTION_MIX: &[(u32, u32)] = &[
     (CUS0::OPCODE, CUS0::INSTRUCTION_MIX),
     (UNKNOWN::OPCODE, UNKNOWN::INSTRUCTION_MIX),
 ];
+
+#[cfg(test)]
+mod tests {
+    use remu_isa::isa::extension_enum::{RV32I, RV32I_wjCus0, RV32I_zve32x_zvl128b};
+    use remu_state::{StateFastProfile, StatePolicy};
+
+    use super::decode;
+
+    fn gpr_dest<P: StatePolicy>(inst: u32) -> Option<u8> {
+        decode::<P>(inst).gpr_dest()
+    }
+
+    #[test]
+    fn gpr_dest_follows_the_decoder() {
+        type I = StateFastProfile<RV32I>;
+        assert_eq!(gpr_dest::<I>(0x0015_0513), Some(10)); // addi a0, a0, 1
+        assert_eq!(gpr_dest::<I>(0x00a1_2023), None); // sw   a0, 0(sp)
+        assert_eq!(gpr_dest::<I>(0x0000_0073), None); // ecall
+        assert_eq!(gpr_dest::<I>(0x3020_0073), None); // mret
+        assert_eq!(gpr_dest::<I>(0x3420_25f3), Some(11)); // csrr a1, mcause
+        assert_eq!(gpr_dest::<I>(0x0000_0013), None); // nop writes x0
+    }
+
+    #[test]
+    fn gpr_dest_covers_custom_and_vector_opcodes() {
+        // NN_LOAD a0, a1 (custom-0) writes rd only where the extension exists.
+        assert_eq!(gpr_dest::<StateFastProfile<RV32I_wjCus0>>(0x0005_a50b), Some(10));
+        assert_eq!(gpr_dest::<StateFastProfile<RV32I>>(0x0005_a50b), None);
+        type V = StateFastProfile<RV32I_zve32x_zvl128b>;
+        assert_eq!(gpr_dest::<V>(0x0105_f557), Some(10)); // vsetvli a0, a1, e32, m1
+        assert_eq!(gpr_dest::<V>(0x4280_2557), Some(10)); // vmv.x.s a0, v8
+        assert_eq!(gpr_dest::<V>(0x0285_80d7), None); // vadd.vv v1, v8, v11
+    }
+}
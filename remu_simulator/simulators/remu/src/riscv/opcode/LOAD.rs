@@ -44,7 +44,11 @@ pub(crate) fn decode<P: remu_state::StatePolicy>(inst: u32) -> DecodedInst {
 }
 
 #[inline(always)]
-pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
+pub(crate) fn execute<
+    P: remu_state::StatePolicy,
+    C: crate::ExecuteContext<P>,
+    const TRACE: u64,
+>(
     ctx: &mut C,
     decoded: &DecodedInst,
 ) -> Result<(), remu_state::StateError> {
@@ -54,23 +58,32 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
     let addr = rs1_val.wrapping_add(decoded.imm);
     match load {
         LoadInst::Lb => {
-            let v: u8 = state.bus.read_8(addr as usize).map_err(StateError::from)?;
+            let v: u8 = state.bus.read_8_traced::<TRACE>(addr as usize).map_err(StateError::from)?;
             state.reg.gpr.raw_write(decoded.rd.into(), (v as i8) as u32);
         }
         LoadInst::Lh => {
-            let v: u16 = state.bus.read_16(addr as usize).map_err(StateError::from)?;
+            let v: u16 = state
+                .bus
+                .read_16_traced::<TRACE>(addr as usize)
+                .map_err(StateError::from)?;
             state.reg.gpr.raw_write(decoded.rd.into(), (v as i16) as u32);
         }
         LoadInst::Lw => {
-            let v: u32 = state.bus.read_32(addr as usize).map_err(StateError::from)?;
+            let v: u32 = state
+                .bus
+                .read_32_traced::<TRACE>(addr as usize)
+                .map_err(StateError::from)?;
             state.reg.gpr.raw_write(decoded.rd.into(), v);
         }
         LoadInst::Lbu => {
-            let v: u8 = state.bus.read_8(addr as usize).map_err(StateError::from)?;
+            let v: u8 = state.bus.read_8_traced::<TRACE>(addr as usize).map_err(StateError::from)?;
             state.reg.gpr.raw_write(decoded.rd.into(), v as u32);
         }
         LoadInst::Lhu => {
-            let v: u16 = state.bus.read_16(addr as usize).map_err(StateError::from)?;
+            let v: u16 = state
+                .bus
+                .read_16_traced::<TRACE>(addr as usize)
+                .map_err(StateError::from)?;
             state.reg.gpr.raw_write(decoded.rd.into(), v as u32);
         }
     }
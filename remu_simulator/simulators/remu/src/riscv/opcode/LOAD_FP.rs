@@ -142,18 +142,20 @@ pub(crate) fn decode<P: remu_state::StatePolicy>(inst: u32) -> DecodedInst {
 /// Max VLENB we support; used for stack buffer in vle8.
 const MAX_VLENB: usize = 16;
 
-pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
+pub(crate) fn execute<
+    P: remu_state::StatePolicy,
+    C: crate::ExecuteContext<P>,
+    const TRACE: u64,
+>(
     ctx: &mut C,
     decoded: &DecodedInst,
 ) -> Result<(), remu_state::StateError> {
     let Inst::LoadFp(load_fp) = decoded.inst else { unreachable!() };
 
-    if <<P::ISA as RvIsa>::VConfig as VExtensionConfig>::VLENB > 0 {
-        let state = ctx.state_mut();
-        if state.reg.csr.mstatus_vs_off() {
-            UNKNOWN::trap_illegal_instruction(state);
-            return Ok(());
-        }
+    if <<P::ISA as RvIsa>::VConfig as VExtensionConfig>::VLENB > 0
+        && ctx.state_mut().reg.csr.mstatus_vs_off()
+    {
+        return UNKNOWN::trap_illegal_instruction(ctx);
     }
 
     match load_fp {
@@ -179,7 +181,7 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                     for f in 0..4 {
                         let addr =
                             base.wrapping_add((i * 4 + f) as u32) as usize;
-                        let val = state.bus.read_8(addr).map_err(StateError::from)?;
+                        let val = state.bus.read_8_traced::<TRACE>(addr).map_err(StateError::from)?;
                         vd_buf[f][i] = val;
                     }
                 }
@@ -202,7 +204,7 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                 for j in 0..vlenb {
                     chunk[j] = state
                         .bus
-                        .read_8(base.wrapping_add(j))
+                        .read_8_traced::<TRACE>(base.wrapping_add(j))
                         .map_err(StateError::from)?;
                 }
                 state.reg.vr.raw_write(vd, &chunk);
@@ -224,7 +226,7 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                     for j in 0..vlenb {
                         chunk[j] = state
                             .bus
-                            .read_8(base.wrapping_add(r * vlenb).wrapping_add(j))
+                            .read_8_traced::<TRACE>(base.wrapping_add(r * vlenb).wrapping_add(j))
                             .map_err(StateError::from)?;
                     }
                     state.reg.vr.raw_write(vd + r, &chunk);
@@ -292,7 +294,10 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                         let addr = base
                             .wrapping_add((i as u32).wrapping_mul(stride))
                             as usize;
-                        let val = state.bus.read_16(addr).map_err(StateError::from)?;
+                        let val = state
+                            .bus
+                            .read_16_traced::<TRACE>(addr)
+                            .map_err(StateError::from)?;
                         let off = (i * EEW_BYTES) % vlenb;
                         dst_chunk[off..off + EEW_BYTES]
                             .copy_from_slice(&val.to_le_bytes());
@@ -344,7 +349,10 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                         let addr = base
                             .wrapping_add((i as u32).wrapping_mul(stride))
                             as usize;
-                        let val = state.bus.read_32(addr).map_err(StateError::from)?;
+                        let val = state
+                            .bus
+                            .read_32_traced::<TRACE>(addr)
+                            .map_err(StateError::from)?;
                         let off = (i * SEW_BYTES) % vlenb;
                         dst_chunk[off..off + SEW_BYTES]
                             .copy_from_slice(&val.to_le_bytes());
@@ -411,7 +419,10 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                             continue;
                         }
                         let addr = base.wrapping_add((i as u32).wrapping_mul(EEW_BYTES as u32)) as usize;
-                        let val = state.bus.read_32(addr).map_err(StateError::from)?;
+                        let val = state
+                            .bus
+                            .read_32_traced::<TRACE>(addr)
+                            .map_err(StateError::from)?;
                         let off = (i * EEW_BYTES) % vlenb;
                         dst_chunk[off..off + EEW_BYTES].copy_from_slice(&val.to_le_bytes());
                     }
@@ -452,7 +463,7 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                     for j in 0..count {
                         buf[j] = state
                             .bus
-                            .read_8(
+                            .read_8_traced::<TRACE>(
                                 base.wrapping_add(start as u32)
                                     .wrapping_add(j as u32) as usize,
                             )
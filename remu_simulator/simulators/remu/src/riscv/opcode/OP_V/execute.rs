@@ -13,10 +13,8 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
         _ => return UNKNOWN::execute::<P, C>(ctx, decoded),
     };
 
-    let state = ctx.state_mut();
-    if state.reg.csr.mstatus_vs_off() {
-        UNKNOWN::trap_illegal_instruction(state);
-        return Ok(());
+    if ctx.state_mut().reg.csr.mstatus_vs_off() {
+        return UNKNOWN::trap_illegal_instruction(ctx);
     }
 
     // Only `vmv.x.s` / `vfirst.m` read vector state and write GPR; they do not update VS to Dirty.
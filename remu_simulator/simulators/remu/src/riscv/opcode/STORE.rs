@@ -38,7 +38,11 @@ pub(crate) fn decode<P: remu_state::StatePolicy>(inst: u32) -> DecodedInst {
 }
 
 #[inline(always)]
-pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
+pub(crate) fn execute<
+    P: remu_state::StatePolicy,
+    C: crate::ExecuteContext<P>,
+    const TRACE: u64,
+>(
     ctx: &mut C,
     decoded: &DecodedInst,
 ) -> Result<(), remu_state::StateError> {
@@ -49,18 +53,21 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
     match store {
         StoreInst::Sb => state
             .bus
-            .write_8(addr as usize, state.reg.gpr.raw_read(decoded.rs2.into()) as u8)
+            .write_8_traced::<TRACE>(
+                addr as usize,
+                state.reg.gpr.raw_read(decoded.rs2.into()) as u8,
+            )
             .map_err(StateError::from)?,
         StoreInst::Sh => state
             .bus
-            .write_16(
+            .write_16_traced::<TRACE>(
                 addr as usize,
                 state.reg.gpr.raw_read(decoded.rs2.into()) as u16,
             )
             .map_err(StateError::from)?,
         StoreInst::Sw => state
             .bus
-            .write_32(addr as usize, state.reg.gpr.raw_read(decoded.rs2.into()))
+            .write_32_traced::<TRACE>(addr as usize, state.reg.gpr.raw_read(decoded.rs2.into()))
             .map_err(StateError::from)?,
     }
     *state.reg.pc = state.reg.pc.wrapping_add(4);
@@ -111,18 +111,20 @@ pub(crate) fn decode<P: remu_state::StatePolicy>(inst: u32) -> DecodedInst {
     DecodedInst::default()
 }
 
-pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
+pub(crate) fn execute<
+    P: remu_state::StatePolicy,
+    C: crate::ExecuteContext<P>,
+    const TRACE: u64,
+>(
     ctx: &mut C,
     decoded: &DecodedInst,
 ) -> Result<(), remu_state::StateError> {
     let Inst::StoreFp(store) = decoded.inst else { unreachable!() };
 
-    if <<P::ISA as RvIsa>::VConfig as VExtensionConfig>::VLENB > 0 {
-        let state = ctx.state_mut();
-        if state.reg.csr.mstatus_vs_off() {
-            UNKNOWN::trap_illegal_instruction(state);
-            return Ok(());
-        }
+    if <<P::ISA as RvIsa>::VConfig as VExtensionConfig>::VLENB > 0
+        && ctx.state_mut().reg.csr.mstatus_vs_off()
+    {
+        return UNKNOWN::trap_illegal_instruction(ctx);
     }
 
     match store {
@@ -139,7 +141,10 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                     for (j, &byte) in chunk.iter().enumerate() {
                         state
                             .bus
-                            .write_8(base.wrapping_add(r * vlenb).wrapping_add(j), byte)
+                            .write_8_traced::<TRACE>(
+                                base.wrapping_add(r * vlenb).wrapping_add(j),
+                                byte,
+                            )
                             .map_err(StateError::from)?;
                     }
                 }
@@ -165,7 +170,7 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                 for (i, &byte) in data.iter().enumerate() {
                     state
                         .bus
-                        .write_8(rs1_val.wrapping_add(i as u32) as usize, byte)
+                        .write_8_traced::<TRACE>(rs1_val.wrapping_add(i as u32) as usize, byte)
                         .map_err(StateError::from)?;
                 }
                 *state.reg.pc = state.reg.pc.wrapping_add(4);
@@ -208,7 +213,7 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                     let chunk = state.reg.vr.raw_read(vs3 + reg_i);
                     state
                         .bus
-                        .write_8(base.wrapping_add(i) as usize, chunk[off])
+                        .write_8_traced::<TRACE>(base.wrapping_add(i) as usize, chunk[off])
                         .map_err(StateError::from)?;
                 }
                 *state.reg.pc = state.reg.pc.wrapping_add(4);
@@ -252,7 +257,10 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                     let val = u16::from_le_bytes(chunk[off..off + 2].try_into().unwrap());
                     state
                         .bus
-                        .write_16(base.wrapping_add(i.wrapping_mul(2)) as usize, val)
+                        .write_16_traced::<TRACE>(
+                            base.wrapping_add(i.wrapping_mul(2)) as usize,
+                            val,
+                        )
                         .map_err(StateError::from)?;
                 }
                 *state.reg.pc = state.reg.pc.wrapping_add(4);
@@ -296,7 +304,10 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                     let val = u32::from_le_bytes(chunk[off..off + 4].try_into().unwrap());
                     state
                         .bus
-                        .write_32(base.wrapping_add(i.wrapping_mul(4)) as usize, val)
+                        .write_32_traced::<TRACE>(
+                            base.wrapping_add(i.wrapping_mul(4)) as usize,
+                            val,
+                        )
                         .map_err(StateError::from)?;
                 }
                 *state.reg.pc = state.reg.pc.wrapping_add(4);
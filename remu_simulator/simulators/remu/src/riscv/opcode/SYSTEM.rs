@@ -2,13 +2,13 @@
 
 use remu_isa::isa::reg::{Csr as CsrKind, RegAccess};
 
-use crate::riscv::{opcode::UNKNOWN, DecodedInst, Inst, csr, funct3, rd, rs1};
+use crate::riscv::{opcode::UNKNOWN, DecodedInst, Inst, csr, funct3, rd, rs1, trap_return};
 
 pub(crate) const OPCODE: u32 = 0b111_0011;
 pub(crate) const INSTRUCTION_MIX: u32 = 20;
 
 mod func3 {
-    pub(super) const PRIV: u32 = 0b000; // ECALL, EBREAK
+    pub(super) const PRIV: u32 = 0b000; // ECALL, EBREAK, MRET
     pub(super) const CSRRW: u32 = 0b001;
     pub(super) const CSRRS: u32 = 0b010;
     pub(super) const CSRRC: u32 = 0b011;
@@ -17,7 +17,7 @@ mod func3 {
     pub(super) const CSRRCI: u32 = 0b111;
 }
 
-/// imm[11:0] for PRIV (funct3=0): inst[31:20]. 0 = ecall, 1 = ebreak.
+/// imm[11:0] for PRIV (funct3=0): inst[31:20]. 0 = ecall, 1 = ebreak, 0x302 = mret.
 #[inline(always)]
 fn imm_priv(inst: u32) -> u32 {
     (inst >> 20) & 0xFFF
@@ -27,6 +27,7 @@ fn imm_priv(inst: u32) -> u32 {
 pub(crate) enum SystemInst {
     Ecall,
     Ebreak,
+    Mret,
     Csrrw,
     Csrrs,
     Csrrc,
@@ -42,6 +43,7 @@ pub(crate) fn decode<P: remu_state::StatePolicy>(inst: u32) -> DecodedInst {
         func3::PRIV => match imm_priv(inst) {
             0 => SystemInst::Ecall,
             1 => SystemInst::Ebreak,
+            0x302 => SystemInst::Mret,
             _ => return DecodedInst::default(),
         },
         func3::CSRRW => SystemInst::Csrrw,
@@ -112,6 +114,7 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
             let pc = *state.reg.pc;
             ctx.on_ebreak(pc)
         }
+        SystemInst::Mret => trap_return(ctx),
         SystemInst::Csrrw | SystemInst::Csrrs | SystemInst::Csrrc
         | SystemInst::Csrrwi | SystemInst::Csrrsi | SystemInst::Csrrci => {
             let csr_imm = (decoded.imm & 0xFFF) as u16;
@@ -126,8 +129,7 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
                 }
             };
             if k.illegal_when_vs_off() && state.reg.csr.mstatus_vs_off() {
-                UNKNOWN::trap_illegal_instruction(state);
-                return Ok(());
+                return UNKNOWN::trap_illegal_instruction(ctx);
             }
             let old = state.reg.read_csr(k);
             let new_val = match sys {
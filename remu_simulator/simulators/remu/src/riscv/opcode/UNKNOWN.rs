@@ -1,17 +1,14 @@
-use remu_state::{State, StatePolicy};
 use remu_isa::isa::reg::Mcause;
+use remu_state::{StateError, StatePolicy};
 
-use crate::riscv::{DecodedInst, Inst};
+use crate::riscv::{DecodedInst, Inst, trap_entry};
 
 /// Illegal-instruction trap (M-mode); shared by [`execute`] and vector `mstatus.VS` checks.
 #[inline(always)]
-pub(crate) fn trap_illegal_instruction<P: StatePolicy>(state: &mut State<P>) {
-    let fault_pc = *state.reg.pc;
-    state.reg.csr.mepc = fault_pc;
-    state.reg.csr.mcause = Mcause::IllegalInstruction.to_u32();
-    state.reg.csr.mtval = 0;
-    state.reg.csr.mstatus_apply_trap_entry();
-    *state.reg.pc = state.reg.csr.mtvec_base().into();
+pub(crate) fn trap_illegal_instruction<P: StatePolicy, C: crate::ExecuteContext<P>>(
+    ctx: &mut C,
+) -> Result<(), StateError> {
+    trap_entry(ctx, Mcause::IllegalInstruction, 0)
 }
 
 pub(crate) const OPCODE: u32 = 0b111_1111;
@@ -33,6 +30,5 @@ pub(crate) fn execute<P: remu_state::StatePolicy, C: crate::ExecuteContext<P>>(
     ctx: &mut C,
     _decoded: &DecodedInst,
 ) -> Result<(), remu_state::StateError> {
-    trap_illegal_instruction(ctx.state_mut());
-    Ok(())
+    trap_illegal_instruction(ctx)
 }
@@ -0,0 +1,184 @@
+//! M-mode trap entry and `mret`; every trap goes through here so the context sees it.
+
+use remu_isa::isa::reg::Mcause;
+use remu_state::{StateError, StatePolicy};
+use remu_types::TrapEvent;
+
+/// Take a trap at the current PC: update `mepc` / `mcause` / `mtval` / `mstatus`, jump to `mtvec`.
+#[inline(always)]
+pub(crate) fn trap_entry<P: StatePolicy, C: crate::ExecuteContext<P>>(
+    ctx: &mut C,
+    cause: Mcause,
+    tval: u32,
+) -> Result<(), StateError> {
+    let state = ctx.state_mut();
+    let epc = *state.reg.pc;
+    let cause = cause.to_u32();
+    state.reg.csr.mepc = epc;
+    state.reg.csr.mcause = cause;
+    state.reg.csr.mtval = tval;
+    state.reg.csr.mstatus_apply_trap_entry();
+    let handler = state.reg.csr.mtvec_target(cause);
+    *state.reg.pc = handler;
+    ctx.on_trap(TrapEvent::Entry {
+        cause,
+        epc,
+        tval,
+        handler,
+    })
+}
+
+/// `mret`: restore `mstatus` and resume at `mepc`.
+#[inline(always)]
+pub(crate) fn trap_return<P: StatePolicy, C: crate::ExecuteContext<P>>(
+    ctx: &mut C,
+) -> Result<(), StateError> {
+    let state = ctx.state_mut();
+    let pc = *state.reg.pc;
+    let epc = state.reg.csr.mepc;
+    state.reg.csr.mstatus_apply_trap_return();
+    *state.reg.pc = epc;
+    let cause = state.reg.csr.mcause;
+    ctx.on_trap(TrapEvent::Return { cause, pc, epc })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::ops::Range;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use clap::Parser;
+    use remu_isa::isa::extension_enum::RV32I;
+    use remu_isa::isa::reg::Gpr;
+    use remu_simulator::{SimulatorCore, SimulatorDut, SimulatorInnerError, SimulatorOption};
+    use remu_state::StateFastProfile;
+    use remu_types::{AllUsize, DynDiagError, Tracer, TracerDyn};
+
+    use super::*;
+    use crate::SimulatorRemu;
+
+    type Remu = SimulatorRemu<StateFastProfile<RV32I>, true>;
+
+    struct Quiet;
+
+    impl Tracer for Quiet {
+        fn print(&self, _: &str) {}
+        fn mem_print(&self, _: usize, _: &[u8], _: Result<(), Box<dyn DynDiagError>>) {}
+        fn mem_show(&self, _: usize, _: Result<AllUsize, Box<dyn DynDiagError>>) {}
+        fn mem_show_map(&self, _: Vec<(String, Range<usize>)>) {}
+        fn reg_print(&self, _: &[(Gpr, u32); 32], _: Range<usize>) {}
+        fn reg_show(&self, _: Gpr, _: u32) {}
+        fn disasm(&self, _: u64, _: u32) {}
+    }
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        opt: SimulatorOption,
+    }
+
+    const PC: u32 = 0x8000_0000;
+    const HANDLER: u32 = 0x8000_0100;
+    const MRET: u32 = 0x3020_0073;
+    const ECALL: u32 = 0x0000_0073;
+
+    /// A hart at `PC` with `mtvec = HANDLER` and interrupts enabled; `program` is loaded at `PC`.
+    fn remu(program: &[u32]) -> Remu {
+        let opt = Args::parse_from(["remu"]).opt;
+        let tracer: TracerDyn = Rc::new(RefCell::new(Quiet));
+        let mut remu = Remu::new(opt, tracer, Arc::new(AtomicBool::new(false)));
+        let state = SimulatorCore::state_mut(&mut remu);
+        let bytes: Vec<u8> = program.iter().flat_map(|i| i.to_le_bytes()).collect();
+        state.bus.write_bytes_at(PC as usize, &bytes).unwrap();
+        *state.reg.pc = PC;
+        state.reg.csr.mtvec = HANDLER;
+        state.reg.csr.set_mstatus_mie(true);
+        remu
+    }
+
+    #[test]
+    fn entry_saves_context_and_masks_interrupts() {
+        let mut remu = remu(&[]);
+        trap_entry(&mut remu, Mcause::IllegalInstruction, 0x1234).unwrap();
+        let state = SimulatorCore::state(&remu);
+        assert_eq!(*state.reg.pc, HANDLER);
+        assert_eq!(state.reg.csr.mepc, PC);
+        assert_eq!(state.reg.csr.mcause, Mcause::IllegalInstruction.to_u32());
+        assert_eq!(state.reg.csr.mtval, 0x1234);
+        assert!(!state.reg.csr.mstatus_mie());
+        assert!(state.reg.csr.mstatus_mpie());
+    }
+
+    #[test]
+    fn mret_restores_mie_and_resumes_at_mepc() {
+        let mut remu = remu(&[]);
+        trap_entry(&mut remu, Mcause::IllegalInstruction, 0).unwrap();
+        let state = SimulatorCore::state_mut(&mut remu);
+        state.reg.csr.mepc = PC + 4;
+        state.bus.write_bytes_at(HANDLER as usize, &MRET.to_le_bytes()).unwrap();
+        remu.step_once::<0>().unwrap();
+        let state = SimulatorCore::state(&remu);
+        assert_eq!(*state.reg.pc, PC + 4);
+        assert!(state.reg.csr.mstatus_mie());
+        assert!(state.reg.csr.mstatus_mpie());
+    }
+
+    #[test]
+    fn vectored_mtvec_offsets_interrupts_only() {
+        let mut remu = remu(&[]);
+        SimulatorCore::state_mut(&mut remu).reg.csr.mtvec = HANDLER | 1;
+        trap_entry(&mut remu, Mcause::MachineExternalInterrupt, 0).unwrap();
+        assert_eq!(*SimulatorCore::state(&remu).reg.pc, HANDLER + 11 * 4);
+        trap_entry(&mut remu, Mcause::IllegalInstruction, 0).unwrap();
+        assert_eq!(*SimulatorCore::state(&remu).reg.pc, HANDLER);
+    }
+
+    #[test]
+    fn zero_mtvec_without_memory_at_zero_has_no_handler() {
+        let mut remu = remu(&[]);
+        SimulatorCore::state_mut(&mut remu).reg.csr.mtvec = 0;
+        let err = trap_entry(&mut remu, Mcause::IllegalInstruction, 0).unwrap_err();
+        assert!(matches!(
+            err,
+            StateError::NoTrapHandler {
+                cause: Mcause::IllegalInstruction,
+                epc: PC
+            }
+        ));
+    }
+
+    #[test]
+    fn catchpoint_stops_at_the_handler() {
+        // The all-zero word is an illegal instruction.
+        let mut remu = remu(&[0]);
+        remu.set_catch(Mcause::IllegalInstruction).unwrap();
+        let err = remu.step_once::<0>().unwrap_err();
+        assert!(matches!(
+            err,
+            SimulatorInnerError::CatchpointHit {
+                cause: Mcause::IllegalInstruction,
+                epc: PC
+            }
+        ));
+        let state = SimulatorCore::state(&remu);
+        assert_eq!(*state.reg.pc, HANDLER);
+        assert_eq!(state.reg.csr.mepc, PC);
+
+        remu.del_catch(Mcause::IllegalInstruction).unwrap();
+        *SimulatorCore::state_mut(&mut remu).reg.pc = PC;
+        remu.step_once::<0>().unwrap();
+        assert_eq!(*SimulatorCore::state(&remu).reg.pc, HANDLER);
+    }
+
+    #[test]
+    fn ecall_does_not_trap() {
+        let mut remu = remu(&[ECALL]);
+        remu.step_once::<0>().unwrap();
+        let state = SimulatorCore::state(&remu);
+        assert_eq!(*state.reg.pc, PC + 4);
+        assert_eq!(state.reg.csr.mcause, 0);
+    }
+}
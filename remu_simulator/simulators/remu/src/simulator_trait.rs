@@ -1,8 +1,10 @@
 use std::collections::HashMap;
 
+use remu_isa::isa::reg::Mcause;
+use remu_state::bus::AccessKind;
 use remu_state::reg::riscv::RiscvReg;
-use remu_state::{State, StateCmd, StateError};
-use remu_types::{DifftestMismatchItem, RegGroup, TracerDyn};
+use remu_state::{State, StateCmd, StateError, StateSnapshot};
+use remu_types::{DifftestMismatchItem, RegGroup, TraceFlags, TraceKind, TracerDyn, TrapEvent};
 
 use remu_simulator::{
     SimulatorCore, SimulatorDut, SimulatorInnerError, SimulatorOption, SimulatorPolicy,
@@ -28,7 +30,7 @@ pub enum BreakpointState {
     Active,
 }
 
-/// Execution context for decode+execute: provides state, icache flush, and ebreak handling.
+/// Execution context for decode+execute: provides state, icache flush, ebreak and trap handling.
 pub(crate) trait ExecuteContext<P: StatePolicy> {
     fn state_mut(&mut self) -> &mut State<P>;
     #[inline]
@@ -38,16 +40,31 @@ pub(crate) trait ExecuteContext<P: StatePolicy> {
     fn on_ebreak(&mut self, pc: u32) -> Result<(), StateError> {
         Err(StateError::BreakpointHit(pc))
     }
+
+    /// Called after trap entry / `mret` has updated CSRs and PC. Default: continue.
+    #[inline(always)]
+    fn on_trap(&mut self, event: TrapEvent) -> Result<(), StateError> {
+        let _ = event;
+        Ok(())
+    }
 }
 
 pub struct SimulatorRemu<P: SimulatorPolicy, const IS_DUT: bool> {
     state: State<P>,
     tracer: TracerDyn,
     icache: Icache<ICACHE_SIZE>,
-    /// Breakpoint PC -> original instruction (only used when IS_DUT).
+    /// Patched breakpoint PC -> original instruction (only used when IS_DUT). Ids, conditions
+    /// and counters live in the debugger.
     breakpoints: HashMap<u32, u32>,
     /// When IDLE, ebreak stops; when Active, ebreak runs the original instruction (only used when IS_DUT).
     breakpoint_state: BreakpointState,
+    /// Trap causes that stop execution after trap entry (only used when IS_DUT).
+    catches: Vec<Mcause>,
+    /// Exception trace: report trap entry / `mret` via the tracer (only used when IS_DUT).
+    etrace: bool,
+    /// Instruction the last step fetched and the GPR it writes, recorded only when stepping
+    /// with iringbuf tracing.
+    last_inst: Option<(u32, Option<u8>)>,
 }
 
 impl<P: SimulatorPolicy, const IS_DUT: bool> ExecuteContext<P> for SimulatorRemu<P, IS_DUT> {
@@ -69,21 +86,50 @@ impl<P: SimulatorPolicy, const IS_DUT: bool> ExecuteContext<P> for SimulatorRemu
             BreakpointState::Active => {
                 let orig = self.breakpoints.get(&pc).copied().unwrap();
                 let decoded = decode::<P>(orig);
-                self.execute_inst(&decoded)?;
+                // Rare path: check watchpoints at run time rather than threading `TRACE` here.
+                self.execute_inst::<{ TraceFlags::WATCH }>(&decoded)?;
                 self.breakpoint_state = BreakpointState::Idle;
                 Ok(())
             }
         }
     }
+    fn on_trap(&mut self, event: TrapEvent) -> Result<(), StateError> {
+        if IS_DUT && self.etrace {
+            self.tracer.borrow().trap_print(&event);
+        }
+        let TrapEvent::Entry { cause, epc, handler, .. } = event else {
+            return Ok(());
+        };
+        let cause = Mcause::from_u32(cause).unwrap();
+        // A zero mtvec would otherwise surface later as an unexplained unmapped fetch at 0.
+        if handler == 0 && !self.state.bus.is_mapped(0) {
+            return Err(StateError::NoTrapHandler { cause, epc });
+        }
+        if IS_DUT && self.catches.contains(&cause) {
+            return Err(StateError::CatchpointHit { cause, epc });
+        }
+        Ok(())
+    }
 }
 
 impl<P: SimulatorPolicy, const IS_DUT: bool> SimulatorRemu<P, IS_DUT> {
+    /// Keep the fetched instruction and the GPR it writes for the iringbuf, with a breakpoint
+    /// patch undone.
+    #[inline(never)]
+    fn record_inst(&mut self, pc: u32, inst: u32, decoded: &crate::riscv::DecodedInst) {
+        self.last_inst = Some(match self.breakpoints.get(&pc) {
+            Some(&orig) if inst == EBREAK_INST => (orig, decode::<P>(orig).gpr_dest()),
+            _ => (inst, decoded.gpr_dest()),
+        });
+    }
+
+    /// Watchpoints are checked only when `TRACE` has `TraceFlags::WATCH`.
     #[inline(always)]
-    fn execute_inst(
+    fn execute_inst<const TRACE: u64>(
         &mut self,
         decoded: &crate::riscv::DecodedInst,
     ) -> Result<(), StateError> {
-        crate::riscv::execute(self, decoded)
+        crate::riscv::execute::<P, Self, TRACE>(self, decoded)
     }
 }
 
@@ -99,6 +145,15 @@ impl<P: SimulatorPolicy, const IS_DUT: bool> SimulatorCore<P> for SimulatorRemu<
             icache: Icache::new(),
             breakpoints: HashMap::new(),
             breakpoint_state: BreakpointState::default(),
+            catches: Vec::new(),
+            etrace: false,
+            last_inst: None,
+        }
+    }
+
+    fn on_trace_change(&mut self, kind: TraceKind, enabled: bool) {
+        if kind == TraceKind::Exception {
+            self.etrace = enabled;
         }
     }
 
@@ -117,19 +172,29 @@ impl<P: SimulatorPolicy, const IS_DUT: bool> SimulatorCore<P> for SimulatorRemu<
 
     #[inline(always)]
     fn step_once<const TRACE: u64>(&mut self) -> Result<(), SimulatorInnerError> {
-        use remu_types::TraceFlags;
+        if IS_DUT {
+            self.check_interrupt().map_err(from_state_error)?;
+        }
         let pc = *self.state.reg.pc;
+        if TraceFlags::iringbuf(TRACE) && IS_DUT {
+            self.last_inst = None;
+        }
         let entry = self.icache.get_entry_mut(pc);
         if entry.addr == pc {
             let decoded = entry.decoded;
-            self.execute_inst(&decoded).map_err(from_state_error)?;
+            if TraceFlags::iringbuf(TRACE) && IS_DUT {
+                self.record_inst(pc, self.icache.raw(pc), &decoded);
+            }
+            if let Err(e) = self.execute_inst::<TRACE>(&decoded) {
+                return self.step_error(e);
+            }
             if TraceFlags::instruction(TRACE) && IS_DUT {
                 let inst = if let Some(&orig) = self.breakpoints.get(&pc) {
                     orig
                 } else {
                     self.state
                         .bus
-                        .read_32(pc as usize)
+                        .fetch_32(pc as usize)
                         .map_err(|e| from_state_error(StateError::from(e)))
                         .unwrap()
                 };
@@ -137,11 +202,10 @@ impl<P: SimulatorPolicy, const IS_DUT: bool> SimulatorCore<P> for SimulatorRemu<
             }
             return Ok(());
         }
-        let inst = self
-            .state
-            .bus
-            .read_32(pc as usize)
-            .map_err(|e| from_state_error(StateError::from(e)))?;
+        let inst = match self.state.bus.fetch_32(pc as usize) {
+            Ok(inst) => inst,
+            Err(e) => return self.step_error(StateError::from(e)),
+        };
         if TraceFlags::instruction(TRACE) && IS_DUT {
             let trace_inst = if let Some(&orig) = self.breakpoints.get(&pc) {
                 orig
@@ -151,9 +215,16 @@ impl<P: SimulatorPolicy, const IS_DUT: bool> SimulatorCore<P> for SimulatorRemu<
             self.tracer.borrow().disasm(pc as u64, trace_inst);
         }
         let d = decode::<P>(inst);
+        if TraceFlags::iringbuf(TRACE) && IS_DUT {
+            self.record_inst(pc, inst, &d);
+        }
+        self.icache.set_raw(pc, inst);
+        let entry = self.icache.get_entry_mut(pc);
         entry.addr = pc;
         entry.decoded = d;
-        self.execute_inst(&d).map_err(from_state_error)?;
+        if let Err(e) = self.execute_inst::<TRACE>(&d) {
+            return self.step_error(e);
+        }
         Ok(())
     }
 
@@ -240,6 +311,81 @@ impl<P: SimulatorPolicy, const IS_DUT: bool> SimulatorCore<P> for SimulatorRemu<
             Some(buf.into_boxed_slice())
         }
     }
+
+    fn sync_mem_from(&mut self, addr: usize, data: &[u8]) -> Result<(), SimulatorInnerError> {
+        self.state
+            .bus
+            .write_bytes_at(addr, data)
+            .map_err(|e| from_state_error(StateError::from(e)))
+    }
+
+    fn restore_state(&mut self, snap: &StateSnapshot<P::ISA>) -> Result<(), SimulatorInnerError> {
+        self.state.restore(snap);
+        if IS_DUT {
+            // The snapshot holds unpatched code: re-patch, keeping what each patch covers now.
+            for (&addr, orig) in self.breakpoints.iter_mut() {
+                *orig = self
+                    .state
+                    .bus
+                    .fetch_32(addr as usize)
+                    .map_err(StateError::from)?;
+                self.state
+                    .bus
+                    .write_32_no_observer(addr as usize, EBREAK_INST)
+                    .map_err(StateError::from)?;
+            }
+            self.breakpoint_state = self.breakpoint_state_at_pc();
+        }
+        self.icache.flush();
+        Ok(())
+    }
+}
+
+impl<P: SimulatorPolicy, const IS_DUT: bool> SimulatorRemu<P, IS_DUT> {
+    /// Poll devices, update `mip.MEIP` from the PLIC and, when it is enabled, enter the trap
+    /// handler; the step then runs the handler's first instruction.
+    #[inline(always)]
+    fn check_interrupt(&mut self) -> Result<(), StateError> {
+        let meip = self.state.bus.poll_devices()?;
+        self.state.reg.csr.set_mip_meip(meip);
+        if meip && self.state.reg.csr.external_interrupt_ready() {
+            self.state.bus.note_interrupt();
+            // A breakpoint being stepped over was not executed: it must hit again after `mret`.
+            self.breakpoint_state = BreakpointState::Idle;
+            crate::riscv::trap_entry(self, Mcause::MachineExternalInterrupt, 0)?;
+        }
+        Ok(())
+    }
+
+    /// A step failed. An access the memory attributes forbid becomes the matching access-fault
+    /// exception under `--access-fault trap` (the instruction has had no effect, so `mepc` is
+    /// its PC); anything else stops execution.
+    #[cold]
+    #[inline(never)]
+    fn step_error(&mut self, e: StateError) -> Result<(), SimulatorInnerError> {
+        let Some((kind, addr)) = e
+            .access_fault()
+            .filter(|_| self.state.bus.traps_access_faults())
+        else {
+            return Err(from_state_error(e));
+        };
+        let cause = match kind {
+            AccessKind::Read => Mcause::LoadAccessFault,
+            AccessKind::Write => Mcause::StoreAccessFault,
+            AccessKind::Fetch => Mcause::InstructionAccessFault,
+        };
+        self.breakpoint_state = BreakpointState::Idle;
+        crate::riscv::trap_entry(self, cause, addr as u32).map_err(from_state_error)
+    }
+
+    /// Active when the PC sits on a patched breakpoint, so resuming runs its instruction.
+    fn breakpoint_state_at_pc(&self) -> BreakpointState {
+        if self.breakpoints.contains_key(&*self.state.reg.pc) {
+            BreakpointState::Active
+        } else {
+            BreakpointState::Idle
+        }
+    }
 }
 
 impl<P: SimulatorPolicy> SimulatorDut for SimulatorRemu<P, true> {
@@ -257,7 +403,7 @@ impl<P: SimulatorPolicy> SimulatorDut for SimulatorRemu<P, true> {
         let orig = self
             .state
             .bus
-            .read_32(addr as usize)
+            .fetch_32(addr as usize)
             .map_err(StateError::from)
             .map_err(SimulatorInnerError::from)?;
         self.state
@@ -278,6 +424,10 @@ impl<P: SimulatorPolicy> SimulatorDut for SimulatorRemu<P, true> {
                 .map_err(StateError::from)
                 .map_err(SimulatorInnerError::from)?;
             self.icache.invalidate(addr);
+            // Stopped on this breakpoint: the original instruction now runs without the ebreak.
+            if addr == *self.state.reg.pc {
+                self.breakpoint_state = BreakpointState::Idle;
+            }
             Ok(())
         } else {
             Err(SimulatorInnerError::BreakpointError(format!(
@@ -286,10 +436,97 @@ impl<P: SimulatorPolicy> SimulatorDut for SimulatorRemu<P, true> {
         }
     }
 
-    fn print_breakpoints(&self) {
-        let mut addrs: Vec<u32> = self.breakpoints.keys().copied().collect();
-        addrs.sort();
-        self.tracer.borrow().breakpoint_print(&addrs);
+    #[inline(always)]
+    fn fetch_inst(&mut self, pc: u32) -> Option<u32> {
+        if let Some(&orig) = self.breakpoints.get(&pc) {
+            return Some(orig);
+        }
+        self.state.bus.fetch_32(pc as usize).ok()
+    }
+
+    #[inline(always)]
+    fn last_inst(&mut self, _pc: u32) -> Option<(u32, Option<u8>)> {
+        self.last_inst
+    }
+
+    fn flush_icache(&mut self) {
+        self.icache.flush();
+    }
+
+    fn write_memory(&mut self, addr: usize, data: &[u8]) -> Result<(), SimulatorInnerError> {
+        self.state
+            .bus
+            .write_bytes(addr, data)
+            .map_err(StateError::from)?;
+        let end = addr + data.len();
+        for (&at, orig) in &mut self.breakpoints {
+            let start = at as usize;
+            if start + 4 <= addr || start >= end {
+                continue;
+            }
+            let mut bytes = orig.to_le_bytes();
+            for (i, b) in bytes.iter_mut().enumerate() {
+                if let Some(&new) = (start + i).checked_sub(addr).and_then(|o| data.get(o)) {
+                    *b = new;
+                }
+            }
+            *orig = u32::from_le_bytes(bytes);
+            self.state
+                .bus
+                .write_32_no_observer(start, EBREAK_INST)
+                .map_err(StateError::from)?;
+        }
+        Ok(())
+    }
+
+    fn save_state(
+        &mut self,
+        prev: Option<&StateSnapshot<P::ISA>>,
+    ) -> Result<StateSnapshot<P::ISA>, SimulatorInnerError> {
+        for (&addr, &orig) in &self.breakpoints {
+            self.state
+                .bus
+                .write_32_no_observer(addr as usize, orig)
+                .map_err(StateError::from)?;
+        }
+        let snap = self.state.snapshot(prev);
+        for &addr in self.breakpoints.keys() {
+            self.state
+                .bus
+                .write_32_no_observer(addr as usize, EBREAK_INST)
+                .map_err(StateError::from)?;
+        }
+        Ok(snap)
+    }
+
+    fn resume_over_breakpoint(&mut self) {
+        self.breakpoint_state = self.breakpoint_state_at_pc();
+    }
+
+    fn rearm_breakpoint(&mut self) {
+        self.breakpoint_state = BreakpointState::Idle;
+    }
+
+    fn set_catch(&mut self, cause: Mcause) -> Result<(), SimulatorInnerError> {
+        if !self.catches.contains(&cause) {
+            self.catches.push(cause);
+        }
+        Ok(())
+    }
+
+    fn del_catch(&mut self, cause: Mcause) -> Result<(), SimulatorInnerError> {
+        if let Some(pos) = self.catches.iter().position(|&c| c == cause) {
+            self.catches.remove(pos);
+            Ok(())
+        } else {
+            Err(SimulatorInnerError::BreakpointError(format!(
+                "catchpoint for {cause} not found"
+            )))
+        }
+    }
+
+    fn print_catches(&self) {
+        self.tracer.borrow().catch_print(&self.catches);
     }
 }
 
@@ -2,9 +2,11 @@ use std::ffi::CString;
 use std::marker::PhantomData;
 use std::os::raw::c_uint;
 
-use remu_state::bus::{BusOption, MemoryEntry, try_load_elf_into_memory};
+use remu_state::bus::{
+    BusOption, MemoryEntry, try_load_dtb_into_memory, try_load_elf_into_memory,
+};
 use remu_state::reg::riscv::RiscvReg;
-use remu_state::{State, StateCmd};
+use remu_state::{State, StateCmd, StateError, StateSnapshot};
 use remu_isa::isa::RvIsa;
 use remu_isa::isa::extension_v::VExtensionConfig;
 use remu_isa::isa::reg::{Fpr, Gpr, RegAccess, VrState as VrStateTrait};
@@ -49,6 +51,7 @@ impl<P: SimulatorPolicy> SimulatorCore<P> for SimulatorSpike<P> {
             .collect();
 
         try_load_elf_into_memory(&mut memory, &bus_option.elf, &tracer);
+        try_load_dtb_into_memory(&mut memory, &bus_option, P::ISA::ISA_STR, &tracer);
 
         if memory.is_empty() {
             return Self {
@@ -68,7 +71,10 @@ impl<P: SimulatorPolicy> SimulatorCore<P> for SimulatorSpike<P> {
             .collect();
 
         let init_pc = opt.state.reg.init_pc;
-        let init_gpr = [0u32; 32];
+        let mut init_gpr = [0u32; 32];
+        if let Some(dtb) = bus_option.dtb {
+            init_gpr[Gpr::A1.idx()] = dtb as u32;
+        }
 
         // ISA_STR must match our VConfig (e.g. rv32i_zve32x_zvl128b). Spike parses zvl128b from
         // ISA string to set VLEN; no Spike source modification.
@@ -308,6 +314,35 @@ impl<P: SimulatorPolicy> SimulatorCore<P> for SimulatorSpike<P> {
         }
         Ok(())
     }
+
+    /// Memory only; registers follow through `sync_regs_from`.
+    fn sync_mem_from(&mut self, addr: usize, data: &[u8]) -> Result<(), SimulatorInnerError> {
+        let Some(ctx) = self.ctx else {
+            return Ok(());
+        };
+        if unsafe { spike_difftest_write_mem(ctx, addr, data.as_ptr(), data.len()) } != 0 {
+            return Err(SimulatorInnerError::RefError(format!(
+                "spike_difftest_write_mem failed at 0x{addr:08x}"
+            )));
+        }
+        Ok(())
+    }
+
+    fn restore_state(&mut self, snap: &StateSnapshot<P::ISA>) -> Result<(), SimulatorInnerError> {
+        let Some(ctx) = self.ctx else {
+            return Err(SimulatorInnerError::RefError(
+                "spike difftest not initialized".to_string(),
+            ));
+        };
+        for (addr, page) in snap.bus.memory.iter().flat_map(|r| r.pages()) {
+            if unsafe { spike_difftest_write_mem(ctx, addr, page.as_ptr(), page.len()) } != 0 {
+                return Err(SimulatorInnerError::RefError(format!(
+                    "spike_difftest_write_mem failed at 0x{addr:08x}"
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<P: SimulatorPolicy> SimulatorRef<P> for SimulatorSpike<P> {
@@ -357,7 +392,7 @@ fn state_exec_reg(
                     new_gpr[i] = unsafe { *gpr_ptr.add(2 * i) };
                 }
                 let new_regs = DifftestRegs {
-                    pc: *value,
+                    pc: value.get().map_err(StateError::from)?,
                     gpr: new_gpr,
                 };
                 unsafe { spike_difftest_sync_regs_to_spike(ctx, &new_regs) };
@@ -383,7 +418,7 @@ fn state_exec_reg(
                     new_gpr[i] = unsafe { *gpr_ptr.add(2 * i) };
                 }
                 if index.idx() != 0 {
-                    new_gpr[index.idx()] = *value;
+                    new_gpr[index.idx()] = value.get().map_err(StateError::from)?;
                 }
                 let new_regs = DifftestRegs { pc, gpr: new_gpr };
                 unsafe { spike_difftest_sync_regs_to_spike(ctx, &new_regs) };
@@ -469,11 +504,11 @@ fn state_exec_bus(
     match subcmd {
         remu_state::bus::BusCmd::Read { subcmd } => {
             let (addr, width) = match subcmd {
-                ReadCommand::U8(a) => (a.addr, 1),
-                ReadCommand::U16(a) => (a.addr, 2),
-                ReadCommand::U32(a) => (a.addr, 4),
-                ReadCommand::U64(a) => (a.addr, 8),
-                ReadCommand::U128(a) => (a.addr, 16),
+                ReadCommand::U8(a) => (a.addr.get().map_err(StateError::from)?, 1),
+                ReadCommand::U16(a) => (a.addr.get().map_err(StateError::from)?, 2),
+                ReadCommand::U32(a) => (a.addr.get().map_err(StateError::from)?, 4),
+                ReadCommand::U64(a) => (a.addr.get().map_err(StateError::from)?, 8),
+                ReadCommand::U128(a) => (a.addr.get().map_err(StateError::from)?, 16),
             };
             let mut buf = [0u8; 16];
             let buf_slice = &mut buf[..width];
@@ -502,24 +537,25 @@ fn state_exec_bus(
         }
         remu_state::bus::BusCmd::Print { addr, count } => {
             const PRINT_BUF_SIZE: usize = 256;
-            let count = (*count).min(PRINT_BUF_SIZE);
+            let addr = addr.get().map_err(StateError::from)?;
+            let count = count.get().map_err(StateError::from)?.min(PRINT_BUF_SIZE);
             let mut buf = [0u8; PRINT_BUF_SIZE];
             let buf_slice = &mut buf[..count];
             let result =
-                if unsafe { spike_difftest_read_mem(ctx, *addr, buf_slice.as_mut_ptr(), count) }
+                if unsafe { spike_difftest_read_mem(ctx, addr, buf_slice.as_mut_ptr(), count) }
                     == 0
                 {
                     Ok(())
                 } else {
-                    Err(Box::new(remu_state::bus::BusError::unmapped(*addr))
+                    Err(Box::new(remu_state::bus::BusError::unmapped(addr))
                         as Box<dyn DynDiagError>)
                 };
-            tracer.borrow().mem_print(*addr, buf_slice, result);
+            tracer.borrow().mem_print(addr, buf_slice, result);
         }
         remu_state::bus::BusCmd::Write { subcmd } => match subcmd {
             WriteCommand::U8 { addr, value } => {
-                let bytes = value.to_le_bytes();
-                if unsafe { spike_difftest_write_mem(ctx, *addr, bytes.as_ptr(), bytes.len()) } != 0
+                let (addr, bytes) = (addr.get().map_err(StateError::from)?, value.get().map_err(StateError::from)?.to_le_bytes());
+                if unsafe { spike_difftest_write_mem(ctx, addr, bytes.as_ptr(), bytes.len()) } != 0
                 {
                     return Err(SimulatorInnerError::RefError(format!(
                         "spike_difftest_write_mem failed: addr={:#x}",
@@ -528,8 +564,8 @@ fn state_exec_bus(
                 }
             }
             WriteCommand::U16 { addr, value } => {
-                let bytes = value.to_le_bytes();
-                if unsafe { spike_difftest_write_mem(ctx, *addr, bytes.as_ptr(), bytes.len()) } != 0
+                let (addr, bytes) = (addr.get().map_err(StateError::from)?, value.get().map_err(StateError::from)?.to_le_bytes());
+                if unsafe { spike_difftest_write_mem(ctx, addr, bytes.as_ptr(), bytes.len()) } != 0
                 {
                     return Err(SimulatorInnerError::RefError(format!(
                         "spike_difftest_write_mem failed: addr={:#x}",
@@ -538,8 +574,8 @@ fn state_exec_bus(
                 }
             }
             WriteCommand::U32 { addr, value } => {
-                let bytes = value.to_le_bytes();
-                if unsafe { spike_difftest_write_mem(ctx, *addr, bytes.as_ptr(), bytes.len()) } != 0
+                let (addr, bytes) = (addr.get().map_err(StateError::from)?, value.get().map_err(StateError::from)?.to_le_bytes());
+                if unsafe { spike_difftest_write_mem(ctx, addr, bytes.as_ptr(), bytes.len()) } != 0
                 {
                     return Err(SimulatorInnerError::RefError(format!(
                         "spike_difftest_write_mem failed: addr={:#x}",
@@ -548,8 +584,8 @@ fn state_exec_bus(
                 }
             }
             WriteCommand::U64 { addr, value } => {
-                let bytes = value.to_le_bytes();
-                if unsafe { spike_difftest_write_mem(ctx, *addr, bytes.as_ptr(), bytes.len()) } != 0
+                let (addr, bytes) = (addr.get().map_err(StateError::from)?, value.get().map_err(StateError::from)?.to_le_bytes());
+                if unsafe { spike_difftest_write_mem(ctx, addr, bytes.as_ptr(), bytes.len()) } != 0
                 {
                     return Err(SimulatorInnerError::RefError(format!(
                         "spike_difftest_write_mem failed: addr={:#x}",
@@ -558,8 +594,8 @@ fn state_exec_bus(
                 }
             }
             WriteCommand::U128 { addr, value } => {
-                let bytes = value.to_le_bytes();
-                if unsafe { spike_difftest_write_mem(ctx, *addr, bytes.as_ptr(), bytes.len()) } != 0
+                let (addr, bytes) = (addr.get().map_err(StateError::from)?, value.get().map_err(StateError::from)?.to_le_bytes());
+                if unsafe { spike_difftest_write_mem(ctx, addr, bytes.as_ptr(), bytes.len()) } != 0
                 {
                     return Err(SimulatorInnerError::RefError(format!(
                         "spike_difftest_write_mem failed: addr={:#x}",
@@ -569,7 +605,7 @@ fn state_exec_bus(
             }
         },
         remu_state::bus::BusCmd::Set { address, value } => {
-            let mut addr = *address;
+            let mut addr = address.get().map_err(StateError::from)?;
             for chunk in value.iter() {
                 if chunk.is_empty() {
                     continue;
@@ -1,6 +1,7 @@
 use std::fmt;
 
 use remu_state::StateError;
+use remu_isa::isa::reg::Mcause;
 use remu_types::{DifftestMismatchItem, ExitCode};
 use thiserror::Error;
 
@@ -30,12 +31,27 @@ pub enum SimulatorInnerError {
     #[error("interrupted")]
     Interrupted,
 
+    /// The guest asked for a reboot; the harness brings the DUT back to power-on state.
+    #[error("reboot requested")]
+    Reboot,
+
+    /// A watchdog with `action=stop` ran out before the guest kicked it.
+    #[error("watchdog timeout")]
+    WatchdogTimeout,
+
     #[error("breakpoint: {0}")]
     BreakpointError(String),
 
+    #[error("snapshot: {0}")]
+    SnapshotError(String),
+
     /// DUT hit a breakpoint (ebreak at this PC). Execution stopped.
     #[error("breakpoint hit at 0x{0:08x}")]
     BreakpointHit(u32),
+
+    /// DUT took a trap whose cause is being caught. The trap has been entered (PC at the handler).
+    #[error("catchpoint hit: {cause} at 0x{epc:08x}")]
+    CatchpointHit { cause: Mcause, epc: u32 },
 }
 
 impl SimulatorInnerError {
@@ -46,8 +62,12 @@ impl SimulatorInnerError {
             SimulatorInnerError::RefError(_)
             | SimulatorInnerError::ProgramExit(_)
             | SimulatorInnerError::Interrupted
+            | SimulatorInnerError::Reboot
+            | SimulatorInnerError::WatchdogTimeout
             | SimulatorInnerError::BreakpointError(_)
-            | SimulatorInnerError::BreakpointHit(_) => None,
+            | SimulatorInnerError::SnapshotError(_)
+            | SimulatorInnerError::BreakpointHit(_)
+            | SimulatorInnerError::CatchpointHit { .. } => None,
         }
     }
 }
@@ -55,8 +75,16 @@ impl SimulatorInnerError {
 pub fn from_state_error(e: StateError) -> SimulatorInnerError {
     if let Some(exit_code) = e.exit_code() {
         SimulatorInnerError::ProgramExit(exit_code)
+    } else if e.is_reboot() {
+        SimulatorInnerError::Reboot
+    } else if e.is_watchdog_timeout() {
+        SimulatorInnerError::WatchdogTimeout
+    } else if e.is_escape_key() {
+        SimulatorInnerError::Interrupted
     } else if let Some(pc) = e.breakpoint_pc() {
         SimulatorInnerError::BreakpointHit(pc)
+    } else if let Some((cause, epc)) = e.catchpoint() {
+        SimulatorInnerError::CatchpointHit { cause, epc }
     } else {
         SimulatorInnerError::StateAccessError(e)
     }
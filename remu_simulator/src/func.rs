@@ -24,6 +24,16 @@ pub enum TraceCmd {
         #[arg(value_parser = parse_switch, action = ArgAction::Set)]
         enable: bool,
     },
+    /// Exception Trace (trap entry / mret)
+    Exception {
+        #[arg(value_parser = parse_switch, action = ArgAction::Set)]
+        enable: bool,
+    },
+    /// Instruction Ring Buffer (last N instructions, dumped on failure)
+    Iringbuf {
+        #[arg(value_parser = parse_switch, action = ArgAction::Set)]
+        enable: bool,
+    },
 }
 
 fn parse_switch(s: &str) -> Result<bool, String> {
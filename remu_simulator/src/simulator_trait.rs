@@ -3,7 +3,8 @@ use std::sync::atomic::AtomicBool;
 
 use remu_state::bus::ObserverEvent;
 use remu_state::reg::riscv::RiscvReg;
-use remu_state::{State, StateCmd, StatePolicy};
+use remu_isa::isa::reg::Mcause;
+use remu_state::{State, StateCmd, StatePolicy, StateSnapshot};
 use remu_types::{DifftestMismatchItem, TraceKind, TracerDyn};
 
 use crate::SimulatorOption;
@@ -70,6 +71,24 @@ pub trait SimulatorCore<P: StatePolicy> {
         let _ = (addr, dut_data);
         None
     }
+
+    /// Write `data` to memory at `addr`, for RAM written by a DUT device (DMA): ref models have
+    /// no devices.
+    #[inline(always)]
+    fn sync_mem_from(&mut self, addr: usize, data: &[u8]) -> Result<(), SimulatorInnerError> {
+        let _ = (addr, data);
+        Ok(())
+    }
+
+    /// Rewind to `snap` (taken from the DUT of this run). Ref models may restore only memory:
+    /// the harness syncs their registers from the DUT afterwards.
+    #[inline(always)]
+    fn restore_state(&mut self, snap: &StateSnapshot<P::ISA>) -> Result<(), SimulatorInnerError> {
+        let _ = snap;
+        Err(SimulatorInnerError::SnapshotError(
+            "snapshots are not supported by this simulator".into(),
+        ))
+    }
 }
 
 pub trait SimulatorDut: SimulatorCore<<Self as SimulatorDut>::Policy> {
@@ -86,10 +105,79 @@ pub trait SimulatorDut: SimulatorCore<<Self as SimulatorDut>::Policy> {
         Ok(())
     }
 
-    /// Print all breakpoints via the tracer (e.g. list addresses). Default: no-op.
+    /// Raw instruction at `pc` as the program sees it (breakpoint patches undone).
+    #[inline(always)]
+    fn fetch_inst(&mut self, pc: u32) -> Option<u32> {
+        self.state_mut().bus.fetch_32(pc as usize).ok()
+    }
+
+    /// Raw instruction (breakpoint patches undone) the last `step_once` with iringbuf tracing
+    /// ran from `pc`, and the GPR its decode writes; `None` if its fetch failed. Default: read
+    /// it again from memory, with no destination known.
+    #[inline(always)]
+    fn last_inst(&mut self, pc: u32) -> Option<(u32, Option<u8>)> {
+        self.fetch_inst(pc).map(|inst| (inst, None))
+    }
+
+    /// Memory was changed from outside the program (debugger, GDB): drop cached decodes.
+    #[inline(always)]
+    fn flush_icache(&mut self) {}
+
+    /// Write memory from outside the program (debugger, GDB), without observers. Bytes that
+    /// land on a patched breakpoint replace the instruction it saved; the patch stays.
+    #[inline(always)]
+    fn write_memory(&mut self, addr: usize, data: &[u8]) -> Result<(), SimulatorInnerError> {
+        self.state_mut()
+            .bus
+            .write_bytes(addr, data)
+            .map_err(remu_state::StateError::from)?;
+        Ok(())
+    }
+
+    /// Snapshot of the state as the program sees it (breakpoint patches undone); memory pages
+    /// unchanged since `prev` are shared with it.
+    #[inline(always)]
+    fn save_state(
+        &mut self,
+        prev: Option<&StateSnapshot<<Self::Policy as StatePolicy>::ISA>>,
+    ) -> Result<StateSnapshot<<Self::Policy as StatePolicy>::ISA>, SimulatorInnerError> {
+        let _ = prev;
+        Err(SimulatorInnerError::SnapshotError(
+            "snapshots are not supported by this simulator".into(),
+        ))
+    }
+
+    /// Execution was moved to the current PC from outside (snapshot restore, reverse
+    /// execution): a breakpoint there counts as already reported, so resuming runs it.
+    #[inline(always)]
+    fn resume_over_breakpoint(&mut self) {}
+
+    /// The program itself moved to the current PC (reboot): a breakpoint there has not been
+    /// reported yet, so it hits.
+    #[inline(always)]
+    fn rearm_breakpoint(&mut self) {}
+
+    /// Stop after entering a trap with this cause. Default: catchpoints unsupported.
+    #[inline(always)]
+    fn set_catch(&mut self, cause: Mcause) -> Result<(), SimulatorInnerError> {
+        let _ = cause;
+        Err(SimulatorInnerError::BreakpointError(
+            "catchpoints are not supported by this simulator".into(),
+        ))
+    }
+
+    #[inline(always)]
+    fn del_catch(&mut self, cause: Mcause) -> Result<(), SimulatorInnerError> {
+        let _ = cause;
+        Err(SimulatorInnerError::BreakpointError(
+            "catchpoints are not supported by this simulator".into(),
+        ))
+    }
+
+    /// Print all catchpoints via the tracer. Default: no-op.
     #[inline(always)]
-    fn print_breakpoints(&self) {
-        // Default: no breakpoints to print.
+    fn print_catches(&self) {
+        // Default: no catchpoints to print.
     }
 
     /// Platform-specific statistics (e.g. cycle count, IPC). Receives ctx for derived stats.
@@ -1,131 +1,251 @@
 use remu_isa::isa::RvIsa;
+use remu_types::TraceFlags;
 
-use crate::bus::{Bus, BusError, BusObserver};
+use crate::bus::{AccessKind, Bus, BusError, BusObserver};
 
 impl<I: RvIsa, O: BusObserver> Bus<I, O> {
     #[inline(always)]
-    pub(crate) fn read_8_impl<const NOTIFY_OBSERVER: bool>(
+    pub(crate) fn read_8_impl<const NOTIFY_OBSERVER: bool, const WATCH: bool>(
         &mut self,
         addr: usize,
     ) -> Result<u8, BusError> {
         if let Some(v) = self.memory.read_8(addr) {
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mem_read_8(addr, v);
+            }
             return Ok(v);
         }
 
-        if let Some(d) = self.find_device_mut(addr..addr + 1) {
+        if let Some(d) = self.find_device_mut(addr..addr + 1)? {
             let val = d.1.read_8(addr - d.0)?;
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mmio_read_8(addr, val);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mmio_read_8(addr, val);
+            }
             return Ok(val);
         }
 
-        Err(BusError::unmapped(addr))
+        let mut buf = [0; 1];
+        self.read_miss::<NOTIFY_OBSERVER>(addr, &mut buf)?;
+        Ok(u8::from_le_bytes(buf))
     }
 
     #[inline(always)]
     pub fn read_8(&mut self, addr: usize) -> Result<u8, BusError> {
-        self.read_8_impl::<true>(addr)
+        self.read_8_impl::<true, true>(addr)
+    }
+
+    /// Guest load from a simulator step: watchpoints are checked only when `TRACE` has
+    /// `TraceFlags::WATCH`.
+    #[inline(always)]
+    pub fn read_8_traced<const TRACE: u64>(&mut self, addr: usize) -> Result<u8, BusError> {
+        if TraceFlags::watch(TRACE) {
+            self.read_8_impl::<true, true>(addr)
+        } else {
+            self.read_8_impl::<true, false>(addr)
+        }
     }
 
     #[inline(always)]
-    pub(crate) fn read_16_impl<const NOTIFY_OBSERVER: bool>(
+    pub(crate) fn read_16_impl<const NOTIFY_OBSERVER: bool, const WATCH: bool>(
         &mut self,
         addr: usize,
     ) -> Result<u16, BusError> {
         if let Some(v) = self.memory.read_16(addr) {
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mem_read_16(addr, v);
+            }
             return Ok(v);
         }
 
-        if let Some(d) = self.find_device_mut(addr..addr + 2) {
+        if let Some(d) = self.find_device_mut(addr..addr + 2)? {
             let val = d.1.read_16(addr - d.0)?;
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mmio_read_16(addr, val);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mmio_read_16(addr, val);
+            }
             return Ok(val);
         }
 
-        Err(BusError::unmapped(addr))
+        let mut buf = [0; 2];
+        self.read_miss::<NOTIFY_OBSERVER>(addr, &mut buf)?;
+        Ok(u16::from_le_bytes(buf))
     }
 
     #[inline(always)]
     pub fn read_16(&mut self, addr: usize) -> Result<u16, BusError> {
-        self.read_16_impl::<true>(addr)
+        self.read_16_impl::<true, true>(addr)
+    }
+
+    /// Guest load from a simulator step: watchpoints are checked only when `TRACE` has
+    /// `TraceFlags::WATCH`.
+    #[inline(always)]
+    pub fn read_16_traced<const TRACE: u64>(&mut self, addr: usize) -> Result<u16, BusError> {
+        if TraceFlags::watch(TRACE) {
+            self.read_16_impl::<true, true>(addr)
+        } else {
+            self.read_16_impl::<true, false>(addr)
+        }
     }
 
     #[inline(always)]
-    pub(crate) fn read_32_impl<const NOTIFY_OBSERVER: bool>(
+    pub(crate) fn read_32_impl<const NOTIFY_OBSERVER: bool, const WATCH: bool>(
         &mut self,
         addr: usize,
     ) -> Result<u32, BusError> {
         if let Some(v) = self.memory.read_32(addr) {
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mem_read_32(addr, v);
+            }
             return Ok(v);
         }
 
-        if let Some(d) = self.find_device_mut(addr..addr + 4) {
+        if let Some(d) = self.find_device_mut(addr..addr + 4)? {
             let val = d.1.read_32(addr - d.0)?;
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mmio_read_32(addr, val);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mmio_read_32(addr, val);
+            }
             return Ok(val);
         }
 
-        Err(BusError::unmapped(addr))
+        let mut buf = [0; 4];
+        self.read_miss::<NOTIFY_OBSERVER>(addr, &mut buf)?;
+        Ok(u32::from_le_bytes(buf))
     }
 
     #[inline(always)]
     pub fn read_32(&mut self, addr: usize) -> Result<u32, BusError> {
-        self.read_32_impl::<true>(addr)
+        self.read_32_impl::<true, true>(addr)
+    }
+
+    /// Guest load from a simulator step: watchpoints are checked only when `TRACE` has
+    /// `TraceFlags::WATCH`.
+    #[inline(always)]
+    pub fn read_32_traced<const TRACE: u64>(&mut self, addr: usize) -> Result<u32, BusError> {
+        if TraceFlags::watch(TRACE) {
+            self.read_32_impl::<true, true>(addr)
+        } else {
+            self.read_32_impl::<true, false>(addr)
+        }
     }
 
     #[inline(always)]
-    pub(crate) fn read_64_impl<const NOTIFY_OBSERVER: bool>(
+    pub(crate) fn read_64_impl<const NOTIFY_OBSERVER: bool, const WATCH: bool>(
         &mut self,
         addr: usize,
     ) -> Result<u64, BusError> {
         if let Some(v) = self.memory.read_64(addr) {
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mem_read_64(addr, v);
+            }
             return Ok(v);
         }
 
-        if let Some(d) = self.find_device_mut(addr..addr + 8) {
+        if let Some(d) = self.find_device_mut(addr..addr + 8)? {
             let val = d.1.read_64(addr - d.0)?;
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mmio_read_64(addr, val);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mmio_read_64(addr, val);
+            }
             return Ok(val);
         }
 
-        Err(BusError::unmapped(addr))
+        let mut buf = [0; 8];
+        self.read_miss::<NOTIFY_OBSERVER>(addr, &mut buf)?;
+        Ok(u64::from_le_bytes(buf))
     }
 
     #[inline(always)]
     pub fn read_64(&mut self, addr: usize) -> Result<u64, BusError> {
-        self.read_64_impl::<true>(addr)
+        self.read_64_impl::<true, true>(addr)
     }
 
+    /// Guest load from a simulator step: watchpoints are checked only when `TRACE` has
+    /// `TraceFlags::WATCH`.
     #[inline(always)]
-    pub(crate) fn read_128_impl<const NOTIFY_OBSERVER: bool>(
+    pub fn read_64_traced<const TRACE: u64>(&mut self, addr: usize) -> Result<u64, BusError> {
+        if TraceFlags::watch(TRACE) {
+            self.read_64_impl::<true, true>(addr)
+        } else {
+            self.read_64_impl::<true, false>(addr)
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn read_128_impl<const NOTIFY_OBSERVER: bool, const WATCH: bool>(
         &mut self,
         addr: usize,
     ) -> Result<u128, BusError> {
         if let Some(v) = self.memory.read_128(addr) {
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mem_read_128(addr, v);
+            }
             return Ok(v);
         }
 
-        if let Some(d) = self.find_device_mut(addr..addr + 16) {
+        if let Some(d) = self.find_device_mut(addr..addr + 16)? {
             let val = d.1.read_128(addr - d.0)?;
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mmio_read_128(addr, val);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mmio_read_128(addr, val);
+            }
             return Ok(val);
         }
 
-        Err(BusError::unmapped(addr))
+        let mut buf = [0; 16];
+        self.read_miss::<NOTIFY_OBSERVER>(addr, &mut buf)?;
+        Ok(u128::from_le_bytes(buf))
     }
 
     #[inline(always)]
     pub fn read_128(&mut self, addr: usize) -> Result<u128, BusError> {
-        self.read_128_impl::<true>(addr)
+        self.read_128_impl::<true, true>(addr)
+    }
+
+    /// Guest load from a simulator step: watchpoints are checked only when `TRACE` has
+    /// `TraceFlags::WATCH`.
+    #[inline(always)]
+    pub fn read_128_traced<const TRACE: u64>(&mut self, addr: usize) -> Result<u128, BusError> {
+        if TraceFlags::watch(TRACE) {
+            self.read_128_impl::<true, true>(addr)
+        } else {
+            self.read_128_impl::<true, false>(addr)
+        }
+    }
+
+    /// Instruction fetch: like `read_32`, but RAM fetches are not data accesses for watchpoints.
+    #[inline(always)]
+    pub fn fetch_32(&mut self, addr: usize) -> Result<u32, BusError> {
+        if let Some(v) = self.memory.fetch_32(addr) {
+            return Ok(v);
+        }
+        self.fetch_miss(addr)
+    }
+
+    /// Fetch outside executable RAM: from a device, or an access fault in a region without `x`.
+    #[cold]
+    #[inline(never)]
+    fn fetch_miss(&mut self, addr: usize) -> Result<u32, BusError> {
+        if let Some((region, attrs)) = self.memory.attrs_at(addr..addr + 4) {
+            return Err(BusError::AccessFault {
+                kind: AccessKind::Fetch,
+                addr,
+                region: region.to_string(),
+                attrs,
+            });
+        }
+        self.read_32_impl::<true, true>(addr)
     }
 
     #[inline(always)]
@@ -138,111 +258,189 @@ impl<I: RvIsa, O: BusObserver> Bus<I, O> {
     }
 
     #[inline(always)]
-    pub(crate) fn write_8_impl<const NOTIFY_OBSERVER: bool>(
+    pub(crate) fn write_8_impl<const NOTIFY_OBSERVER: bool, const WATCH: bool>(
         &mut self,
         addr: usize,
         value: u8,
     ) -> Result<(), BusError> {
+        if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+            self.watch_capture_old(addr, 1);
+        }
+
         if self.memory.write_8(addr, value).is_some() {
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mem_write_8(addr, value);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mem_write_8(addr, value);
+            }
 
             return Ok(());
         }
 
-        if let Some(d) = self.find_device_mut(addr..addr + 1) {
+        if let Some(d) = self.find_device_mut(addr..addr + 1)? {
             d.1.write_8(addr - d.0, value)?;
+            self.device_dma();
 
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mmio_write_8(addr, value);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mmio_write_8(addr, value);
+            }
 
             return Ok(());
         }
 
-        Err(BusError::unmapped(addr))
+        self.write_miss::<NOTIFY_OBSERVER>(addr, &value.to_le_bytes())
     }
 
     #[inline(always)]
     pub fn write_8(&mut self, addr: usize, value: u8) -> Result<(), BusError> {
-        self.write_8_impl::<true>(addr, value)
+        self.write_8_impl::<true, true>(addr, value)
     }
 
+    /// Guest store from a simulator step: watchpoints are checked only when `TRACE` has
+    /// `TraceFlags::WATCH`.
     #[inline(always)]
-    pub(crate) fn write_16_impl<const NOTIFY_OBSERVER: bool>(
+    pub fn write_8_traced<const TRACE: u64>(
+        &mut self,
+        addr: usize,
+        value: u8,
+    ) -> Result<(), BusError> {
+        if TraceFlags::watch(TRACE) {
+            self.write_8_impl::<true, true>(addr, value)
+        } else {
+            self.write_8_impl::<true, false>(addr, value)
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn write_16_impl<const NOTIFY_OBSERVER: bool, const WATCH: bool>(
         &mut self,
         addr: usize,
         value: u16,
     ) -> Result<(), BusError> {
+        if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+            self.watch_capture_old(addr, 2);
+        }
+
         if self.memory.write_16(addr, value).is_some() {
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mem_write_16(addr, value);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mem_write_16(addr, value);
+            }
 
             return Ok(());
         }
 
-        if let Some(d) = self.find_device_mut(addr..addr + 2) {
+        if let Some(d) = self.find_device_mut(addr..addr + 2)? {
             d.1.write_16(addr - d.0, value)?;
+            self.device_dma();
 
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mmio_write_16(addr, value);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mmio_write_16(addr, value);
+            }
 
             return Ok(());
         }
 
-        Err(BusError::unmapped(addr))
+        self.write_miss::<NOTIFY_OBSERVER>(addr, &value.to_le_bytes())
     }
 
     #[inline(always)]
     pub fn write_16(&mut self, addr: usize, value: u16) -> Result<(), BusError> {
-        self.write_16_impl::<true>(addr, value)
+        self.write_16_impl::<true, true>(addr, value)
     }
 
+    /// Guest store from a simulator step: watchpoints are checked only when `TRACE` has
+    /// `TraceFlags::WATCH`.
     #[inline(always)]
-    pub(crate) fn write_32_impl<const NOTIFY_OBSERVER: bool>(
+    pub fn write_16_traced<const TRACE: u64>(
+        &mut self,
+        addr: usize,
+        value: u16,
+    ) -> Result<(), BusError> {
+        if TraceFlags::watch(TRACE) {
+            self.write_16_impl::<true, true>(addr, value)
+        } else {
+            self.write_16_impl::<true, false>(addr, value)
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn write_32_impl<const NOTIFY_OBSERVER: bool, const WATCH: bool>(
         &mut self,
         addr: usize,
         value: u32,
     ) -> Result<(), BusError> {
+        if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+            self.watch_capture_old(addr, 4);
+        }
+
         if self.memory.write_32(addr, value).is_some() {
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mem_write_32(addr, value);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mem_write_32(addr, value);
+            }
 
             return Ok(());
         }
 
-        if let Some(d) = self.find_device_mut(addr..addr + 4) {
+        if let Some(d) = self.find_device_mut(addr..addr + 4)? {
             d.1.write_32(addr - d.0, value)?;
+            self.device_dma();
 
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mmio_write_32(addr, value);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mmio_write_32(addr, value);
+            }
 
             return Ok(());
         }
 
-        Err(BusError::unmapped(addr))
+        self.write_miss::<NOTIFY_OBSERVER>(addr, &value.to_le_bytes())
     }
 
     #[inline(always)]
     pub fn write_32(&mut self, addr: usize, value: u32) -> Result<(), BusError> {
-        self.write_32_impl::<true>(addr, value)
+        self.write_32_impl::<true, true>(addr, value)
+    }
+
+    /// Guest store from a simulator step: watchpoints are checked only when `TRACE` has
+    /// `TraceFlags::WATCH`.
+    #[inline(always)]
+    pub fn write_32_traced<const TRACE: u64>(
+        &mut self,
+        addr: usize,
+        value: u32,
+    ) -> Result<(), BusError> {
+        if TraceFlags::watch(TRACE) {
+            self.write_32_impl::<true, true>(addr, value)
+        } else {
+            self.write_32_impl::<true, false>(addr, value)
+        }
     }
 
     /// Write 32-bit to memory/MMIO without notifying the observer (e.g. for breakpoint patch).
     #[inline(always)]
     pub fn write_32_no_observer(&mut self, addr: usize, value: u32) -> Result<(), BusError> {
-        self.write_32_impl::<false>(addr, value)
+        self.write_32_impl::<false, false>(addr, value)
     }
 
     /// Write 32-bit with byte mask. Reads current value (no observer), merges in masked bytes, writes once.
     #[inline(always)]
     pub fn write_32_masked(&mut self, addr: usize, data: u32, wstrb: u32) -> Result<(), BusError> {
-        let old = self.read_32_impl::<false>(addr).unwrap_or(0);
+        let old = self.read_32_impl::<false, false>(addr).unwrap_or(0);
         let mut merged = old;
         for i in 0..4 {
             if (wstrb & (1 << i)) != 0 {
@@ -254,67 +452,119 @@ impl<I: RvIsa, O: BusObserver> Bus<I, O> {
     }
 
     #[inline(always)]
-    pub(crate) fn write_64_impl<const NOTIFY_OBSERVER: bool>(
+    pub(crate) fn write_64_impl<const NOTIFY_OBSERVER: bool, const WATCH: bool>(
         &mut self,
         addr: usize,
         value: u64,
     ) -> Result<(), BusError> {
+        if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+            self.watch_capture_old(addr, 8);
+        }
+
         if self.memory.write_64(addr, value).is_some() {
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mem_write_64(addr, value);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mem_write_64(addr, value);
+            }
 
             return Ok(());
         }
 
-        if let Some(d) = self.find_device_mut(addr..addr + 8) {
+        if let Some(d) = self.find_device_mut(addr..addr + 8)? {
             d.1.write_64(addr - d.0, value)?;
+            self.device_dma();
 
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mmio_write_64(addr, value);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mmio_write_64(addr, value);
+            }
 
             return Ok(());
         }
 
-        Err(BusError::unmapped(addr))
+        self.write_miss::<NOTIFY_OBSERVER>(addr, &value.to_le_bytes())
     }
 
     #[inline(always)]
     pub fn write_64(&mut self, addr: usize, value: u64) -> Result<(), BusError> {
-        self.write_64_impl::<true>(addr, value)
+        self.write_64_impl::<true, true>(addr, value)
     }
 
+    /// Guest store from a simulator step: watchpoints are checked only when `TRACE` has
+    /// `TraceFlags::WATCH`.
     #[inline(always)]
-    pub(crate) fn write_128_impl<const NOTIFY_OBSERVER: bool>(
+    pub fn write_64_traced<const TRACE: u64>(
+        &mut self,
+        addr: usize,
+        value: u64,
+    ) -> Result<(), BusError> {
+        if TraceFlags::watch(TRACE) {
+            self.write_64_impl::<true, true>(addr, value)
+        } else {
+            self.write_64_impl::<true, false>(addr, value)
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn write_128_impl<const NOTIFY_OBSERVER: bool, const WATCH: bool>(
         &mut self,
         addr: usize,
         value: u128,
     ) -> Result<(), BusError> {
+        if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+            self.watch_capture_old(addr, 16);
+        }
+
         if self.memory.write_128(addr, value).is_some() {
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mem_write_128(addr, value);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mem_write_128(addr, value);
+            }
 
             return Ok(());
         }
 
-        if let Some(d) = self.find_device_mut(addr..addr + 16) {
+        if let Some(d) = self.find_device_mut(addr..addr + 16)? {
             d.1.write_128(addr - d.0, value)?;
+            self.device_dma();
 
             if O::ENABLED && NOTIFY_OBSERVER {
                 self.observer.on_mmio_write_128(addr, value);
             }
+            if WATCH && NOTIFY_OBSERVER && self.watch.is_armed() {
+                self.watch.on_mmio_write_128(addr, value);
+            }
 
             return Ok(());
         }
 
-        Err(BusError::unmapped(addr))
+        self.write_miss::<NOTIFY_OBSERVER>(addr, &value.to_le_bytes())
     }
 
     #[inline(always)]
     pub fn write_128(&mut self, addr: usize, value: u128) -> Result<(), BusError> {
-        self.write_128_impl::<true>(addr, value)
+        self.write_128_impl::<true, true>(addr, value)
+    }
+
+    /// Guest store from a simulator step: watchpoints are checked only when `TRACE` has
+    /// `TraceFlags::WATCH`.
+    #[inline(always)]
+    pub fn write_128_traced<const TRACE: u64>(
+        &mut self,
+        addr: usize,
+        value: u128,
+    ) -> Result<(), BusError> {
+        if TraceFlags::watch(TRACE) {
+            self.write_128_impl::<true, true>(addr, value)
+        } else {
+            self.write_128_impl::<true, false>(addr, value)
+        }
     }
 
     #[inline(always)]
@@ -325,4 +575,68 @@ impl<I: RvIsa, O: BusObserver> Bus<I, O> {
 
         Err(BusError::unmapped(addr))
     }
+
+    /// Neither RAM nor a device took a read: in a region without `r` it is an access fault,
+    /// except for the debugger, which reads it anyway.
+    #[cold]
+    #[inline(never)]
+    fn read_miss<const NOTIFY_OBSERVER: bool>(
+        &mut self,
+        addr: usize,
+        buf: &mut [u8],
+    ) -> Result<(), BusError> {
+        let Some((region, attrs)) = self.memory.attrs_at(addr..addr + buf.len()) else {
+            return Err(BusError::unmapped(addr));
+        };
+        if NOTIFY_OBSERVER {
+            return Err(BusError::AccessFault {
+                kind: AccessKind::Read,
+                addr,
+                region: region.to_string(),
+                attrs,
+            });
+        }
+        self.memory
+            .read_bytes(addr, buf)
+            .ok_or_else(|| BusError::unmapped(addr))
+    }
+
+    /// Write counterpart of [`read_miss`](Self::read_miss): the debugger may patch ROM.
+    #[cold]
+    #[inline(never)]
+    fn write_miss<const NOTIFY_OBSERVER: bool>(
+        &mut self,
+        addr: usize,
+        buf: &[u8],
+    ) -> Result<(), BusError> {
+        let Some((region, attrs)) = self.memory.attrs_at(addr..addr + buf.len()) else {
+            return Err(BusError::unmapped(addr));
+        };
+        if NOTIFY_OBSERVER {
+            return Err(BusError::AccessFault {
+                kind: AccessKind::Write,
+                addr,
+                region: region.to_string(),
+                attrs,
+            });
+        }
+        self.memory
+            .write_bytes(addr, buf)
+            .ok_or_else(|| BusError::unmapped(addr))
+    }
+
+    /// Slow path of a write while watchpoints are armed: remember the bytes about to be overwritten.
+    #[cold]
+    #[inline(never)]
+    fn watch_capture_old(&mut self, addr: usize, size: usize) {
+        let old = if self.watch.find(addr, size, true).is_some() {
+            let mut buf = [0u8; 16];
+            self.memory
+                .read_bytes(addr, &mut buf[..size])
+                .map(|()| u128::from_le_bytes(buf))
+        } else {
+            None
+        };
+        self.watch.set_old(old);
+    }
 }
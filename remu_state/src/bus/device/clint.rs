@@ -1,19 +1,27 @@
 //! CLINT (Core Local Interruptor) device — standard RISC-V layout, timing only.
 //!
 //! No interrupt delivery; registers are read/write as per spec for layout compatibility.
-//! mtime is derived from host time at 10 MHz.
+//! mtime is derived from host time at 10 MHz, or from the retired instruction count (see
+//! [`MtimeSource`]) so that runs are reproducible.
 //!
 //! Layout (single hart):
 //! - 0x0000: msip (4 bytes)
 //! - 0x4000: mtimecmp (8 bytes)
-//! - 0xBFF8: mtime (8 bytes, read-only; value = elapsed host time at 10 MHz)
+//! - 0xBFF8: mtime (8 bytes, read-only)
 
-use std::time::Instant;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-use crate::bus::{device::DeviceAccess, BusError};
+use crate::bus::{
+    BusError,
+    device::{DeviceAccess, GuestMemory},
+};
 
 /// CLINT size per RISC-V platform spec (e.g. SiFive).
-const CLINT_SIZE: usize = 0xC000;
+pub(crate) const CLINT_SIZE: usize = 0xC000;
+
+/// mtime ticks per second (the device tree's timebase-frequency).
+pub(crate) const MTIME_FREQ: u32 = 10_000_000;
 
 /// mtime register offset (64-bit); high 32 bits at +4.
 const MTIME_OFF: usize = 0xBFF8;
@@ -25,34 +33,103 @@ const MSIP_OFF: usize = 0x0000;
 
 fn mtime_ticks_from_elapsed_nanos(nanos: u128) -> u64 {
     // 10 MHz => 10^7 ticks per second; 1 ns => 10^7/10^9 = 1/100 tick => ticks = nanos / 100
-    (nanos / 100) as u64
+    (nanos / (1_000_000_000 / MTIME_FREQ as u128)) as u64
+}
+
+/// What drives `mtime`. Parsed from `--mtime`: `host`, or `instret[:N]` for one tick every `N`
+/// retired instructions (default 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MtimeSource {
+    /// Elapsed host time at 10 MHz.
+    #[default]
+    Host,
+    /// Retired instructions: the same program and inputs always read the same `mtime`.
+    Instret { per_tick: u64 },
+}
+
+impl FromStr for MtimeSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (kind, per_tick) = match s.split_once(':') {
+            Some((kind, n)) => (kind, Some(n)),
+            None => (s, None),
+        };
+        match (kind, per_tick) {
+            ("host", None) => Ok(Self::Host),
+            ("instret", None) => Ok(Self::Instret { per_tick: 1 }),
+            ("instret", Some(n)) => match n.trim().parse::<u64>() {
+                Ok(per_tick) if per_tick > 0 => Ok(Self::Instret { per_tick }),
+                _ => Err(format!(
+                    "invalid mtime source {s:?}: instructions per tick must be a positive integer"
+                )),
+            },
+            _ => Err(format!(
+                "unknown mtime source {s:?}; expected host or instret[:N]"
+            )),
+        }
+    }
+}
+
+enum Clock {
+    Host {
+        base_instant: Instant,
+    },
+    Instret {
+        /// Instruction count at which `mtime` was 0 (wrapping).
+        base: u64,
+        per_tick: u64,
+    },
 }
 
 pub struct Clint {
-    base_instant: Instant,
+    clock: Clock,
+    /// Instructions retired as of the last [`tick`](DeviceAccess::tick).
+    now: u64,
     msip: u32,
     mtimecmp: u64,
 }
 
 impl Clint {
-    pub fn new() -> Self {
+    /// A CLINT created before the first instruction retires: the instret clock starts at 0.
+    pub fn new(source: MtimeSource) -> Self {
+        let clock = match source {
+            MtimeSource::Host => Clock::Host {
+                base_instant: Instant::now(),
+            },
+            MtimeSource::Instret { per_tick } => Clock::Instret { base: 0, per_tick },
+        };
         Self {
-            base_instant: Instant::now(),
+            clock,
+            now: 0,
             msip: 0,
             mtimecmp: 0,
         }
     }
 
+    /// `mtime` when `now` instructions have retired.
+    fn mtime_at(&self, now: u64) -> u64 {
+        match &self.clock {
+            Clock::Host { base_instant } => {
+                let elapsed = base_instant.elapsed();
+                let nanos = elapsed.as_nanos();
+                mtime_ticks_from_elapsed_nanos(nanos)
+            }
+            Clock::Instret { per_tick, .. } => self.elapsed_insts(now) / per_tick,
+        }
+    }
+
     fn mtime_now(&self) -> u64 {
-        let elapsed = self.base_instant.elapsed();
-        let nanos = elapsed.as_nanos();
-        mtime_ticks_from_elapsed_nanos(nanos)
+        self.mtime_at(self.now)
     }
-}
 
-impl Default for Clint {
-    fn default() -> Self {
-        Self::new()
+    /// Instructions retired between `mtime` 0 and `now` (instret clock only).
+    fn elapsed_insts(&self, now: u64) -> u64 {
+        match &self.clock {
+            Clock::Host { .. } => 0,
+            Clock::Instret { base, .. } => now.wrapping_sub(*base),
+        }
     }
 }
 
@@ -65,6 +142,61 @@ impl DeviceAccess for Clint {
         CLINT_SIZE
     }
 
+    /// msip, mtimecmp, mtime (little-endian), then with the instret clock the instructions
+    /// retired since `mtime` last advanced. Restoring rebases the clock so `mtime` resumes from the
+    /// saved value.
+    fn save(&self, now: u64) -> Vec<u8> {
+        let mut state = Vec::with_capacity(28);
+        state.extend_from_slice(&self.msip.to_le_bytes());
+        state.extend_from_slice(&self.mtimecmp.to_le_bytes());
+        state.extend_from_slice(&self.mtime_at(now).to_le_bytes());
+        if let Clock::Instret { per_tick, .. } = &self.clock {
+            state.extend_from_slice(&(self.elapsed_insts(now) % per_tick).to_le_bytes());
+        }
+        state
+    }
+
+    fn restore(&mut self, state: &[u8], now: u64) {
+        let Some((msip, rest)) = state.split_first_chunk::<4>() else {
+            return;
+        };
+        let Some((mtimecmp, rest)) = rest.split_first_chunk::<8>() else {
+            return;
+        };
+        let Some((mtime, rest)) = rest.split_first_chunk::<8>() else {
+            return;
+        };
+        self.msip = u32::from_le_bytes(*msip);
+        self.mtimecmp = u64::from_le_bytes(*mtimecmp);
+        let mtime = u64::from_le_bytes(*mtime);
+        match &mut self.clock {
+            Clock::Host { base_instant } => {
+                let elapsed = Duration::from_nanos(mtime.saturating_mul(100));
+                let now = Instant::now();
+                *base_instant = now.checked_sub(elapsed).unwrap_or(now);
+            }
+            Clock::Instret { base, per_tick } => {
+                let phase = rest
+                    .first_chunk::<8>()
+                    .map_or(0, |phase| u64::from_le_bytes(*phase));
+                let elapsed = mtime.wrapping_mul(*per_tick).wrapping_add(phase);
+                *base = now.wrapping_sub(elapsed);
+            }
+        }
+        self.now = now;
+    }
+
+    /// `mtime` keeps counting.
+    fn reset(&mut self) {
+        self.msip = 0;
+        self.mtimecmp = 0;
+    }
+
+    fn tick(&mut self, now: u64, _mem: &mut GuestMemory) -> Result<(), BusError> {
+        self.now = now;
+        Ok(())
+    }
+
     fn read_32(&mut self, offset: usize) -> Result<u32, BusError> {
         Ok(match offset {
             MSIP_OFF => self.msip,
@@ -99,3 +231,68 @@ impl DeviceAccess for Clint {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Memory;
+
+    fn tick(clint: &mut Clint, now: u64) {
+        let mut memory = Memory::new(Box::new([]));
+        clint.tick(now, &mut GuestMemory::new(&mut memory)).unwrap();
+    }
+
+    #[test]
+    fn mtime_source_parses() {
+        assert_eq!("host".parse(), Ok(MtimeSource::Host));
+        assert_eq!(
+            " instret ".parse(),
+            Ok(MtimeSource::Instret { per_tick: 1 })
+        );
+        assert_eq!(
+            "instret:4".parse(),
+            Ok(MtimeSource::Instret { per_tick: 4 })
+        );
+        assert!("instret:0".parse::<MtimeSource>().is_err());
+        assert!("instret:x".parse::<MtimeSource>().is_err());
+        assert!("host:2".parse::<MtimeSource>().is_err());
+        assert!("foo".parse::<MtimeSource>().is_err());
+    }
+
+    #[test]
+    fn instret_clock_is_deterministic() {
+        let source = MtimeSource::Instret { per_tick: 3 };
+        let (mut a, mut b) = (Clint::new(source), Clint::new(source));
+        for now in [0, 2, 3, 100, 1_000_001] {
+            tick(&mut a, now);
+            tick(&mut b, now);
+            assert_eq!(a.read_64(MTIME_OFF).unwrap(), now / 3);
+            assert_eq!(b.read_64(MTIME_OFF).unwrap(), now / 3);
+        }
+    }
+
+    #[test]
+    fn restore_keeps_mtime_and_phase() {
+        let source = MtimeSource::Instret { per_tick: 4 };
+        let mut clint = Clint::new(source);
+        tick(&mut clint, 10);
+        clint.write_64(MTIMECMP_OFF, 0x1234).unwrap();
+        clint.write_32(MSIP_OFF, 1).unwrap();
+        let state = clint.save(10);
+
+        // Restored at a different instruction count, e.g. after a rewind.
+        let mut restored = Clint::new(source);
+        restored.restore(&state, 1000);
+        assert_eq!(restored.read_64(MTIMECMP_OFF).unwrap(), 0x1234);
+        assert_eq!(restored.read_32(MSIP_OFF).unwrap(), 1);
+        for step in 0..9 {
+            tick(&mut clint, 10 + step);
+            tick(&mut restored, 1000 + step);
+            assert_eq!(
+                restored.read_64(MTIME_OFF).unwrap(),
+                clint.read_64(MTIME_OFF).unwrap(),
+                "{step} instructions after restoring"
+            );
+        }
+    }
+}
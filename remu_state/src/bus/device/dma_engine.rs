@@ -0,0 +1,297 @@
+//! DMA engine: channels that copy guest memory over simulated time.
+//!
+//! Each of `channels=N` channels (default 4, at most 8) copies `rate=BYTES` bytes per retired
+//! instruction (default 4), front to back. Copies go through guest RAM like the other DMA
+//! devices, so the difftest reference receives the written bytes; an address outside RAM stops
+//! the channel with an error. Progress is made when the bus polls devices (after MMIO accesses,
+//! otherwise every 1024 instructions), so a guest polling the status sees completion on time.
+//!
+//! The interrupt line (`irq=`) is high while a channel with interrupts enabled is done or
+//! failed and its status is not cleared.
+//!
+//! Registers (32-bit), per channel at `0x20 * N`:
+//! - 0x00: source address
+//! - 0x04: destination address
+//! - 0x08: length in bytes
+//! - 0x0c: control; writing bit 0 starts a copy (reads 1 while busy), bit 1 enables the
+//!   interrupt
+//! - 0x10: status; bit 0 done, bit 1 error; write 1s to clear
+//! - 0x14: bytes left to copy (read)
+
+use crate::bus::{
+    BusError,
+    device::{DeviceAccess, GuestMemory},
+};
+
+const SRC_OFF: usize = 0x00;
+const DST_OFF: usize = 0x04;
+const LEN_OFF: usize = 0x08;
+const CTRL_OFF: usize = 0x0c;
+const STATUS_OFF: usize = 0x10;
+const REMAINING_OFF: usize = 0x14;
+
+const CHANNEL_STRIDE: usize = 0x20;
+
+const CTRL_START: u32 = 1 << 0;
+const CTRL_IRQ_ENABLE: u32 = 1 << 1;
+const STATUS_DONE: u32 = 1 << 0;
+const STATUS_ERROR: u32 = 1 << 1;
+
+pub(crate) const MAX_DMA_CHANNELS: usize = 8;
+
+/// A `dma` device's `--dev` parameters: `channels=N`, `rate=BYTES`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmaConfig {
+    pub channels: usize,
+    pub rate: u32,
+}
+
+impl Default for DmaConfig {
+    fn default() -> Self {
+        Self {
+            channels: 4,
+            rate: 4,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct Channel {
+    src: u32,
+    dst: u32,
+    len: u32,
+    irq_enable: bool,
+    status: u32,
+    /// Bytes copied of the copy in flight; `None` when idle.
+    copied: Option<u32>,
+    /// `instret` when the copy in flight last made progress.
+    last: u64,
+}
+
+impl Channel {
+    const SAVED_LEN: usize = 29;
+
+    fn remaining(&self) -> u32 {
+        self.copied.map_or(0, |copied| self.len - copied)
+    }
+
+    /// Copy what is due by `now`, at `rate` bytes per instruction.
+    fn advance(&mut self, mem: &mut GuestMemory, now: u64, rate: u64) {
+        let Some(copied) = self.copied else {
+            return;
+        };
+        let budget = now.wrapping_sub(self.last).saturating_mul(rate);
+        if budget == 0 {
+            return;
+        }
+        self.last = now;
+        let n = budget.min(self.remaining() as u64) as u32;
+        let mut buf = vec![0; n as usize];
+        let src = self.src.wrapping_add(copied) as usize;
+        let dst = self.dst.wrapping_add(copied) as usize;
+        if let Err(e) = mem.read(src, &mut buf).and_then(|()| mem.write(dst, &buf)) {
+            tracing::warn!("dma: copy 0x{:x} -> 0x{:x} failed: {e}", self.src, self.dst);
+            self.copied = None;
+            self.status |= STATUS_ERROR;
+            return;
+        }
+        self.copied = Some(copied + n);
+        if self.remaining() == 0 {
+            self.copied = None;
+            self.status |= STATUS_DONE;
+        }
+    }
+
+    fn save(&self, state: &mut Vec<u8>, now: u64) {
+        for reg in [self.src, self.dst, self.len, self.status] {
+            state.extend_from_slice(&reg.to_le_bytes());
+        }
+        state.push(self.irq_enable as u8);
+        state.extend_from_slice(&self.copied.unwrap_or(u32::MAX).to_le_bytes());
+        state.extend_from_slice(&now.wrapping_sub(self.last).to_le_bytes());
+    }
+
+    fn restore(state: &[u8], now: u64) -> Self {
+        let word = |i: usize| u32::from_le_bytes(state[i..i + 4].try_into().unwrap());
+        let copied = word(17);
+        let since = u64::from_le_bytes(state[21..29].try_into().unwrap());
+        Self {
+            src: word(0),
+            dst: word(4),
+            len: word(8),
+            status: word(12),
+            irq_enable: state[16] != 0,
+            copied: (copied != u32::MAX).then_some(copied),
+            last: now.wrapping_sub(since),
+        }
+    }
+}
+
+pub struct DmaEngine {
+    channels: Box<[Channel]>,
+    rate: u64,
+    /// Instructions retired as of the last [`tick`](DeviceAccess::tick).
+    now: u64,
+}
+
+impl DmaEngine {
+    pub(crate) fn new(config: DmaConfig) -> Self {
+        Self {
+            channels: vec![Channel::default(); config.channels].into_boxed_slice(),
+            rate: config.rate as u64,
+            now: 0,
+        }
+    }
+
+    fn channel(&mut self, offset: usize) -> Option<(&mut Channel, usize)> {
+        let channel = self.channels.get_mut(offset / CHANNEL_STRIDE)?;
+        Some((channel, offset % CHANNEL_STRIDE))
+    }
+}
+
+impl DeviceAccess for DmaEngine {
+    fn name(&self) -> &str {
+        "dma"
+    }
+
+    fn size(&self) -> usize {
+        self.channels.len() * CHANNEL_STRIDE
+    }
+
+    /// Per channel: source, destination, length and status (little-endian `u32`s), interrupt
+    /// enable (one byte), bytes copied (`u32`, `u32::MAX` when idle), then instructions since
+    /// it last made progress (`u64`).
+    fn save(&self, now: u64) -> Vec<u8> {
+        let mut state = Vec::with_capacity(self.channels.len() * Channel::SAVED_LEN);
+        for channel in self.channels.iter() {
+            channel.save(&mut state, now);
+        }
+        state
+    }
+
+    fn restore(&mut self, state: &[u8], now: u64) {
+        if state.len() != self.channels.len() * Channel::SAVED_LEN {
+            return;
+        }
+        self.now = now;
+        for (channel, state) in self
+            .channels
+            .iter_mut()
+            .zip(state.chunks_exact(Channel::SAVED_LEN))
+        {
+            *channel = Channel::restore(state, now);
+        }
+    }
+
+    /// Copies in flight are dropped.
+    fn reset(&mut self) {
+        self.channels.fill(Channel::default());
+    }
+
+    fn tick(&mut self, now: u64, mem: &mut GuestMemory) -> Result<(), BusError> {
+        self.now = now;
+        for channel in self.channels.iter_mut() {
+            channel.advance(mem, now, self.rate);
+        }
+        Ok(())
+    }
+
+    fn irq_level(&mut self) -> bool {
+        self.channels
+            .iter()
+            .any(|c| c.irq_enable && c.status & (STATUS_DONE | STATUS_ERROR) != 0)
+    }
+
+    fn read_32(&mut self, offset: usize) -> Result<u32, BusError> {
+        let Some((channel, reg)) = self.channel(offset) else {
+            return Ok(0);
+        };
+        Ok(match reg {
+            SRC_OFF => channel.src,
+            DST_OFF => channel.dst,
+            LEN_OFF => channel.len,
+            CTRL_OFF => channel.copied.is_some() as u32 | (channel.irq_enable as u32) << 1,
+            STATUS_OFF => channel.status,
+            REMAINING_OFF => channel.remaining(),
+            _ => 0,
+        })
+    }
+
+    fn write_32(&mut self, offset: usize, value: u32) -> Result<(), BusError> {
+        let now = self.now;
+        let Some((channel, reg)) = self.channel(offset) else {
+            return Ok(());
+        };
+        let busy = channel.copied.is_some();
+        match reg {
+            SRC_OFF if !busy => channel.src = value,
+            DST_OFF if !busy => channel.dst = value,
+            LEN_OFF if !busy => channel.len = value,
+            CTRL_OFF => {
+                channel.irq_enable = value & CTRL_IRQ_ENABLE != 0;
+                if value & CTRL_START != 0 && !busy {
+                    channel.status = 0;
+                    channel.copied = Some(0);
+                    channel.last = now;
+                    if channel.len == 0 {
+                        channel.copied = None;
+                        channel.status = STATUS_DONE;
+                    }
+                }
+            }
+            STATUS_OFF => channel.status &= !value,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{MemAttrs, MemRegionSpec, Memory, MemoryEntry};
+
+    #[test]
+    fn copy_advances_with_instret() {
+        let ram = MemRegionSpec {
+            name: "ram".to_string(),
+            region: 0x8000_0000..0x8000_1000,
+            attrs: MemAttrs::RWX,
+            image: None,
+        };
+        let mut memory = Memory::new(Box::new([MemoryEntry::new(ram).unwrap()]));
+        let pattern: Vec<u8> = (0..16).collect();
+        memory.write_bytes(0x8000_0000, &pattern).unwrap();
+        let mut mem = GuestMemory::new(&mut memory);
+
+        let config = DmaConfig {
+            channels: 2,
+            rate: 4,
+        };
+        let mut dma = DmaEngine::new(config);
+        dma.tick(10, &mut mem).unwrap();
+        let ch1 = CHANNEL_STRIDE;
+        dma.write_32(ch1 + SRC_OFF, 0x8000_0000).unwrap();
+        dma.write_32(ch1 + DST_OFF, 0x8000_0800).unwrap();
+        dma.write_32(ch1 + LEN_OFF, 16).unwrap();
+        dma.write_32(ch1 + CTRL_OFF, CTRL_START | CTRL_IRQ_ENABLE)
+            .unwrap();
+
+        dma.tick(12, &mut mem).unwrap();
+        assert_eq!(dma.read_32(ch1 + REMAINING_OFF).unwrap(), 8);
+        assert!(!dma.irq_level());
+
+        dma.tick(20, &mut mem).unwrap();
+        assert_eq!(dma.read_32(ch1 + STATUS_OFF).unwrap(), STATUS_DONE);
+        assert_eq!(dma.read_32(ch1 + CTRL_OFF).unwrap(), CTRL_IRQ_ENABLE);
+        assert!(dma.irq_level());
+        dma.write_32(ch1 + STATUS_OFF, STATUS_DONE).unwrap();
+        assert!(!dma.irq_level());
+
+        let writes = mem.into_writes();
+        assert_eq!(writes.len(), 2);
+        let mut copied = [0; 16];
+        memory.read_bytes(0x8000_0800, &mut copied).unwrap();
+        assert_eq!(copied[..], pattern[..]);
+    }
+}
@@ -0,0 +1,269 @@
+//! Goldfish RTC (`google,goldfish-rtc`, as on QEMU's virt machine): nanoseconds since the Unix
+//! epoch and one alarm.
+//!
+//! Time follows `clock=` (default: `--mtime`). `host` is the wall clock; `instret[:N]` advances
+//! 100 ns every `N` retired instructions from `epoch=SECONDS` (default 0), so runs are
+//! reproducible. With `host`, `epoch=` sets the time at startup instead of the host's.
+//!
+//! Registers (32-bit):
+//! - 0x00: time, low half; reading it latches the high half for 0x04
+//! - 0x04: time, high half
+//! - 0x08: alarm, low half; writing it arms the alarm at `(alarm high << 32) | value`
+//! - 0x0c: alarm, high half
+//! - 0x10: interrupt enable
+//! - 0x14: clear alarm (write)
+//! - 0x18: alarm status, 1 while armed
+//! - 0x1c: clear interrupt (write)
+//!
+//! The interrupt line (`irq=`) is high while an expired alarm is not cleared and interrupts are
+//! enabled.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bus::{
+    BusError,
+    device::{DeviceAccess, GuestMemory, MtimeSource},
+};
+
+const TIME_LOW_OFF: usize = 0x00;
+const TIME_HIGH_OFF: usize = 0x04;
+const ALARM_LOW_OFF: usize = 0x08;
+const ALARM_HIGH_OFF: usize = 0x0c;
+const IRQ_ENABLED_OFF: usize = 0x10;
+const CLEAR_ALARM_OFF: usize = 0x14;
+const ALARM_STATUS_OFF: usize = 0x18;
+const CLEAR_INTERRUPT_OFF: usize = 0x1c;
+
+/// A `goldfish_rtc` device's `--dev` parameters: `clock=host|instret[:N]`, `epoch=SECONDS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RtcConfig {
+    pub clock: Option<MtimeSource>,
+    pub epoch: Option<u64>,
+}
+
+pub struct GoldfishRtc {
+    clock: MtimeSource,
+    /// Instructions retired as of the last [`tick`](DeviceAccess::tick).
+    insts: u64,
+    /// Added (wrapping) to the raw clock to give the guest's time.
+    offset: u64,
+    time_high: u32,
+    alarm: u64,
+    alarm_running: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+}
+
+impl GoldfishRtc {
+    pub(crate) fn new(config: RtcConfig, default_clock: MtimeSource) -> Self {
+        let mut rtc = Self {
+            clock: config.clock.unwrap_or(default_clock),
+            insts: 0,
+            offset: 0,
+            time_high: 0,
+            alarm: 0,
+            alarm_running: false,
+            irq_enabled: false,
+            irq_pending: false,
+        };
+        match (rtc.clock, config.epoch) {
+            (MtimeSource::Host, None) => {}
+            (_, epoch) => rtc.set_now(epoch.unwrap_or(0).saturating_mul(1_000_000_000)),
+        }
+        rtc
+    }
+
+    /// Nanoseconds on the underlying clock once `insts` instructions have retired.
+    fn raw_nanos(&self, insts: u64) -> u64 {
+        match self.clock {
+            MtimeSource::Host => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64),
+            MtimeSource::Instret { per_tick } => insts / per_tick * 100,
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.raw_nanos(self.insts).wrapping_add(self.offset)
+    }
+
+    fn set_now(&mut self, nanos: u64) {
+        self.offset = nanos.wrapping_sub(self.raw_nanos(self.insts));
+    }
+
+    /// Fire the alarm once its time has come.
+    fn update_alarm(&mut self) {
+        if self.alarm_running && self.now() >= self.alarm {
+            self.alarm_running = false;
+            self.irq_pending = true;
+        }
+    }
+}
+
+impl DeviceAccess for GoldfishRtc {
+    fn name(&self) -> &str {
+        "goldfish_rtc"
+    }
+
+    fn size(&self) -> usize {
+        0x20
+    }
+
+    /// Time, alarm (little-endian `u64`s), latched time high half (`u32`), then alarm running,
+    /// interrupt enabled and interrupt pending (one byte each). Restoring sets the clock back
+    /// to the saved time.
+    fn save(&self, now: u64) -> Vec<u8> {
+        let time = self.raw_nanos(now).wrapping_add(self.offset);
+        let mut state = Vec::with_capacity(23);
+        state.extend_from_slice(&time.to_le_bytes());
+        state.extend_from_slice(&self.alarm.to_le_bytes());
+        state.extend_from_slice(&self.time_high.to_le_bytes());
+        state.extend([self.alarm_running, self.irq_enabled, self.irq_pending].map(u8::from));
+        state
+    }
+
+    fn restore(&mut self, state: &[u8], now: u64) {
+        let Some((time, rest)) = state.split_first_chunk::<8>() else {
+            return;
+        };
+        let Some((alarm, rest)) = rest.split_first_chunk::<8>() else {
+            return;
+        };
+        let Some((time_high, rest)) = rest.split_first_chunk::<4>() else {
+            return;
+        };
+        let [alarm_running, irq_enabled, irq_pending] = *rest else {
+            return;
+        };
+        self.insts = now;
+        self.set_now(u64::from_le_bytes(*time));
+        self.alarm = u64::from_le_bytes(*alarm);
+        self.time_high = u32::from_le_bytes(*time_high);
+        self.alarm_running = alarm_running != 0;
+        self.irq_enabled = irq_enabled != 0;
+        self.irq_pending = irq_pending != 0;
+    }
+
+    /// The alarm is disarmed; the time keeps going.
+    fn reset(&mut self) {
+        self.alarm = 0;
+        self.alarm_running = false;
+        self.irq_enabled = false;
+        self.irq_pending = false;
+    }
+
+    fn tick(&mut self, now: u64, _mem: &mut GuestMemory) -> Result<(), BusError> {
+        self.insts = now;
+        Ok(())
+    }
+
+    fn irq_level(&mut self) -> bool {
+        self.update_alarm();
+        self.irq_pending && self.irq_enabled
+    }
+
+    fn read_32(&mut self, offset: usize) -> Result<u32, BusError> {
+        self.update_alarm();
+        Ok(match offset {
+            TIME_LOW_OFF => {
+                let now = self.now();
+                self.time_high = (now >> 32) as u32;
+                now as u32
+            }
+            TIME_HIGH_OFF => self.time_high,
+            ALARM_LOW_OFF => self.alarm as u32,
+            ALARM_HIGH_OFF => (self.alarm >> 32) as u32,
+            IRQ_ENABLED_OFF => self.irq_enabled as u32,
+            ALARM_STATUS_OFF => self.alarm_running as u32,
+            _ => 0,
+        })
+    }
+
+    fn write_32(&mut self, offset: usize, value: u32) -> Result<(), BusError> {
+        match offset {
+            TIME_LOW_OFF => self.set_now((self.now() & !0xffff_ffff) | value as u64),
+            TIME_HIGH_OFF => self.set_now((self.now() & 0xffff_ffff) | (value as u64) << 32),
+            ALARM_LOW_OFF => {
+                self.alarm = (self.alarm & !0xffff_ffff) | value as u64;
+                self.alarm_running = true;
+            }
+            ALARM_HIGH_OFF => self.alarm = (self.alarm & 0xffff_ffff) | (value as u64) << 32,
+            IRQ_ENABLED_OFF => self.irq_enabled = value & 1 != 0,
+            CLEAR_ALARM_OFF => self.alarm_running = false,
+            CLEAR_INTERRUPT_OFF => self.irq_pending = false,
+            _ => {}
+        }
+        self.update_alarm();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Memory;
+
+    fn tick(rtc: &mut GoldfishRtc, now: u64) {
+        let mut memory = Memory::new(Box::new([]));
+        rtc.tick(now, &mut GuestMemory::new(&mut memory)).unwrap();
+    }
+
+    fn time(rtc: &mut GoldfishRtc) -> u64 {
+        let low = rtc.read_32(TIME_LOW_OFF).unwrap() as u64;
+        (rtc.read_32(TIME_HIGH_OFF).unwrap() as u64) << 32 | low
+    }
+
+    /// Counting from 5 s past the epoch, 100 ns every 2 instructions.
+    fn rtc() -> GoldfishRtc {
+        let config = RtcConfig {
+            clock: Some(MtimeSource::Instret { per_tick: 2 }),
+            epoch: Some(5),
+        };
+        GoldfishRtc::new(config, MtimeSource::Host)
+    }
+
+    #[test]
+    fn instret_clock_counts_from_the_epoch() {
+        let mut rtc = rtc();
+        assert_eq!(time(&mut rtc), 5_000_000_000);
+        tick(&mut rtc, 9);
+        assert_eq!(time(&mut rtc), 5_000_000_400);
+
+        // Setting the time moves the clock; it keeps counting from there.
+        rtc.write_32(TIME_HIGH_OFF, 0).unwrap();
+        rtc.write_32(TIME_LOW_OFF, 1000).unwrap();
+        tick(&mut rtc, 11);
+        assert_eq!(time(&mut rtc), 1100);
+    }
+
+    #[test]
+    fn alarm_raises_the_irq() {
+        let mut rtc = rtc();
+        let alarm = time(&mut rtc) + 300;
+        rtc.write_32(IRQ_ENABLED_OFF, 1).unwrap();
+        rtc.write_32(ALARM_HIGH_OFF, (alarm >> 32) as u32).unwrap();
+        rtc.write_32(ALARM_LOW_OFF, alarm as u32).unwrap();
+        assert_eq!(rtc.read_32(ALARM_STATUS_OFF).unwrap(), 1);
+
+        tick(&mut rtc, 5);
+        assert!(!rtc.irq_level());
+        tick(&mut rtc, 6);
+        assert!(rtc.irq_level());
+        assert_eq!(rtc.read_32(ALARM_STATUS_OFF).unwrap(), 0);
+        rtc.write_32(CLEAR_INTERRUPT_OFF, 1).unwrap();
+        assert!(!rtc.irq_level());
+
+        // A disabled interrupt leaves the line low, and a cleared alarm never fires.
+        rtc.write_32(IRQ_ENABLED_OFF, 0).unwrap();
+        rtc.write_32(ALARM_LOW_OFF, alarm as u32 + 100).unwrap();
+        tick(&mut rtc, 8);
+        assert!(!rtc.irq_level());
+        rtc.write_32(IRQ_ENABLED_OFF, 1).unwrap();
+        assert!(rtc.irq_level());
+        rtc.write_32(CLEAR_INTERRUPT_OFF, 1).unwrap();
+        rtc.write_32(ALARM_LOW_OFF, alarm as u32 + 1000).unwrap();
+        rtc.write_32(CLEAR_ALARM_OFF, 1).unwrap();
+        tick(&mut rtc, 100);
+        assert!(!rtc.irq_level());
+    }
+}
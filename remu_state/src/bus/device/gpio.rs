@@ -0,0 +1,262 @@
+//! GPIO: 32 pins, each an input or an output.
+//!
+//! Input levels come from `script=PATH`; without one, every input reads low. A script line is
+//! `INSTS PIN high|low`: the pin takes that level once the guest has retired `INSTS`
+//! instructions, so a scripted run is deterministic. `#` starts a comment.
+//!
+//! Every change of a driven output level is an event, written as a script line to `log=PATH`
+//! (which can drive the inputs of another run) or logged without one.
+//!
+//! Registers (32-bit, bit `N` is pin `N`):
+//! - 0x00: pin levels (read): the output value for outputs, the scripted level for inputs
+//! - 0x04: direction, 1 for output
+//! - 0x08: output value
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::bus::{
+    BusError,
+    device::{DeviceAccess, GuestMemory},
+};
+
+const INPUT_OFF: usize = 0x00;
+const DIR_OFF: usize = 0x04;
+const OUTPUT_OFF: usize = 0x08;
+
+const PINS: u32 = 32;
+
+/// A `gpio` device's `--dev` parameters: `script=PATH`, `log=PATH`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GpioConfig {
+    pub script: Option<PathBuf>,
+    pub log: Option<PathBuf>,
+}
+
+/// Parse a pin script into `(insts, pin, level)` triples, ordered by time (stable for equal
+/// times).
+pub(crate) fn parse_script(text: &str) -> Result<Vec<(u64, u32, bool)>, String> {
+    let mut events = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let err = |msg: &str| format!("line {}: {msg}: {line:?}", n + 1);
+        let [at, pin, level] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(err("expected INSTS PIN high|low"));
+        };
+        let at = at
+            .parse::<u64>()
+            .map_err(|_| err("invalid instruction count"))?;
+        let pin = match pin.parse::<u32>() {
+            Ok(pin) if pin < PINS => pin,
+            _ => return Err(err("pin must be 0..31")),
+        };
+        let level = match level {
+            "high" => true,
+            "low" => false,
+            _ => return Err(err("expected high or low")),
+        };
+        events.push((at, pin, level));
+    }
+    events.sort_by_key(|&(at, _, _)| at);
+    Ok(events)
+}
+
+pub struct Gpio {
+    script: Vec<(u64, u32, bool)>,
+    /// Index of the first script event not yet applied.
+    next: usize,
+    input: u32,
+    dir: u32,
+    output: u32,
+    log: Option<BufWriter<File>>,
+    /// Pin changes are not logged (see [`DeviceAccess::mute`]).
+    muted: bool,
+    /// Instructions retired as of the last [`tick`](DeviceAccess::tick).
+    insts: u64,
+    /// `insts` value at which the script's clock was 0 (wrapping).
+    base: u64,
+}
+
+impl Gpio {
+    pub(crate) fn new(config: &GpioConfig) -> Self {
+        let script = config.script.as_ref().map_or_else(Vec::new, |path| {
+            std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| parse_script(&text))
+                .unwrap_or_else(|e| {
+                    tracing::error!("gpio script {}: {e}", path.display());
+                    Vec::new()
+                })
+        });
+        let log = config.log.as_ref().and_then(|path| {
+            File::create(path)
+                .map(BufWriter::new)
+                .map_err(|e| tracing::error!("gpio log {}: {e}", path.display()))
+                .ok()
+        });
+        Self {
+            script,
+            next: 0,
+            input: 0,
+            dir: 0,
+            output: 0,
+            log,
+            muted: false,
+            insts: 0,
+            base: 0,
+        }
+    }
+
+    fn elapsed_insts(&self, insts: u64) -> u64 {
+        insts.wrapping_sub(self.base)
+    }
+
+    /// Apply the script events that are due.
+    fn poll(&mut self) {
+        let now = self.elapsed_insts(self.insts);
+        while let Some(&(at, pin, level)) = self.script.get(self.next)
+            && at <= now
+        {
+            self.input = (self.input & !(1 << pin)) | (level as u32) << pin;
+            self.next += 1;
+        }
+    }
+
+    fn driven(&self) -> u32 {
+        self.output & self.dir
+    }
+
+    /// Report the outputs that changed since `before` was driven.
+    fn log_changes(&mut self, before: u32) {
+        let changed = before ^ self.driven();
+        if changed == 0 || self.muted {
+            return;
+        }
+        let now = self.elapsed_insts(self.insts);
+        for pin in (0..PINS).filter(|pin| changed & (1 << pin) != 0) {
+            let level = if self.driven() & (1 << pin) != 0 {
+                "high"
+            } else {
+                "low"
+            };
+            match &mut self.log {
+                Some(log) => {
+                    if let Err(e) = writeln!(log, "{now} {pin} {level}") {
+                        tracing::error!("gpio log: {e}");
+                        self.log = None;
+                    }
+                }
+                None => tracing::info!("gpio: pin {pin} {level} at instruction {now}"),
+            }
+        }
+    }
+}
+
+impl DeviceAccess for Gpio {
+    fn name(&self) -> &str {
+        "gpio"
+    }
+
+    fn size(&self) -> usize {
+        0xc
+    }
+
+    /// Instructions retired on the script's clock and the index of the next script event
+    /// (little-endian `u64`s), then input, direction and output (`u32`s). Restoring rebases
+    /// the clock, so a rewound run sees the script again from the saved point.
+    fn save(&self, now: u64) -> Vec<u8> {
+        let mut state = Vec::with_capacity(28);
+        state.extend_from_slice(&self.elapsed_insts(now).to_le_bytes());
+        state.extend_from_slice(&(self.next as u64).to_le_bytes());
+        for reg in [self.input, self.dir, self.output] {
+            state.extend_from_slice(&reg.to_le_bytes());
+        }
+        state
+    }
+
+    fn restore(&mut self, state: &[u8], now: u64) {
+        let Some((elapsed, rest)) = state.split_first_chunk::<8>() else {
+            return;
+        };
+        let Some((next, rest)) = rest.split_first_chunk::<8>() else {
+            return;
+        };
+        let [input, dir, output] = [0, 4, 8].map(|i| {
+            rest.get(i..i + 4)
+                .map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap()))
+        });
+        self.insts = now;
+        self.base = now.wrapping_sub(u64::from_le_bytes(*elapsed));
+        self.next = (u64::from_le_bytes(*next) as usize).min(self.script.len());
+        self.input = input;
+        self.dir = dir;
+        self.output = output;
+    }
+
+    /// All pins back to inputs; input levels and the script carry on.
+    fn reset(&mut self) {
+        let before = self.driven();
+        self.dir = 0;
+        self.output = 0;
+        self.log_changes(before);
+    }
+
+    fn flush(&mut self) {
+        if let Some(log) = &mut self.log {
+            let _ = log.flush();
+        }
+    }
+
+    fn mute(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    fn tick(&mut self, now: u64, _mem: &mut GuestMemory) -> Result<(), BusError> {
+        self.insts = now;
+        Ok(())
+    }
+
+    fn read_32(&mut self, offset: usize) -> Result<u32, BusError> {
+        self.poll();
+        Ok(match offset {
+            INPUT_OFF => self.driven() | (self.input & !self.dir),
+            DIR_OFF => self.dir,
+            OUTPUT_OFF => self.output,
+            _ => 0,
+        })
+    }
+
+    fn write_32(&mut self, offset: usize, value: u32) -> Result<(), BusError> {
+        let before = self.driven();
+        match offset {
+            DIR_OFF => self.dir = value,
+            OUTPUT_OFF => self.output = value,
+            _ => return Ok(()),
+        }
+        self.log_changes(before);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_events_are_ordered_by_time() {
+        let events = parse_script(
+            "# button on pin 3\n\
+             500 3 low\n\
+             100 3 high   # pressed\n\
+             100 31 high\n",
+        )
+        .unwrap();
+        assert_eq!(events, [(100, 3, true), (100, 31, true), (500, 3, false)]);
+        assert!(parse_script("10 32 high").is_err());
+        assert!(parse_script("10 1 up").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+//! Guest RAM as seen by a device doing DMA (see `DeviceAccess::dma`).
+
+use crate::bus::{BusError, Memory};
+
+/// RAM access for one [`dma`](super::DeviceAccess::dma) call. Writes are recorded so the bus
+/// can replay them to the difftest reference, which has no devices.
+pub(crate) struct GuestMemory<'a> {
+    memory: &'a mut Memory,
+    writes: Vec<(usize, Box<[u8]>)>,
+}
+
+impl<'a> GuestMemory<'a> {
+    pub(crate) fn new(memory: &'a mut Memory) -> Self {
+        Self {
+            memory,
+            writes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn read(&mut self, addr: usize, buf: &mut [u8]) -> Result<(), BusError> {
+        self.memory
+            .read_bytes(addr, buf)
+            .ok_or_else(|| BusError::unmapped(addr))
+    }
+
+    pub(crate) fn write(&mut self, addr: usize, data: &[u8]) -> Result<(), BusError> {
+        self.memory
+            .write_bytes(addr, data)
+            .ok_or_else(|| BusError::unmapped(addr))?;
+        self.writes.push((addr, Box::from(data)));
+        Ok(())
+    }
+
+    pub(crate) fn read_u16(&mut self, addr: usize) -> Result<u16, BusError> {
+        let mut b = [0; 2];
+        self.read(addr, &mut b)?;
+        Ok(u16::from_le_bytes(b))
+    }
+
+    pub(crate) fn read_u32(&mut self, addr: usize) -> Result<u32, BusError> {
+        let mut b = [0; 4];
+        self.read(addr, &mut b)?;
+        Ok(u32::from_le_bytes(b))
+    }
+
+    pub(crate) fn read_u64(&mut self, addr: usize) -> Result<u64, BusError> {
+        let mut b = [0; 8];
+        self.read(addr, &mut b)?;
+        Ok(u64::from_le_bytes(b))
+    }
+
+    pub(crate) fn write_u16(&mut self, addr: usize, value: u16) -> Result<(), BusError> {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub(crate) fn write_u32(&mut self, addr: usize, value: u32) -> Result<(), BusError> {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    /// The writes made, in order.
+    pub(crate) fn into_writes(self) -> Vec<(usize, Box<[u8]>)> {
+        self.writes
+    }
+}
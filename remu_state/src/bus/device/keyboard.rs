@@ -0,0 +1,310 @@
+//! Keyboard (NEMU/AM layout): a queue of scan codes, read one event at a time.
+//!
+//! Reading the data register pops the next event as `(keydown << 15) | code`, with codes
+//! numbered as AM's `AM_KEY_*`, or returns 0 (`AM_KEY_NONE`) when the queue is empty. Events
+//! come from `script=PATH`, or from the terminal without one.
+//!
+//! A script line is `INSTS down|up|press KEY`: the event is queued once the guest has retired
+//! `INSTS` instructions, so a scripted run is deterministic. `#` starts a comment. Keys are the
+//! `AM_KEY_` names without the prefix (`A`, `1`, `SPACE`, `RETURN`, `UP`, ...), in any case.
+//!
+//! Terminal input is polled when the guest reads the register or the interrupt line is
+//! sampled; each character is a press (down, then up) and arrow-key escape sequences map to
+//! the arrow keys. It competes with a UART reading stdin, so leave `--uart-input` at `none`.
+//!
+//! Registers:
+//! - 0x0: next event (reading pops it)
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use crate::bus::{
+    BusError,
+    device::{DeviceAccess, GuestMemory, StdinMode, read_ready},
+};
+
+const DATA_OFF: usize = 0x0;
+
+const KEYDOWN: u32 = 1 << 15;
+
+/// `AM_KEY_*` names in code order; `AM_KEY_NONE` (0) is not a key.
+const KEY_NAMES: &str = "\
+    ESCAPE F1 F2 F3 F4 F5 F6 F7 F8 F9 F10 F11 F12 \
+    GRAVE 1 2 3 4 5 6 7 8 9 0 MINUS EQUALS BACKSPACE \
+    TAB Q W E R T Y U I O P LEFTBRACKET RIGHTBRACKET BACKSLASH \
+    CAPSLOCK A S D F G H J K L SEMICOLON APOSTROPHE RETURN \
+    LSHIFT Z X C V B N M COMMA PERIOD SLASH RSHIFT \
+    LCTRL APPLICATION LALT SPACE RALT RCTRL \
+    UP DOWN LEFT RIGHT INSERT DELETE HOME END PAGEUP PAGEDOWN";
+
+/// Where a `keyboard` device's events come from: `script=PATH`, or the terminal.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum KeyboardInput {
+    #[default]
+    Terminal,
+    Script(PathBuf),
+}
+
+fn key_code(name: &str) -> Option<u32> {
+    KEY_NAMES
+        .split_whitespace()
+        .position(|k| k.eq_ignore_ascii_case(name))
+        .map(|i| i as u32 + 1)
+}
+
+/// Parse a key script into `(insts, event)` pairs, ordered by time (stable for equal times).
+pub(crate) fn parse_script(text: &str) -> Result<Vec<(u64, u32)>, String> {
+    let mut events = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let err = |msg: &str| format!("line {}: {msg}: {line:?}", n + 1);
+        let [at, action, key] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(err("expected INSTS down|up|press KEY"));
+        };
+        let at = at
+            .parse::<u64>()
+            .map_err(|_| err("invalid instruction count"))?;
+        let code = key_code(key).ok_or_else(|| err("unknown key"))?;
+        match action {
+            "down" => events.push((at, KEYDOWN | code)),
+            "up" => events.push((at, code)),
+            "press" => events.extend([(at, KEYDOWN | code), (at, code)]),
+            _ => return Err(err("expected down, up or press")),
+        }
+    }
+    events.sort_by_key(|&(at, _)| at);
+    Ok(events)
+}
+
+/// The key a terminal character stands for.
+fn char_key(c: u8) -> Option<u32> {
+    let name = match c {
+        b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => {
+            return key_code(std::str::from_utf8(&[c]).ok()?);
+        }
+        b'`' => "GRAVE",
+        b'-' => "MINUS",
+        b'=' => "EQUALS",
+        b'[' => "LEFTBRACKET",
+        b']' => "RIGHTBRACKET",
+        b'\\' => "BACKSLASH",
+        b';' => "SEMICOLON",
+        b'\'' => "APOSTROPHE",
+        b',' => "COMMA",
+        b'.' => "PERIOD",
+        b'/' => "SLASH",
+        b' ' => "SPACE",
+        b'\t' => "TAB",
+        b'\n' | b'\r' => "RETURN",
+        0x7f | 0x08 => "BACKSPACE",
+        0x1b => "ESCAPE",
+        _ => return None,
+    };
+    key_code(name)
+}
+
+enum Source {
+    /// The terminal mode is switched at the first poll.
+    Terminal(Option<StdinMode>),
+    Script {
+        events: Vec<(u64, u32)>,
+        /// Index of the first event not yet queued.
+        next: usize,
+    },
+}
+
+pub struct Keyboard {
+    source: Source,
+    queue: VecDeque<u32>,
+    /// Instructions retired as of the last [`tick`](DeviceAccess::tick).
+    insts: u64,
+    /// `insts` value at which the script's clock was 0 (wrapping).
+    base: u64,
+}
+
+impl Keyboard {
+    pub(crate) fn new(input: &KeyboardInput) -> Self {
+        let source = match input {
+            KeyboardInput::Terminal => Source::Terminal(None),
+            KeyboardInput::Script(path) => {
+                let events = std::fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| parse_script(&text))
+                    .unwrap_or_else(|e| {
+                        tracing::error!("keyboard script {}: {e}", path.display());
+                        Vec::new()
+                    });
+                Source::Script { events, next: 0 }
+            }
+        };
+        Self {
+            source,
+            queue: VecDeque::new(),
+            insts: 0,
+            base: 0,
+        }
+    }
+
+    fn elapsed_insts(&self, insts: u64) -> u64 {
+        insts.wrapping_sub(self.base)
+    }
+
+    /// Queue the script events that are due, or what the terminal has typed.
+    fn poll(&mut self) {
+        let now = self.elapsed_insts(self.insts);
+        match &mut self.source {
+            Source::Script { events, next } => {
+                while let Some(&(at, event)) = events.get(*next)
+                    && at <= now
+                {
+                    self.queue.push_back(event);
+                    *next += 1;
+                }
+            }
+            Source::Terminal(mode) => {
+                mode.get_or_insert_with(StdinMode::enter);
+                let mut buf = [0u8; 64];
+                let n = read_ready(libc::STDIN_FILENO, &mut buf);
+                let mut input = &buf[..n];
+                while let Some((&c, rest)) = input.split_first() {
+                    input = rest;
+                    let code = match (c, input) {
+                        (0x1b, [b'[', arrow @ b'A'..=b'D', rest @ ..]) => {
+                            input = rest;
+                            let name = match arrow {
+                                b'A' => "UP",
+                                b'B' => "DOWN",
+                                b'C' => "RIGHT",
+                                _ => "LEFT",
+                            };
+                            key_code(name)
+                        }
+                        _ => char_key(c),
+                    };
+                    if let Some(code) = code {
+                        self.queue.extend([KEYDOWN | code, code]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl DeviceAccess for Keyboard {
+    fn name(&self) -> &str {
+        "keyboard"
+    }
+
+    fn size(&self) -> usize {
+        4
+    }
+
+    /// Instructions retired on the script's clock and the index of the next script event
+    /// (little-endian `u64`s), then the queued events as `u32`s. Restoring rebases the clock,
+    /// so a rewound run sees the script again from the saved point.
+    fn save(&self, now: u64) -> Vec<u8> {
+        let next = match &self.source {
+            Source::Script { next, .. } => *next as u64,
+            Source::Terminal(_) => 0,
+        };
+        let mut state = Vec::with_capacity(16 + self.queue.len() * 4);
+        state.extend_from_slice(&self.elapsed_insts(now).to_le_bytes());
+        state.extend_from_slice(&next.to_le_bytes());
+        state.extend(self.queue.iter().flat_map(|e| e.to_le_bytes()));
+        state
+    }
+
+    fn restore(&mut self, state: &[u8], now: u64) {
+        let Some((elapsed, rest)) = state.split_first_chunk::<8>() else {
+            return;
+        };
+        let Some((saved_next, rest)) = rest.split_first_chunk::<8>() else {
+            return;
+        };
+        self.insts = now;
+        self.base = now.wrapping_sub(u64::from_le_bytes(*elapsed));
+        if let Source::Script { events, next } = &mut self.source {
+            *next = (u64::from_le_bytes(*saved_next) as usize).min(events.len());
+        }
+        self.queue = rest
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+    }
+
+    /// Events already queued are dropped; the script carries on from where it is.
+    fn reset(&mut self) {
+        self.queue.clear();
+    }
+
+    fn tick(&mut self, now: u64, _mem: &mut GuestMemory) -> Result<(), BusError> {
+        self.insts = now;
+        Ok(())
+    }
+
+    fn irq_level(&mut self) -> bool {
+        self.poll();
+        !self.queue.is_empty()
+    }
+
+    fn read_32(&mut self, offset: usize) -> Result<u32, BusError> {
+        if offset != DATA_OFF {
+            return Ok(0);
+        }
+        self.poll();
+        Ok(self.queue.pop_front().unwrap_or(0))
+    }
+
+    fn write_32(&mut self, _offset: usize, _value: u32) -> Result<(), BusError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_events_are_ordered_by_time() {
+        let events = parse_script(
+            "# warm up first\n\
+             2000 up a\n\
+             1000 down A   # hold\n\
+             \n\
+             1500 press space\n",
+        )
+        .unwrap();
+        assert_eq!(
+            events,
+            [
+                (1000, KEYDOWN | 43),
+                (1500, KEYDOWN | 70),
+                (1500, 70),
+                (2000, 43)
+            ]
+        );
+    }
+
+    #[test]
+    fn script_errors_name_the_line() {
+        let err = parse_script("10 down A\n20 hold A\n").unwrap_err();
+        assert!(
+            err.starts_with("line 2: expected down, up or press"),
+            "{err}"
+        );
+        assert!(parse_script("10 down NOPE").is_err());
+        assert!(parse_script("soon down A").is_err());
+    }
+
+    #[test]
+    fn key_codes_follow_am_numbering() {
+        assert_eq!(key_code("ESCAPE"), Some(1));
+        assert_eq!(key_code("0"), Some(24));
+        assert_eq!(key_code("return"), Some(54));
+        assert_eq!(key_code("PAGEDOWN"), Some(82));
+        assert_eq!(char_key(b'\n'), key_code("RETURN"));
+    }
+}
@@ -1,14 +1,103 @@
-remu_macro::mod_flat!(uart_simple, uart16550, sifive_test_finisher, clint);
+remu_macro::mod_flat!(
+    uart_simple,
+    uart16550,
+    uart_input,
+    uart_output,
+    sifive_test_finisher,
+    syscon,
+    clint,
+    plic,
+    guest_memory,
+    virtio_blk,
+    vga,
+    keyboard,
+    goldfish_rtc,
+    watchdog,
+    gpio,
+    spi_flash,
+    dma_engine
+);
 
 use std::backtrace::Backtrace;
+use std::path::PathBuf;
 use std::str::FromStr;
 
-use crate::bus::{BusError, parse_usize_allow_hex_underscore};
+use crate::bus::{BusError, PAGE_SIZE, parse_usize_allow_hex_underscore};
 
 pub(crate) trait DeviceAccess: Send + Sync {
     fn name(&self) -> &str;
     fn size(&self) -> usize;
 
+    /// Register state for snapshots, restored by [`restore`](Self::restore). `now` is the
+    /// instruction count as for [`tick`](Self::tick), so clocks can be saved relative to it.
+    /// Stateless devices keep the defaults.
+    fn save(&self, now: u64) -> Vec<u8> {
+        let _ = now;
+        Vec::new()
+    }
+    fn restore(&mut self, state: &[u8], now: u64) {
+        let _ = (state, now);
+    }
+
+    /// Guest reboot: registers back to their power-on values. What lives outside the guest
+    /// (disk contents, output sinks, input not yet read, clocks) carries on.
+    fn reset(&mut self) {}
+
+    /// Catch up to `now`, the instructions retired so far: work that does not wait for the
+    /// guest, such as a watchdog running out or a DMA copy making progress, and the time
+    /// instruction-clocked registers read until the next tick. An error stops the hart as a
+    /// failed access would. Called before every access to the device and whenever the bus
+    /// samples interrupt lines, with guest RAM as for [`dma`](Self::dma).
+    fn tick(&mut self, now: u64, mem: &mut GuestMemory) -> Result<(), BusError> {
+        let _ = (now, mem);
+        Ok(())
+    }
+
+    /// Push out buffered output; called when a run stops.
+    fn flush(&mut self) {}
+
+    /// While muted, output sinks and event logs drop what the device emits: the instructions
+    /// being replayed already showed it.
+    fn mute(&mut self, muted: bool) {
+        let _ = muted;
+    }
+
+    /// Output kept in memory by a `out=capture` UART.
+    fn captured_output(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Work started by a register write that touches guest RAM, such as serving a virtqueue
+    /// after a notification. Called by the bus after every write to the device.
+    fn dma(&mut self, mem: &mut GuestMemory) {
+        let _ = mem;
+    }
+
+    /// Write the current frame of a display device to `path`; `None` for other devices.
+    fn dump_display(
+        &mut self,
+        mem: &mut GuestMemory,
+        path: &std::path::Path,
+    ) -> Option<std::io::Result<()>> {
+        let _ = (mem, path);
+        None
+    }
+
+    /// Level of this device's interrupt line, for devices wired to a PLIC source with `irq=`.
+    fn irq_level(&mut self) -> bool {
+        false
+    }
+
+    /// Interrupt controller side: drive source `source` to `level`.
+    fn set_irq_level(&mut self, source: u32, level: bool) {
+        let _ = (source, level);
+    }
+
+    /// Interrupt controller side: whether the hart's external interrupt (`mip.MEIP`) is raised.
+    fn external_irq(&self) -> bool {
+        false
+    }
+
     fn read_8(&mut self, offset: usize) -> Result<u8, BusError> {
         let _ = offset;
         Err(BusError::UnsupportedAccessWidth(8, Backtrace::capture()))
@@ -59,6 +148,16 @@ pub enum DeviceKind {
     UartSimple,
     Uart16550,
     Clint,
+    Plic,
+    VirtioBlk,
+    Vga,
+    Keyboard,
+    GoldfishRtc,
+    Watchdog,
+    Gpio,
+    SpiFlash,
+    Dma,
+    Syscon,
     SifiveTestFinisher,
 }
 
@@ -69,9 +168,33 @@ impl DeviceKind {
             Self::UartSimple => "uart_simple",
             Self::Uart16550 => "uart16550",
             Self::Clint => "clint",
+            Self::Plic => "plic",
+            Self::VirtioBlk => "virtio_blk",
+            Self::Vga => "vga",
+            Self::Keyboard => "keyboard",
+            Self::GoldfishRtc => "goldfish_rtc",
+            Self::Watchdog => "watchdog",
+            Self::Gpio => "gpio",
+            Self::SpiFlash => "spi_flash",
+            Self::Dma => "dma",
+            Self::Syscon => "syscon",
             Self::SifiveTestFinisher => "sifive_test_finisher",
         }
     }
+
+    /// Whether the device has an interrupt line that `irq=` can wire to the PLIC.
+    #[inline]
+    pub const fn has_irq(self) -> bool {
+        matches!(
+            self,
+            Self::Uart16550
+                | Self::VirtioBlk
+                | Self::Keyboard
+                | Self::GoldfishRtc
+                | Self::Watchdog
+                | Self::Dma
+        )
+    }
 }
 
 impl FromStr for DeviceKind {
@@ -82,9 +205,19 @@ impl FromStr for DeviceKind {
             "uart_simple" => Ok(Self::UartSimple),
             "uart16550" => Ok(Self::Uart16550),
             "clint" => Ok(Self::Clint),
+            "plic" => Ok(Self::Plic),
+            "virtio_blk" => Ok(Self::VirtioBlk),
+            "vga" => Ok(Self::Vga),
+            "keyboard" => Ok(Self::Keyboard),
+            "goldfish_rtc" => Ok(Self::GoldfishRtc),
+            "watchdog" => Ok(Self::Watchdog),
+            "gpio" => Ok(Self::Gpio),
+            "spi_flash" => Ok(Self::SpiFlash),
+            "dma" => Ok(Self::Dma),
+            "syscon" => Ok(Self::Syscon),
             "sifive_test_finisher" => Ok(Self::SifiveTestFinisher),
             _ => Err(format!(
-                "unknown device kind {s:?}; expected uart_simple, uart16550, clint, sifive_test_finisher"
+                "unknown device kind {s:?}; expected uart_simple, uart16550, clint, plic, virtio_blk, vga, keyboard, goldfish_rtc, watchdog, gpio, spi_flash, dma, syscon, sifive_test_finisher"
             )),
         }
     }
@@ -94,6 +227,28 @@ impl FromStr for DeviceKind {
 pub struct DeviceConfig {
     pub kind: DeviceKind,
     pub start: usize,
+    /// `out=`: output sink of a UART.
+    pub out: Option<UartOutput>,
+    /// `irq=`: PLIC source driven by the device's interrupt line.
+    pub irq: Option<u32>,
+    /// `image=` and `mode=`: backing image of a `virtio_blk`.
+    pub image: Option<DiskImage>,
+    /// `fb=`, `res=`, `dump=` and `record=`: framebuffer and frame output of a `vga`.
+    pub display: Option<DisplayConfig>,
+    /// `script=`: event source of a `keyboard` (the terminal without it).
+    pub keys: Option<KeyboardInput>,
+    /// `clock=` and `epoch=`: time source of a `goldfish_rtc`.
+    pub rtc: Option<RtcConfig>,
+    /// `poweroff=` and `reboot=`: magic values of a `syscon`.
+    pub syscon: Option<SysconConfig>,
+    /// `clock=`, `timeout=` and `action=`: countdown and expiry action of a `watchdog`.
+    pub watchdog: Option<WatchdogConfig>,
+    /// `script=` and `log=`: input script and output event log of a `gpio`.
+    pub gpio: Option<GpioConfig>,
+    /// `xip=`, `size=` and `image=`: flash array of a `spi_flash`.
+    pub flash: Option<FlashConfig>,
+    /// `channels=` and `rate=`: channel count and copy speed of a `dma`.
+    pub dma: Option<DmaConfig>,
 }
 
 impl FromStr for DeviceConfig {
@@ -105,24 +260,282 @@ impl FromStr for DeviceConfig {
             return Err("empty device spec".to_string());
         }
 
-        let (kind_str, start_str) = input.split_once('@').ok_or_else(|| {
-            "invalid device spec: missing '@' (expected <kind>@<start>)".to_string()
+        let (kind_str, rest) = input.split_once('@').ok_or_else(|| {
+            "invalid device spec: missing '@' (expected <kind>@<start>[,key=value...])".to_string()
         })?;
+        let mut params = rest.split(',');
+        let start_str = params.next().unwrap_or_default();
 
         let kind = DeviceKind::from_str(kind_str)?;
         let start = parse_usize_allow_hex_underscore(start_str, "device address")?;
 
-        Ok(DeviceConfig { kind, start })
+        let mut out = None;
+        let mut irq = None;
+        let mut image = None;
+        let mut mode = None;
+        let mut fb = None;
+        let mut res = None;
+        let mut dump = None;
+        let mut record = None;
+        let mut script = None;
+        let mut rtc = RtcConfig::default();
+        let mut syscon = SysconConfig::default();
+        let mut watchdog = WatchdogConfig::default();
+        let mut gpio = GpioConfig::default();
+        let mut xip = None;
+        let mut flash_size = None;
+        let mut dma = DmaConfig::default();
+        for param in params {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| format!("invalid device parameter {param:?}: expected key=value"))?;
+            match key.trim() {
+                "out" if matches!(kind, DeviceKind::UartSimple | DeviceKind::Uart16550) => {
+                    out = Some(UartOutput::from_str(value)?);
+                }
+                "irq" if kind.has_irq() => {
+                    let source = parse_usize_allow_hex_underscore(value, "irq")?;
+                    if !(1..PLIC_SOURCES as usize).contains(&source) {
+                        return Err(format!(
+                            "irq {source} out of range; PLIC sources are 1..{}",
+                            PLIC_SOURCES - 1
+                        ));
+                    }
+                    irq = Some(source as u32);
+                }
+                "image" if matches!(kind, DeviceKind::VirtioBlk | DeviceKind::SpiFlash) => {
+                    image = Some(PathBuf::from(value.trim()));
+                }
+                "mode" if kind == DeviceKind::VirtioBlk => {
+                    mode = Some(ImageMode::from_str(value)?);
+                }
+                "fb" if kind == DeviceKind::Vga => {
+                    let addr = parse_usize_allow_hex_underscore(value, "framebuffer address")?;
+                    if !addr.is_multiple_of(PAGE_SIZE) {
+                        return Err(format!(
+                            "framebuffer address 0x{addr:x} must be page-aligned"
+                        ));
+                    }
+                    fb = Some(addr);
+                }
+                "res" if kind == DeviceKind::Vga => res = Some(parse_resolution(value)?),
+                "dump" if kind == DeviceKind::Vga => dump = Some(PathBuf::from(value.trim())),
+                "record" if kind == DeviceKind::Vga => {
+                    record = Some(PathBuf::from(value.trim()));
+                }
+                "script" if kind == DeviceKind::Keyboard => {
+                    script = Some(PathBuf::from(value.trim()));
+                }
+                "script" if kind == DeviceKind::Gpio => {
+                    gpio.script = Some(PathBuf::from(value.trim()));
+                }
+                "log" if kind == DeviceKind::Gpio => gpio.log = Some(PathBuf::from(value.trim())),
+                "xip" if kind == DeviceKind::SpiFlash => {
+                    let addr = parse_usize_allow_hex_underscore(value, "xip address")?;
+                    if !addr.is_multiple_of(PAGE_SIZE) {
+                        return Err(format!("xip address 0x{addr:x} must be page-aligned"));
+                    }
+                    xip = Some(addr);
+                }
+                "size" if kind == DeviceKind::SpiFlash => {
+                    let size = parse_usize_allow_hex_underscore(value, "flash size")?;
+                    if size == 0 || size > DEFAULT_FLASH_SIZE || !size.is_multiple_of(64 << 10) {
+                        return Err(format!(
+                            "flash size 0x{size:x} must be a multiple of 64 KiB, up to 16 MiB"
+                        ));
+                    }
+                    flash_size = Some(size);
+                }
+                "clock" if kind == DeviceKind::GoldfishRtc => {
+                    rtc.clock = Some(MtimeSource::from_str(value)?);
+                }
+                "epoch" if kind == DeviceKind::GoldfishRtc => {
+                    rtc.epoch = Some(parse_usize_allow_hex_underscore(value, "epoch")? as u64);
+                }
+                "poweroff" if kind == DeviceKind::Syscon => {
+                    syscon.poweroff = parse_u32(value, "poweroff value")?;
+                }
+                "reboot" if kind == DeviceKind::Syscon => {
+                    syscon.reboot = parse_u32(value, "reboot value")?;
+                }
+                "clock" if kind == DeviceKind::Watchdog => {
+                    watchdog.clock = Some(MtimeSource::from_str(value)?);
+                }
+                "timeout" if kind == DeviceKind::Watchdog => {
+                    watchdog.timeout = Some(parse_u32(value, "watchdog timeout")?);
+                }
+                "action" if kind == DeviceKind::Watchdog => {
+                    watchdog.action = WatchdogAction::from_str(value)?;
+                }
+                "channels" if kind == DeviceKind::Dma => {
+                    let channels = parse_usize_allow_hex_underscore(value, "channels")?;
+                    if !(1..=MAX_DMA_CHANNELS).contains(&channels) {
+                        return Err(format!(
+                            "dma channels {channels} out of range; expected 1..={MAX_DMA_CHANNELS}"
+                        ));
+                    }
+                    dma.channels = channels;
+                }
+                "rate" if kind == DeviceKind::Dma => {
+                    dma.rate = parse_u32(value, "dma rate")?;
+                    if dma.rate == 0 {
+                        return Err("dma rate must be at least 1 byte per instruction".to_string());
+                    }
+                }
+                key => {
+                    return Err(format!(
+                        "unknown parameter {key:?} for device {}",
+                        kind.as_str()
+                    ));
+                }
+            }
+        }
+
+        let flash = match (kind, xip) {
+            (DeviceKind::SpiFlash, None) => {
+                return Err("spi_flash needs an XIP window: add ,xip=ADDR".to_string());
+            }
+            (_, xip) => xip.map(|xip| FlashConfig {
+                xip,
+                size: flash_size.unwrap_or(DEFAULT_FLASH_SIZE),
+                image: image.take(),
+            }),
+        };
+
+        let image = match (kind, image) {
+            (DeviceKind::VirtioBlk, None) => {
+                return Err("virtio_blk needs an image: add ,image=PATH".to_string());
+            }
+            (_, path) => path.map(|path| DiskImage {
+                path,
+                mode: mode.unwrap_or_default(),
+            }),
+        };
+
+        let display = match (kind, fb) {
+            (DeviceKind::Vga, None) => {
+                return Err("vga needs a framebuffer: add ,fb=ADDR".to_string());
+            }
+            (_, fb) => fb.map(|fb| {
+                let (width, height) = res.unwrap_or(DEFAULT_RESOLUTION);
+                DisplayConfig {
+                    fb,
+                    width,
+                    height,
+                    dump,
+                    record,
+                }
+            }),
+        };
+
+        let keys = (kind == DeviceKind::Keyboard)
+            .then(|| script.map(KeyboardInput::Script).unwrap_or_default());
+        let rtc = (kind == DeviceKind::GoldfishRtc).then_some(rtc);
+        let syscon = (kind == DeviceKind::Syscon).then_some(syscon);
+        if kind == DeviceKind::Watchdog && watchdog.action == WatchdogAction::Irq && irq.is_none()
+        {
+            return Err("watchdog action=irq needs an interrupt line: add ,irq=N".to_string());
+        }
+        let watchdog = (kind == DeviceKind::Watchdog).then_some(watchdog);
+        let gpio = (kind == DeviceKind::Gpio).then_some(gpio);
+        let dma = (kind == DeviceKind::Dma).then_some(dma);
+
+        Ok(DeviceConfig {
+            kind,
+            start,
+            out,
+            irq,
+            image,
+            display,
+            keys,
+            rtc,
+            syscon,
+            watchdog,
+            gpio,
+            flash,
+            dma,
+        })
+    }
+}
+
+fn parse_u32(value: &str, field: &str) -> Result<u32, String> {
+    let n = parse_usize_allow_hex_underscore(value, field)?;
+    u32::try_from(n).map_err(|_| format!("{field} 0x{n:x} does not fit in 32 bits"))
+}
+
+/// Bus-wide resources handed to devices as they are created.
+pub(crate) struct DeviceEnv {
+    pub(crate) mtime: MtimeSource,
+    /// Goes to the first UART created; later ones get no input.
+    pub(crate) uart_input: Option<UartInput>,
+}
+
+impl DeviceEnv {
+    fn uart_io(&mut self, out: Option<UartOutput>) -> (UartRx, UartTx) {
+        let rx = UartRx::new(self.uart_input.take().unwrap_or(UartInput::None));
+        let tx = UartTx::new(out.unwrap_or_default(), &rx);
+        (rx, tx)
     }
 }
 
-pub(crate) fn instantiate_device(kind: DeviceKind) -> Box<dyn DeviceAccess> {
-    match kind {
-        DeviceKind::UartSimple => Box::new(uart_simple::SimpleUart::new()),
-        DeviceKind::Uart16550 => Box::new(uart16550::Uart16550::new()),
+pub(crate) fn instantiate_device(
+    config: &DeviceConfig,
+    env: &mut DeviceEnv,
+) -> Box<dyn DeviceAccess> {
+    match config.kind {
+        DeviceKind::UartSimple => {
+            let (rx, tx) = env.uart_io(config.out.clone());
+            Box::new(uart_simple::SimpleUart::new(rx, tx))
+        }
+        DeviceKind::Uart16550 => {
+            let (rx, tx) = env.uart_io(config.out.clone());
+            Box::new(uart16550::Uart16550::new(rx, tx))
+        }
         DeviceKind::SifiveTestFinisher => {
             Box::new(sifive_test_finisher::SifiveTestFinisher::new())
         }
-        DeviceKind::Clint => Box::new(clint::Clint::new()),
+        DeviceKind::Clint => Box::new(clint::Clint::new(env.mtime)),
+        DeviceKind::Plic => Box::new(plic::Plic::new()),
+        DeviceKind::VirtioBlk => {
+            let image = config
+                .image
+                .as_ref()
+                .expect("virtio_blk config without an image");
+            Box::new(virtio_blk::VirtioBlk::new(image))
+        }
+        DeviceKind::Vga => {
+            let display = config
+                .display
+                .clone()
+                .expect("vga config without a framebuffer");
+            Box::new(vga::Vga::new(display))
+        }
+        DeviceKind::Syscon => Box::new(syscon::Syscon::new(config.syscon.unwrap_or_default())),
+        DeviceKind::GoldfishRtc => Box::new(goldfish_rtc::GoldfishRtc::new(
+            config.rtc.unwrap_or_default(),
+            env.mtime,
+        )),
+        DeviceKind::Watchdog => Box::new(watchdog::Watchdog::new(
+            config.watchdog.unwrap_or_default(),
+            env.mtime,
+        )),
+        DeviceKind::Gpio => {
+            let gpio = config.gpio.clone().unwrap_or_default();
+            Box::new(gpio::Gpio::new(&gpio))
+        }
+        DeviceKind::SpiFlash => {
+            let flash = config
+                .flash
+                .as_ref()
+                .expect("spi_flash config without an XIP window");
+            Box::new(spi_flash::SpiFlash::new(flash))
+        }
+        DeviceKind::Dma => Box::new(dma_engine::DmaEngine::new(
+            config.dma.unwrap_or_default(),
+        )),
+        DeviceKind::Keyboard => {
+            let input = config.keys.clone().unwrap_or_default();
+            Box::new(keyboard::Keyboard::new(&input))
+        }
     }
 }
@@ -0,0 +1,278 @@
+//! PLIC (Platform-Level Interrupt Controller) — SiFive/RISC-V layout, one hart, M-mode context.
+//!
+//! Devices given `irq=N` in `--dev` drive source `N`; the bus samples their lines after every
+//! MMIO access and every 1024 instructions (see `Bus::poll_devices`). Sources are level
+//! triggered: a source stays pending while its line is high and it is not claimed, and is
+//! sampled again when the claim completes. The hart sees `mip.MEIP` while an enabled source
+//! with priority above the threshold is pending.
+//!
+//! Layout (sources 1..32, context 0):
+//! - 0x000000: source priorities (4 bytes each; source 0 is reserved)
+//! - 0x001000: pending bits (read-only)
+//! - 0x002000: enable bits for context 0
+//! - 0x200000: priority threshold for context 0
+//! - 0x200004: claim (read) / complete (write) for context 0
+
+use std::backtrace::Backtrace;
+
+use crate::bus::{BusError, device::DeviceAccess};
+
+/// PLIC size per the SiFive memory map.
+pub(crate) const PLIC_SIZE: usize = 0x400_0000;
+
+/// Number of interrupt sources, including the reserved source 0.
+pub(crate) const PLIC_SOURCES: u32 = 32;
+
+const PRIORITY_OFF: usize = 0x0000;
+const PENDING_OFF: usize = 0x1000;
+const ENABLE_OFF: usize = 0x2000;
+const THRESHOLD_OFF: usize = 0x20_0000;
+const CLAIM_OFF: usize = 0x20_0004;
+
+/// Priorities and the threshold are 3 bits wide, as on the FU540.
+const PRIORITY_MASK: u32 = 0x7;
+
+pub struct Plic {
+    priority: [u32; PLIC_SOURCES as usize],
+    pending: u32,
+    enable: u32,
+    threshold: u32,
+    /// Sources claimed and not yet completed: their gateway accepts no new request.
+    claimed: u32,
+    /// Current line level of each source.
+    level: u32,
+}
+
+impl Plic {
+    pub(crate) fn new() -> Self {
+        Self {
+            priority: [0; PLIC_SOURCES as usize],
+            pending: 0,
+            enable: 0,
+            threshold: 0,
+            claimed: 0,
+            level: 0,
+        }
+    }
+
+    /// Highest-priority pending and enabled source above the threshold (lowest id on ties).
+    fn best(&self) -> Option<u32> {
+        let candidates = self.pending & self.enable & !1;
+        (1..PLIC_SOURCES)
+            .filter(|&i| candidates & (1 << i) != 0 && self.priority[i as usize] > self.threshold)
+            .max_by_key(|&i| (self.priority[i as usize], std::cmp::Reverse(i)))
+    }
+
+    fn claim(&mut self) -> u32 {
+        let Some(source) = self.best() else {
+            return 0;
+        };
+        self.pending &= !(1 << source);
+        self.claimed |= 1 << source;
+        source
+    }
+
+    fn complete(&mut self, source: u32) {
+        if source == 0 || source >= PLIC_SOURCES {
+            return;
+        }
+        self.claimed &= !(1 << source);
+        self.pending |= self.level & (1 << source);
+    }
+}
+
+impl DeviceAccess for Plic {
+    fn name(&self) -> &str {
+        "plic"
+    }
+
+    fn size(&self) -> usize {
+        PLIC_SIZE
+    }
+
+    /// Priorities, then pending, enable, threshold, claimed and level, as little-endian `u32`s.
+    fn save(&self, _now: u64) -> Vec<u8> {
+        self.priority
+            .iter()
+            .chain(&[
+                self.pending,
+                self.enable,
+                self.threshold,
+                self.claimed,
+                self.level,
+            ])
+            .flat_map(|v| v.to_le_bytes())
+            .collect()
+    }
+
+    fn restore(&mut self, state: &[u8], _now: u64) {
+        let words: Vec<u32> = state
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        let Some((priority, rest)) = words.split_first_chunk::<{ PLIC_SOURCES as usize }>() else {
+            return;
+        };
+        let [pending, enable, threshold, claimed, level] = *rest else {
+            return;
+        };
+        self.priority = *priority;
+        self.pending = pending;
+        self.enable = enable;
+        self.threshold = threshold;
+        self.claimed = claimed;
+        self.level = level;
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn set_irq_level(&mut self, source: u32, level: bool) {
+        let bit = 1 << source;
+        if level {
+            self.level |= bit;
+            if self.claimed & bit == 0 {
+                self.pending |= bit;
+            }
+        } else {
+            self.level &= !bit;
+            self.pending &= !bit;
+        }
+    }
+
+    fn external_irq(&self) -> bool {
+        self.best().is_some()
+    }
+
+    fn read_32(&mut self, offset: usize) -> Result<u32, BusError> {
+        if !offset.is_multiple_of(4) {
+            return Err(BusError::UnsupportedAccessWidth(32, Backtrace::capture()));
+        }
+        Ok(match offset {
+            PRIORITY_OFF..PENDING_OFF => {
+                let source = (offset - PRIORITY_OFF) / 4;
+                self.priority.get(source).copied().unwrap_or(0)
+            }
+            PENDING_OFF => self.pending,
+            ENABLE_OFF => self.enable,
+            THRESHOLD_OFF => self.threshold,
+            CLAIM_OFF => self.claim(),
+            _ => 0,
+        })
+    }
+
+    fn write_32(&mut self, offset: usize, value: u32) -> Result<(), BusError> {
+        if !offset.is_multiple_of(4) {
+            return Err(BusError::UnsupportedAccessWidth(32, Backtrace::capture()));
+        }
+        match offset {
+            PRIORITY_OFF..PENDING_OFF => {
+                let source = (offset - PRIORITY_OFF) / 4;
+                if (1..PLIC_SOURCES as usize).contains(&source) {
+                    self.priority[source] = value & PRIORITY_MASK;
+                }
+            }
+            ENABLE_OFF => self.enable = value & !1,
+            THRESHOLD_OFF => self.threshold = value & PRIORITY_MASK,
+            CLAIM_OFF => self.complete(value),
+            _ => {} // pending is read-only; other contexts do not exist
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PLIC with sources `(id, priority)` enabled.
+    fn enabled(sources: &[(u32, u32)]) -> Plic {
+        let mut plic = Plic::new();
+        let mut enable = 0;
+        for &(id, priority) in sources {
+            plic.write_32(PRIORITY_OFF + 4 * id as usize, priority)
+                .unwrap();
+            enable |= 1 << id;
+        }
+        plic.write_32(ENABLE_OFF, enable).unwrap();
+        plic
+    }
+
+    #[test]
+    fn claim_and_complete() {
+        let mut plic = enabled(&[(3, 1)]);
+        assert!(!plic.external_irq());
+        assert_eq!(plic.read_32(CLAIM_OFF).unwrap(), 0);
+
+        plic.set_irq_level(3, true);
+        assert!(plic.external_irq());
+        assert_eq!(plic.read_32(PENDING_OFF).unwrap(), 1 << 3);
+        assert_eq!(plic.read_32(CLAIM_OFF).unwrap(), 3);
+        assert_eq!(plic.read_32(PENDING_OFF).unwrap(), 0);
+        assert!(!plic.external_irq());
+
+        // The device drops its line before the handler completes: nothing is left pending.
+        plic.set_irq_level(3, false);
+        plic.write_32(CLAIM_OFF, 3).unwrap();
+        assert!(!plic.external_irq());
+        assert_eq!(plic.read_32(CLAIM_OFF).unwrap(), 0);
+    }
+
+    #[test]
+    fn threshold_masks_lower_priorities() {
+        let mut plic = enabled(&[(1, 2), (2, 5)]);
+        plic.set_irq_level(1, true);
+        plic.write_32(THRESHOLD_OFF, 2).unwrap();
+        assert!(!plic.external_irq());
+        assert_eq!(plic.read_32(CLAIM_OFF).unwrap(), 0);
+
+        plic.set_irq_level(2, true);
+        assert_eq!(plic.read_32(CLAIM_OFF).unwrap(), 2);
+        plic.write_32(THRESHOLD_OFF, 1).unwrap();
+        assert_eq!(plic.read_32(CLAIM_OFF).unwrap(), 1);
+
+        // Priority 0 never interrupts, even with the threshold at 0.
+        let mut plic = enabled(&[(4, 0)]);
+        plic.set_irq_level(4, true);
+        assert!(!plic.external_irq());
+    }
+
+    #[test]
+    fn highest_priority_wins_and_ties_go_to_the_lowest_id() {
+        let mut plic = enabled(&[(2, 3), (5, 7), (7, 7), (9, 1)]);
+        for source in [2, 5, 7, 9] {
+            plic.set_irq_level(source, true);
+        }
+        let order: Vec<u32> = (0..5).map(|_| plic.read_32(CLAIM_OFF).unwrap()).collect();
+        assert_eq!(order, [5, 7, 2, 9, 0]);
+
+        // Disabled sources are pending but never claimed.
+        plic.write_32(ENABLE_OFF, 1 << 9).unwrap();
+        for source in [2, 5, 7, 9] {
+            plic.write_32(CLAIM_OFF, source).unwrap();
+        }
+        assert_eq!(plic.read_32(CLAIM_OFF).unwrap(), 9);
+        assert_ne!(plic.read_32(PENDING_OFF).unwrap() & (1 << 5), 0);
+    }
+
+    #[test]
+    fn level_still_high_pends_again_after_complete() {
+        let mut plic = enabled(&[(6, 1)]);
+        plic.set_irq_level(6, true);
+        assert_eq!(plic.read_32(CLAIM_OFF).unwrap(), 6);
+
+        // While claimed, the gateway holds the request back.
+        plic.set_irq_level(6, true);
+        assert!(!plic.external_irq());
+        assert_eq!(plic.read_32(CLAIM_OFF).unwrap(), 0);
+
+        plic.write_32(CLAIM_OFF, 6).unwrap();
+        assert!(plic.external_irq());
+        assert_eq!(plic.read_32(CLAIM_OFF).unwrap(), 6);
+
+        // Completing a source that was not claimed changes nothing.
+        plic.write_32(CLAIM_OFF, 4).unwrap();
+        assert!(!plic.external_irq());
+    }
+}
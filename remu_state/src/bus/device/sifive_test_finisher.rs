@@ -1,6 +1,9 @@
 use remu_types::ExitCode;
 
-use crate::bus::{BusError, device::DeviceAccess};
+use crate::bus::{
+    BusError,
+    device::{DeviceAccess, FINISHER_PASS, FINISHER_RESET},
+};
 
 pub struct SifiveTestFinisher;
 
@@ -33,8 +36,9 @@ impl DeviceAccess for SifiveTestFinisher {
     fn write_32(&mut self, offset: usize, value: u32) -> Result<(), BusError> {
         let _ = offset;
         let exit_code = match value {
-            0x5555 => ExitCode::Good,
-            _ => ExitCode::Bad, // 0x3333 (fail) or other
+            FINISHER_PASS => ExitCode::Good,
+            FINISHER_RESET => return Err(BusError::Reboot),
+            _ => ExitCode::Bad((value >> 16) as u16), // (status << 16) | 0x3333, or other
         };
         Err(BusError::ProgramExit(exit_code))
     }
@@ -0,0 +1,329 @@
+//! SPI controller with a NOR flash (25-series command set, 3-byte addresses) on its one chip
+//! select.
+//!
+//! The flash array is also mapped for execute-in-place at `xip=ADDR`, `size=BYTES` long
+//! (default 16 MiB): it is a memory region, so the guest and the difftest reference fetch and
+//! load from it directly, and ELF segments linked there are loaded into it. It starts erased
+//! (all ones), then holds `image=PATH` if given; the image file itself is never written.
+//! Programs and erases change the region, so snapshots keep them and a guest reboot does not
+//! undo them. The region is `rx`: a CPU store to it is an access fault (see `--access-fault`).
+//!
+//! Commands: 0x03 read, 0x0b fast read, 0x02 page program, 0x20 sector (4 KiB) erase, 0xd8
+//! block (64 KiB) erase, 0xc7/0x60 chip erase, 0x06/0x04 write enable/disable, 0x05 read
+//! status and 0x9f read JEDEC ID. Programs and erases need write enable and take effect when
+//! chip select is released; they complete at once, so the busy status bit is never set.
+//!
+//! Registers (32-bit):
+//! - 0x00: transmit: writing shifts out the low byte, and the byte shifted in is received
+//! - 0x04: receive: bit 31 set when empty, else the next received byte (8-byte FIFO; bytes
+//!   received while it is full are dropped)
+//! - 0x08: chip select, bit 0; clearing it ends the command
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use crate::bus::{
+    BusError, MemAttrs, MemRegionSpec, Memory,
+    device::{DeviceAccess, GuestMemory},
+};
+
+const TXDATA_OFF: usize = 0x00;
+const RXDATA_OFF: usize = 0x04;
+const CS_OFF: usize = 0x08;
+
+const RX_EMPTY: u32 = 1 << 31;
+const RX_FIFO_DEPTH: usize = 8;
+
+const CMD_READ: u8 = 0x03;
+const CMD_FAST_READ: u8 = 0x0b;
+const CMD_PAGE_PROGRAM: u8 = 0x02;
+const CMD_SECTOR_ERASE: u8 = 0x20;
+const CMD_BLOCK_ERASE: u8 = 0xd8;
+const CMD_CHIP_ERASE: u8 = 0xc7;
+const CMD_CHIP_ERASE_ALT: u8 = 0x60;
+const CMD_WRITE_ENABLE: u8 = 0x06;
+const CMD_WRITE_DISABLE: u8 = 0x04;
+const CMD_READ_STATUS: u8 = 0x05;
+const CMD_READ_ID: u8 = 0x9f;
+
+const STATUS_WEL: u8 = 1 << 1;
+/// Winbond, 25Q series; the third ID byte is log2 of the size.
+const JEDEC_ID: [u8; 2] = [0xef, 0x40];
+
+const PAGE: usize = 256;
+const SECTOR: usize = 4 << 10;
+const BLOCK: usize = 64 << 10;
+
+pub(crate) const DEFAULT_FLASH_SIZE: usize = 16 << 20;
+
+/// A `spi_flash` device's `--dev` parameters: `xip=ADDR`, `size=BYTES`, `image=PATH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashConfig {
+    pub xip: usize,
+    pub size: usize,
+    pub image: Option<PathBuf>,
+}
+
+impl FlashConfig {
+    /// The memory region holding the flash array. The CPU cannot write it; programs and
+    /// erases go through the controller.
+    pub fn xip_region(&self) -> MemRegionSpec {
+        MemRegionSpec {
+            name: "spi_flash".to_string(),
+            region: self.xip..self.xip + self.size,
+            attrs: MemAttrs::RX,
+            image: None,
+        }
+    }
+
+    /// Fill the region with the erased flash and `image=`.
+    pub(crate) fn preload(&self, memory: &mut Memory) {
+        let mut contents = vec![0xff; self.size];
+        if let Some(path) = &self.image {
+            match std::fs::read(path) {
+                Ok(image) if image.len() > self.size => tracing::error!(
+                    "flash image {} ({} bytes) does not fit in {} bytes",
+                    path.display(),
+                    image.len(),
+                    self.size
+                ),
+                Ok(image) => contents[..image.len()].copy_from_slice(&image),
+                Err(e) => tracing::error!("flash image {}: {e}", path.display()),
+            }
+        }
+        memory
+            .write_bytes(self.xip, &contents)
+            .expect("flash region is mapped");
+    }
+}
+
+/// A register write, acted on in [`dma`](DeviceAccess::dma) where the flash array is at hand.
+#[derive(Clone, Copy)]
+enum Pending {
+    Byte(u8),
+    Select(bool),
+}
+
+pub struct SpiFlash {
+    xip: usize,
+    size: usize,
+    pending: Option<Pending>,
+    rx: VecDeque<u8>,
+    selected: bool,
+    write_enabled: bool,
+    /// Opcode and address bytes of the command so far.
+    header: Vec<u8>,
+    /// Bytes shifted in after the header.
+    data_len: usize,
+    /// Page program data, at its offset in the page; unwritten bytes are ones.
+    page: Box<[u8; PAGE]>,
+}
+
+impl SpiFlash {
+    pub(crate) fn new(config: &FlashConfig) -> Self {
+        Self {
+            xip: config.xip,
+            size: config.size,
+            pending: None,
+            rx: VecDeque::new(),
+            selected: false,
+            write_enabled: false,
+            header: Vec::new(),
+            data_len: 0,
+            page: Box::new([0xff; PAGE]),
+        }
+    }
+
+    /// Header length of a command: opcode, address and dummy bytes.
+    fn header_len(op: u8) -> usize {
+        match op {
+            CMD_FAST_READ => 5,
+            CMD_READ | CMD_PAGE_PROGRAM | CMD_SECTOR_ERASE | CMD_BLOCK_ERASE => 4,
+            _ => 1,
+        }
+    }
+
+    fn addr(&self) -> usize {
+        let [_, a2, a1, a0, ..] = self.header[..] else {
+            return 0;
+        };
+        u32::from_be_bytes([0, a2, a1, a0]) as usize % self.size
+    }
+
+    /// Shift `byte` out to the flash; returns the byte shifted back.
+    fn transfer(&mut self, mem: &mut GuestMemory, byte: u8) -> Result<u8, BusError> {
+        if !self.selected {
+            return Ok(0xff);
+        }
+        let Some(&op) = self.header.first() else {
+            self.header.push(byte);
+            return Ok(0xff);
+        };
+        if self.header.len() < Self::header_len(op) {
+            self.header.push(byte);
+            return Ok(0xff);
+        }
+        let n = self.data_len;
+        self.data_len += 1;
+        Ok(match op {
+            CMD_READ | CMD_FAST_READ => {
+                let mut b = [0];
+                mem.read(self.xip + (self.addr() + n) % self.size, &mut b)?;
+                b[0]
+            }
+            CMD_PAGE_PROGRAM => {
+                self.page[(self.addr() + n) % PAGE] = byte;
+                0xff
+            }
+            CMD_READ_STATUS => {
+                if self.write_enabled {
+                    STATUS_WEL
+                } else {
+                    0
+                }
+            }
+            CMD_READ_ID => match n {
+                0 | 1 => JEDEC_ID[n],
+                2 => self.size.ilog2() as u8,
+                _ => 0,
+            },
+            _ => 0xff,
+        })
+    }
+
+    /// Chip select released: carry out the command.
+    fn finish(&mut self, mem: &mut GuestMemory) -> Result<(), BusError> {
+        let Some(&op) = self.header.first() else {
+            return Ok(());
+        };
+        let complete = self.header.len() == Self::header_len(op);
+        match op {
+            CMD_WRITE_ENABLE => self.write_enabled = true,
+            CMD_WRITE_DISABLE => self.write_enabled = false,
+            CMD_PAGE_PROGRAM | CMD_SECTOR_ERASE | CMD_BLOCK_ERASE | CMD_CHIP_ERASE
+            | CMD_CHIP_ERASE_ALT
+                if complete =>
+            {
+                if !std::mem::take(&mut self.write_enabled) {
+                    tracing::warn!("spi_flash: command 0x{op:02x} without write enable");
+                } else if op == CMD_PAGE_PROGRAM {
+                    let page = self.xip + self.addr() / PAGE * PAGE;
+                    let mut old = [0; PAGE];
+                    mem.read(page, &mut old)?;
+                    for (b, new) in old.iter_mut().zip(self.page.iter()) {
+                        *b &= new;
+                    }
+                    mem.write(page, &old)?;
+                } else {
+                    let len = match op {
+                        CMD_SECTOR_ERASE => SECTOR,
+                        CMD_BLOCK_ERASE => BLOCK,
+                        _ => self.size,
+                    };
+                    let start = self.xip + self.addr() / len * len;
+                    mem.write(start, &vec![0xff; len])?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn end_command(&mut self) {
+        self.header.clear();
+        self.data_len = 0;
+        self.page.fill(0xff);
+    }
+}
+
+impl DeviceAccess for SpiFlash {
+    fn name(&self) -> &str {
+        "spi_flash"
+    }
+
+    fn size(&self) -> usize {
+        0xc
+    }
+
+    /// Chip select, write enable, header length (one byte each), data length (little-endian
+    /// `u64`), header bytes, page buffer, then the receive FIFO.
+    fn save(&self, _now: u64) -> Vec<u8> {
+        let mut state = Vec::with_capacity(11 + self.header.len() + PAGE + self.rx.len());
+        state.extend([self.selected, self.write_enabled].map(u8::from));
+        state.push(self.header.len() as u8);
+        state.extend_from_slice(&(self.data_len as u64).to_le_bytes());
+        state.extend_from_slice(&self.header);
+        state.extend_from_slice(&self.page[..]);
+        state.extend(self.rx.iter());
+        state
+    }
+
+    fn restore(&mut self, state: &[u8], _now: u64) {
+        let Some(([selected, write_enabled, header_len], rest)) = state.split_first_chunk::<3>()
+        else {
+            return;
+        };
+        let Some((data_len, rest)) = rest.split_first_chunk::<8>() else {
+            return;
+        };
+        let Some((header, rest)) = rest.split_at_checked(*header_len as usize) else {
+            return;
+        };
+        let Some((page, rx)) = rest.split_first_chunk::<PAGE>() else {
+            return;
+        };
+        self.pending = None;
+        self.selected = *selected != 0;
+        self.write_enabled = *write_enabled != 0;
+        self.data_len = u64::from_le_bytes(*data_len) as usize;
+        self.header = header.to_vec();
+        *self.page = *page;
+        self.rx = rx.iter().copied().collect();
+    }
+
+    /// The flash contents stay; the command in flight is dropped.
+    fn reset(&mut self) {
+        self.pending = None;
+        self.rx.clear();
+        self.selected = false;
+        self.write_enabled = false;
+        self.end_command();
+    }
+
+    fn dma(&mut self, mem: &mut GuestMemory) {
+        let result = match self.pending.take() {
+            Some(Pending::Byte(byte)) => self.transfer(mem, byte).map(|rx| {
+                if self.rx.len() < RX_FIFO_DEPTH {
+                    self.rx.push_back(rx);
+                }
+            }),
+            Some(Pending::Select(select)) if select != self.selected => {
+                self.selected = select;
+                let result = if select { Ok(()) } else { self.finish(mem) };
+                self.end_command();
+                result
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            tracing::error!("spi_flash: {e}");
+        }
+    }
+
+    fn read_32(&mut self, offset: usize) -> Result<u32, BusError> {
+        Ok(match offset {
+            RXDATA_OFF => self.rx.pop_front().map_or(RX_EMPTY, u32::from),
+            CS_OFF => self.selected as u32,
+            _ => 0,
+        })
+    }
+
+    fn write_32(&mut self, offset: usize, value: u32) -> Result<(), BusError> {
+        self.pending = match offset {
+            TXDATA_OFF => Some(Pending::Byte(value as u8)),
+            CS_OFF => Some(Pending::Select(value & 1 != 0)),
+            _ => None,
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+//! Syscon power-off/reboot register, compatible with QEMU's `sifive,test` device as used by
+//! the `syscon-poweroff` and `syscon-reboot` device-tree bindings.
+//!
+//! A 32-bit write to offset 0 of:
+//! - the power-off value (`poweroff=`, default 0x5555) exits with success
+//! - `(status << 16) | 0x3333` exits with failure
+//! - the reboot value (`reboot=`, default 0x7777) resets the hart and reloads the program
+//!
+//! Other values are ignored, as QEMU does.
+
+use remu_types::ExitCode;
+
+use crate::bus::{BusError, device::DeviceAccess};
+
+pub(crate) const FINISHER_PASS: u32 = 0x5555;
+pub(crate) const FINISHER_FAIL: u32 = 0x3333;
+pub(crate) const FINISHER_RESET: u32 = 0x7777;
+
+/// A `syscon` device's `--dev` parameters: `poweroff=VALUE`, `reboot=VALUE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SysconConfig {
+    pub poweroff: u32,
+    pub reboot: u32,
+}
+
+impl Default for SysconConfig {
+    fn default() -> Self {
+        Self {
+            poweroff: FINISHER_PASS,
+            reboot: FINISHER_RESET,
+        }
+    }
+}
+
+pub struct Syscon {
+    config: SysconConfig,
+}
+
+impl Syscon {
+    pub(crate) fn new(config: SysconConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl DeviceAccess for Syscon {
+    fn name(&self) -> &str {
+        "syscon"
+    }
+
+    fn size(&self) -> usize {
+        4
+    }
+
+    fn read_32(&mut self, _offset: usize) -> Result<u32, BusError> {
+        Ok(0)
+    }
+
+    fn write_32(&mut self, offset: usize, value: u32) -> Result<(), BusError> {
+        if offset != 0 {
+            return Ok(());
+        }
+        if value == self.config.poweroff {
+            Err(BusError::ProgramExit(ExitCode::Good))
+        } else if value == self.config.reboot {
+            Err(BusError::Reboot)
+        } else if value & 0xffff == FINISHER_FAIL {
+            Err(BusError::ProgramExit(ExitCode::Bad((value >> 16) as u16)))
+        } else {
+            tracing::warn!("syscon: ignoring write of 0x{value:x}");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(syscon: &mut Syscon, value: u32) -> Result<(), BusError> {
+        syscon.write_32(0, value)
+    }
+
+    #[test]
+    fn default_values_power_off_and_reboot() {
+        let mut syscon = Syscon::new(SysconConfig::default());
+        assert!(matches!(
+            write(&mut syscon, 0x5555),
+            Err(BusError::ProgramExit(ExitCode::Good))
+        ));
+        assert!(matches!(write(&mut syscon, 0x7777), Err(BusError::Reboot)));
+        assert!(write(&mut syscon, 0x1234).is_ok());
+        assert!(syscon.write_32(4, 0x5555).is_ok());
+    }
+
+    #[test]
+    fn failure_carries_the_status() {
+        let mut syscon = Syscon::new(SysconConfig::default());
+        assert!(matches!(
+            write(&mut syscon, (42 << 16) | 0x3333),
+            Err(BusError::ProgramExit(ExitCode::Bad(42)))
+        ));
+        assert!(matches!(
+            write(&mut syscon, 0xffff_3333),
+            Err(BusError::ProgramExit(ExitCode::Bad(0xffff)))
+        ));
+    }
+
+    #[test]
+    fn configured_values_replace_the_defaults() {
+        let mut syscon = Syscon::new(SysconConfig {
+            poweroff: 0x1,
+            reboot: 0x2,
+        });
+        assert!(matches!(
+            write(&mut syscon, 0x1),
+            Err(BusError::ProgramExit(ExitCode::Good))
+        ));
+        assert!(matches!(write(&mut syscon, 0x2), Err(BusError::Reboot)));
+        assert!(write(&mut syscon, 0x5555).is_ok());
+        assert!(write(&mut syscon, 0x7777).is_ok());
+    }
+}
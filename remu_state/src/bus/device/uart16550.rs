@@ -6,33 +6,48 @@
 //! - 2: IIR(r)/FCR(w)
 //! - 3: LCR(r/w)
 //! - 4: MCR(r/w)
-//! - 5: LSR(r) — bit5=THRE, bit6=TEMT
+//! - 5: LSR(r) — bit0=DR, bit5=THRE, bit6=TEMT
 //! - 6: MSR(r)
 //! - 7: scratch(r/w)
 
-use std::io::{self, Write};
-
-use crate::bus::{BusError, device::DeviceAccess};
+use crate::bus::{
+    BusError,
+    device::{DeviceAccess, GuestMemory, UartRx, UartTx},
+};
 
+/// LSR bit: Data Ready (receive FIFO not empty).
+const LSR_DR: u8 = 1 << 0;
 /// LSR bit: Transmitter Holding Register Empty (always ready in emulation).
 const LSR_THRE: u8 = 1 << 5;
 /// LSR bit: Transmitter Empty.
 const LSR_TEMT: u8 = 1 << 6;
 /// LCR bit: Divisor Latch Access.
 const LCR_DLAB: u8 = 1 << 7;
+/// IER bit: Received Data Available interrupt.
+const IER_ERBFI: u8 = 1 << 0;
+/// IER bit: Transmitter Holding Register Empty interrupt.
+const IER_ETBEI: u8 = 1 << 1;
+/// IIR values (bit0 = 1: none pending).
+const IIR_NONE: u8 = 0x01;
+const IIR_THRE: u8 = 0x02;
+const IIR_RDA: u8 = 0x04;
 
 pub struct Uart16550 {
     lcr: u8,
     ier: u8,
     mcr: u8,
+    rx: UartRx,
+    tx: UartTx,
 }
 
 impl Uart16550 {
-    pub fn new() -> Self {
+    pub(crate) fn new(rx: UartRx, tx: UartTx) -> Self {
         Self {
             lcr: 0,
             ier: 0,
             mcr: 0,
+            rx,
+            tx,
         }
     }
 
@@ -40,11 +55,16 @@ impl Uart16550 {
     fn dlab(&self) -> bool {
         self.lcr & LCR_DLAB != 0
     }
-}
 
-impl Default for Uart16550 {
-    fn default() -> Self {
-        Self::new()
+    /// Highest-priority enabled interrupt, as IIR reports it.
+    fn iir(&self) -> u8 {
+        if self.ier & IER_ERBFI != 0 && self.rx.has_data() {
+            IIR_RDA
+        } else if self.ier & IER_ETBEI != 0 {
+            IIR_THRE // THR is always empty
+        } else {
+            IIR_NONE
+        }
     }
 }
 
@@ -57,13 +77,69 @@ impl DeviceAccess for Uart16550 {
         8
     }
 
+    /// LCR, IER, MCR, the length of the captured output (little-endian `u64`), then the bytes
+    /// waiting in the receive FIFO.
+    fn save(&self, _now: u64) -> Vec<u8> {
+        let mut state = vec![self.lcr, self.ier, self.mcr];
+        state.extend_from_slice(&(self.tx.captured_len() as u64).to_le_bytes());
+        state.extend(self.rx.pending());
+        state
+    }
+
+    fn restore(&mut self, state: &[u8], _now: u64) {
+        let [lcr, ier, mcr, ref rest @ ..] = *state else {
+            return;
+        };
+        let Some((captured, pending)) = rest.split_first_chunk::<8>() else {
+            return;
+        };
+        self.lcr = lcr;
+        self.ier = ier;
+        self.mcr = mcr;
+        self.tx.truncate_capture(u64::from_le_bytes(*captured) as usize);
+        self.rx.set_pending(pending);
+    }
+
+    fn reset(&mut self) {
+        self.lcr = 0;
+        self.ier = 0;
+        self.mcr = 0;
+    }
+
+    fn flush(&mut self) {
+        let _ = self.tx.flush();
+    }
+
+    fn mute(&mut self, muted: bool) {
+        self.tx.mute(muted);
+    }
+
+    fn captured_output(&self) -> Option<&[u8]> {
+        self.tx.captured()
+    }
+
+    /// With the RX interrupt enabled, the guest is waiting for input: poll the host here.
+    fn tick(&mut self, now: u64, _mem: &mut GuestMemory) -> Result<(), BusError> {
+        self.rx.tick(now);
+        if self.ier & IER_ERBFI != 0 {
+            self.rx.ready()?;
+        }
+        Ok(())
+    }
+
+    fn irq_level(&mut self) -> bool {
+        self.iir() != IIR_NONE
+    }
+
     fn read_8(&mut self, offset: usize) -> Result<u8, BusError> {
         Ok(match offset {
             0 => {
                 if self.dlab() {
                     0 // DLL (divisor latch low), no baud emulation
                 } else {
-                    0 // RBR: no input
+                    // Input is being consumed: show what the guest printed so far.
+                    self.tx.flush()?;
+                    self.rx.pop()?.unwrap_or(0) // RBR
                 }
             }
             1 => {
@@ -73,12 +149,21 @@ impl DeviceAccess for Uart16550 {
                     self.ier
                 }
             }
-            2 => 0x01, // IIR: no interrupt pending
+            2 => self.iir(),
             3 => self.lcr,
             4 => self.mcr,
-            5 => LSR_THRE | LSR_TEMT, // always ready to send
-            6 => 0,                   // MSR
-            7 => 0,                   // scratch
+            5 => {
+                let ready = self.rx.ready()?;
+                if !ready && self.rx.has_host_input() {
+                    // The guest may be about to wait for input: show its prompt.
+                    self.tx.flush()?;
+                }
+                // always ready to send
+                let dr = if ready { LSR_DR } else { 0 };
+                dr | LSR_THRE | LSR_TEMT
+            }
+            6 => 0, // MSR
+            7 => 0, // scratch
             _ => 0,
         })
     }
@@ -87,14 +172,7 @@ impl DeviceAccess for Uart16550 {
         match offset {
             0 => {
                 if !self.dlab() {
-                    let stdout = io::stdout();
-                    let mut handle = stdout.lock();
-                    handle
-                        .write_all(&[value])
-                        .map_err(|_| BusError::IoError(std::backtrace::Backtrace::capture()))?;
-                    handle
-                        .flush()
-                        .map_err(|_| BusError::IoError(std::backtrace::Backtrace::capture()))?;
+                    self.tx.write(value)?;
                 }
                 // THR; when DLAB=1 this is DLL, ignore
             }
@@ -143,3 +221,114 @@ impl DeviceAccess for Uart16550 {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::bus::device::{UartInput, UartOutput};
+
+    const RBR: usize = 0;
+    const IER: usize = 1;
+    const IIR: usize = 2;
+    const LCR: usize = 3;
+    const LSR: usize = 5;
+
+    /// An input file in the temp directory, removed when dropped.
+    struct TempInput(PathBuf);
+
+    impl Drop for TempInput {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// A UART receiving `input` from a file, with captured output.
+    fn uart(name: &str, input: &[u8]) -> (Uart16550, TempInput) {
+        let name = format!("remu-{}-{name}.in", std::process::id());
+        let file = TempInput(std::env::temp_dir().join(name));
+        std::fs::write(&file.0, input).unwrap();
+        let rx = UartRx::new(UartInput::File(file.0.clone()));
+        let tx = UartTx::new(UartOutput::Capture, &rx);
+        (Uart16550::new(rx, tx), file)
+    }
+
+    fn data_ready(uart: &mut Uart16550) -> bool {
+        uart.read_8(LSR).unwrap() & LSR_DR != 0
+    }
+
+    #[test]
+    fn rx_fifo_drains_through_rbr() {
+        let (mut uart, _file) = uart("drain", b"hi\x1d");
+        assert!(data_ready(&mut uart));
+        assert_eq!(uart.read_8(RBR).unwrap(), b'h');
+        assert_eq!(uart.read_8(RBR).unwrap(), b'i');
+        // Ctrl-] from a file is data, not the escape key.
+        assert_eq!(uart.read_8(RBR).unwrap(), 0x1d);
+        assert!(!data_ready(&mut uart));
+        assert_eq!(uart.read_8(RBR).unwrap(), 0);
+        assert_eq!(uart.read_8(LSR).unwrap(), LSR_THRE | LSR_TEMT);
+    }
+
+    #[test]
+    fn dlab_hides_rbr_without_consuming() {
+        let (mut uart, _file) = uart("dlab", b"x");
+        uart.write_8(LCR, LCR_DLAB).unwrap();
+        assert_eq!(uart.read_8(RBR).unwrap(), 0);
+        uart.write_8(LCR, 0).unwrap();
+        assert_eq!(uart.read_8(RBR).unwrap(), b'x');
+    }
+
+    #[test]
+    fn iir_identifies_rx_before_thre() {
+        let (mut uart, _file) = uart("iir", b"a");
+        assert_eq!(uart.read_8(IIR).unwrap(), IIR_NONE);
+        assert!(!uart.irq_level());
+
+        uart.write_8(IER, IER_ERBFI | IER_ETBEI).unwrap();
+        assert_eq!(uart.read_8(IER).unwrap(), IER_ERBFI | IER_ETBEI);
+        assert_eq!(uart.read_8(IIR).unwrap(), IIR_RDA);
+        assert!(uart.irq_level());
+
+        uart.read_8(RBR).unwrap();
+        assert_eq!(uart.read_8(IIR).unwrap(), IIR_THRE);
+
+        uart.write_8(IER, IER_ERBFI).unwrap();
+        assert_eq!(uart.read_8(IIR).unwrap(), IIR_NONE);
+        assert!(!uart.irq_level());
+    }
+
+    #[test]
+    fn restore_cuts_the_capture_back() {
+        let (mut uart, _file) = uart("capture", b"");
+        for &b in b"ab" {
+            uart.write_8(RBR, b).unwrap();
+        }
+        let state = uart.save(0);
+        for &b in b"cd" {
+            uart.write_8(RBR, b).unwrap();
+        }
+        assert_eq!(uart.captured_output(), Some(&b"abcd"[..]));
+        uart.restore(&state, 0);
+        assert_eq!(uart.captured_output(), Some(&b"ab"[..]));
+    }
+
+    #[test]
+    fn save_and_restore_keep_unread_input() {
+        let (mut uart, _file) = uart("save", b"xyz");
+        uart.write_8(IER, IER_ERBFI).unwrap();
+        assert_eq!(uart.read_8(RBR).unwrap(), b'x');
+        let state = uart.save(0);
+
+        while data_ready(&mut uart) {
+            uart.read_8(RBR).unwrap();
+        }
+        uart.write_8(IER, 0).unwrap();
+        uart.restore(&state, 0);
+        assert_eq!(uart.read_8(IIR).unwrap(), IIR_RDA);
+        assert_eq!(uart.read_8(RBR).unwrap(), b'y');
+        assert_eq!(uart.read_8(RBR).unwrap(), b'z');
+        assert!(!data_ready(&mut uart));
+    }
+}
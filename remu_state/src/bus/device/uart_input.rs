@@ -0,0 +1,318 @@
+//! UART receive side: a FIFO fed from the terminal, a file or a pseudo-terminal.
+//!
+//! Host input is polled (never blocking) only when the guest looks for received data and the
+//! FIFO is empty, at most once every [`HOST_POLL_INTERVAL`] instructions, so nothing is
+//! consumed while the debugger prompt owns the terminal. A terminal on stdin is switched to
+//! non-canonical, no-echo mode the first time it is polled, until the device is dropped (see
+//! [`StdinMode`]); signals stay enabled, so Ctrl-C still stops the guest. Typing
+//! [`ESCAPE_KEY`] (Ctrl-]) also returns to the prompt, and is never passed to the guest.
+
+use std::collections::VecDeque;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Mutex, Once, PoisonError};
+
+use crate::bus::BusError;
+
+/// Ctrl-] typed on a terminal stdin: stops the guest with [`BusError::EscapeKey`].
+pub(crate) const ESCAPE_KEY: u8 = 0x1d;
+
+/// Instructions between host polls while the receive FIFO stays empty.
+const HOST_POLL_INTERVAL: u64 = 1024;
+
+/// Where guest UART input comes from. Parsed from `--uart-input`: `none`, `stdin`,
+/// `file:PATH` or `pty`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum UartInput {
+    #[default]
+    None,
+    Stdin,
+    /// The whole file is queued at startup.
+    File(PathBuf),
+    /// A new pseudo-terminal; its path is logged at startup.
+    Pty,
+}
+
+impl FromStr for UartInput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(path) = s.strip_prefix("file:") {
+            return Ok(Self::File(PathBuf::from(path)));
+        }
+        match s {
+            "none" => Ok(Self::None),
+            "stdin" => Ok(Self::Stdin),
+            "pty" => Ok(Self::Pty),
+            _ => Err(format!(
+                "unknown uart input {s:?}; expected none, stdin, file:PATH or pty"
+            )),
+        }
+    }
+}
+
+enum Source {
+    None,
+    /// The terminal mode is switched at the first poll.
+    Stdin(Option<StdinMode>),
+    Pty(OwnedFd),
+}
+
+pub(crate) struct UartRx {
+    fifo: VecDeque<u8>,
+    source: Source,
+    /// Instructions retired as of the last [`tick`](Self::tick).
+    now: u64,
+    /// `now` at the last host poll.
+    polled_at: Option<u64>,
+}
+
+impl UartRx {
+    pub(crate) fn new(input: UartInput) -> Self {
+        let mut fifo = VecDeque::new();
+        let source = match input {
+            UartInput::None => Source::None,
+            UartInput::Stdin => Source::Stdin(None),
+            UartInput::File(path) => {
+                match std::fs::read(&path) {
+                    Ok(data) => fifo.extend(data),
+                    Err(e) => tracing::error!("uart input {}: {e}", path.display()),
+                }
+                Source::None
+            }
+            UartInput::Pty => match open_pty() {
+                Ok((fd, path)) => {
+                    tracing::info!("uart input on pseudo-terminal {path}");
+                    Source::Pty(fd)
+                }
+                Err(e) => {
+                    tracing::error!("uart input: cannot open a pseudo-terminal: {e}");
+                    Source::None
+                }
+            },
+        };
+        Self {
+            fifo,
+            source,
+            now: 0,
+            polled_at: None,
+        }
+    }
+
+    pub(crate) fn tick(&mut self, now: u64) {
+        self.now = now;
+    }
+
+    /// Whether a byte is waiting (LSR.DR), pulling in host input if the FIFO is empty and the
+    /// host is due a poll.
+    pub(crate) fn ready(&mut self) -> Result<bool, BusError> {
+        let due = self
+            .polled_at
+            .is_none_or(|at| self.now.wrapping_sub(at) >= HOST_POLL_INTERVAL);
+        if self.fifo.is_empty() && due {
+            self.fill()?;
+        }
+        Ok(!self.fifo.is_empty())
+    }
+
+    /// Whether more input can arrive from the host (a terminal or pseudo-terminal).
+    pub(crate) fn has_host_input(&self) -> bool {
+        !matches!(self.source, Source::None)
+    }
+
+    /// Whether a byte is waiting, without polling the host.
+    pub(crate) fn has_data(&self) -> bool {
+        !self.fifo.is_empty()
+    }
+
+    pub(crate) fn pop(&mut self) -> Result<Option<u8>, BusError> {
+        self.ready()?;
+        Ok(self.fifo.pop_front())
+    }
+
+    /// Bytes received but not yet read by the guest.
+    pub(crate) fn pending(&self) -> impl Iterator<Item = u8> + '_ {
+        self.fifo.iter().copied()
+    }
+
+    pub(crate) fn set_pending(&mut self, bytes: &[u8]) {
+        self.fifo = bytes.iter().copied().collect();
+    }
+
+    /// The pseudo-terminal input comes from, if any.
+    pub(crate) fn pty(&self) -> Option<BorrowedFd<'_>> {
+        match &self.source {
+            Source::Pty(fd) => Some(fd.as_fd()),
+            _ => None,
+        }
+    }
+
+    fn fill(&mut self) -> Result<(), BusError> {
+        self.polled_at = Some(self.now);
+        let fd = match &mut self.source {
+            Source::None => return Ok(()),
+            Source::Stdin(mode) => {
+                mode.get_or_insert_with(StdinMode::enter);
+                libc::STDIN_FILENO
+            }
+            Source::Pty(fd) => fd.as_raw_fd(),
+        };
+        let mut buf = [0u8; 256];
+        let n = read_ready(fd, &mut buf);
+        let terminal = matches!(self.source, Source::Stdin(_));
+        self.receive(&buf[..n], terminal)
+    }
+
+    /// Queue `input` from the host; typed on the terminal, [`ESCAPE_KEY`] is dropped and stops
+    /// the guest.
+    fn receive(&mut self, input: &[u8], terminal: bool) -> Result<(), BusError> {
+        if terminal && input.contains(&ESCAPE_KEY) {
+            self.fifo.extend(input.iter().filter(|&&b| b != ESCAPE_KEY));
+            return Err(BusError::EscapeKey);
+        }
+        self.fifo.extend(input);
+        Ok(())
+    }
+}
+
+/// Read what `fd` has available without blocking.
+pub(crate) fn read_ready(fd: RawFd, buf: &mut [u8]) -> usize {
+    let mut pfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    if unsafe { libc::poll(&mut pfd, 1, 0) } <= 0 || pfd.revents & libc::POLLIN == 0 {
+        return 0;
+    }
+    let n = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+    n.max(0) as usize
+}
+
+/// Live [`StdinMode`]s, and the settings of a terminal stdin from before the first of them.
+static STDIN_MODE: Mutex<(usize, Option<libc::termios>)> = Mutex::new((0, None));
+
+/// Keeps a terminal stdin without line buffering and echo (signals stay on) while alive. When
+/// the last one is dropped, or on a panic (which aborts in release builds, skipping drops),
+/// the terminal gets its settings back. A no-op when stdin is not a terminal.
+pub(crate) struct StdinMode(());
+
+impl StdinMode {
+    pub(crate) fn enter() -> Self {
+        let mut mode = STDIN_MODE.lock().unwrap_or_else(PoisonError::into_inner);
+        if mode.0 == 0 {
+            mode.1 = unsafe { noncanonical_stdin() };
+            if mode.1.is_some() {
+                static PANIC_HOOK: Once = Once::new();
+                PANIC_HOOK.call_once(|| {
+                    let prev = std::panic::take_hook();
+                    std::panic::set_hook(Box::new(move |info| {
+                        // The panicking thread may hold the lock; then leave the terminal be.
+                        if let Ok(mode) = STDIN_MODE.try_lock() {
+                            restore_stdin(mode.1.as_ref());
+                        }
+                        prev(info);
+                    }));
+                });
+            }
+        }
+        mode.0 += 1;
+        Self(())
+    }
+}
+
+impl Drop for StdinMode {
+    fn drop(&mut self) {
+        let mut mode = STDIN_MODE.lock().unwrap_or_else(PoisonError::into_inner);
+        mode.0 -= 1;
+        if mode.0 == 0 {
+            restore_stdin(mode.1.take().as_ref());
+        }
+    }
+}
+
+/// Turn off line buffering and echo on a terminal stdin; returns the previous settings.
+unsafe fn noncanonical_stdin() -> Option<libc::termios> {
+    unsafe {
+        if libc::isatty(libc::STDIN_FILENO) == 0 {
+            return None;
+        }
+        let mut orig: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut orig) != 0 {
+            return None;
+        }
+        let mut term = orig;
+        term.c_lflag &= !(libc::ICANON | libc::ECHO);
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &term);
+        Some(orig)
+    }
+}
+
+fn restore_stdin(orig: Option<&libc::termios>) {
+    if let Some(orig) = orig {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, orig) };
+    }
+}
+
+/// Open a pseudo-terminal master; returns it with the path of the slave side.
+pub(crate) fn open_pty() -> std::io::Result<(OwnedFd, String)> {
+    unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+        if libc::grantpt(fd.as_raw_fd()) != 0 || libc::unlockpt(fd.as_raw_fd()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let mut name = [0 as libc::c_char; 128];
+        if libc::ptsname_r(fd.as_raw_fd(), name.as_mut_ptr(), name.len()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let path = std::ffi::CStr::from_ptr(name.as_ptr())
+            .to_string_lossy()
+            .into_owned();
+        Ok((fd, path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_inputs() {
+        assert_eq!("none".parse(), Ok(UartInput::None));
+        assert_eq!(" stdin ".parse(), Ok(UartInput::Stdin));
+        assert_eq!("pty".parse(), Ok(UartInput::Pty));
+        assert_eq!(
+            "file:in.txt".parse(),
+            Ok(UartInput::File(PathBuf::from("in.txt")))
+        );
+        assert!("tty".parse::<UartInput>().is_err());
+    }
+
+    #[test]
+    fn escape_key_stops_only_terminal_input() {
+        let mut rx = UartRx::new(UartInput::None);
+        assert!(matches!(
+            rx.receive(b"ab\x1dc", true),
+            Err(BusError::EscapeKey)
+        ));
+        assert_eq!(rx.pending().collect::<Vec<_>>(), b"abc");
+
+        // Anywhere else it is an ordinary byte.
+        let mut rx = UartRx::new(UartInput::None);
+        rx.receive(b"ab\x1dc", false).unwrap();
+        assert_eq!(rx.pending().collect::<Vec<_>>(), b"ab\x1dc");
+    }
+
+    #[test]
+    fn missing_file_gives_no_input() {
+        let mut rx = UartRx::new(UartInput::File(PathBuf::from("/nonexistent/remu-uart-input")));
+        assert!(!rx.ready().unwrap());
+        assert!(!rx.has_host_input());
+    }
+}
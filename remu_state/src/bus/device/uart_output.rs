@@ -0,0 +1,261 @@
+//! UART transmit side: where guest output goes, chosen per device with `out=` in `--dev`.
+//!
+//! Terminal output (stdout, pty) is buffered and flushed at each newline, when the guest reads
+//! a received byte or finds none waiting from a host input (so a prompt shows before it waits
+//! for input) and when a run stops. File output is written when its buffer fills and at exit.
+//! Captured output is part of the device state: a restored snapshot cuts it back, and a
+//! replay, though muted, captures again what it re-executes.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::bus::BusError;
+use crate::bus::device::{UartRx, open_pty};
+
+/// Most bytes `out=capture` keeps; later output is dropped.
+const CAPTURE_LIMIT: usize = 16 << 20;
+
+/// Where a UART's output goes. Parsed from `out=` in a `--dev` spec: `stdout`, `file:PATH`,
+/// `pty`, `capture` or `none`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum UartOutput {
+    #[default]
+    Stdout,
+    File(PathBuf),
+    /// The UART's input pseudo-terminal if it has one, else a new one (path logged).
+    Pty,
+    /// Kept in memory, for `--expect-output` and tests (see `Bus::captured_output`).
+    Capture,
+    None,
+}
+
+impl FromStr for UartOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(path) = s.strip_prefix("file:") {
+            return Ok(Self::File(PathBuf::from(path)));
+        }
+        match s {
+            "stdout" => Ok(Self::Stdout),
+            "pty" => Ok(Self::Pty),
+            "capture" => Ok(Self::Capture),
+            "none" => Ok(Self::None),
+            _ => Err(format!(
+                "unknown uart output {s:?}; expected stdout, file:PATH, pty, capture or none"
+            )),
+        }
+    }
+}
+
+enum Sink {
+    Stdout,
+    File(BufWriter<File>),
+    Pty(OwnedFd),
+    Capture(Vec<u8>),
+    None,
+}
+
+pub(crate) struct UartTx {
+    sink: Sink,
+    buf: Vec<u8>,
+    muted: bool,
+}
+
+impl UartTx {
+    pub(crate) fn new(out: UartOutput, rx: &UartRx) -> Self {
+        let sink = match out {
+            UartOutput::Stdout => Sink::Stdout,
+            UartOutput::File(path) => match File::create(&path) {
+                Ok(file) => Sink::File(BufWriter::new(file)),
+                Err(e) => {
+                    tracing::error!("uart output {}: {e}", path.display());
+                    Sink::None
+                }
+            },
+            UartOutput::Pty => {
+                let pty = match rx.pty() {
+                    Some(fd) => fd.try_clone_to_owned(),
+                    None => open_pty().map(|(fd, path)| {
+                        tracing::info!("uart output on pseudo-terminal {path}");
+                        fd
+                    }),
+                };
+                match pty {
+                    Ok(fd) => Sink::Pty(fd),
+                    Err(e) => {
+                        tracing::error!("uart output: cannot open a pseudo-terminal: {e}");
+                        Sink::None
+                    }
+                }
+            }
+            UartOutput::Capture => Sink::Capture(Vec::new()),
+            UartOutput::None => Sink::None,
+        };
+        Self {
+            sink,
+            buf: Vec::new(),
+            muted: false,
+        }
+    }
+
+    /// Drop written bytes until unmuted, except into a capture; what is already buffered goes
+    /// out first.
+    pub(crate) fn mute(&mut self, muted: bool) {
+        if muted {
+            let _ = self.flush();
+        }
+        self.muted = muted;
+    }
+
+    pub(crate) fn write(&mut self, byte: u8) -> Result<(), BusError> {
+        match &mut self.sink {
+            Sink::Capture(out) => {
+                if out.len() < CAPTURE_LIMIT {
+                    out.push(byte);
+                    if out.len() == CAPTURE_LIMIT {
+                        tracing::warn!("uart capture full, dropping further output");
+                    }
+                }
+            }
+            _ if self.muted => {}
+            Sink::None => {}
+            Sink::File(file) => file.write_all(&[byte]).map_err(|_| io_error())?,
+            Sink::Stdout | Sink::Pty(_) => {
+                self.buf.push(byte);
+                if byte == b'\n' {
+                    self.flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), BusError> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let result = match &mut self.sink {
+            Sink::Stdout => {
+                let mut handle = io::stdout().lock();
+                handle.write_all(&self.buf).and_then(|()| handle.flush())
+            }
+            Sink::Pty(fd) => {
+                // Nobody may be reading the other side: drop what does not fit.
+                unsafe { libc::write(fd.as_raw_fd(), self.buf.as_ptr().cast(), self.buf.len()) };
+                Ok(())
+            }
+            Sink::File(_) | Sink::Capture(_) | Sink::None => Ok(()),
+        };
+        self.buf.clear();
+        result.map_err(|_| io_error())
+    }
+
+    pub(crate) fn captured(&self) -> Option<&[u8]> {
+        match &self.sink {
+            Sink::Capture(out) => Some(out),
+            _ => None,
+        }
+    }
+
+    /// Bytes captured so far, for the device state; 0 when not capturing.
+    pub(crate) fn captured_len(&self) -> usize {
+        self.captured().map_or(0, <[u8]>::len)
+    }
+
+    /// Back to the first `len` captured bytes, as of the device state being restored.
+    pub(crate) fn truncate_capture(&mut self, len: usize) {
+        if let Sink::Capture(out) = &mut self.sink {
+            out.truncate(len);
+        }
+    }
+}
+
+fn io_error() -> BusError {
+    BusError::IoError(std::backtrace::Backtrace::capture())
+}
+
+impl Drop for UartTx {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::device::UartInput;
+
+    fn tx(out: UartOutput) -> UartTx {
+        UartTx::new(out, &UartRx::new(UartInput::None))
+    }
+
+    fn write(tx: &mut UartTx, bytes: &[u8]) {
+        for &b in bytes {
+            tx.write(b).unwrap();
+        }
+    }
+
+    #[test]
+    fn parse_outputs() {
+        assert_eq!("stdout".parse(), Ok(UartOutput::Stdout));
+        assert_eq!(" capture ".parse(), Ok(UartOutput::Capture));
+        assert_eq!("none".parse(), Ok(UartOutput::None));
+        assert_eq!("pty".parse(), Ok(UartOutput::Pty));
+        assert_eq!(
+            "file:out.txt".parse(),
+            Ok(UartOutput::File(PathBuf::from("out.txt")))
+        );
+        assert!("tty".parse::<UartOutput>().is_err());
+    }
+
+    #[test]
+    fn capture_keeps_muted_output_and_truncates() {
+        let mut tx = tx(UartOutput::Capture);
+        write(&mut tx, b"ab\n");
+        tx.mute(true);
+        write(&mut tx, b"cd");
+        tx.mute(false);
+        assert_eq!(tx.captured(), Some(&b"ab\ncd"[..]));
+        assert_eq!(tx.captured_len(), 5);
+        tx.truncate_capture(2);
+        write(&mut tx, b"x");
+        assert_eq!(tx.captured(), Some(&b"abx"[..]));
+    }
+
+    #[test]
+    fn capture_is_bounded() {
+        let mut tx = tx(UartOutput::Capture);
+        write(&mut tx, &vec![b'.'; CAPTURE_LIMIT + 3]);
+        assert_eq!(tx.captured_len(), CAPTURE_LIMIT);
+    }
+
+    #[test]
+    fn other_sinks_capture_nothing() {
+        let mut tx = tx(UartOutput::None);
+        write(&mut tx, b"gone\n");
+        assert_eq!(tx.captured(), None);
+        assert_eq!(tx.captured_len(), 0);
+        tx.truncate_capture(0);
+    }
+
+    #[test]
+    fn file_sink_is_written_when_full_or_dropped() {
+        let path = std::env::temp_dir().join(format!("remu-{}-uart.out", std::process::id()));
+        let mut tx = tx(UartOutput::File(path.clone()));
+        write(&mut tx, b"line one\nline two\n");
+        tx.flush().unwrap();
+        // Newlines and run stops do not write to the file.
+        assert_eq!(std::fs::read(&path).unwrap(), b"");
+        tx.mute(true);
+        write(&mut tx, b"muted\n");
+        tx.mute(false);
+        drop(tx);
+        assert_eq!(std::fs::read(&path).unwrap(), b"line one\nline two\n");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,12 +1,17 @@
-use std::io::{self, Write};
+use crate::bus::{
+    BusError,
+    device::{DeviceAccess, GuestMemory, UartRx, UartTx},
+};
 
-use crate::bus::{BusError, device::DeviceAccess};
-
-pub struct SimpleUart;
+/// One byte register: writes print, reads return the next input byte (0 when there is none).
+pub struct SimpleUart {
+    rx: UartRx,
+    tx: UartTx,
+}
 
 impl SimpleUart {
-    pub fn new() -> Self {
-        Self
+    pub(crate) fn new(rx: UartRx, tx: UartTx) -> Self {
+        Self { rx, tx }
     }
 }
 
@@ -19,22 +24,47 @@ impl DeviceAccess for SimpleUart {
         1
     }
 
+    /// Length of the captured output (little-endian `u64`), then the bytes waiting in the
+    /// receive FIFO.
+    fn save(&self, _now: u64) -> Vec<u8> {
+        let mut state = (self.tx.captured_len() as u64).to_le_bytes().to_vec();
+        state.extend(self.rx.pending());
+        state
+    }
+
+    fn restore(&mut self, state: &[u8], _now: u64) {
+        let Some((captured, pending)) = state.split_first_chunk::<8>() else {
+            return;
+        };
+        self.tx.truncate_capture(u64::from_le_bytes(*captured) as usize);
+        self.rx.set_pending(pending);
+    }
+
+    fn flush(&mut self) {
+        let _ = self.tx.flush();
+    }
+
+    fn mute(&mut self, muted: bool) {
+        self.tx.mute(muted);
+    }
+
+    fn captured_output(&self) -> Option<&[u8]> {
+        self.tx.captured()
+    }
+
+    fn tick(&mut self, now: u64, _mem: &mut GuestMemory) -> Result<(), BusError> {
+        self.rx.tick(now);
+        Ok(())
+    }
+
     fn read_8(&mut self, offset: usize) -> Result<u8, BusError> {
         let _ = offset;
-        Ok(0)
+        self.tx.flush()?;
+        Ok(self.rx.pop()?.unwrap_or(0))
     }
 
     fn write_8(&mut self, offset: usize, value: u8) -> Result<(), BusError> {
         let _ = offset;
-
-        let stdout = io::stdout();
-        let mut handle = stdout.lock();
-        handle
-            .write_all(&[value])
-            .map_err(|_| BusError::IoError(std::backtrace::Backtrace::capture()))?;
-        handle
-            .flush()
-            .map_err(|_| BusError::IoError(std::backtrace::Backtrace::capture()))?;
-        Ok(())
+        self.tx.write(value)
     }
 }
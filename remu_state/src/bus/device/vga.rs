@@ -0,0 +1,271 @@
+//! VGA-style display (NEMU/AM layout): control registers plus a framebuffer in RAM.
+//!
+//! The framebuffer is an ordinary memory region (`vga_fb`, added to the bus by the device's
+//! `fb=` parameter) holding `width * height` pixels as little-endian `0x00RRGGBB` words, so
+//! guest drawing costs no more than any store and is kept by snapshots. Frames are rendered
+//! from it on a sync write, or with `display dump`: to `dump=PATH` (overwritten each sync) and
+//! to numbered files in `record=DIR`.
+//!
+//! Control registers:
+//! - 0x0: screen size, `(width << 16) | height` (read-only)
+//! - 0x4: sync; writing non-zero renders the frame, then it reads back 0
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+
+use crate::bus::{
+    BusError, MemAttrs, MemRegionSpec, PAGE_SIZE,
+    device::{DeviceAccess, GuestMemory},
+};
+
+const SIZE_OFF: usize = 0x0;
+const SYNC_OFF: usize = 0x4;
+
+/// Screen size when `res=` is not given (AM's default).
+pub const DEFAULT_RESOLUTION: (u32, u32) = (400, 300);
+
+/// A `vga` device's `--dev` parameters: `fb=ADDR`, `res=WxH`, `dump=PATH`, `record=DIR`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayConfig {
+    pub fb: usize,
+    pub width: u32,
+    pub height: u32,
+    pub dump: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+}
+
+impl DisplayConfig {
+    /// The RAM region backing the framebuffer, rounded up to whole pages.
+    pub fn fb_region(&self) -> MemRegionSpec {
+        let bytes = self.width as usize * self.height as usize * 4;
+        MemRegionSpec {
+            name: "vga_fb".to_string(),
+            region: self.fb..self.fb + bytes.next_multiple_of(PAGE_SIZE),
+            attrs: MemAttrs {
+                exec: false,
+                ..MemAttrs::RWX
+            },
+            image: None,
+        }
+    }
+}
+
+/// Parse `res=` as `WIDTHxHEIGHT`.
+pub(crate) fn parse_resolution(s: &str) -> Result<(u32, u32), String> {
+    let (w, h) = s
+        .trim()
+        .split_once('x')
+        .ok_or_else(|| format!("invalid resolution {s:?}: expected WIDTHxHEIGHT"))?;
+    let dim = |v: &str| match v.parse::<u32>() {
+        Ok(n) if (1..=0xffff).contains(&n) => Ok(n),
+        _ => Err(format!("invalid resolution {s:?}: sides must be 1..65535")),
+    };
+    Ok((dim(w)?, dim(h)?))
+}
+
+pub struct Vga {
+    config: DisplayConfig,
+    /// A sync was written: render on the next [`dma`](DeviceAccess::dma).
+    sync: bool,
+    /// Number of the next `record=` frame.
+    frame: u64,
+}
+
+impl Vga {
+    pub(crate) fn new(config: DisplayConfig) -> Self {
+        if let Some(dir) = &config.record
+            && let Err(e) = std::fs::create_dir_all(dir)
+        {
+            tracing::error!("vga record {}: {e}", dir.display());
+        }
+        Self {
+            config,
+            sync: false,
+            frame: 0,
+        }
+    }
+
+    /// The current frame as packed RGB bytes.
+    fn frame_rgb(&self, mem: &mut GuestMemory) -> Result<Vec<u8>, BusError> {
+        let pixels = self.config.width as usize * self.config.height as usize;
+        let mut raw = vec![0u8; pixels * 4];
+        mem.read(self.config.fb, &mut raw)?;
+        Ok(raw
+            .chunks_exact(4)
+            .flat_map(|p| [p[2], p[1], p[0]])
+            .collect())
+    }
+
+    fn render(&mut self, mem: &mut GuestMemory) -> io::Result<()> {
+        let rgb = self.frame_rgb(mem).map_err(io::Error::other)?;
+        let (w, h) = (self.config.width, self.config.height);
+        if let Some(path) = &self.config.dump {
+            write_image(path, w, h, &rgb)?;
+        }
+        if let Some(dir) = &self.config.record {
+            let path = dir.join(format!("frame-{:05}.png", self.frame));
+            write_image(&path, w, h, &rgb)?;
+            self.frame += 1;
+        }
+        Ok(())
+    }
+}
+
+impl DeviceAccess for Vga {
+    fn name(&self) -> &str {
+        "vga"
+    }
+
+    fn size(&self) -> usize {
+        8
+    }
+
+    /// The next `record=` frame number, so a rewound run overwrites what it replays.
+    fn save(&self, _now: u64) -> Vec<u8> {
+        self.frame.to_le_bytes().to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8], _now: u64) {
+        if let Some(frame) = state.first_chunk::<8>() {
+            self.frame = u64::from_le_bytes(*frame);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.sync = false;
+    }
+
+    fn dma(&mut self, mem: &mut GuestMemory) {
+        if !std::mem::take(&mut self.sync) {
+            return;
+        }
+        if let Err(e) = self.render(mem) {
+            tracing::error!("vga: cannot write frame: {e}");
+        }
+    }
+
+    fn dump_display(&mut self, mem: &mut GuestMemory, path: &Path) -> Option<io::Result<()>> {
+        let (w, h) = (self.config.width, self.config.height);
+        Some(
+            self.frame_rgb(mem)
+                .map_err(io::Error::other)
+                .and_then(|rgb| write_image(path, w, h, &rgb)),
+        )
+    }
+
+    fn read_32(&mut self, offset: usize) -> Result<u32, BusError> {
+        Ok(match offset {
+            SIZE_OFF => (self.config.width << 16) | self.config.height,
+            _ => 0, // sync reads back 0 once the frame is out
+        })
+    }
+
+    fn write_32(&mut self, offset: usize, value: u32) -> Result<(), BusError> {
+        if offset == SYNC_OFF && value != 0 {
+            self.sync = true;
+        }
+        Ok(())
+    }
+}
+
+/// Write an RGB image: PPM (binary `P6`) when `path` ends in `.ppm`, PNG otherwise.
+fn write_image(path: &Path, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ppm"))
+    {
+        write_ppm(&mut out, width, height, rgb)?;
+    } else {
+        write_png(&mut out, width, height, rgb)?;
+    }
+    out.flush()
+}
+
+fn write_ppm(out: &mut impl Write, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    write!(out, "P6\n{width} {height}\n255\n")?;
+    out.write_all(rgb)
+}
+
+/// 8-bit RGB, no interlace, every scanline with filter type 0.
+fn write_png(out: &mut impl Write, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+    png_chunk(out, b"IHDR", &ihdr)?;
+
+    let mut z = ZlibEncoder::new(Vec::new(), Compression::fast());
+    for row in rgb.chunks_exact(width as usize * 3) {
+        z.write_all(&[0])?;
+        z.write_all(row)?;
+    }
+    png_chunk(out, b"IDAT", &z.finish()?)?;
+    png_chunk(out, b"IEND", &[])
+}
+
+fn png_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc = flate2::Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc.sum().to_be_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+
+    use super::*;
+
+    // 2x1: red, then blue.
+    const RGB: [u8; 6] = [0xff, 0, 0, 0, 0, 0xff];
+
+    #[test]
+    fn ppm_is_header_then_pixels() {
+        let mut out = Vec::new();
+        write_ppm(&mut out, 2, 1, &RGB).unwrap();
+        assert_eq!(out, b"P6\n2 1\n255\n\xff\x00\x00\x00\x00\xff");
+    }
+
+    #[test]
+    fn png_chunks_carry_size_and_filtered_rows() {
+        let mut out = Vec::new();
+        write_png(&mut out, 2, 1, &RGB).unwrap();
+        assert_eq!(&out[..8], b"\x89PNG\r\n\x1a\n");
+
+        let mut chunks = Vec::new();
+        let mut rest = &out[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let kind = &rest[4..8];
+            let data = &rest[8..8 + len];
+            let mut crc = flate2::Crc::new();
+            crc.update(kind);
+            crc.update(data);
+            let stored = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+            assert_eq!(crc.sum(), stored, "crc of {:?}", std::str::from_utf8(kind));
+            chunks.push((kind.to_vec(), data.to_vec()));
+            rest = &rest[12 + len..];
+        }
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(k, _)| &k[..]).collect();
+        assert_eq!(kinds, [&b"IHDR"[..], b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+
+        let mut pixels = Vec::new();
+        ZlibDecoder::new(&chunks[1].1[..])
+            .read_to_end(&mut pixels)
+            .unwrap();
+        assert_eq!(pixels, [0, 0xff, 0, 0, 0, 0, 0xff]);
+    }
+}
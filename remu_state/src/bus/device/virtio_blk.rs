@@ -0,0 +1,823 @@
+//! virtio-mmio block device (virtio 1.x, MMIO transport version 2) backed by a disk image.
+//!
+//! One request queue, split virtqueue layout. Requests are served when the driver writes
+//! QueueNotify: descriptors, rings and buffers are accessed in guest RAM (see [`GuestMemory`]),
+//! and the used-buffer interrupt drives the device's `irq=` line to the PLIC.
+//!
+//! The image file itself is never written. In `cow` mode guest writes go to an in-memory
+//! overlay, which snapshots and checkpoints keep; in `ro` mode the device offers
+//! `VIRTIO_BLK_F_RO` and fails write requests.
+//!
+//! Layout (offsets from the device base):
+//! - 0x000: MagicValue, 0x004: Version, 0x008: DeviceID, 0x00c: VendorID
+//! - 0x010/0x014: DeviceFeatures/DeviceFeaturesSel, 0x020/0x024: DriverFeatures/DriverFeaturesSel
+//! - 0x030: QueueSel, 0x034: QueueNumMax, 0x038: QueueNum, 0x044: QueueReady, 0x050: QueueNotify
+//! - 0x060: InterruptStatus, 0x064: InterruptACK, 0x070: Status
+//! - 0x080..0x0a4: QueueDesc/QueueDriver/QueueDevice (low, high), 0x0fc: ConfigGeneration
+//! - 0x100: config space (`capacity` in 512-byte sectors, 8 bytes)
+
+use std::backtrace::Backtrace;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::bus::{
+    BusError,
+    device::{DeviceAccess, GuestMemory},
+};
+
+const MAGIC: u32 = 0x7472_6976; // "virt"
+const VERSION: u32 = 2;
+const DEVICE_ID_BLOCK: u32 = 2;
+const VENDOR_ID: u32 = 0x554d_4552; // "REMU"
+
+const MAGIC_OFF: usize = 0x000;
+const VERSION_OFF: usize = 0x004;
+const DEVICE_ID_OFF: usize = 0x008;
+const VENDOR_ID_OFF: usize = 0x00c;
+const DEVICE_FEATURES_OFF: usize = 0x010;
+const DEVICE_FEATURES_SEL_OFF: usize = 0x014;
+const DRIVER_FEATURES_OFF: usize = 0x020;
+const DRIVER_FEATURES_SEL_OFF: usize = 0x024;
+const QUEUE_SEL_OFF: usize = 0x030;
+const QUEUE_NUM_MAX_OFF: usize = 0x034;
+const QUEUE_NUM_OFF: usize = 0x038;
+const QUEUE_READY_OFF: usize = 0x044;
+const QUEUE_NOTIFY_OFF: usize = 0x050;
+const INTERRUPT_STATUS_OFF: usize = 0x060;
+const INTERRUPT_ACK_OFF: usize = 0x064;
+const STATUS_OFF: usize = 0x070;
+const QUEUE_DESC_LOW_OFF: usize = 0x080;
+const QUEUE_DESC_HIGH_OFF: usize = 0x084;
+const QUEUE_DRIVER_LOW_OFF: usize = 0x090;
+const QUEUE_DRIVER_HIGH_OFF: usize = 0x094;
+const QUEUE_DEVICE_LOW_OFF: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH_OFF: usize = 0x0a4;
+const CONFIG_GENERATION_OFF: usize = 0x0fc;
+const CONFIG_OFF: usize = 0x100;
+
+const VIRTIO_BLK_F_RO: u64 = 1 << 5;
+const VIRTIO_BLK_F_FLUSH: u64 = 1 << 9;
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+/// Status bit: the device hit an error it cannot recover from until reset.
+const STATUS_NEEDS_RESET: u32 = 0x40;
+/// InterruptStatus bit: the used ring was updated.
+const INTERRUPT_USED_BUFFER: u32 = 1 << 0;
+
+const QUEUE_NUM_MAX: u32 = 256;
+
+const VIRTQ_DESC_F_NEXT: u16 = 1;
+const VIRTQ_DESC_F_WRITE: u16 = 2;
+/// Driver ring flag: do not interrupt when buffers are used.
+const VIRTQ_AVAIL_F_NO_INTERRUPT: u16 = 1;
+
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_T_FLUSH: u32 = 4;
+const VIRTIO_BLK_T_GET_ID: u32 = 8;
+
+const VIRTIO_BLK_S_OK: u8 = 0;
+const VIRTIO_BLK_S_IOERR: u8 = 1;
+const VIRTIO_BLK_S_UNSUPP: u8 = 2;
+
+const SECTOR_SIZE: usize = 512;
+/// `virtio_blk_outhdr`: type, reserved, sector.
+const REQUEST_HEADER_SIZE: usize = 16;
+const DEVICE_ID: &[u8] = b"remu-virtio-blk";
+
+/// How a `virtio_blk` uses its image. Parsed from `mode=` in a `--dev` spec: `cow` (default)
+/// or `ro`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageMode {
+    /// Guest writes land in memory; the file is left unchanged.
+    #[default]
+    CopyOnWrite,
+    ReadOnly,
+}
+
+impl FromStr for ImageMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "cow" => Ok(Self::CopyOnWrite),
+            "ro" => Ok(Self::ReadOnly),
+            _ => Err(format!("unknown image mode {s:?}; expected cow or ro")),
+        }
+    }
+}
+
+/// Where a `virtio_blk` gets its image from: `image=` and `mode=` in its `--dev` spec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskImage {
+    pub path: PathBuf,
+    pub mode: ImageMode,
+}
+
+/// The image file plus the sectors the guest has written.
+struct Disk {
+    file: Option<File>,
+    len: u64,
+    overlay: BTreeMap<u64, Box<[u8]>>,
+    mode: ImageMode,
+}
+
+impl Disk {
+    fn open(image: &DiskImage) -> Self {
+        let path = &image.path;
+        let file = File::open(path).and_then(|f| Ok((f.metadata()?.len(), f)));
+        let (len, file) = match file {
+            Ok((len, file)) => (len, Some(file)),
+            Err(e) => {
+                tracing::error!("virtio_blk image {}: {e}", path.display());
+                (0, None)
+            }
+        };
+        Self {
+            file,
+            len,
+            overlay: BTreeMap::new(),
+            mode: image.mode,
+        }
+    }
+
+    /// Size in sectors; a partial last sector reads as zero-padded.
+    fn capacity(&self) -> u64 {
+        self.len.div_ceil(SECTOR_SIZE as u64)
+    }
+
+    fn read_sector(&self, sector: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        if let Some(data) = self.overlay.get(&sector) {
+            buf.copy_from_slice(data);
+            return Ok(());
+        }
+        buf.fill(0);
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let offset = sector * SECTOR_SIZE as u64;
+        let n = (self.len - offset).min(SECTOR_SIZE as u64) as usize;
+        file.read_exact_at(&mut buf[..n], offset)
+    }
+
+    fn write_sector(&mut self, sector: u64, data: &[u8]) {
+        self.overlay.insert(sector, Box::from(data));
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    num: u32,
+    ready: bool,
+    desc: u64,
+    driver: u64,
+    device: u64,
+    /// Next driver ring entry to serve.
+    last_avail: u16,
+}
+
+/// One request's buffers: what the driver wrote, and where the device may write back.
+struct Request {
+    out: Vec<u8>,
+    /// `(address, length)` of the device-writable descriptors, in chain order.
+    writable: Vec<(u64, u32)>,
+}
+
+impl Request {
+    fn writable_len(&self) -> usize {
+        self.writable.iter().map(|&(_, len)| len as usize).sum()
+    }
+
+    /// Write `data` into the writable buffers starting `offset` bytes in.
+    fn write_back(
+        &self,
+        mem: &mut GuestMemory,
+        mut offset: usize,
+        mut data: &[u8],
+    ) -> Result<(), BusError> {
+        for &(addr, len) in &self.writable {
+            let len = len as usize;
+            if offset >= len {
+                offset -= len;
+                continue;
+            }
+            let n = (len - offset).min(data.len());
+            mem.write(addr as usize + offset, &data[..n])?;
+            data = &data[n..];
+            offset = 0;
+            if data.is_empty() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct VirtioBlk {
+    disk: Disk,
+    device_features_sel: u32,
+    driver_features_sel: u32,
+    driver_features: u64,
+    queue_sel: u32,
+    queue: Queue,
+    status: u32,
+    interrupt_status: u32,
+    /// QueueNotify was written: serve the queue on the next [`dma`](DeviceAccess::dma).
+    notified: bool,
+}
+
+impl VirtioBlk {
+    pub(crate) fn new(image: &DiskImage) -> Self {
+        Self {
+            disk: Disk::open(image),
+            device_features_sel: 0,
+            driver_features_sel: 0,
+            driver_features: 0,
+            queue_sel: 0,
+            queue: Queue::default(),
+            status: 0,
+            interrupt_status: 0,
+            notified: false,
+        }
+    }
+
+    fn device_features(&self) -> u64 {
+        let ro = match self.disk.mode {
+            ImageMode::ReadOnly => VIRTIO_BLK_F_RO,
+            ImageMode::CopyOnWrite => 0,
+        };
+        VIRTIO_F_VERSION_1 | VIRTIO_BLK_F_FLUSH | ro
+    }
+
+    fn config_read(&self, offset: usize) -> u32 {
+        let capacity = self.disk.capacity().to_le_bytes();
+        let mut word = [0u8; 4];
+        for (i, b) in word.iter_mut().enumerate() {
+            *b = capacity.get(offset + i).copied().unwrap_or(0);
+        }
+        u32::from_le_bytes(word)
+    }
+
+    /// Serve every request the driver has made available.
+    fn serve_queue(&mut self, mem: &mut GuestMemory) -> Result<(), BusError> {
+        let q = &self.queue;
+        let (num, desc, driver, device) = (q.num, q.desc, q.driver, q.device);
+        let avail_flags = mem.read_u16(driver as usize)?;
+        let avail_idx = mem.read_u16(driver as usize + 2)?;
+        let mut used = false;
+        while self.queue.last_avail != avail_idx {
+            let slot = self.queue.last_avail as u32 % num;
+            let head = mem.read_u16(driver as usize + 4 + 2 * slot as usize)?;
+            let request = read_chain(mem, desc, num, head)?;
+            let written = self.handle(mem, &request)?;
+
+            let used_idx = mem.read_u16(device as usize + 2)?;
+            let entry = device as usize + 4 + 8 * (used_idx as u32 % num) as usize;
+            mem.write_u32(entry, head as u32)?;
+            mem.write_u32(entry + 4, written as u32)?;
+            mem.write_u16(device as usize + 2, used_idx.wrapping_add(1))?;
+            self.queue.last_avail = self.queue.last_avail.wrapping_add(1);
+            used = true;
+        }
+        if used && avail_flags & VIRTQ_AVAIL_F_NO_INTERRUPT == 0 {
+            self.interrupt_status |= INTERRUPT_USED_BUFFER;
+        }
+        Ok(())
+    }
+
+    /// Carry out one request; returns the bytes written to its writable buffers.
+    fn handle(&mut self, mem: &mut GuestMemory, request: &Request) -> Result<usize, BusError> {
+        let writable = request.writable_len();
+        if request.out.len() < REQUEST_HEADER_SIZE || writable == 0 {
+            return Err(BusError::IoError(Backtrace::capture()));
+        }
+        let header = &request.out[..REQUEST_HEADER_SIZE];
+        let kind = u32::from_le_bytes(header[0..4].try_into().unwrap());
+        let sector = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let data_in = writable - 1; // the status byte comes last
+
+        let (status, written) = match kind {
+            VIRTIO_BLK_T_IN => {
+                let mut data = vec![0u8; data_in];
+                let ok = self.in_range(sector, data.len())
+                    && data
+                        .chunks_mut(SECTOR_SIZE)
+                        .zip(sector..)
+                        .all(|(chunk, s)| self.read_into(s, chunk));
+                if !ok {
+                    // Leave the guest's buffer alone rather than hand it a partial read.
+                    (VIRTIO_BLK_S_IOERR, 0)
+                } else {
+                    request.write_back(mem, 0, &data)?;
+                    (VIRTIO_BLK_S_OK, data_in)
+                }
+            }
+            VIRTIO_BLK_T_OUT => {
+                let data = &request.out[REQUEST_HEADER_SIZE..];
+                let ok = self.disk.mode == ImageMode::CopyOnWrite
+                    && data.len().is_multiple_of(SECTOR_SIZE)
+                    && self.in_range(sector, data.len());
+                if ok {
+                    for (chunk, s) in data.chunks(SECTOR_SIZE).zip(sector..) {
+                        self.disk.write_sector(s, chunk);
+                    }
+                }
+                (status(ok), 0)
+            }
+            VIRTIO_BLK_T_FLUSH => (VIRTIO_BLK_S_OK, 0),
+            VIRTIO_BLK_T_GET_ID => {
+                let n = DEVICE_ID.len().min(data_in);
+                request.write_back(mem, 0, &DEVICE_ID[..n])?;
+                (VIRTIO_BLK_S_OK, data_in)
+            }
+            _ => (VIRTIO_BLK_S_UNSUPP, 0),
+        };
+        request.write_back(mem, data_in, &[status])?;
+        Ok(written + 1)
+    }
+
+    fn in_range(&self, sector: u64, len: usize) -> bool {
+        sector
+            .checked_add(len.div_ceil(SECTOR_SIZE) as u64)
+            .is_some_and(|end| end <= self.disk.capacity())
+    }
+
+    /// Read sector `sector` into `chunk` (which may be shorter than a sector).
+    fn read_into(&self, sector: u64, chunk: &mut [u8]) -> bool {
+        let mut buf = [0u8; SECTOR_SIZE];
+        match self.disk.read_sector(sector, &mut buf) {
+            Ok(()) => {
+                chunk.copy_from_slice(&buf[..chunk.len()]);
+                true
+            }
+            Err(e) => {
+                tracing::error!("virtio_blk: reading sector {sector}: {e}");
+                false
+            }
+        }
+    }
+}
+
+fn status(ok: bool) -> u8 {
+    if ok {
+        VIRTIO_BLK_S_OK
+    } else {
+        VIRTIO_BLK_S_IOERR
+    }
+}
+
+/// Follow the descriptor chain starting at `head`.
+fn read_chain(mem: &mut GuestMemory, desc: u64, num: u32, head: u16) -> Result<Request, BusError> {
+    let mut request = Request {
+        out: Vec::new(),
+        writable: Vec::new(),
+    };
+    let mut index = head;
+    // A chain visits each descriptor at most once; more means the driver made a loop.
+    for _ in 0..num {
+        if index as u32 >= num {
+            break;
+        }
+        let entry = desc as usize + 16 * index as usize;
+        let addr = mem.read_u64(entry)?;
+        let len = mem.read_u32(entry + 8)?;
+        let flags = mem.read_u16(entry + 12)?;
+        let next = mem.read_u16(entry + 14)?;
+        if flags & VIRTQ_DESC_F_WRITE != 0 {
+            request.writable.push((addr, len));
+        } else {
+            let start = request.out.len();
+            request.out.resize(start + len as usize, 0);
+            mem.read(addr as usize, &mut request.out[start..])?;
+        }
+        if flags & VIRTQ_DESC_F_NEXT == 0 {
+            return Ok(request);
+        }
+        index = next;
+    }
+    Err(BusError::IoError(Backtrace::capture()))
+}
+
+impl DeviceAccess for VirtioBlk {
+    fn name(&self) -> &str {
+        "virtio_blk"
+    }
+
+    fn size(&self) -> usize {
+        0x1000
+    }
+
+    /// Transport registers and queue position as little-endian `u64`s, then the overlay as
+    /// a sector count followed by `(sector, data)` pairs.
+    fn save(&self, _now: u64) -> Vec<u8> {
+        let q = &self.queue;
+        let mut state: Vec<u8> = [
+            self.device_features_sel as u64,
+            self.driver_features_sel as u64,
+            self.driver_features,
+            self.queue_sel as u64,
+            q.num as u64,
+            q.ready as u64,
+            q.desc,
+            q.driver,
+            q.device,
+            q.last_avail as u64,
+            self.status as u64,
+            self.interrupt_status as u64,
+            self.notified as u64,
+            self.disk.overlay.len() as u64,
+        ]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+        for (sector, data) in &self.disk.overlay {
+            state.extend_from_slice(&sector.to_le_bytes());
+            state.extend_from_slice(data);
+        }
+        state
+    }
+
+    fn restore(&mut self, state: &[u8], _now: u64) {
+        let Some((regs, mut rest)) = state.split_first_chunk::<{ 14 * 8 }>() else {
+            return;
+        };
+        let mut words = regs
+            .chunks_exact(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()));
+        let mut next = || words.next().unwrap();
+        self.device_features_sel = next() as u32;
+        self.driver_features_sel = next() as u32;
+        self.driver_features = next();
+        self.queue_sel = next() as u32;
+        self.queue = Queue {
+            num: next() as u32,
+            ready: next() != 0,
+            desc: next(),
+            driver: next(),
+            device: next(),
+            last_avail: next() as u16,
+        };
+        self.status = next() as u32;
+        self.interrupt_status = next() as u32;
+        self.notified = next() != 0;
+        let sectors = next();
+        self.disk.overlay.clear();
+        for _ in 0..sectors {
+            let Some((sector, tail)) = rest.split_first_chunk::<8>() else {
+                return;
+            };
+            let Some((data, tail)) = tail.split_at_checked(SECTOR_SIZE) else {
+                return;
+            };
+            self.disk
+                .overlay
+                .insert(u64::from_le_bytes(*sector), Box::from(data));
+            rest = tail;
+        }
+    }
+
+    /// Also what writing 0 to Status does. The overlay is the disk, so it stays.
+    fn reset(&mut self) {
+        self.device_features_sel = 0;
+        self.driver_features_sel = 0;
+        self.driver_features = 0;
+        self.queue_sel = 0;
+        self.queue = Queue::default();
+        self.status = 0;
+        self.interrupt_status = 0;
+        self.notified = false;
+    }
+
+    fn irq_level(&mut self) -> bool {
+        self.interrupt_status != 0
+    }
+
+    fn dma(&mut self, mem: &mut GuestMemory) {
+        if !std::mem::take(&mut self.notified)
+            || !self.queue.ready
+            || self.queue.num == 0
+            || self.status & STATUS_NEEDS_RESET != 0
+        {
+            return;
+        }
+        if let Err(e) = self.serve_queue(mem) {
+            tracing::warn!("virtio_blk: bad request ({e}); device needs reset");
+            self.status |= STATUS_NEEDS_RESET;
+        }
+    }
+
+    fn read_8(&mut self, offset: usize) -> Result<u8, BusError> {
+        if offset < CONFIG_OFF {
+            return Err(BusError::UnsupportedAccessWidth(8, Backtrace::capture()));
+        }
+        Ok(self.config_read(offset - CONFIG_OFF) as u8)
+    }
+
+    fn read_16(&mut self, offset: usize) -> Result<u16, BusError> {
+        if offset < CONFIG_OFF {
+            return Err(BusError::UnsupportedAccessWidth(16, Backtrace::capture()));
+        }
+        Ok(self.config_read(offset - CONFIG_OFF) as u16)
+    }
+
+    fn read_32(&mut self, offset: usize) -> Result<u32, BusError> {
+        let sel = |sel: u32, v: u64| match sel {
+            0 => v as u32,
+            1 => (v >> 32) as u32,
+            _ => 0,
+        };
+        let q = &self.queue;
+        let selected = self.queue_sel == 0;
+        Ok(match offset {
+            MAGIC_OFF => MAGIC,
+            VERSION_OFF => VERSION,
+            DEVICE_ID_OFF => DEVICE_ID_BLOCK,
+            VENDOR_ID_OFF => VENDOR_ID,
+            DEVICE_FEATURES_OFF => sel(self.device_features_sel, self.device_features()),
+            QUEUE_NUM_MAX_OFF if selected => QUEUE_NUM_MAX,
+            QUEUE_READY_OFF if selected => q.ready as u32,
+            INTERRUPT_STATUS_OFF => self.interrupt_status,
+            STATUS_OFF => self.status,
+            QUEUE_DESC_LOW_OFF if selected => q.desc as u32,
+            QUEUE_DESC_HIGH_OFF if selected => (q.desc >> 32) as u32,
+            QUEUE_DRIVER_LOW_OFF if selected => q.driver as u32,
+            QUEUE_DRIVER_HIGH_OFF if selected => (q.driver >> 32) as u32,
+            QUEUE_DEVICE_LOW_OFF if selected => q.device as u32,
+            QUEUE_DEVICE_HIGH_OFF if selected => (q.device >> 32) as u32,
+            CONFIG_GENERATION_OFF => 0,
+            CONFIG_OFF.. => self.config_read(offset - CONFIG_OFF),
+            _ => 0,
+        })
+    }
+
+    fn write_32(&mut self, offset: usize, value: u32) -> Result<(), BusError> {
+        let set_low = |v: &mut u64| *v = (*v & !0xffff_ffff) | value as u64;
+        let set_high = |v: &mut u64| *v = (*v & 0xffff_ffff) | ((value as u64) << 32);
+        let selected = self.queue_sel == 0;
+        let q = &mut self.queue;
+        match offset {
+            DEVICE_FEATURES_SEL_OFF => self.device_features_sel = value,
+            DRIVER_FEATURES_OFF => match self.driver_features_sel {
+                0 => set_low(&mut self.driver_features),
+                1 => set_high(&mut self.driver_features),
+                _ => {}
+            },
+            DRIVER_FEATURES_SEL_OFF => self.driver_features_sel = value,
+            QUEUE_SEL_OFF => self.queue_sel = value,
+            QUEUE_NUM_OFF if selected => q.num = value.min(QUEUE_NUM_MAX),
+            QUEUE_READY_OFF if selected => q.ready = value & 1 != 0,
+            QUEUE_NOTIFY_OFF => self.notified = value == 0,
+            INTERRUPT_ACK_OFF => self.interrupt_status &= !value,
+            STATUS_OFF if value == 0 => self.reset(),
+            STATUS_OFF => self.status = value,
+            QUEUE_DESC_LOW_OFF if selected => set_low(&mut q.desc),
+            QUEUE_DESC_HIGH_OFF if selected => set_high(&mut q.desc),
+            QUEUE_DRIVER_LOW_OFF if selected => set_low(&mut q.driver),
+            QUEUE_DRIVER_HIGH_OFF if selected => set_high(&mut q.driver),
+            QUEUE_DEVICE_LOW_OFF if selected => set_low(&mut q.device),
+            QUEUE_DEVICE_HIGH_OFF if selected => set_high(&mut q.device),
+            _ => {} // read-only registers, other queues; config space is read-only
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{MemAttrs, MemRegionSpec, Memory, MemoryEntry};
+
+    const DESC: u64 = 0x8000_0000;
+    const DRIVER: u64 = 0x8000_1000;
+    const DEVICE: u64 = 0x8000_2000;
+    const HEADER: u64 = 0x8000_3000;
+    const DATA: u64 = 0x8000_4000;
+    const STATUS: u64 = 0x8000_5000;
+    const NUM: u32 = 8;
+
+    /// An image file in the temp directory, removed when dropped.
+    struct TempImage(PathBuf);
+
+    impl Drop for TempImage {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// A device with its queue set up in guest RAM, over a 4-sector image whose sector `s`
+    /// is filled with `s + 1`.
+    struct Rig {
+        blk: VirtioBlk,
+        memory: Memory,
+        image: TempImage,
+    }
+
+    impl Rig {
+        fn new(name: &str, mode: ImageMode) -> Self {
+            let name = format!("remu-{}-{name}.img", std::process::id());
+            let image = TempImage(std::env::temp_dir().join(name));
+            let contents: Vec<u8> = (1..=4).flat_map(|s| [s; SECTOR_SIZE]).collect();
+            std::fs::write(&image.0, contents).unwrap();
+            let ram = MemRegionSpec {
+                name: "ram".to_string(),
+                region: 0x8000_0000..0x8001_0000,
+                attrs: MemAttrs::RWX,
+                image: None,
+            };
+            let memory = Memory::new(Box::new([MemoryEntry::new(ram).unwrap()]));
+            let mut blk = VirtioBlk::new(&DiskImage {
+                path: image.0.clone(),
+                mode,
+            });
+            for (offset, value) in [
+                (QUEUE_NUM_OFF, NUM),
+                (QUEUE_DESC_LOW_OFF, DESC as u32),
+                (QUEUE_DRIVER_LOW_OFF, DRIVER as u32),
+                (QUEUE_DEVICE_LOW_OFF, DEVICE as u32),
+                (QUEUE_READY_OFF, 1),
+                (STATUS_OFF, 0xf),
+            ] {
+                blk.write_32(offset, value).unwrap();
+            }
+            Self { blk, memory, image }
+        }
+
+        /// Make the chain `(addr, len, flags)` available, starting at descriptor 0, and notify.
+        /// The last descriptor's `next` points back at the first.
+        fn submit(&mut self, chain: &[(u64, u32, u16)], avail_flags: u16) {
+            for (i, &(addr, len, flags)) in chain.iter().enumerate() {
+                let entry = DESC as usize + 16 * i;
+                let next = if i + 1 < chain.len() {
+                    VIRTQ_DESC_F_NEXT
+                } else {
+                    0
+                };
+                self.memory.write_bytes(entry, &addr.to_le_bytes()).unwrap();
+                self.memory
+                    .write_bytes(entry + 8, &len.to_le_bytes())
+                    .unwrap();
+                self.memory
+                    .write_bytes(entry + 12, &(flags | next).to_le_bytes())
+                    .unwrap();
+                self.memory
+                    .write_bytes(entry + 14, &(((i + 1) % chain.len()) as u16).to_le_bytes())
+                    .unwrap();
+            }
+            let idx = self.read_u16(DRIVER + 2);
+            let slot = DRIVER as usize + 4 + 2 * (idx as u32 % NUM) as usize;
+            self.memory.write_bytes(slot, &0u16.to_le_bytes()).unwrap();
+            self.memory
+                .write_bytes(DRIVER as usize, &avail_flags.to_le_bytes())
+                .unwrap();
+            self.memory
+                .write_bytes(DRIVER as usize + 2, &idx.wrapping_add(1).to_le_bytes())
+                .unwrap();
+            self.blk.write_32(QUEUE_NOTIFY_OFF, 0).unwrap();
+            self.blk.dma(&mut GuestMemory::new(&mut self.memory));
+        }
+
+        /// One request of `kind` on `sector` with a 512-byte data buffer; returns its status.
+        fn request(&mut self, kind: u32, sector: u64) -> u8 {
+            let mut header = [0u8; REQUEST_HEADER_SIZE];
+            header[0..4].copy_from_slice(&kind.to_le_bytes());
+            header[8..16].copy_from_slice(&sector.to_le_bytes());
+            self.memory.write_bytes(HEADER as usize, &header).unwrap();
+            self.memory.write_bytes(STATUS as usize, &[0xff]).unwrap();
+            let data_flags = if kind == VIRTIO_BLK_T_IN {
+                VIRTQ_DESC_F_WRITE
+            } else {
+                0
+            };
+            self.submit(
+                &[
+                    (HEADER, REQUEST_HEADER_SIZE as u32, 0),
+                    (DATA, SECTOR_SIZE as u32, data_flags),
+                    (STATUS, 1, VIRTQ_DESC_F_WRITE),
+                ],
+                0,
+            );
+            self.read_bytes(STATUS)[0]
+        }
+
+        fn read_u16(&mut self, addr: u64) -> u16 {
+            let mut b = [0; 2];
+            self.memory.read_bytes(addr as usize, &mut b).unwrap();
+            u16::from_le_bytes(b)
+        }
+
+        fn read_bytes(&mut self, addr: u64) -> [u8; SECTOR_SIZE] {
+            let mut buf = [0; SECTOR_SIZE];
+            self.memory.read_bytes(addr as usize, &mut buf).unwrap();
+            buf
+        }
+
+        /// Length the device reported in used ring entry `i`.
+        fn used_len(&mut self, i: u64) -> u32 {
+            let mut b = [0; 4];
+            let entry = DEVICE as usize + 4 + 8 * i as usize;
+            self.memory.read_bytes(entry + 4, &mut b).unwrap();
+            u32::from_le_bytes(b)
+        }
+    }
+
+    #[test]
+    fn reads_sectors() {
+        let mut rig = Rig::new("read", ImageMode::CopyOnWrite);
+        assert_eq!(rig.blk.read_32(CONFIG_OFF).unwrap(), 4);
+        assert_eq!(rig.request(VIRTIO_BLK_T_IN, 2), VIRTIO_BLK_S_OK);
+        assert_eq!(rig.read_bytes(DATA), [3; SECTOR_SIZE]);
+        assert_eq!(rig.read_u16(DEVICE + 2), 1);
+        assert_eq!(rig.used_len(0), SECTOR_SIZE as u32 + 1);
+        assert!(rig.blk.irq_level());
+        rig.blk
+            .write_32(INTERRUPT_ACK_OFF, INTERRUPT_USED_BUFFER)
+            .unwrap();
+        assert!(!rig.blk.irq_level());
+
+        assert_eq!(rig.request(VIRTIO_BLK_T_IN, 4), VIRTIO_BLK_S_IOERR);
+    }
+
+    #[test]
+    fn writes_go_to_the_overlay() {
+        let mut rig = Rig::new("cow", ImageMode::CopyOnWrite);
+        rig.memory
+            .write_bytes(DATA as usize, &[0xaa; SECTOR_SIZE])
+            .unwrap();
+        assert_eq!(rig.request(VIRTIO_BLK_T_OUT, 1), VIRTIO_BLK_S_OK);
+        assert_eq!(rig.used_len(0), 1);
+
+        rig.memory
+            .write_bytes(DATA as usize, &[0; SECTOR_SIZE])
+            .unwrap();
+        assert_eq!(rig.request(VIRTIO_BLK_T_IN, 1), VIRTIO_BLK_S_OK);
+        assert_eq!(rig.read_bytes(DATA), [0xaa; SECTOR_SIZE]);
+        let file = std::fs::read(&rig.image.0).unwrap();
+        assert_eq!(file[SECTOR_SIZE..2 * SECTOR_SIZE], [2; SECTOR_SIZE]);
+    }
+
+    #[test]
+    fn read_only_rejects_writes() {
+        let mut rig = Rig::new("ro", ImageMode::ReadOnly);
+        assert_ne!(
+            rig.blk.read_32(DEVICE_FEATURES_OFF).unwrap() as u64 & VIRTIO_BLK_F_RO,
+            0
+        );
+        rig.memory
+            .write_bytes(DATA as usize, &[0xaa; SECTOR_SIZE])
+            .unwrap();
+        assert_eq!(rig.request(VIRTIO_BLK_T_OUT, 1), VIRTIO_BLK_S_IOERR);
+        assert_eq!(rig.request(VIRTIO_BLK_T_IN, 1), VIRTIO_BLK_S_OK);
+        assert_eq!(rig.read_bytes(DATA), [2; SECTOR_SIZE]);
+    }
+
+    #[test]
+    fn failed_read_leaves_the_buffer_alone() {
+        let mut rig = Rig::new("short", ImageMode::CopyOnWrite);
+        // The image shrinks under the device: its capacity still says 4 sectors.
+        File::options()
+            .write(true)
+            .open(&rig.image.0)
+            .unwrap()
+            .set_len(SECTOR_SIZE as u64)
+            .unwrap();
+        rig.memory
+            .write_bytes(DATA as usize, &[0x55; SECTOR_SIZE])
+            .unwrap();
+        assert_eq!(rig.request(VIRTIO_BLK_T_IN, 2), VIRTIO_BLK_S_IOERR);
+        assert_eq!(rig.read_bytes(DATA), [0x55; SECTOR_SIZE]);
+        assert_eq!(rig.used_len(0), 1);
+    }
+
+    #[test]
+    fn no_interrupt_flag_suppresses_the_irq() {
+        let mut rig = Rig::new("noirq", ImageMode::CopyOnWrite);
+        rig.memory
+            .write_bytes(HEADER as usize, &[0; REQUEST_HEADER_SIZE])
+            .unwrap();
+        rig.submit(
+            &[
+                (HEADER, REQUEST_HEADER_SIZE as u32, 0),
+                (DATA, SECTOR_SIZE as u32, VIRTQ_DESC_F_WRITE),
+                (STATUS, 1, VIRTQ_DESC_F_WRITE),
+            ],
+            VIRTQ_AVAIL_F_NO_INTERRUPT,
+        );
+        assert_eq!(rig.read_u16(DEVICE + 2), 1);
+        assert_eq!(rig.read_bytes(STATUS)[0], VIRTIO_BLK_S_OK);
+        assert!(!rig.blk.irq_level());
+    }
+
+    #[test]
+    fn descriptor_loop_needs_reset() {
+        let mut rig = Rig::new("loop", ImageMode::CopyOnWrite);
+        let header = (HEADER, REQUEST_HEADER_SIZE as u32, VIRTQ_DESC_F_NEXT);
+        rig.submit(&[header, header], 0);
+
+        assert_ne!(rig.blk.read_32(STATUS_OFF).unwrap() & STATUS_NEEDS_RESET, 0);
+        assert_eq!(rig.read_u16(DEVICE + 2), 0);
+        assert!(!rig.blk.irq_level());
+    }
+}
@@ -0,0 +1,244 @@
+//! Watchdog timer: once enabled, it must be kicked within its timeout or it fires.
+//!
+//! Ticks follow `clock=` (default: `--mtime`), so `clock=instret` counts retired instructions.
+//! `timeout=TICKS` starts it enabled at power-on, which bounds a run whose guest never touches
+//! it. On expiry it does `action=`:
+//! - `reset` (default): reboot the guest, as a syscon reboot does
+//! - `irq`: raise its interrupt line (`irq=`) until the status is cleared
+//! - `stop`: end the run with a watchdog timeout (`--batch` exits with status 122)
+//!
+//! The countdown stops on expiry and a kick restarts it. Expiry is noticed when the bus polls
+//! devices (after MMIO accesses, otherwise every 1024 instructions), so it may come that late.
+//!
+//! Registers (32-bit):
+//! - 0x00: control; bit 0 enables, and enabling restarts the countdown
+//! - 0x04: timeout in ticks
+//! - 0x08: kick (write any value)
+//! - 0x0c: ticks left (read), 0 once expired or disabled
+//! - 0x10: status; bit 0 is set on expiry, write 1 to clear
+
+use std::str::FromStr;
+use std::time::Instant;
+
+use crate::bus::{
+    BusError,
+    device::{DeviceAccess, GuestMemory, MtimeSource},
+};
+
+const CTRL_OFF: usize = 0x00;
+const TIMEOUT_OFF: usize = 0x04;
+const KICK_OFF: usize = 0x08;
+const COUNT_OFF: usize = 0x0c;
+const STATUS_OFF: usize = 0x10;
+
+/// What a `watchdog` does when it runs out: `action=reset|irq|stop`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WatchdogAction {
+    #[default]
+    Reset,
+    Irq,
+    Stop,
+}
+
+impl FromStr for WatchdogAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "reset" => Ok(Self::Reset),
+            "irq" => Ok(Self::Irq),
+            "stop" => Ok(Self::Stop),
+            _ => Err(format!(
+                "unknown watchdog action {s:?}; expected reset, irq or stop"
+            )),
+        }
+    }
+}
+
+/// A `watchdog` device's `--dev` parameters: `clock=host|instret[:N]`, `timeout=TICKS`,
+/// `action=reset|irq|stop`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WatchdogConfig {
+    pub clock: Option<MtimeSource>,
+    pub timeout: Option<u32>,
+    pub action: WatchdogAction,
+}
+
+pub struct Watchdog {
+    config: WatchdogConfig,
+    clock: MtimeSource,
+    /// Instructions retired as of the last [`tick`](DeviceAccess::tick).
+    insts: u64,
+    base_instant: Instant,
+    enabled: bool,
+    timeout: u32,
+    /// Tick at which the watchdog fires, while it counts down.
+    deadline: Option<u64>,
+    expired: bool,
+}
+
+impl Watchdog {
+    pub(crate) fn new(config: WatchdogConfig, default_clock: MtimeSource) -> Self {
+        let mut watchdog = Self {
+            config,
+            clock: config.clock.unwrap_or(default_clock),
+            insts: 0,
+            base_instant: Instant::now(),
+            enabled: false,
+            timeout: 0,
+            deadline: None,
+            expired: false,
+        };
+        watchdog.reset();
+        watchdog
+    }
+
+    /// Ticks on the underlying clock once `insts` instructions have retired.
+    fn ticks_at(&self, insts: u64) -> u64 {
+        match self.clock {
+            MtimeSource::Host => (self.base_instant.elapsed().as_nanos() / 100) as u64,
+            MtimeSource::Instret { per_tick } => insts / per_tick,
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.ticks_at(self.insts)
+    }
+
+    fn kick(&mut self) {
+        self.deadline = self
+            .enabled
+            .then(|| self.now().saturating_add(self.timeout as u64));
+    }
+
+    fn ticks_left(&self, now: u64) -> u64 {
+        self.deadline
+            .map_or(0, |deadline| deadline.saturating_sub(now))
+    }
+}
+
+impl DeviceAccess for Watchdog {
+    fn name(&self) -> &str {
+        "watchdog"
+    }
+
+    fn size(&self) -> usize {
+        0x14
+    }
+
+    /// Ticks left (little-endian `u64`, `u64::MAX` when not counting), timeout (`u32`), then
+    /// enabled and expired (one byte each).
+    fn save(&self, now: u64) -> Vec<u8> {
+        let now = self.ticks_at(now);
+        let left = self.deadline.map_or(u64::MAX, |_| self.ticks_left(now));
+        let mut state = Vec::with_capacity(14);
+        state.extend_from_slice(&left.to_le_bytes());
+        state.extend_from_slice(&self.timeout.to_le_bytes());
+        state.extend([self.enabled, self.expired].map(u8::from));
+        state
+    }
+
+    fn restore(&mut self, state: &[u8], now: u64) {
+        let Some((left, rest)) = state.split_first_chunk::<8>() else {
+            return;
+        };
+        let Some((timeout, rest)) = rest.split_first_chunk::<4>() else {
+            return;
+        };
+        let [enabled, expired] = *rest else {
+            return;
+        };
+        let left = u64::from_le_bytes(*left);
+        self.insts = now;
+        self.deadline = (left != u64::MAX).then(|| self.now().saturating_add(left));
+        self.timeout = u32::from_le_bytes(*timeout);
+        self.enabled = enabled != 0;
+        self.expired = expired != 0;
+    }
+
+    /// Back to the `timeout=` given at power-on: counting from now, or disabled without it.
+    fn reset(&mut self) {
+        self.enabled = self.config.timeout.is_some();
+        self.timeout = self.config.timeout.unwrap_or(0);
+        self.expired = false;
+        self.kick();
+    }
+
+    fn tick(&mut self, now: u64, _mem: &mut GuestMemory) -> Result<(), BusError> {
+        self.insts = now;
+        match self.deadline {
+            Some(deadline) if self.now() >= deadline => {}
+            _ => return Ok(()),
+        }
+        self.deadline = None;
+        self.expired = true;
+        match self.config.action {
+            WatchdogAction::Reset => {
+                tracing::warn!("watchdog: timed out, rebooting");
+                Err(BusError::Reboot)
+            }
+            WatchdogAction::Irq => Ok(()),
+            WatchdogAction::Stop => Err(BusError::WatchdogTimeout),
+        }
+    }
+
+    fn irq_level(&mut self) -> bool {
+        self.expired && self.config.action == WatchdogAction::Irq
+    }
+
+    fn read_32(&mut self, offset: usize) -> Result<u32, BusError> {
+        Ok(match offset {
+            CTRL_OFF => self.enabled as u32,
+            TIMEOUT_OFF => self.timeout,
+            COUNT_OFF => self.ticks_left(self.now()).min(u32::MAX as u64) as u32,
+            STATUS_OFF => self.expired as u32,
+            _ => 0,
+        })
+    }
+
+    fn write_32(&mut self, offset: usize, value: u32) -> Result<(), BusError> {
+        match offset {
+            CTRL_OFF => {
+                let enable = value & 1 != 0;
+                if enable != self.enabled {
+                    self.enabled = enable;
+                    self.kick();
+                }
+            }
+            TIMEOUT_OFF => self.timeout = value,
+            KICK_OFF => self.kick(),
+            STATUS_OFF if value & 1 != 0 => self.expired = false,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Memory;
+
+    #[test]
+    fn kicks_hold_off_expiry() {
+        let config = WatchdogConfig {
+            clock: None,
+            timeout: Some(100),
+            action: WatchdogAction::Stop,
+        };
+        let mut wdt = Watchdog::new(config, MtimeSource::Instret { per_tick: 1 });
+        let mut memory = Memory::new(Box::new([]));
+        let mut mem = GuestMemory::new(&mut memory);
+        assert!(wdt.tick(99, &mut mem).is_ok());
+        wdt.write_32(KICK_OFF, 0).unwrap();
+        assert!(wdt.tick(198, &mut mem).is_ok());
+        assert_eq!(wdt.read_32(COUNT_OFF).unwrap(), 1);
+        assert!(matches!(
+            wdt.tick(199, &mut mem),
+            Err(BusError::WatchdogTimeout)
+        ));
+        assert_eq!(wdt.read_32(STATUS_OFF).unwrap(), 1);
+        // Stopped counting: no second expiry until kicked.
+        assert!(wdt.tick(199, &mut mem).is_ok());
+    }
+}
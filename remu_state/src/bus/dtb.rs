@@ -0,0 +1,381 @@
+//! Device tree for the guest: a flattened device tree (DTB) describing the bus, placed in guest
+//! memory at `--dtb ADDR`. The hart then starts with `a0` = hart ID (0) and `a1` = `ADDR`, as
+//! firmware and kernels expect.
+//!
+//! It holds the writable `--mem` regions as memory (ROM is left out), the hart with its ISA
+//! string and the CLINT's 10 MHz timebase, and the devices with a standard binding: CLINT, PLIC,
+//! 16550 UARTs, virtio-mmio disks, goldfish RTCs and the SiFive test finisher (with poweroff and
+//! reboot nodes). Device interrupts go to the first PLIC. Other devices are left out.
+
+use std::collections::BTreeMap;
+
+use crate::bus::{
+    BusOption, MemoryEntry,
+    device::{
+        CLINT_SIZE, DeviceConfig, DeviceKind, FINISHER_PASS, FINISHER_RESET, MTIME_FREQ, PLIC_SIZE,
+        PLIC_SOURCES,
+    },
+};
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_VERSION: u32 = 17;
+const FDT_LAST_COMP_VERSION: u32 = 16;
+const FDT_HEADER_LEN: usize = 40;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_END: u32 = 9;
+
+const CPU_INTC_PHANDLE: u32 = 1;
+const PLIC_PHANDLE: u32 = 2;
+/// Phandles from here on go to test finishers, for their poweroff and reboot nodes.
+const FIRST_FREE_PHANDLE: u32 = 3;
+
+/// Machine external, timer and software interrupt causes, as wired to the hart's controller.
+const IRQ_M_EXT: u32 = 11;
+const IRQ_M_TIMER: u32 = 7;
+const IRQ_M_SOFT: u32 = 3;
+
+/// Input clock a 16550 driver divides down from; remu's UART ignores the divisor.
+const UART_CLOCK: u32 = 3_686_400;
+
+/// Builds the structure and strings blocks of a DTB.
+#[derive(Default)]
+struct FdtWriter {
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+    string_offsets: BTreeMap<String, u32>,
+}
+
+impl FdtWriter {
+    fn token(&mut self, token: u32) {
+        self.structure.extend_from_slice(&token.to_be_bytes());
+    }
+
+    fn pad(&mut self) {
+        let len = self.structure.len().next_multiple_of(4);
+        self.structure.resize(len, 0);
+    }
+
+    fn begin_node(&mut self, name: &str) {
+        self.token(FDT_BEGIN_NODE);
+        self.structure.extend_from_slice(name.as_bytes());
+        self.structure.push(0);
+        self.pad();
+    }
+
+    fn end_node(&mut self) {
+        self.token(FDT_END_NODE);
+    }
+
+    fn prop(&mut self, name: &str, value: &[u8]) {
+        let offset = match self.string_offsets.get(name) {
+            Some(&offset) => offset,
+            None => {
+                let offset = self.strings.len() as u32;
+                self.strings.extend_from_slice(name.as_bytes());
+                self.strings.push(0);
+                self.string_offsets.insert(name.to_string(), offset);
+                offset
+            }
+        };
+        self.token(FDT_PROP);
+        self.token(value.len() as u32);
+        self.token(offset);
+        self.structure.extend_from_slice(value);
+        self.pad();
+    }
+
+    fn prop_empty(&mut self, name: &str) {
+        self.prop(name, &[]);
+    }
+
+    fn prop_cells(&mut self, name: &str, cells: &[u32]) {
+        let value: Vec<u8> = cells.iter().flat_map(|c| c.to_be_bytes()).collect();
+        self.prop(name, &value);
+    }
+
+    fn prop_u32(&mut self, name: &str, value: u32) {
+        self.prop_cells(name, &[value]);
+    }
+
+    fn prop_strs(&mut self, name: &str, values: &[&str]) {
+        let value: Vec<u8> = values.iter().flat_map(|s| s.bytes().chain([0])).collect();
+        self.prop(name, &value);
+    }
+
+    fn prop_str(&mut self, name: &str, value: &str) {
+        self.prop_strs(name, &[value]);
+    }
+
+    /// `reg` with two address and two size cells.
+    fn prop_reg(&mut self, start: usize, size: usize) {
+        let (start, size) = (start as u64, size as u64);
+        self.prop_cells(
+            "reg",
+            &[
+                (start >> 32) as u32,
+                start as u32,
+                (size >> 32) as u32,
+                size as u32,
+            ],
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.token(FDT_END);
+        // Header, then an empty memory reservation map (one zero entry).
+        let off_rsvmap = FDT_HEADER_LEN;
+        let off_struct = off_rsvmap + 16;
+        let off_strings = off_struct + self.structure.len();
+        let total = off_strings + self.strings.len();
+        let header = [
+            FDT_MAGIC,
+            total as u32,
+            off_struct as u32,
+            off_strings as u32,
+            off_rsvmap as u32,
+            FDT_VERSION,
+            FDT_LAST_COMP_VERSION,
+            0, // boot_cpuid_phys
+            self.strings.len() as u32,
+            self.structure.len() as u32,
+        ];
+        let mut blob: Vec<u8> = header.iter().flat_map(|w| w.to_be_bytes()).collect();
+        blob.resize(off_struct, 0);
+        blob.extend_from_slice(&self.structure);
+        blob.extend_from_slice(&self.strings);
+        blob
+    }
+}
+
+/// The `riscv,isa` string for remu's ISA string: the base letters, then the always-present
+/// Zicsr and Zifencei, then the ISA's own extensions (non-standard ones as `x` extensions).
+pub(crate) fn dt_isa_string(isa: &str) -> String {
+    let isa = isa.to_ascii_lowercase().replace("riscv", "rv");
+    let (base, extensions) = isa.split_once('_').unwrap_or((&isa, ""));
+    let mut dt = format!("{base}_zicsr_zifencei");
+    for ext in extensions.split('_').filter(|ext| !ext.is_empty()) {
+        dt.push('_');
+        if !ext.starts_with(['z', 's', 'x']) {
+            dt.push('x');
+        }
+        dt.push_str(ext);
+    }
+    dt
+}
+
+fn write_cpus(fdt: &mut FdtWriter, isa: &str) {
+    let isa = dt_isa_string(isa);
+    let (base, multi) = isa.split_once('_').unwrap_or((&isa, ""));
+    let letters = base.trim_start_matches("rv32").trim_start_matches("rv64");
+    let extensions: Vec<&str> = letters
+        .char_indices()
+        .map(|(i, _)| &letters[i..i + 1])
+        .chain(multi.split('_'))
+        .collect();
+    let xlen = &base[..4];
+
+    fdt.begin_node("cpus");
+    fdt.prop_u32("#address-cells", 1);
+    fdt.prop_u32("#size-cells", 0);
+    fdt.prop_u32("timebase-frequency", MTIME_FREQ);
+    fdt.begin_node("cpu@0");
+    fdt.prop_str("device_type", "cpu");
+    fdt.prop_u32("reg", 0);
+    fdt.prop_str("status", "okay");
+    fdt.prop_str("compatible", "riscv");
+    fdt.prop_str("riscv,isa", &isa);
+    fdt.prop_str("riscv,isa-base", &format!("{xlen}i"));
+    fdt.prop_strs("riscv,isa-extensions", &extensions);
+    fdt.begin_node("interrupt-controller");
+    fdt.prop_u32("#interrupt-cells", 1);
+    fdt.prop_empty("interrupt-controller");
+    fdt.prop_str("compatible", "riscv,cpu-intc");
+    fdt.prop_u32("phandle", CPU_INTC_PHANDLE);
+    fdt.end_node();
+    fdt.end_node();
+    fdt.end_node();
+}
+
+/// The node of one device, if it has a binding; returns its path under `/soc`.
+fn write_device(
+    fdt: &mut FdtWriter,
+    config: &DeviceConfig,
+    has_plic: bool,
+    phandle: &mut u32,
+    finishers: &mut Vec<u32>,
+) -> Option<String> {
+    let (name, compatible, size): (&str, &[&str], usize) = match config.kind {
+        DeviceKind::Clint => ("clint", &["sifive,clint0", "riscv,clint0"], CLINT_SIZE),
+        DeviceKind::Plic => ("plic", &["sifive,plic-1.0.0", "riscv,plic0"], PLIC_SIZE),
+        DeviceKind::Uart16550 => ("serial", &["ns16550a"], 8),
+        DeviceKind::VirtioBlk => ("virtio_mmio", &["virtio,mmio"], 0x1000),
+        DeviceKind::GoldfishRtc => ("rtc", &["google,goldfish-rtc"], 0x20),
+        DeviceKind::SifiveTestFinisher => ("test", &["sifive,test1", "sifive,test0", "syscon"], 4),
+        _ => return None,
+    };
+    let node = format!("{name}@{:x}", config.start);
+    fdt.begin_node(&node);
+    fdt.prop_strs("compatible", compatible);
+    fdt.prop_reg(config.start, size);
+    match config.kind {
+        DeviceKind::Clint => fdt.prop_cells(
+            "interrupts-extended",
+            &[CPU_INTC_PHANDLE, IRQ_M_SOFT, CPU_INTC_PHANDLE, IRQ_M_TIMER],
+        ),
+        DeviceKind::Plic => {
+            fdt.prop_u32("#address-cells", 0);
+            fdt.prop_u32("#interrupt-cells", 1);
+            fdt.prop_empty("interrupt-controller");
+            fdt.prop_cells("interrupts-extended", &[CPU_INTC_PHANDLE, IRQ_M_EXT]);
+            fdt.prop_u32("riscv,ndev", PLIC_SOURCES - 1);
+            fdt.prop_u32("phandle", PLIC_PHANDLE);
+        }
+        DeviceKind::Uart16550 => fdt.prop_u32("clock-frequency", UART_CLOCK),
+        DeviceKind::SifiveTestFinisher => {
+            fdt.prop_u32("phandle", *phandle);
+            finishers.push(*phandle);
+            *phandle += 1;
+        }
+        _ => {}
+    }
+    if let Some(irq) = config.irq
+        && has_plic
+    {
+        fdt.prop_u32("interrupt-parent", PLIC_PHANDLE);
+        fdt.prop_u32("interrupts", irq);
+    }
+    fdt.end_node();
+    Some(format!("/soc/{node}"))
+}
+
+/// The DTB describing `opt`'s bus for a hart of ISA `isa` (an [`RvIsa::ISA_STR`]).
+///
+/// [`RvIsa::ISA_STR`]: remu_isa::isa::RvIsa::ISA_STR
+pub fn device_tree(opt: &BusOption, isa: &str) -> Vec<u8> {
+    let mut fdt = FdtWriter::default();
+    fdt.begin_node("");
+    fdt.prop_u32("#address-cells", 2);
+    fdt.prop_u32("#size-cells", 2);
+    fdt.prop_str("compatible", "remu");
+    fdt.prop_str("model", "remu");
+
+    for region in opt.mem.iter().filter(|region| region.attrs.write) {
+        fdt.begin_node(&format!("memory@{:x}", region.base()));
+        fdt.prop_str("device_type", "memory");
+        fdt.prop_reg(region.base(), region.size());
+        fdt.end_node();
+    }
+
+    write_cpus(&mut fdt, isa);
+
+    let has_plic = opt
+        .devices
+        .iter()
+        .any(|config| config.kind == DeviceKind::Plic);
+    let mut phandle = FIRST_FREE_PHANDLE;
+    let mut finishers = Vec::new();
+    let mut stdout = None;
+    fdt.begin_node("soc");
+    fdt.prop_u32("#address-cells", 2);
+    fdt.prop_u32("#size-cells", 2);
+    fdt.prop_str("compatible", "simple-bus");
+    fdt.prop_empty("ranges");
+    for config in &opt.devices {
+        let path = write_device(&mut fdt, config, has_plic, &mut phandle, &mut finishers);
+        if config.kind == DeviceKind::Uart16550 && stdout.is_none() {
+            stdout = path;
+        }
+    }
+    fdt.end_node();
+
+    if let Some(&finisher) = finishers.first() {
+        for (name, value) in [("poweroff", FINISHER_PASS), ("reboot", FINISHER_RESET)] {
+            fdt.begin_node(name);
+            fdt.prop_str("compatible", &format!("syscon-{name}"));
+            fdt.prop_u32("regmap", finisher);
+            fdt.prop_u32("offset", 0);
+            fdt.prop_u32("value", value);
+            fdt.end_node();
+        }
+    }
+
+    fdt.begin_node("chosen");
+    if let Some(stdout) = &stdout {
+        fdt.prop_str("stdout-path", stdout);
+    }
+    fdt.end_node();
+
+    fdt.end_node();
+    fdt.finish()
+}
+
+/// Place the device tree in memory at `--dtb`, if given. Like the ELF, it can only go into
+/// RAM; it is written after the ELF, so it wins where they overlap.
+pub fn try_load_dtb_into_memory(
+    memory: &mut [MemoryEntry],
+    opt: &BusOption,
+    isa: &str,
+    tracer: &remu_types::TracerDyn,
+) {
+    let Some(addr) = opt.dtb else {
+        return;
+    };
+    let dtb = device_tree(opt, isa);
+    let range = addr..addr + dtb.len();
+    match memory.iter_mut().find(|m| m.contains(range.clone())) {
+        Some(m) => {
+            m.write_bytes(addr, &dtb);
+            tracing::info!(
+                "Placed device tree ({} bytes) in memory region '{}' at 0x{:08x}",
+                dtb.len(),
+                m.name,
+                addr
+            );
+        }
+        None => tracer.borrow().print(&format!(
+            "Device tree ({} bytes) at 0x{addr:08x} does not fit in any memory region",
+            dtb.len()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isa_strings_name_standard_and_custom_extensions() {
+        assert_eq!(dt_isa_string("rv32i"), "rv32i_zicsr_zifencei");
+        assert_eq!(
+            dt_isa_string("rv32im_zve32x_zvl128b"),
+            "rv32im_zicsr_zifencei_zve32x_zvl128b"
+        );
+        assert_eq!(
+            dt_isa_string("riscv32im_wjCus0"),
+            "rv32im_zicsr_zifencei_xwjcus0"
+        );
+    }
+
+    #[test]
+    fn blob_is_well_formed() {
+        let mut fdt = FdtWriter::default();
+        fdt.begin_node("");
+        fdt.prop_u32("#size-cells", 2);
+        fdt.begin_node("cpus");
+        fdt.prop_u32("#size-cells", 0);
+        fdt.end_node();
+        fdt.end_node();
+        let blob = fdt.finish();
+        let word = |i: usize| u32::from_be_bytes(blob[i..i + 4].try_into().unwrap());
+        assert_eq!(word(0), FDT_MAGIC);
+        assert_eq!(word(4) as usize, blob.len());
+        // One string for both properties.
+        assert_eq!(word(32), "#size-cells\0".len() as u32);
+        let structure = &blob[word(8) as usize..word(12) as usize];
+        assert_eq!(structure.len(), word(36) as usize);
+        assert_eq!(structure[structure.len() - 4..], FDT_END.to_be_bytes());
+    }
+}
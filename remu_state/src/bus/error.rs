@@ -3,7 +3,7 @@ use thiserror::Error;
 
 use remu_types::ExitCode;
 
-use crate::bus::{AccessKind, MemFault};
+use crate::bus::{AccessKind, MemAttrs, MemFault};
 
 /// In-memory fault type returned by RAM-backed `Memory` operations.
 ///
@@ -33,6 +33,15 @@ pub enum BusError {
         backtrace: Backtrace,
     },
 
+    /// A guest access the region's attributes do not allow.
+    #[error("access fault: {kind:?} at 0x{addr:016x} in region '{region}' ({attrs})")]
+    AccessFault {
+        kind: AccessKind,
+        addr: usize,
+        region: String,
+        attrs: MemAttrs,
+    },
+
     #[error("Memory Fault {0}")]
     MemError(#[source] MemFault, #[backtrace] Backtrace),
 
@@ -44,6 +53,18 @@ pub enum BusError {
 
     #[error("program exit: {0}")]
     ProgramExit(ExitCode),
+
+    /// The guest asked for a reboot (syscon / test finisher reset value).
+    #[error("reboot requested")]
+    Reboot,
+
+    /// A watchdog with `action=stop` ran out.
+    #[error("watchdog timeout")]
+    WatchdogTimeout,
+
+    /// The escape key was typed on the terminal feeding a UART.
+    #[error("escape key")]
+    EscapeKey,
 }
 
 impl BusError {
@@ -63,7 +84,11 @@ impl BusError {
             BusError::MemError(_, backtrace) => Some(backtrace),
             BusError::UnsupportedAccessWidth(_, backtrace) => Some(backtrace),
             BusError::IoError(backtrace) => Some(backtrace),
-            BusError::ProgramExit(_) => None,
+            BusError::AccessFault { .. }
+            | BusError::ProgramExit(_)
+            | BusError::Reboot
+            | BusError::WatchdogTimeout
+            | BusError::EscapeKey => None,
         }
     }
 }
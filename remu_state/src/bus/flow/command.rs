@@ -1,4 +1,4 @@
-use remu_fmt::{parse_byte_vec, parse_prefixed_uint};
+use remu_fmt::{ExprContext, ExprError, NumArg, parse_byte_vec};
 
 #[derive(Debug, clap::Subcommand)]
 pub enum BusCmd {
@@ -10,13 +10,13 @@ pub enum BusCmd {
 
     /// Print Memory Contents
     Print {
-        /// Address to start printing from (e.g. `0x1000`, `0o377`, `0b1010`, `1234`, `0d1234`)
-        #[arg(value_parser = parse_prefixed_uint::<usize>)]
-        addr: usize,
+        /// Address to start printing from (e.g. `0x1000`, `0o377`, `0b1010`, `1234`, `main`)
+        #[arg(value_parser = NumArg::<usize>::parse)]
+        addr: NumArg<usize>,
 
         /// Number of bytes to print (e.g. `16`, `0x10`)
-        #[arg(value_parser = parse_prefixed_uint::<usize>)]
-        count: usize,
+        #[arg(value_parser = NumArg::<usize>::parse)]
+        count: NumArg<usize>,
     },
 
     /// Write Memory Value
@@ -28,8 +28,8 @@ pub enum BusCmd {
     /// Set Memory Value
     Set {
         /// Address to set
-        #[arg(value_parser = parse_prefixed_uint::<usize>)]
-        address: usize,
+        #[arg(value_parser = NumArg::<usize>::parse)]
+        address: NumArg<usize>,
         /// Value to set (e.g. `0xdead_beef` or `[0xde, 0xad, 0xbe, 0xef]` or `[0xdead, 0xbe, 0xef]`)
         #[arg(value_parser = parse_byte_vec)]
         value: Vec<Vec<u8>>,
@@ -51,58 +51,101 @@ pub enum ReadCommand {
 #[derive(Debug, clap::Args)]
 pub struct ReadArgs {
     /// Address to start read
-    #[arg(value_parser = parse_prefixed_uint::<usize>)]
-    pub addr: usize,
+    #[arg(value_parser = NumArg::<usize>::parse)]
+    pub addr: NumArg<usize>,
 }
 
 #[derive(Debug, clap::Subcommand)]
 pub enum WriteCommand {
     U8 {
         /// Address to start write
-        #[arg(value_parser = parse_prefixed_uint::<usize>)]
-        addr: usize,
+        #[arg(value_parser = NumArg::<usize>::parse)]
+        addr: NumArg<usize>,
 
         /// Value to write
-        #[arg(value_parser = parse_prefixed_uint::<u8>)]
-        value: u8,
+        #[arg(value_parser = NumArg::<u8>::parse)]
+        value: NumArg<u8>,
     },
 
     U16 {
         /// Address to start write
-        #[arg(value_parser = parse_prefixed_uint::<usize>)]
-        addr: usize,
+        #[arg(value_parser = NumArg::<usize>::parse)]
+        addr: NumArg<usize>,
 
         /// Value to write
-        #[arg(value_parser = parse_prefixed_uint::<u16>)]
-        value: u16,
+        #[arg(value_parser = NumArg::<u16>::parse)]
+        value: NumArg<u16>,
     },
 
     U32 {
         /// Address to start write
-        #[arg(value_parser = parse_prefixed_uint::<usize>)]
-        addr: usize,
+        #[arg(value_parser = NumArg::<usize>::parse)]
+        addr: NumArg<usize>,
 
         /// Value to write
-        #[arg(value_parser = parse_prefixed_uint::<u32>)]
-        value: u32,
+        #[arg(value_parser = NumArg::<u32>::parse)]
+        value: NumArg<u32>,
     },
     U64 {
         /// Address to start write
-        #[arg(value_parser = parse_prefixed_uint::<usize>)]
-        addr: usize,
+        #[arg(value_parser = NumArg::<usize>::parse)]
+        addr: NumArg<usize>,
 
         /// Value to write
-        #[arg(value_parser = parse_prefixed_uint::<u64>)]
-        value: u64,
+        #[arg(value_parser = NumArg::<u64>::parse)]
+        value: NumArg<u64>,
     },
 
     U128 {
         /// Address to start write
-        #[arg(value_parser = parse_prefixed_uint::<usize>)]
-        addr: usize,
+        #[arg(value_parser = NumArg::<usize>::parse)]
+        addr: NumArg<usize>,
 
         /// Value to write
-        #[arg(value_parser = parse_prefixed_uint::<u128>)]
-        value: u128,
+        #[arg(value_parser = NumArg::<u128>::parse)]
+        value: NumArg<u128>,
     },
 }
+
+impl BusCmd {
+    /// Evaluate the numeric arguments (see [`NumArg`]).
+    pub fn resolve_args(&mut self, ctx: &mut dyn ExprContext) -> Result<(), ExprError> {
+        match self {
+            BusCmd::Read { subcmd } => match subcmd {
+                ReadCommand::U8(arg)
+                | ReadCommand::U16(arg)
+                | ReadCommand::U32(arg)
+                | ReadCommand::U64(arg)
+                | ReadCommand::U128(arg) => arg.addr.resolve(ctx),
+            },
+            BusCmd::Print { addr, count } => {
+                addr.resolve(ctx)?;
+                count.resolve(ctx)
+            }
+            BusCmd::Write { subcmd } => match subcmd {
+                WriteCommand::U8 { addr, value } => {
+                    addr.resolve(ctx)?;
+                    value.resolve(ctx)
+                }
+                WriteCommand::U16 { addr, value } => {
+                    addr.resolve(ctx)?;
+                    value.resolve(ctx)
+                }
+                WriteCommand::U32 { addr, value } => {
+                    addr.resolve(ctx)?;
+                    value.resolve(ctx)
+                }
+                WriteCommand::U64 { addr, value } => {
+                    addr.resolve(ctx)?;
+                    value.resolve(ctx)
+                }
+                WriteCommand::U128 { addr, value } => {
+                    addr.resolve(ctx)?;
+                    value.resolve(ctx)
+                }
+            },
+            BusCmd::Set { address, .. } => address.resolve(ctx),
+            BusCmd::MemMap => Ok(()),
+        }
+    }
+}
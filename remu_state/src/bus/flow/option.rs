@@ -2,30 +2,71 @@ use std::path::PathBuf;
 
 use clap::ValueHint;
 
-use crate::bus::{MemRegionSpec, device::DeviceConfig};
+use crate::bus::{
+    AccessFaultAction, MemRegionSpec, parse_usize_allow_hex_underscore,
+    device::{DeviceConfig, MtimeSource, UartInput},
+};
 
 #[derive(clap::Args, Debug, Clone)]
 pub struct BusOption {
+    /// RAM region, optionally with attributes (a subset of `rwx`, default `rwx`) and initial
+    /// contents: `rom@0x2000_0000:0x2001_0000:rx,image=boot.bin`
     #[arg(
         long = "mem",
-        value_name = "NAME@START:END",
+        value_name = "NAME@START:END[:ATTRS][,image=PATH]",
         action = clap::ArgAction::Append,
         default_value = "ram@0x8000_0000:0x8800_0000"
     )]
     pub mem: Vec<MemRegionSpec>,
 
+    /// MMIO device with optional parameters: `out=SINK` (UARTs), `irq=N` (PLIC source),
+    /// `image=PATH`/`mode=cow|ro` (virtio_blk), `fb=ADDR`/`res=WxH`/`dump=PATH`/`record=DIR` (vga),
+    /// `script=PATH` (keyboard), `clock=host|instret[:N]`/`epoch=SECONDS` (goldfish_rtc),
+    /// `poweroff=VALUE`/`reboot=VALUE` (syscon), `clock=`/`timeout=TICKS`/`action=reset|irq|stop`
+    /// (watchdog), `script=PATH`/`log=PATH` (gpio), `xip=ADDR`/`size=BYTES`/`image=PATH`
+    /// (spi_flash), `channels=N`/`rate=BYTES` (dma)
     #[arg(
         long = "dev",
-        value_name = "KIND@START",
+        value_name = "KIND@START[,KEY=VALUE...]",
         action = clap::ArgAction::Append,
         default_values = ["uart16550@0x1000_0000", "sifive_test_finisher@0x0010_0000", "clint@0x0200_0000"]
     )]
     pub devices: Vec<DeviceConfig>,
 
+    /// CLINT mtime source: `host` (wall clock at 10 MHz) or `instret[:N]` (one tick every N
+    /// retired instructions, for bit-identical reruns)
+    #[arg(long, value_name = "host|instret[:N]", default_value = "host")]
+    pub mtime: MtimeSource,
+
+    /// Guest UART input: `none`, `stdin` (the terminal; Ctrl-] returns to the debugger prompt),
+    /// `file:PATH` or `pty` (a new pseudo-terminal, path logged at startup)
+    #[arg(long, value_name = "SOURCE", default_value = "none")]
+    pub uart_input: UartInput,
+
+    /// Guest access a region's attributes forbid (a store to ROM, a fetch outside `x`):
+    /// `trap` takes the access-fault exception, `stop` stops with the access reported. Only the
+    /// `remu` difftest reference knows the attributes
+    #[arg(long, value_name = "trap|stop", default_value = "stop")]
+    pub access_fault: AccessFaultAction,
+
+    /// Generate a device tree for this bus and place it in RAM at ADDR; the hart starts with
+    /// `a0` = hart ID (0) and `a1` = ADDR
+    #[arg(long, value_name = "ADDR", value_parser = dtb_addr)]
+    pub dtb: Option<usize>,
+
     #[arg(long = "elf", alias = "bin", value_name = "PATH", value_parser = file_exists, value_hint = ValueHint::FilePath)]
     pub elf: Option<PathBuf>,
 }
 
+fn dtb_addr(s: &str) -> Result<usize, String> {
+    let addr = parse_usize_allow_hex_underscore(s, "dtb address")?;
+    if addr.is_multiple_of(8) {
+        Ok(addr)
+    } else {
+        Err(format!("device tree address 0x{addr:x} must be 8-byte aligned"))
+    }
+}
+
 fn file_exists(s: &str) -> Result<PathBuf, String> {
     let path = PathBuf::from(s);
     if path.exists() && path.is_file() {
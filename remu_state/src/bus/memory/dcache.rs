@@ -8,12 +8,16 @@ pub(crate) const PAGE_MASK: usize = PAGE_SIZE - 1;
 /// Sentinel for empty slot. Real page numbers are never this (no guest has page usize::MAX).
 pub(crate) const INVALID_TAG: usize = usize::MAX;
 
-/// One cache line: VPN (tag) + addend. Host pointer for guest `addr` = `addr.wrapping_add(addend)`.
-/// Addend = host_page_base - guest_page_base, so one ADD on hit (no AND for offset).
+/// One cache line: VPN (tag) per access kind + addend. Host pointer for guest `addr` =
+/// `addr.wrapping_add(addend)`. Addend = host_page_base - guest_page_base, so one ADD on hit
+/// (no AND for offset). A tag is only set for the accesses the region's attributes allow, so a
+/// forbidden access misses and is caught on the cold path; the hit path is unchanged.
 #[derive(Clone, Copy)]
-#[repr(align(16))]
+#[repr(align(32))]
 pub(crate) struct DcacheEntry {
-    pub(crate) tag: usize,
+    pub(crate) read_tag: usize,
+    pub(crate) write_tag: usize,
+    pub(crate) exec_tag: usize,
     pub(crate) addend: usize,
 }
 
@@ -28,13 +32,16 @@ impl<const SIZE: usize> Dcache<SIZE> {
             SIZE > 0 && (SIZE & (SIZE - 1)) == 0,
             "Dcache SIZE must be a power of 2"
         );
+        let empty = DcacheEntry {
+            read_tag: INVALID_TAG,
+            write_tag: INVALID_TAG,
+            exec_tag: INVALID_TAG,
+            addend: 0,
+        };
+        // Built on the heap: the array would not fit on a thread's stack.
+        let data = vec![empty; SIZE].into_boxed_slice();
         Self {
-            data: Box::new(
-                [DcacheEntry {
-                    tag: INVALID_TAG,
-                    addend: 0,
-                }; SIZE],
-            ),
+            data: data.try_into().unwrap_or_else(|_| unreachable!()),
         }
     }
 
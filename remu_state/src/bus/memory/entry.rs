@@ -1,4 +1,5 @@
 use core::ops::Range;
+use std::path::PathBuf;
 
 use thiserror::Error;
 
@@ -27,6 +28,10 @@ pub struct MemRegionSpec {
     pub name: String,
     /// Half-open address range: [start, end)
     pub region: Range<usize>,
+    /// What the guest may do with the region (default `rwx`).
+    pub attrs: MemAttrs,
+    /// File whose contents the region starts with (`,image=PATH`).
+    pub image: Option<PathBuf>,
 }
 
 impl MemRegionSpec {
@@ -52,8 +57,22 @@ impl std::str::FromStr for MemRegionSpec {
             return Err("empty mem region spec".to_string());
         }
 
+        let (input, image) = match input.split_once(',') {
+            Some((input, option)) => match option.split_once('=') {
+                Some(("image", path)) if !path.trim().is_empty() => {
+                    (input, Some(PathBuf::from(path.trim())))
+                }
+                _ => {
+                    return Err(format!(
+                        "invalid mem region spec: unknown option '{option}' (expected image=PATH)"
+                    ));
+                }
+            },
+            None => (input, None),
+        };
+
         let (name, rest) = input.split_once('@').ok_or_else(|| {
-            "invalid mem region spec: missing '@' (expected <name>@<start>:<end>)".to_string()
+            "invalid mem region spec: missing '@' (expected <name>@<start>:<end>[:<attrs>])".to_string()
         })?;
 
         let name = name.trim();
@@ -62,9 +81,14 @@ impl std::str::FromStr for MemRegionSpec {
         }
 
         let (start_str, end_str) = rest.split_once(':').ok_or_else(|| {
-            "invalid mem region spec: missing ':' (expected <name>@<start>:<end>)".to_string()
+            "invalid mem region spec: missing ':' (expected <name>@<start>:<end>[:<attrs>])".to_string()
         })?;
 
+        let (end_str, attrs) = match end_str.split_once(':') {
+            Some((end_str, attrs)) => (end_str, attrs.parse()?),
+            None => (end_str, MemAttrs::default()),
+        };
+
         let start = parse_usize_allow_hex_underscore(start_str, "start")?;
         let end = parse_usize_allow_hex_underscore(end_str, "end")?;
 
@@ -83,15 +107,125 @@ impl std::str::FromStr for MemRegionSpec {
         Ok(MemRegionSpec {
             name: name.to_string(),
             region: start..end,
+            attrs,
+            image,
         })
     }
 }
 
-/// A memory access kind (read/write), used for diagnostics.
+/// A memory access kind (read/write/instruction fetch), used for diagnostics and permissions.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AccessKind {
     Read,
     Write,
+    Fetch,
+}
+
+/// Region attributes: which guest accesses a memory region takes, written as a subset of `rwx`
+/// (`rx` for ROM, `r` for read-only data, `x` for execute-only). The debugger, ELF loading and
+/// device DMA are not restricted by them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemAttrs {
+    pub read: bool,
+    pub write: bool,
+    pub exec: bool,
+}
+
+impl MemAttrs {
+    pub const RWX: Self = Self {
+        read: true,
+        write: true,
+        exec: true,
+    };
+    pub const RX: Self = Self {
+        read: true,
+        write: false,
+        exec: true,
+    };
+
+    #[inline(always)]
+    pub fn allows(self, kind: AccessKind) -> bool {
+        match kind {
+            AccessKind::Read => self.read,
+            AccessKind::Write => self.write,
+            AccessKind::Fetch => self.exec,
+        }
+    }
+}
+
+impl Default for MemAttrs {
+    fn default() -> Self {
+        Self::RWX
+    }
+}
+
+impl std::fmt::Display for MemAttrs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (set, c) in [(self.read, 'r'), (self.write, 'w'), (self.exec, 'x')] {
+            if set {
+                write!(f, "{c}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for MemAttrs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("invalid mem region spec: empty attributes".to_string());
+        }
+        let mut attrs = Self {
+            read: false,
+            write: false,
+            exec: false,
+        };
+        for c in s.chars() {
+            let flag = match c {
+                'r' => &mut attrs.read,
+                'w' => &mut attrs.write,
+                'x' => &mut attrs.exec,
+                _ => {
+                    return Err(format!(
+                        "invalid mem region spec: attributes '{s}' must be a subset of rwx"
+                    ));
+                }
+            };
+            if std::mem::replace(flag, true) {
+                return Err(format!(
+                    "invalid mem region spec: '{c}' repeated in attributes '{s}'"
+                ));
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+/// What a guest access that a region's attributes forbid does: `--access-fault trap|stop`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccessFaultAction {
+    /// Take the load/store/instruction access-fault exception, as the hardware would.
+    Trap,
+    /// Stop execution at the faulting instruction, with the access reported.
+    #[default]
+    Stop,
+}
+
+impl std::str::FromStr for AccessFaultAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "trap" => Ok(Self::Trap),
+            "stop" => Ok(Self::Stop),
+            _ => Err(format!(
+                "unknown access fault action {s:?}; expected trap or stop"
+            )),
+        }
+    }
 }
 
 /// Page size used by D-cache; memory regions must be page-aligned and sized in whole pages.
@@ -107,11 +241,13 @@ pub const REGION_TAIL_PADDING: usize = 128;
 pub struct MemoryEntry {
     pub name: String,
     pub range: Range<usize>,
+    pub attrs: MemAttrs,
     storage: Box<[u8]>,
 }
 
 impl MemoryEntry {
-    /// Creates a RAM-backed region from a `MemRegionSpec`. Allocates zero-filled RAM.
+    /// Creates a RAM-backed region from a `MemRegionSpec`. Allocates zero-filled RAM, then
+    /// copies in the region's `image=` if it has one.
     pub fn new(region: MemRegionSpec) -> Result<Self, MemFault> {
         let start = region.region.start;
         let end = region.region.end;
@@ -152,11 +288,24 @@ impl MemoryEntry {
             size,
         })?;
 
-        let storage = vec![0u8; size_usize + REGION_TAIL_PADDING].into_boxed_slice();
+        let mut storage = vec![0u8; size_usize + REGION_TAIL_PADDING].into_boxed_slice();
+        if let Some(path) = &region.image {
+            match std::fs::read(path) {
+                Ok(image) if image.len() > size => tracing::error!(
+                    "memory image {} ({} bytes) does not fit in region '{}' ({size} bytes)",
+                    path.display(),
+                    image.len(),
+                    region.name
+                ),
+                Ok(image) => storage[..image.len()].copy_from_slice(&image),
+                Err(e) => tracing::error!("memory image {}: {e}", path.display()),
+            }
+        }
 
         Ok(Self {
             name: region.name,
             range: start..end,
+            attrs: region.attrs,
             storage,
         })
     }
@@ -179,6 +328,18 @@ impl MemoryEntry {
         )
     }
 
+    /// Region contents, without the tail padding.
+    #[inline(always)]
+    pub fn bytes(&self) -> &[u8] {
+        &self.storage[..self.range.end - self.range.start]
+    }
+
+    #[inline(always)]
+    pub(crate) fn bytes_mut(&mut self) -> &mut [u8] {
+        let len = self.range.end - self.range.start;
+        &mut self.storage[..len]
+    }
+
     #[inline(always)]
     pub fn contains(&self, range: Range<usize>) -> bool {
         (range.start >= self.range.start) && (range.end <= self.range.end)
@@ -278,3 +439,33 @@ impl MemoryEntry {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_spec_takes_attributes_and_image() {
+        let spec: MemRegionSpec = "rom@0x2000_0000:0x2001_0000:rx,image=boot.bin"
+            .parse()
+            .unwrap();
+        assert_eq!(spec.region, 0x2000_0000..0x2001_0000);
+        assert_eq!(spec.attrs, MemAttrs::RX);
+        assert_eq!(spec.image, Some(PathBuf::from("boot.bin")));
+        assert_eq!(spec.attrs.to_string(), "rx");
+
+        let ram: MemRegionSpec = "ram@0x8000_0000:0x8800_0000".parse().unwrap();
+        assert_eq!((ram.attrs, ram.image), (MemAttrs::RWX, None));
+        let xo: MemRegionSpec = "text@0x1000:0x2000:x".parse().unwrap();
+        assert!(xo.attrs.allows(AccessKind::Fetch) && !xo.attrs.allows(AccessKind::Read));
+
+        for bad in [
+            "rom@0x1000:0x2000:",
+            "rom@0x1000:0x2000:rwr",
+            "rom@0x1000:0x2000:ro",
+            "rom@0x1000:0x2000,img=a.bin",
+        ] {
+            assert!(bad.parse::<MemRegionSpec>().is_err(), "{bad}");
+        }
+    }
+}
@@ -3,9 +3,11 @@ remu_macro::mod_flat!(entry, dcache, elf);
 use core::ops::Range;
 
 pub use elf::try_load_elf_into_memory;
-pub use entry::{AccessKind, MemFault, MemRegionSpec, MemoryEntry};
+pub use entry::{
+    AccessFaultAction, AccessKind, MemAttrs, MemFault, MemRegionSpec, MemoryEntry, PAGE_SIZE,
+};
 
-use dcache::{Dcache, PAGE_MASK, PAGE_SHIFT};
+use dcache::{Dcache, INVALID_TAG, PAGE_MASK, PAGE_SHIFT};
 
 const DCACHE_SIZE: usize = 1 << 16;
 
@@ -64,23 +66,41 @@ impl Memory {
         None
     }
 
-    /// Refill D-cache for the page containing `addr`. Returns addend or None if unmapped.
+    /// Refill D-cache for the page containing `addr`. Returns addend, or None if unmapped or
+    /// the region does not allow `kind`.
     #[inline(never)]
-    fn refill_dcache(&mut self, addr: usize) -> Option<usize> {
+    fn refill_dcache(&mut self, addr: usize, kind: AccessKind) -> Option<usize> {
         let page_start = addr & !PAGE_MASK;
         let m = self.find_memory_mut(page_start..page_start + dcache::PAGE_SIZE)?;
+        let attrs = m.attrs;
         let host_base = m.ptr_at_addr(page_start) as usize;
         let addend = host_base.wrapping_sub(page_start);
+        let tag = |allowed: bool| {
+            if allowed {
+                addr >> PAGE_SHIFT
+            } else {
+                INVALID_TAG
+            }
+        };
         let entry = self.dcache.get_entry_mut(addr);
-        entry.tag = addr >> PAGE_SHIFT;
+        entry.read_tag = tag(attrs.read);
+        entry.write_tag = tag(attrs.write);
+        entry.exec_tag = tag(attrs.exec);
         entry.addend = addend;
-        Some(addend)
+        attrs.allows(kind).then_some(addend)
+    }
+
+    /// Attributes of the region holding all of `range`, if one does.
+    #[inline(never)]
+    pub(crate) fn attrs_at(&mut self, range: Range<usize>) -> Option<(&str, MemAttrs)> {
+        self.find_memory_mut(range)
+            .map(|m| (m.name.as_str(), m.attrs))
     }
 
     #[inline(always)]
     pub(crate) fn read_8(&mut self, addr: usize) -> Option<u8> {
         let entry = self.dcache.get_entry_mut(addr);
-        if entry.tag == (addr >> PAGE_SHIFT) {
+        if entry.read_tag == (addr >> PAGE_SHIFT) {
             let host_ptr = addr.wrapping_add(entry.addend) as *const u8;
             return Some(unsafe { *host_ptr });
         }
@@ -89,7 +109,7 @@ impl Memory {
 
     #[inline(never)]
     fn read_8_slow(&mut self, addr: usize) -> Option<u8> {
-        let addend = self.refill_dcache(addr)?;
+        let addend = self.refill_dcache(addr, AccessKind::Read)?;
         let host_ptr = addr.wrapping_add(addend) as *const u8;
         Some(unsafe { *host_ptr })
     }
@@ -97,7 +117,7 @@ impl Memory {
     #[inline(always)]
     pub(crate) fn read_16(&mut self, addr: usize) -> Option<u16> {
         let entry = self.dcache.get_entry_mut(addr);
-        if entry.tag == (addr >> PAGE_SHIFT) {
+        if entry.read_tag == (addr >> PAGE_SHIFT) {
             let host_ptr = addr.wrapping_add(entry.addend) as *const u16;
             return Some(unsafe { host_ptr.read_unaligned() }.to_le());
         }
@@ -106,7 +126,7 @@ impl Memory {
 
     #[inline(never)]
     fn read_16_slow(&mut self, addr: usize) -> Option<u16> {
-        let addend = self.refill_dcache(addr)?;
+        let addend = self.refill_dcache(addr, AccessKind::Read)?;
         let host_ptr = addr.wrapping_add(addend) as *const u16;
         Some(unsafe { host_ptr.read_unaligned() }.to_le())
     }
@@ -114,7 +134,7 @@ impl Memory {
     #[inline(always)]
     pub(crate) fn read_32(&mut self, addr: usize) -> Option<u32> {
         let entry = self.dcache.get_entry_mut(addr);
-        if entry.tag == (addr >> PAGE_SHIFT) {
+        if entry.read_tag == (addr >> PAGE_SHIFT) {
             let host_ptr = addr.wrapping_add(entry.addend) as *const u32;
             return Some(unsafe { host_ptr.read_unaligned() }.to_le());
         }
@@ -123,7 +143,7 @@ impl Memory {
 
     #[inline(never)]
     fn read_32_slow(&mut self, addr: usize) -> Option<u32> {
-        let addend = self.refill_dcache(addr)?;
+        let addend = self.refill_dcache(addr, AccessKind::Read)?;
         let host_ptr = addr.wrapping_add(addend) as *const u32;
         Some(unsafe { host_ptr.read_unaligned() }.to_le())
     }
@@ -131,7 +151,7 @@ impl Memory {
     #[inline(always)]
     pub(crate) fn read_64(&mut self, addr: usize) -> Option<u64> {
         let entry = self.dcache.get_entry_mut(addr);
-        if entry.tag == (addr >> PAGE_SHIFT) {
+        if entry.read_tag == (addr >> PAGE_SHIFT) {
             let host_ptr = addr.wrapping_add(entry.addend) as *const u64;
             return Some(unsafe { host_ptr.read_unaligned() }.to_le());
         }
@@ -140,7 +160,7 @@ impl Memory {
 
     #[inline(never)]
     fn read_64_slow(&mut self, addr: usize) -> Option<u64> {
-        let addend = self.refill_dcache(addr)?;
+        let addend = self.refill_dcache(addr, AccessKind::Read)?;
         let host_ptr = addr.wrapping_add(addend) as *const u64;
         Some(unsafe { host_ptr.read_unaligned() }.to_le())
     }
@@ -148,7 +168,7 @@ impl Memory {
     #[inline(always)]
     pub(crate) fn read_128(&mut self, addr: usize) -> Option<u128> {
         let entry = self.dcache.get_entry_mut(addr);
-        if entry.tag == (addr >> PAGE_SHIFT) {
+        if entry.read_tag == (addr >> PAGE_SHIFT) {
             let host_ptr = addr.wrapping_add(entry.addend) as *const u128;
             return Some(unsafe { host_ptr.read_unaligned() }.to_le());
         }
@@ -157,11 +177,30 @@ impl Memory {
 
     #[inline(never)]
     fn read_128_slow(&mut self, addr: usize) -> Option<u128> {
-        let addend = self.refill_dcache(addr)?;
+        let addend = self.refill_dcache(addr, AccessKind::Read)?;
         let host_ptr = addr.wrapping_add(addend) as *const u128;
         Some(unsafe { host_ptr.read_unaligned() }.to_le())
     }
 
+    /// Instruction fetch: like `read_32`, but needs an executable region.
+    #[inline(always)]
+    pub(crate) fn fetch_32(&mut self, addr: usize) -> Option<u32> {
+        let entry = self.dcache.get_entry_mut(addr);
+        if entry.exec_tag == (addr >> PAGE_SHIFT) {
+            let host_ptr = addr.wrapping_add(entry.addend) as *const u32;
+            return Some(unsafe { host_ptr.read_unaligned() }.to_le());
+        }
+        self.fetch_32_slow(addr)
+    }
+
+    #[inline(never)]
+    fn fetch_32_slow(&mut self, addr: usize) -> Option<u32> {
+        let addend = self.refill_dcache(addr, AccessKind::Fetch)?;
+        let host_ptr = addr.wrapping_add(addend) as *const u32;
+        Some(unsafe { host_ptr.read_unaligned() }.to_le())
+    }
+
+    /// Bypasses the region attributes, as the debugger, ELF loading and device DMA do.
     #[inline(always)]
     pub(crate) fn read_bytes(&mut self, addr: usize, buf: &mut [u8]) -> Option<()> {
         let m = self.find_memory_mut(addr..addr + buf.len())?;
@@ -174,7 +213,7 @@ impl Memory {
     #[inline(always)]
     pub(crate) fn write_8(&mut self, addr: usize, value: u8) -> Option<()> {
         let entry = self.dcache.get_entry_mut(addr);
-        if entry.tag == (addr >> PAGE_SHIFT) {
+        if entry.write_tag == (addr >> PAGE_SHIFT) {
             let host_ptr = addr.wrapping_add(entry.addend) as *mut u8;
             unsafe { *host_ptr = value };
             return Some(());
@@ -184,7 +223,7 @@ impl Memory {
 
     #[inline(never)]
     fn write_8_slow(&mut self, addr: usize, value: u8) -> Option<()> {
-        let addend = self.refill_dcache(addr)?;
+        let addend = self.refill_dcache(addr, AccessKind::Write)?;
         let host_ptr = addr.wrapping_add(addend) as *mut u8;
         unsafe { *host_ptr = value };
         Some(())
@@ -193,7 +232,7 @@ impl Memory {
     #[inline(always)]
     pub(crate) fn write_16(&mut self, addr: usize, value: u16) -> Option<()> {
         let entry = self.dcache.get_entry_mut(addr);
-        if entry.tag == (addr >> PAGE_SHIFT) {
+        if entry.write_tag == (addr >> PAGE_SHIFT) {
             let host_ptr = addr.wrapping_add(entry.addend) as *mut u16;
             unsafe { host_ptr.write_unaligned(value.to_le()) };
             return Some(());
@@ -203,7 +242,7 @@ impl Memory {
 
     #[inline(never)]
     fn write_16_slow(&mut self, addr: usize, value: u16) -> Option<()> {
-        let addend = self.refill_dcache(addr)?;
+        let addend = self.refill_dcache(addr, AccessKind::Write)?;
         let host_ptr = addr.wrapping_add(addend) as *mut u16;
         unsafe { host_ptr.write_unaligned(value.to_le()) };
         Some(())
@@ -212,7 +251,7 @@ impl Memory {
     #[inline(always)]
     pub(crate) fn write_32(&mut self, addr: usize, value: u32) -> Option<()> {
         let entry = self.dcache.get_entry_mut(addr);
-        if entry.tag == (addr >> PAGE_SHIFT) {
+        if entry.write_tag == (addr >> PAGE_SHIFT) {
             let host_ptr = addr.wrapping_add(entry.addend) as *mut u32;
             unsafe { host_ptr.write_unaligned(value.to_le()) };
             return Some(());
@@ -222,7 +261,7 @@ impl Memory {
 
     #[inline(never)]
     fn write_32_slow(&mut self, addr: usize, value: u32) -> Option<()> {
-        let addend = self.refill_dcache(addr)?;
+        let addend = self.refill_dcache(addr, AccessKind::Write)?;
         let host_ptr = addr.wrapping_add(addend) as *mut u32;
         unsafe { host_ptr.write_unaligned(value.to_le()) };
         Some(())
@@ -231,7 +270,7 @@ impl Memory {
     #[inline(always)]
     pub(crate) fn write_64(&mut self, addr: usize, value: u64) -> Option<()> {
         let entry = self.dcache.get_entry_mut(addr);
-        if entry.tag == (addr >> PAGE_SHIFT) {
+        if entry.write_tag == (addr >> PAGE_SHIFT) {
             let host_ptr = addr.wrapping_add(entry.addend) as *mut u64;
             unsafe { host_ptr.write_unaligned(value.to_le()) };
             return Some(());
@@ -241,7 +280,7 @@ impl Memory {
 
     #[inline(never)]
     fn write_64_slow(&mut self, addr: usize, value: u64) -> Option<()> {
-        let addend = self.refill_dcache(addr)?;
+        let addend = self.refill_dcache(addr, AccessKind::Write)?;
         let host_ptr = addr.wrapping_add(addend) as *mut u64;
         unsafe { host_ptr.write_unaligned(value.to_le()) };
         Some(())
@@ -250,7 +289,7 @@ impl Memory {
     #[inline(always)]
     pub(crate) fn write_128(&mut self, addr: usize, value: u128) -> Option<()> {
         let entry = self.dcache.get_entry_mut(addr);
-        if entry.tag == (addr >> PAGE_SHIFT) {
+        if entry.write_tag == (addr >> PAGE_SHIFT) {
             let host_ptr = addr.wrapping_add(entry.addend) as *mut u128;
             unsafe { host_ptr.write_unaligned(value.to_le()) };
             return Some(());
@@ -260,12 +299,13 @@ impl Memory {
 
     #[inline(never)]
     fn write_128_slow(&mut self, addr: usize, value: u128) -> Option<()> {
-        let addend = self.refill_dcache(addr)?;
+        let addend = self.refill_dcache(addr, AccessKind::Write)?;
         let host_ptr = addr.wrapping_add(addend) as *mut u128;
         unsafe { host_ptr.write_unaligned(value.to_le()) };
         Some(())
     }
 
+    /// Bypasses the region attributes, like [`read_bytes`](Self::read_bytes).
     #[inline(always)]
     pub(crate) fn write_bytes(&mut self, addr: usize, buf: &[u8]) -> Option<()> {
         let m = self.find_memory_mut(addr..addr + buf.len())?;
@@ -275,3 +315,31 @@ impl Memory {
         Some(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_gate_guest_accesses_only() {
+        let rom: MemRegionSpec = "rom@0x2000_0000:0x2000_1000:rx".parse().unwrap();
+        let data: MemRegionSpec = "data@0x8000_0000:0x8000_1000:rw".parse().unwrap();
+        let mut memory = Memory::new(Box::new([
+            MemoryEntry::new(rom).unwrap(),
+            MemoryEntry::new(data).unwrap(),
+        ]));
+        memory.write_bytes(0x2000_0000, &[0x13, 0, 0, 0]).unwrap();
+        assert_eq!(memory.fetch_32(0x2000_0000), Some(0x13));
+        assert_eq!(memory.read_32(0x2000_0000), Some(0x13));
+        assert_eq!(memory.write_32(0x2000_0000, 0), None);
+        assert_eq!(memory.read_32(0x2000_0000), Some(0x13));
+
+        assert_eq!(memory.write_32(0x8000_0000, 0x73), Some(()));
+        assert_eq!(memory.read_32(0x8000_0000), Some(0x73));
+        assert_eq!(memory.fetch_32(0x8000_0000), None);
+        assert_eq!(
+            memory.attrs_at(0x8000_0000..0x8000_0004),
+            Some(("data", "rw".parse().unwrap()))
+        );
+    }
+}
@@ -1,33 +1,77 @@
 remu_macro::mod_pub!(device, memory);
 remu_macro::mod_pub_flat!(flow);
-remu_macro::mod_flat!(error, parse, access, observer);
+remu_macro::mod_flat!(error, parse, access, observer, watch, snapshot, dtb);
 
 use std::{marker::PhantomData, ops::Range};
 
 pub use memory::{
-    AccessKind, MemFault, MemRegionSpec, Memory, MemoryEntry, try_load_elf_into_memory,
+    AccessFaultAction, AccessKind, MemAttrs, MemFault, MemRegionSpec, Memory, MemoryEntry,
+    PAGE_SIZE, try_load_elf_into_memory,
 };
 pub use observer::ObserverEvent;
 use remu_isa::AllUsize;
 use remu_isa::isa::RvIsa;
-use remu_types::DynDiagError;
+use remu_types::{DynDiagError, WatchAccess, WatchEvent};
 
-use crate::bus::device::{DeviceAccess, instantiate_device};
+use crate::bus::device::{
+    DeviceAccess, DeviceEnv, DeviceKind, DisplayConfig, FlashConfig, GuestMemory,
+    instantiate_device,
+};
+
+/// Instructions between device polls when there is no MMIO traffic.
+const IRQ_POLL_INTERVAL: u64 = 1024;
+
+/// A device and the bus address it is mapped at.
+type MappedDevice = (usize, Box<dyn DeviceAccess>);
 
 pub struct Bus<I: RvIsa, O: BusObserver> {
     memory: Memory,
-    device: Box<[(usize, Box<dyn DeviceAccess>)]>,
+    device: Box<[MappedDevice]>,
     tracer: remu_types::TracerDyn,
     observer: O,
+    watch: WatchObserver,
+    /// Instructions retired, advanced by [`retire`](Self::retire); handed to devices as the
+    /// time of each [`tick`](DeviceAccess::tick), e.g. for the CLINT under `--mtime instret`.
+    instret: u64,
+    /// Index of the PLIC in `device`, if there is one.
+    plic: Option<usize>,
+    /// Whether devices need polling at all: there is a PLIC, a watchdog or a DMA engine.
+    polled: bool,
+    /// `(device index, PLIC source)` of each device wired with `irq=`.
+    irq_lines: Box<[(usize, u32)]>,
+    /// Set by MMIO accesses: device lines may have changed since the last sample.
+    irq_dirty: bool,
+    /// `instret` when the lines were last sampled.
+    irq_sampled_at: u64,
+    /// PLIC output as of the last sample.
+    meip: bool,
+    /// Index in `device` of the device last accessed.
+    last_device: usize,
+    /// Flash arrays: memory that keeps its contents across a guest reboot.
+    nonvolatile: Box<[Range<usize>]>,
+    /// `--access-fault`: what the CPU does with an access the memory attributes forbid.
+    access_fault: AccessFaultAction,
     _marker: PhantomData<I>,
 }
 
 impl<I: RvIsa, O: BusObserver> Bus<I, O> {
     pub(crate) fn new(opt: BusOption, tracer: remu_types::TracerDyn, is_dut: bool) -> Self {
         let prefix = if is_dut { "[DUT]" } else { "[REF]" };
+        let framebuffers = opt
+            .devices
+            .iter()
+            .filter_map(|config| config.display.as_ref().map(DisplayConfig::fb_region));
+        let flashes: Vec<&FlashConfig> = opt
+            .devices
+            .iter()
+            .filter_map(|config| config.flash.as_ref())
+            .collect();
         let entries: Vec<MemoryEntry> = opt
             .mem
-            .into_iter()
+            .iter()
+            .cloned()
+            .chain(framebuffers)
+            .chain(flashes.iter().map(|flash| flash.xip_region()))
             .map(|region| {
                 tracing::info!(
                     "{} new memory {} region initialized at 0x{:08x}:0x{:08x}",
@@ -42,8 +86,20 @@ impl<I: RvIsa, O: BusObserver> Bus<I, O> {
             .collect();
 
         let mut memory = Memory::new(entries.into_boxed_slice());
+        for flash in &flashes {
+            flash.preload(&mut memory);
+        }
         memory.try_load_elf(&opt.elf, &tracer);
+        try_load_dtb_into_memory(memory.entries_mut(), &opt, I::ISA_STR, &tracer);
+        let nonvolatile = flashes
+            .iter()
+            .map(|flash| flash.xip_region().region)
+            .collect();
 
+        let mut env = DeviceEnv {
+            mtime: opt.mtime,
+            uart_input: Some(opt.uart_input),
+        };
         let device: Vec<(usize, Box<dyn DeviceAccess>)> = if is_dut {
             opt.devices
                 .iter()
@@ -54,28 +110,163 @@ impl<I: RvIsa, O: BusObserver> Bus<I, O> {
                         config.kind.as_str(),
                         config.start
                     );
-                    (config.start, instantiate_device(config.kind))
+                    (config.start, instantiate_device(config, &mut env))
                 })
                 .collect()
         } else {
             Vec::new()
         };
 
+        let plic = opt
+            .devices
+            .iter()
+            .position(|config| config.kind == DeviceKind::Plic)
+            .filter(|_| is_dut);
+        let irq_lines: Vec<(usize, u32)> = opt
+            .devices
+            .iter()
+            .enumerate()
+            .filter_map(|(i, config)| Some((i, config.irq?)))
+            .filter(|_| is_dut)
+            .collect();
+        if plic.is_none() && !irq_lines.is_empty() {
+            tracing::warn!("{prefix} devices with irq= are not connected: no plic in --dev");
+        }
+        let polled = is_dut
+            && opt
+                .devices
+                .iter()
+                .any(|config| {
+                    matches!(
+                        config.kind,
+                        DeviceKind::Plic | DeviceKind::Watchdog | DeviceKind::Dma
+                    )
+                });
+
         Self {
             memory,
             device: device.into_boxed_slice(),
             tracer,
             observer: O::new(),
+            watch: WatchObserver::new(),
+            instret: 0,
+            plic,
+            polled,
+            irq_lines: irq_lines.into_boxed_slice(),
+            irq_dirty: true,
+            irq_sampled_at: 0,
+            meip: false,
+            last_device: 0,
+            nonvolatile,
+            access_fault: opt.access_fault,
             _marker: PhantomData,
         }
     }
 
+    /// Push out buffered device output (UART lines without a newline yet).
+    pub fn flush_output(&mut self) {
+        for (_, device) in self.device.iter_mut() {
+            device.flush();
+        }
+    }
+
+    /// Silence device output sinks and event logs while instructions are replayed, so what
+    /// they emitted the first time is not emitted again.
+    pub fn mute_output(&mut self, muted: bool) {
+        for (_, device) in self.device.iter_mut() {
+            device.mute(muted);
+        }
+    }
+
+    /// Output of the `out=capture` UARTs, in bus order; `None` if no UART captures.
+    pub fn captured_output(&self) -> Option<Vec<u8>> {
+        let mut captures = self
+            .device
+            .iter()
+            .filter_map(|(_, d)| d.captured_output())
+            .peekable();
+        captures.peek()?;
+        Some(captures.flatten().copied().collect())
+    }
+
+    /// Write the current frame of the first display device to `path`; `None` if there is none.
+    pub fn dump_display(&mut self, path: &std::path::Path) -> Option<std::io::Result<()>> {
+        let mut mem = GuestMemory::new(&mut self.memory);
+        self.device
+            .iter_mut()
+            .find_map(|(_, device)| device.dump_display(&mut mem, path))
+    }
+
+    /// Count one retired instruction.
+    #[inline(always)]
+    pub fn retire(&mut self) {
+        self.instret = self.instret.wrapping_add(1);
+    }
+
+    /// Poll devices: run their [`tick`](DeviceAccess::tick)s, then return whether the PLIC
+    /// raises the hart's external interrupt (`mip.MEIP`). Devices are polled after MMIO
+    /// accesses and otherwise every [`IRQ_POLL_INTERVAL`] instructions, so host input (UART RX)
+    /// is not polled on every step.
+    #[inline(always)]
+    pub fn poll_devices(&mut self) -> Result<bool, BusError> {
+        if !self.polled {
+            return Ok(false);
+        }
+        if self.irq_dirty || self.instret.wrapping_sub(self.irq_sampled_at) >= IRQ_POLL_INTERVAL {
+            self.sample_devices()?;
+        }
+        Ok(self.meip)
+    }
+
+    #[inline(never)]
+    fn sample_devices(&mut self) -> Result<(), BusError> {
+        self.irq_dirty = false;
+        self.irq_sampled_at = self.instret;
+        for index in 0..self.device.len() {
+            self.tick_device(index)?;
+        }
+        let Some(plic) = self.plic else {
+            return Ok(());
+        };
+        for &(index, source) in self.irq_lines.iter() {
+            let level = self.device[index].1.irq_level();
+            self.device[plic].1.set_irq_level(source, level);
+        }
+        self.meip = self.device[plic].1.external_irq();
+        Ok(())
+    }
+
+    /// Record that the hart took an interrupt this step, so difftest resyncs the reference
+    /// (which has no devices and cannot take it).
+    #[inline(always)]
+    pub fn note_interrupt(&mut self) {
+        if O::ENABLED {
+            self.observer.on_interrupt();
+        }
+    }
+
     /// Take and clear all observer events this step (MMIO and/or memory writes).
     #[inline(always)]
     pub fn take_observer_events(&mut self) -> Vec<observer::ObserverEvent> {
         self.observer.get_events_and_clear()
     }
 
+    /// Watch `range` for `access`; `id` is reported back in the hit.
+    pub fn set_watch(&mut self, id: u32, range: Range<usize>, access: WatchAccess) {
+        self.watch.set(id, range, access);
+    }
+
+    /// Remove memory watchpoint `id`; false if there is none.
+    pub fn del_watch(&mut self, id: u32) -> bool {
+        self.watch.del(id)
+    }
+
+    /// Take the first watchpoint hit since the last call.
+    #[inline(always)]
+    pub fn take_watch_hit(&mut self) -> Option<(u32, WatchEvent)> {
+        self.watch.take_hit()
+    }
+
     pub fn mem_regions_for_difftest(&mut self) -> Vec<(usize, *mut u8, usize)> {
         self.memory
             .entries_mut()
@@ -100,48 +291,88 @@ impl<I: RvIsa, O: BusObserver> Bus<I, O> {
         }
     }
 
+    /// Whether an access the memory attributes forbid is taken as an access-fault exception
+    /// (`--access-fault trap`) rather than stopping execution.
+    pub fn traps_access_faults(&self) -> bool {
+        self.access_fault == AccessFaultAction::Trap
+    }
+
+    /// Whether `addr` falls inside a memory region or a device window.
+    pub fn is_mapped(&self, addr: usize) -> bool {
+        self.memory
+            .entries()
+            .iter()
+            .any(|m| m.contains(addr..addr + 1))
+            || self
+                .device
+                .iter()
+                .any(|(start, d)| addr >= *start && addr < *start + d.size())
+    }
+
     fn find_device_mut(
         &mut self,
         range: Range<usize>,
-    ) -> Option<(usize, &mut Box<dyn DeviceAccess>)> {
-        for (addr, device) in self.device.iter_mut() {
-            let device_end = *addr + device.size();
-            if range.start >= *addr && range.end <= device_end {
-                return Some((*addr, device));
+    ) -> Result<Option<&mut MappedDevice>, BusError> {
+        self.irq_dirty = true;
+        let Some(i) = self.device.iter().position(|(addr, device)| {
+            range.start >= *addr && range.end <= *addr + device.size()
+        }) else {
+            return Ok(None);
+        };
+        self.last_device = i;
+        self.tick_device(i)?;
+        Ok(Some(&mut self.device[i]))
+    }
+
+    /// Bring device `index` up to the current instruction count; its RAM writes are reported
+    /// to the observer.
+    fn tick_device(&mut self, index: usize) -> Result<(), BusError> {
+        let mut mem = GuestMemory::new(&mut self.memory);
+        let ticked = self.device[index].1.tick(self.instret, &mut mem);
+        if O::ENABLED {
+            for (addr, data) in mem.into_writes() {
+                self.observer.on_dma_write(addr, data);
             }
         }
+        ticked
+    }
 
-        None
+    /// Let the device just written do the DMA the write started; its RAM writes are reported
+    /// to the observer.
+    fn device_dma(&mut self) {
+        let mut mem = GuestMemory::new(&mut self.memory);
+        self.device[self.last_device].1.dma(&mut mem);
+        if O::ENABLED {
+            for (addr, data) in mem.into_writes() {
+                self.observer.on_dma_write(addr, data);
+            }
+        }
     }
 
-    pub(crate) fn execute(&mut self, subcmd: &BusCmd) -> Result<(), BusError> {
+    pub(crate) fn execute(&mut self, subcmd: &BusCmd) -> Result<(), crate::StateError> {
         match subcmd {
             BusCmd::Read { subcmd } => {
                 let (addr, result) = match subcmd {
-                    ReadCommand::U8(arg) => (
-                        arg.addr,
-                        self.read_8_impl::<false>(arg.addr).map(|v| AllUsize::U8(v)),
-                    ),
-                    ReadCommand::U16(arg) => (
-                        arg.addr,
-                        self.read_16_impl::<false>(arg.addr)
-                            .map(|v| AllUsize::U16(v)),
-                    ),
-                    ReadCommand::U32(arg) => (
-                        arg.addr,
-                        self.read_32_impl::<false>(arg.addr)
-                            .map(|v| AllUsize::U32(v)),
-                    ),
-                    ReadCommand::U64(arg) => (
-                        arg.addr,
-                        self.read_64_impl::<false>(arg.addr)
-                            .map(|v| AllUsize::U64(v)),
-                    ),
-                    ReadCommand::U128(arg) => (
-                        arg.addr,
-                        self.read_128_impl::<false>(arg.addr)
-                            .map(|v| AllUsize::U128(v)),
-                    ),
+                    ReadCommand::U8(arg) => {
+                        let addr = arg.addr.get()?;
+                        (addr, self.read_8_impl::<false, false>(addr).map(AllUsize::U8))
+                    }
+                    ReadCommand::U16(arg) => {
+                        let addr = arg.addr.get()?;
+                        (addr, self.read_16_impl::<false, false>(addr).map(AllUsize::U16))
+                    }
+                    ReadCommand::U32(arg) => {
+                        let addr = arg.addr.get()?;
+                        (addr, self.read_32_impl::<false, false>(addr).map(AllUsize::U32))
+                    }
+                    ReadCommand::U64(arg) => {
+                        let addr = arg.addr.get()?;
+                        (addr, self.read_64_impl::<false, false>(addr).map(AllUsize::U64))
+                    }
+                    ReadCommand::U128(arg) => {
+                        let addr = arg.addr.get()?;
+                        (addr, self.read_128_impl::<false, false>(addr).map(AllUsize::U128))
+                    }
                 };
                 self.tracer.borrow().mem_show(
                     addr,
@@ -149,23 +380,32 @@ impl<I: RvIsa, O: BusObserver> Bus<I, O> {
                 );
             }
             BusCmd::Print { addr, count } => {
-                let mut buf = vec![0u8 as u8; *count];
+                let addr = addr.get()?;
+                let mut buf = vec![0u8 as u8; count.get()?];
                 let result = self
-                    .read_bytes(*addr, &mut buf)
+                    .read_bytes(addr, &mut buf)
                     .map_err(|e| Box::new(e) as Box<dyn DynDiagError>);
-                self.tracer.borrow_mut().mem_print(*addr, &buf, result);
+                self.tracer.borrow_mut().mem_print(addr, &buf, result);
             }
             BusCmd::Write { subcmd } => match subcmd {
-                WriteCommand::U8 { addr, value } => self.write_8_impl::<false>(*addr, *value)?,
-                WriteCommand::U16 { addr, value } => self.write_16_impl::<false>(*addr, *value)?,
-                WriteCommand::U32 { addr, value } => self.write_32_impl::<false>(*addr, *value)?,
-                WriteCommand::U64 { addr, value } => self.write_64_impl::<false>(*addr, *value)?,
+                WriteCommand::U8 { addr, value } => {
+                    self.write_8_impl::<false, false>(addr.get()?, value.get()?)?
+                }
+                WriteCommand::U16 { addr, value } => {
+                    self.write_16_impl::<false, false>(addr.get()?, value.get()?)?
+                }
+                WriteCommand::U32 { addr, value } => {
+                    self.write_32_impl::<false, false>(addr.get()?, value.get()?)?
+                }
+                WriteCommand::U64 { addr, value } => {
+                    self.write_64_impl::<false, false>(addr.get()?, value.get()?)?
+                }
                 WriteCommand::U128 { addr, value } => {
-                    self.write_128_impl::<false>(*addr, *value)?
+                    self.write_128_impl::<false, false>(addr.get()?, value.get()?)?
                 }
             },
             BusCmd::Set { address, value } => {
-                let mut addr = *address;
+                let mut addr = address.get()?;
                 for chunk in value.iter() {
                     if chunk.is_empty() {
                         continue;
@@ -1,9 +1,13 @@
 #[derive(Debug, Clone)]
 pub enum ObserverEvent {
-    /// MMIO was accessed this step; harness should sync ref and skip difftest.
+    /// MMIO was accessed or an interrupt taken this step; harness should sync ref and skip
+    /// difftest.
     MmioAccess,
     /// One memory write (to RAM): (start_addr, data). Used for memdiff. Fixed length, so Box<[u8]>.
     MemoryWrite(usize, Box<[u8]>),
+    /// A device wrote RAM (DMA): (start_addr, data). The harness copies it to the ref, which
+    /// has no devices.
+    DmaWrite(usize, Box<[u8]>),
 }
 
 pub trait BusObserver {
@@ -115,6 +119,16 @@ pub trait BusObserver {
         let _ = (addr, val);
     }
 
+    /// The hart took an interrupt this step.
+    #[inline(always)]
+    fn on_interrupt(&mut self) {}
+
+    /// A device wrote `data` to RAM at `addr`.
+    #[inline(always)]
+    fn on_dma_write(&mut self, addr: usize, data: Box<[u8]>) {
+        let _ = (addr, data);
+    }
+
     /// Take and clear all events this step (MMIO and/or memory writes). Default: empty.
     #[inline(always)]
     fn get_events_and_clear(&mut self) -> Vec<ObserverEvent> {
@@ -210,6 +224,14 @@ impl BusObserver for DifftestObserver {
         self.events.push(ObserverEvent::MmioAccess);
     }
 
+    fn on_interrupt(&mut self) {
+        self.events.push(ObserverEvent::MmioAccess);
+    }
+
+    fn on_dma_write(&mut self, addr: usize, data: Box<[u8]>) {
+        self.events.push(ObserverEvent::DmaWrite(addr, data));
+    }
+
     fn get_events_and_clear(&mut self) -> Vec<ObserverEvent> {
         std::mem::take(&mut self.events)
     }
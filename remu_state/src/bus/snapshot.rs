@@ -0,0 +1,249 @@
+//! Bus snapshots: RAM as shared pages plus opaque device state.
+//!
+//! Pages are `Arc`-shared with the previous snapshot whenever their contents are unchanged (and
+//! all-zero pages share one buffer), so a series of snapshots only pays for the pages written
+//! in between.
+
+use std::sync::Arc;
+
+use remu_isa::isa::RvIsa;
+
+use crate::bus::{Bus, BusObserver, PAGE_SIZE};
+use crate::{CheckpointError, Decoder, Encoder};
+
+/// One memory region: its base address and contents in `PAGE_SIZE` pages.
+#[derive(Debug, Clone)]
+pub struct RegionSnapshot {
+    pub base: usize,
+    pages: Box<[Arc<[u8]>]>,
+}
+
+impl RegionSnapshot {
+    /// `(address, contents)` of every page, in address order.
+    pub fn pages(&self) -> impl Iterator<Item = (usize, &[u8])> {
+        self.pages
+            .iter()
+            .enumerate()
+            .map(|(i, page)| (self.base + i * PAGE_SIZE, &page[..]))
+    }
+
+    pub fn size(&self) -> usize {
+        self.pages.len() * PAGE_SIZE
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BusSnapshot {
+    pub memory: Box<[RegionSnapshot]>,
+    /// Per device, in bus order: the bytes returned by its `save`.
+    pub devices: Box<[Vec<u8>]>,
+}
+
+impl BusSnapshot {
+    /// Pages not shared with `other` (all of them when `other` is `None`).
+    pub fn unique_pages(&self, other: Option<&BusSnapshot>) -> usize {
+        self.memory
+            .iter()
+            .enumerate()
+            .map(|(r, region)| {
+                let prev = other.and_then(|o| o.memory.get(r));
+                region
+                    .pages
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, page)| {
+                        !prev
+                            .and_then(|p| p.pages.get(*i))
+                            .is_some_and(|old| Arc::ptr_eq(old, page))
+                    })
+                    .count()
+            })
+            .sum()
+    }
+}
+
+static ZERO_PAGE: [u8; PAGE_SIZE] = [0; PAGE_SIZE];
+
+impl<I: RvIsa, O: BusObserver> Bus<I, O> {
+    /// Capture RAM and device state; pages equal to those of `prev` are shared with it.
+    pub fn snapshot(&self, prev: Option<&BusSnapshot>) -> BusSnapshot {
+        let mut zero: Option<Arc<[u8]>> = None;
+        let memory = self
+            .memory
+            .entries()
+            .iter()
+            .enumerate()
+            .map(|(r, entry)| {
+                let prev = prev
+                    .and_then(|p| p.memory.get(r))
+                    .filter(|p| p.base == entry.range.start);
+                let pages = entry
+                    .bytes()
+                    .chunks(PAGE_SIZE)
+                    .enumerate()
+                    .map(|(i, bytes)| {
+                        if let Some(old) = prev.and_then(|p| p.pages.get(i))
+                            && old[..] == *bytes
+                        {
+                            return Arc::clone(old);
+                        }
+                        if *bytes == ZERO_PAGE {
+                            return Arc::clone(zero.get_or_insert_with(|| Arc::from(bytes)));
+                        }
+                        Arc::from(bytes)
+                    })
+                    .collect();
+                RegionSnapshot {
+                    base: entry.range.start,
+                    pages,
+                }
+            })
+            .collect();
+        BusSnapshot {
+            memory,
+            devices: self.save_devices(),
+        }
+    }
+
+    /// Per device, in bus order: the bytes returned by its `save`.
+    pub fn save_devices(&self) -> Box<[Vec<u8>]> {
+        self.device
+            .iter()
+            .map(|(_, d)| d.save(self.instret))
+            .collect()
+    }
+
+    /// Overwrite RAM and device state from `snap`, which must come from a bus with the same
+    /// memory layout. Host storage is reused, so the D-cache stays valid.
+    pub fn restore(&mut self, snap: &BusSnapshot) {
+        for (entry, region) in self.memory.entries_mut().iter_mut().zip(&snap.memory) {
+            debug_assert_eq!(entry.range.start, region.base);
+            for (dst, page) in entry
+                .bytes_mut()
+                .chunks_mut(PAGE_SIZE)
+                .zip(region.pages.iter())
+            {
+                dst.copy_from_slice(&page[..dst.len()]);
+            }
+        }
+        for ((_, device), state) in self.device.iter_mut().zip(&snap.devices) {
+            device.restore(state, self.instret);
+        }
+        self.irq_dirty = true;
+        self.observer.get_events_and_clear();
+    }
+
+    /// Whether some memory keeps its contents across a guest reboot (flash arrays).
+    pub fn has_nonvolatile(&self) -> bool {
+        !self.nonvolatile.is_empty()
+    }
+
+    /// Guest reboot: in `boot`, the power-on snapshot to restore, replace that memory with
+    /// its contents in `live`.
+    pub fn keep_nonvolatile(&self, live: &BusSnapshot, boot: &mut BusSnapshot) {
+        for (region, live) in boot.memory.iter_mut().zip(&live.memory) {
+            if self.nonvolatile.iter().any(|r| r.start == region.base) {
+                *region = live.clone();
+            }
+        }
+    }
+
+    /// Guest reboot, once RAM is back to power-on: put back the device states saved before
+    /// (see [`save_devices`](Self::save_devices)), then reset every device.
+    pub fn reboot_devices(&mut self, states: &[Vec<u8>]) {
+        for ((_, device), state) in self.device.iter_mut().zip(states) {
+            device.restore(state, self.instret);
+            device.reset();
+        }
+        self.irq_dirty = true;
+    }
+
+    /// Memory regions and devices, for [`check_layout`](Self::check_layout) in checkpoint files.
+    pub(crate) fn encode_layout(&self, enc: &mut Encoder) {
+        let entries = self.memory.entries();
+        enc.u32(entries.len() as u32);
+        for entry in entries {
+            enc.str(&entry.name);
+            enc.u64(entry.range.start as u64);
+            enc.u64(entry.range.end as u64);
+        }
+        enc.u32(self.device.len() as u32);
+        for (base, device) in self.device.iter() {
+            enc.str(device.name());
+            enc.u64(*base as u64);
+        }
+    }
+
+    pub(crate) fn check_layout(&self, dec: &mut Decoder) -> Result<(), CheckpointError> {
+        let mismatch = |what, found: String, expected: String| {
+            Err(CheckpointError::LayoutMismatch {
+                what,
+                found,
+                expected,
+            })
+        };
+        let entries = self.memory.entries();
+        let regions = dec.u32()? as usize;
+        if regions != entries.len() {
+            return mismatch("memory regions", regions.to_string(), entries.len().to_string());
+        }
+        for entry in entries {
+            let (name, start, end) = (dec.str()?, dec.u64()?, dec.u64()?);
+            let expected = format!(
+                "{}@0x{:x}:0x{:x}",
+                entry.name, entry.range.start, entry.range.end
+            );
+            let found = format!("{name}@0x{start:x}:0x{end:x}");
+            if found != expected {
+                return mismatch("memory region", found, expected);
+            }
+        }
+        let devices = dec.u32()? as usize;
+        if devices != self.device.len() {
+            return mismatch("devices", devices.to_string(), self.device.len().to_string());
+        }
+        for (base, device) in self.device.iter() {
+            let (name, start) = (dec.str()?, dec.u64()?);
+            let expected = format!("{}@0x{:x}", device.name(), base);
+            let found = format!("{name}@0x{start:x}");
+            if found != expected {
+                return mismatch("device", found, expected);
+            }
+        }
+        Ok(())
+    }
+
+    /// RAM pages and device states of a checkpoint file whose layout matched this bus. Pages
+    /// left out of the file are zero.
+    pub(crate) fn decode_snapshot(&self, dec: &mut Decoder) -> Result<BusSnapshot, CheckpointError> {
+        let zero: Arc<[u8]> = Arc::from(&ZERO_PAGE[..]);
+        let mut memory = Vec::new();
+        for entry in self.memory.entries() {
+            let base = entry.range.start;
+            let mut pages = vec![Arc::clone(&zero); entry.bytes().len() / PAGE_SIZE];
+            for _ in 0..dec.u32()? {
+                let addr = dec.u64()? as usize;
+                let index = addr
+                    .checked_sub(base)
+                    .filter(|off| off % PAGE_SIZE == 0)
+                    .map(|off| off / PAGE_SIZE)
+                    .filter(|&i| i < pages.len())
+                    .ok_or(CheckpointError::Corrupt("page outside its region"))?;
+                pages[index] = Arc::from(dec.take(PAGE_SIZE)?);
+            }
+            memory.push(RegionSnapshot {
+                base,
+                pages: pages.into_boxed_slice(),
+            });
+        }
+        let mut devices = Vec::new();
+        for _ in 0..self.device.len() {
+            let len = dec.u32()? as usize;
+            devices.push(dec.take(len)?.to_vec());
+        }
+        Ok(BusSnapshot {
+            memory: memory.into_boxed_slice(),
+            devices: devices.into_boxed_slice(),
+        })
+    }
+}
@@ -0,0 +1,194 @@
+use std::ops::Range;
+
+use remu_types::{WatchAccess, WatchEvent};
+
+use crate::bus::BusObserver;
+
+#[derive(Debug, Clone)]
+struct MemWatch {
+    id: u32,
+    range: Range<usize>,
+    access: WatchAccess,
+}
+
+/// Memory watchpoints. Sits next to the profile's observer on the bus and is only consulted by
+/// guest accesses of steps compiled with `TraceFlags::WATCH`, which the harness selects while a
+/// watchpoint is set; other steps compile the check out.
+#[derive(Debug, Clone, Default)]
+pub struct WatchObserver {
+    watches: Vec<MemWatch>,
+    /// Old value of the pending write, captured by the bus before it lands.
+    old: Option<u128>,
+    /// First hit since the last `take_hit` (later accesses in the same step are dropped).
+    hit: Option<(u32, WatchEvent)>,
+}
+
+impl WatchObserver {
+    #[inline(always)]
+    pub fn is_armed(&self) -> bool {
+        !self.watches.is_empty()
+    }
+
+    pub fn set(&mut self, id: u32, range: Range<usize>, access: WatchAccess) {
+        self.watches.push(MemWatch { id, range, access });
+    }
+
+    /// Remove watchpoint `id`; false if it is not a memory watchpoint.
+    pub fn del(&mut self, id: u32) -> bool {
+        let len = self.watches.len();
+        self.watches.retain(|w| w.id != id);
+        if self.watches.is_empty() {
+            self.hit = None;
+        }
+        self.watches.len() != len
+    }
+
+    pub fn take_hit(&mut self) -> Option<(u32, WatchEvent)> {
+        self.hit.take()
+    }
+
+    /// Id of the first watchpoint covering this access.
+    #[inline(always)]
+    pub(crate) fn find(&self, addr: usize, size: usize, is_write: bool) -> Option<u32> {
+        self.watches
+            .iter()
+            .find(|w| {
+                w.access.covers(is_write) && addr < w.range.end && w.range.start < addr + size
+            })
+            .map(|w| w.id)
+    }
+
+    pub(crate) fn set_old(&mut self, old: Option<u128>) {
+        self.old = old;
+    }
+
+    fn on_read(&mut self, addr: usize, size: usize, value: u128) {
+        if self.hit.is_some() {
+            return;
+        }
+        if let Some(id) = self.find(addr, size, false) {
+            self.hit = Some((id, WatchEvent::Read { addr, size, value }));
+        }
+    }
+
+    fn on_write(&mut self, addr: usize, size: usize, new: u128) {
+        let old = self.old.take();
+        if self.hit.is_some() {
+            return;
+        }
+        if let Some(id) = self.find(addr, size, true) {
+            self.hit = Some((
+                id,
+                WatchEvent::Write {
+                    addr,
+                    size,
+                    old,
+                    new,
+                },
+            ));
+        }
+    }
+}
+
+impl BusObserver for WatchObserver {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn on_mem_read_8(&mut self, addr: usize, val: u8) {
+        self.on_read(addr, 1, val as u128);
+    }
+    fn on_mem_read_16(&mut self, addr: usize, val: u16) {
+        self.on_read(addr, 2, val as u128);
+    }
+    fn on_mem_read_32(&mut self, addr: usize, val: u32) {
+        self.on_read(addr, 4, val as u128);
+    }
+    fn on_mem_read_64(&mut self, addr: usize, val: u64) {
+        self.on_read(addr, 8, val as u128);
+    }
+    fn on_mem_read_128(&mut self, addr: usize, val: u128) {
+        self.on_read(addr, 16, val);
+    }
+
+    fn on_mem_write_8(&mut self, addr: usize, val: u8) {
+        self.on_write(addr, 1, val as u128);
+    }
+    fn on_mem_write_16(&mut self, addr: usize, val: u16) {
+        self.on_write(addr, 2, val as u128);
+    }
+    fn on_mem_write_32(&mut self, addr: usize, val: u32) {
+        self.on_write(addr, 4, val as u128);
+    }
+    fn on_mem_write_64(&mut self, addr: usize, val: u64) {
+        self.on_write(addr, 8, val as u128);
+    }
+    fn on_mem_write_128(&mut self, addr: usize, val: u128) {
+        self.on_write(addr, 16, val);
+    }
+
+    fn on_mmio_read_8(&mut self, addr: usize, val: u8) {
+        self.on_read(addr, 1, val as u128);
+    }
+    fn on_mmio_read_16(&mut self, addr: usize, val: u16) {
+        self.on_read(addr, 2, val as u128);
+    }
+    fn on_mmio_read_32(&mut self, addr: usize, val: u32) {
+        self.on_read(addr, 4, val as u128);
+    }
+    fn on_mmio_read_64(&mut self, addr: usize, val: u64) {
+        self.on_read(addr, 8, val as u128);
+    }
+    fn on_mmio_read_128(&mut self, addr: usize, val: u128) {
+        self.on_read(addr, 16, val);
+    }
+
+    fn on_mmio_write_8(&mut self, addr: usize, val: u8) {
+        self.on_write(addr, 1, val as u128);
+    }
+    fn on_mmio_write_16(&mut self, addr: usize, val: u16) {
+        self.on_write(addr, 2, val as u128);
+    }
+    fn on_mmio_write_32(&mut self, addr: usize, val: u32) {
+        self.on_write(addr, 4, val as u128);
+    }
+    fn on_mmio_write_64(&mut self, addr: usize, val: u64) {
+        self.on_write(addr, 8, val as u128);
+    }
+    fn on_mmio_write_128(&mut self, addr: usize, val: u128) {
+        self.on_write(addr, 16, val);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_by_access_and_overlap() {
+        let mut watch = WatchObserver::new();
+        watch.set(1, 0x100..0x104, WatchAccess::Write);
+        watch.on_mem_read_32(0x100, 7);
+        assert_eq!(watch.take_hit(), None);
+        watch.on_mem_write_8(0x104, 1);
+        assert_eq!(watch.take_hit(), None);
+
+        watch.set_old(Some(5));
+        watch.on_mem_write_16(0x0ff, 0xabcd);
+        watch.on_mem_write_8(0x101, 2);
+        assert_eq!(
+            watch.take_hit(),
+            Some((
+                1,
+                WatchEvent::Write {
+                    addr: 0x0ff,
+                    size: 2,
+                    old: Some(5),
+                    new: 0xabcd,
+                },
+            ))
+        );
+        assert!(watch.del(1));
+        assert!(!watch.is_armed());
+    }
+}
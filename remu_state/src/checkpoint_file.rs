@@ -0,0 +1,419 @@
+//! Checkpoint files: a [`StateSnapshot`] written to disk, to be restored by another process.
+//!
+//! Layout: the magic `REMUCKPT`, a little-endian `u32` format version, then a zlib stream with
+//! the ISA string, memory map and device list (checked on load), the instruction count,
+//! registers, the non-zero RAM pages and each device's saved state.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use remu_isa::isa::RvIsa;
+use remu_isa::isa::extension_v::VExtensionConfig;
+use remu_isa::isa::reg::{RegAccess, VectorCsrState, VrState};
+use thiserror::Error;
+
+use crate::reg::riscv::Csr;
+use crate::{State, StatePolicy, StateSnapshot};
+
+const MAGIC: &[u8; 8] = b"REMUCKPT";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("not a checkpoint file")]
+    BadMagic,
+
+    #[error("unsupported checkpoint version {0} (expected {VERSION})")]
+    UnsupportedVersion(u32),
+
+    #[error("checkpoint is for {found}, this simulator runs {expected}")]
+    IsaMismatch { found: String, expected: String },
+
+    #[error("checkpoint {what} differs: file has {found}, this simulator has {expected}")]
+    LayoutMismatch {
+        what: &'static str,
+        found: String,
+        expected: String,
+    },
+
+    #[error("corrupt checkpoint: {0}")]
+    Corrupt(&'static str),
+}
+
+impl<P: StatePolicy> State<P> {
+    /// Write `snap`, taken at instruction `position`, to `path`. The memory map and device list
+    /// recorded are this state's, so `snap` must come from it.
+    pub fn write_checkpoint(
+        &self,
+        snap: &StateSnapshot<P::ISA>,
+        position: u64,
+        path: &Path,
+    ) -> Result<(), CheckpointError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        let mut z = ZlibEncoder::new(out, Compression::default());
+
+        let mut enc = Encoder(Vec::new());
+        enc.str(P::ISA::ISA_STR);
+        enc.u64(position);
+        encode_regs::<P::ISA>(&mut enc, snap);
+        self.bus.encode_layout(&mut enc);
+        z.write_all(&enc.0)?;
+        enc.0.clear();
+        for region in &snap.bus.memory {
+            let pages: Vec<_> = region
+                .pages()
+                .filter(|(_, page)| page.iter().any(|&b| b != 0))
+                .collect();
+            enc.u32(pages.len() as u32);
+            for (addr, page) in pages {
+                enc.u64(addr as u64);
+                enc.bytes(page);
+            }
+            z.write_all(&enc.0)?;
+            enc.0.clear();
+        }
+        for state in &snap.bus.devices {
+            enc.u32(state.len() as u32);
+            enc.bytes(state);
+        }
+        z.write_all(&enc.0)?;
+        z.finish()?.flush()?;
+        Ok(())
+    }
+
+    /// Read a checkpoint written by [`write_checkpoint`](Self::write_checkpoint), checking it
+    /// matches this state's ISA, memory map and devices. Returns the snapshot and its position.
+    pub fn read_checkpoint(
+        &self,
+        path: &Path,
+    ) -> Result<(StateSnapshot<P::ISA>, u64), CheckpointError> {
+        let mut input = BufReader::new(File::open(path)?);
+        let mut header = [0u8; 12];
+        input
+            .read_exact(&mut header)
+            .map_err(|_| CheckpointError::BadMagic)?;
+        if header[..8] != MAGIC[..] {
+            return Err(CheckpointError::BadMagic);
+        }
+        let version = u32::from_le_bytes(header[8..].try_into().unwrap());
+        if version != VERSION {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+        let mut data = Vec::new();
+        ZlibDecoder::new(input)
+            .read_to_end(&mut data)
+            .map_err(|_| CheckpointError::Corrupt("bad compressed data"))?;
+
+        let mut dec = Decoder(&data);
+        let isa = dec.str()?;
+        if isa != P::ISA::ISA_STR {
+            return Err(CheckpointError::IsaMismatch {
+                found: isa.to_string(),
+                expected: P::ISA::ISA_STR.to_string(),
+            });
+        }
+        let position = dec.u64()?;
+        let (pc, gpr, fpr, vr, csr) = decode_regs::<P::ISA>(&mut dec)?;
+        self.bus.check_layout(&mut dec)?;
+        let bus = self.bus.decode_snapshot(&mut dec)?;
+        if !dec.0.is_empty() {
+            return Err(CheckpointError::Corrupt("trailing data"));
+        }
+        let snap = StateSnapshot {
+            pc,
+            gpr,
+            fpr,
+            vr,
+            csr,
+            bus,
+        };
+        Ok((snap, position))
+    }
+}
+
+fn encode_regs<I: RvIsa>(enc: &mut Encoder, snap: &StateSnapshot<I>) {
+    enc.u32(*snap.pc);
+    for i in 0..32 {
+        enc.u32(snap.gpr.raw_read(i));
+    }
+    let fprs = if I::HAS_F { 32 } else { 0 };
+    enc.u32(fprs as u32);
+    for i in 0..fprs {
+        enc.u32(snap.fpr.raw_read(i));
+    }
+    let csr = &snap.csr;
+    for v in [
+        csr.mstatus,
+        csr.mie,
+        csr.mtvec,
+        csr.mscratch,
+        csr.mepc,
+        csr.mcause,
+        csr.mtval,
+        csr.mip,
+    ] {
+        enc.u32(v);
+    }
+    let vector = &csr.vector;
+    for v in [
+        vector.vstart(),
+        vector.vxsat(),
+        vector.vxrm(),
+        vector.vl(),
+        vector.vtype(),
+    ] {
+        enc.u32(v);
+    }
+    let vr = snap.vr.raw_bytes();
+    enc.u32(vr.len() as u32);
+    enc.bytes(vr);
+}
+
+type Regs<I> = (
+    <I as RvIsa>::PcState,
+    <I as RvIsa>::GprState,
+    <I as RvIsa>::FprState,
+    <<I as RvIsa>::VConfig as VExtensionConfig>::VrState,
+    Csr<<I as RvIsa>::VConfig>,
+);
+
+fn decode_regs<I: RvIsa>(dec: &mut Decoder) -> Result<Regs<I>, CheckpointError> {
+    let pc = I::PcState::from(dec.u32()?);
+    let mut gpr = I::GprState::default();
+    for i in 0..32 {
+        gpr.raw_write(i, dec.u32()?);
+    }
+    let mut fpr = I::FprState::default();
+    let fprs = if I::HAS_F { 32 } else { 0 };
+    if dec.u32()? != fprs as u32 {
+        return Err(CheckpointError::Corrupt("floating-point register count"));
+    }
+    for i in 0..fprs {
+        fpr.raw_write(i, dec.u32()?);
+    }
+    let mut csr = Csr::<I::VConfig>::default();
+    for field in [
+        &mut csr.mstatus,
+        &mut csr.mie,
+        &mut csr.mtvec,
+        &mut csr.mscratch,
+        &mut csr.mepc,
+        &mut csr.mcause,
+        &mut csr.mtval,
+        &mut csr.mip,
+    ] {
+        *field = dec.u32()?;
+    }
+    let vector = &mut csr.vector;
+    vector.set_vstart(dec.u32()?);
+    vector.set_vxsat(dec.u32()?);
+    vector.set_vxrm(dec.u32()?);
+    vector.set_vl(dec.u32()?);
+    vector.set_vtype(dec.u32()?);
+
+    let mut vr = <I::VConfig as VExtensionConfig>::VrState::default();
+    let vlenb = vr.raw_bytes().len() / 32;
+    if dec.u32()? as usize != vlenb * 32 {
+        return Err(CheckpointError::Corrupt("vector register file size"));
+    }
+    for i in 0..32 {
+        let reg = dec.take(vlenb)?;
+        vr.raw_write(i, reg);
+    }
+    Ok((pc, gpr, fpr, vr, csr))
+}
+
+pub(crate) struct Encoder(pub(crate) Vec<u8>);
+
+impl Encoder {
+    pub(crate) fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub(crate) fn bytes(&mut self, v: &[u8]) {
+        self.0.extend_from_slice(v);
+    }
+
+    pub(crate) fn str(&mut self, v: &str) {
+        self.u32(v.len() as u32);
+        self.bytes(v.as_bytes());
+    }
+}
+
+pub(crate) struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], CheckpointError> {
+        if self.0.len() < len {
+            return Err(CheckpointError::Corrupt("unexpected end of data"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, CheckpointError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, CheckpointError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn str(&mut self) -> Result<&'a str, CheckpointError> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.take(len)?).map_err(|_| CheckpointError::Corrupt("bad string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::ops::Range;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use clap::Parser;
+    use remu_isa::isa::extension_enum::{RV32I, RV32IM};
+    use remu_isa::isa::reg::Gpr;
+    use remu_types::{AllUsize, DynDiagError, Tracer};
+
+    use super::*;
+    use crate::{StateFastProfile, StateOption};
+
+    struct Quiet;
+
+    impl Tracer for Quiet {
+        fn print(&self, _: &str) {}
+        fn mem_print(&self, _: usize, _: &[u8], _: Result<(), Box<dyn DynDiagError>>) {}
+        fn mem_show(&self, _: usize, _: Result<AllUsize, Box<dyn DynDiagError>>) {}
+        fn mem_show_map(&self, _: Vec<(String, Range<usize>)>) {}
+        fn reg_print(&self, _: &[(Gpr, u32); 32], _: Range<usize>) {}
+        fn reg_show(&self, _: Gpr, _: u32) {}
+        fn disasm(&self, _: u64, _: u32) {}
+    }
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        opt: StateOption,
+    }
+
+    const RAM: &str = "ram@0x8000_0000:0x8001_0000";
+
+    fn state<P: StatePolicy>(mem: &str) -> State<P> {
+        let args = Args::parse_from(["remu", "--mem", mem, "--uart-input", "none"]);
+        State::new(args.opt, Rc::new(RefCell::new(Quiet)), true)
+    }
+
+    /// A file in the temp directory, removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let name = format!("remu-{}-{name}.ckpt", std::process::id());
+            Self(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn written(name: &str) -> TempFile {
+        let mut src = state::<StateFastProfile<RV32I>>(RAM);
+        src.reg.gpr.raw_write(10, 0x1234_5678);
+        src.reg.csr.mepc = 0x8000_0040;
+        src.bus.write_bytes(0x8000_0000, &[0x13, 0, 0, 0]).unwrap();
+        let snap = src.snapshot(None);
+        let file = TempFile::new(name);
+        src.write_checkpoint(&snap, 42, &file.0).unwrap();
+        file
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut src = state::<StateFastProfile<RV32I>>(RAM);
+        src.reg.pc = 0x8000_0100.into();
+        src.reg.gpr.raw_write(10, 0x1234_5678);
+        src.reg.csr.mepc = 0x8000_0040;
+        src.bus.write_bytes(0x8000_0000, &[0x13, 0, 0, 0]).unwrap();
+        src.bus.write_bytes(0x8000_3ffc, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let snap = src.snapshot(None);
+        let file = TempFile::new("round-trip");
+        src.write_checkpoint(&snap, 42, &file.0).unwrap();
+
+        let mut dst = state::<StateFastProfile<RV32I>>(RAM);
+        let (loaded, position) = dst.read_checkpoint(&file.0).unwrap();
+        assert_eq!(position, 42);
+        assert_eq!(loaded.bus.devices, snap.bus.devices);
+        dst.restore(&loaded);
+        assert_eq!(*dst.reg.pc, 0x8000_0100);
+        assert_eq!(dst.reg.gpr.raw_read(10), 0x1234_5678);
+        assert_eq!(dst.reg.csr.mepc, 0x8000_0040);
+        let mut ram = [0u8; 12];
+        dst.bus.read_bytes(0x8000_3ffc, &mut ram[..8]).unwrap();
+        dst.bus.read_bytes(0x8000_0000, &mut ram[8..]).unwrap();
+        assert_eq!(ram, [1, 2, 3, 4, 5, 6, 7, 8, 0x13, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let dst = state::<StateFastProfile<RV32I>>(RAM);
+        let file = written("header");
+        let good = std::fs::read(&file.0).unwrap();
+
+        std::fs::write(&file.0, b"not a checkpoint").unwrap();
+        assert!(matches!(
+            dst.read_checkpoint(&file.0),
+            Err(CheckpointError::BadMagic)
+        ));
+
+        let mut newer = good.clone();
+        newer[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        std::fs::write(&file.0, &newer).unwrap();
+        assert!(matches!(
+            dst.read_checkpoint(&file.0),
+            Err(CheckpointError::UnsupportedVersion(v)) if v == VERSION + 1
+        ));
+
+        std::fs::write(&file.0, &good[..good.len() / 2]).unwrap();
+        assert!(matches!(
+            dst.read_checkpoint(&file.0),
+            Err(CheckpointError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_other_machines() {
+        let file = written("machine");
+        let isa = state::<StateFastProfile<RV32IM>>(RAM);
+        assert!(matches!(
+            isa.read_checkpoint(&file.0),
+            Err(CheckpointError::IsaMismatch { .. })
+        ));
+        let ram = state::<StateFastProfile<RV32I>>("ram@0x8000_0000:0x8002_0000");
+        assert!(matches!(
+            ram.read_checkpoint(&file.0),
+            Err(CheckpointError::LayoutMismatch {
+                what: "memory region",
+                ..
+            })
+        ));
+    }
+}
@@ -1,7 +1,9 @@
 use std::backtrace::Backtrace;
+use remu_fmt::ExprError;
+use remu_isa::isa::reg::Mcause;
 use thiserror::Error;
 
-use crate::bus::BusError;
+use crate::bus::{AccessKind, BusError};
 
 #[derive(Debug, Error)]
 pub enum StateError {
@@ -21,6 +23,20 @@ pub enum StateError {
         csr_addr: u16,
         imm_raw: u32,
     },
+
+    /// Trap taken with `mtvec` = 0 and nothing mapped at address 0.
+    #[error(
+        "trap {cause} at PC 0x{epc:08x} with mtvec = 0: no trap handler installed (write mtvec before the first trap)"
+    )]
+    NoTrapHandler { cause: Mcause, epc: u32 },
+
+    /// Execution stopped by a catchpoint, after trap entry (PC is already at the handler).
+    #[error("catchpoint hit: {cause} at 0x{epc:08x}")]
+    CatchpointHit { cause: Mcause, epc: u32 },
+
+    /// A command argument was not evaluated before the command ran.
+    #[error("{0}")]
+    Argument(#[from] ExprError),
 }
 
 impl From<BusError> for StateError {
@@ -35,7 +51,11 @@ impl StateError {
     pub fn backtrace(&self) -> Option<&Backtrace> {
         match self {
             StateError::BusError(b) => b.backtrace(),
-            StateError::BreakpointHit(_) | StateError::UnimplementedCsr { .. } => None,
+            StateError::BreakpointHit(_)
+            | StateError::UnimplementedCsr { .. }
+            | StateError::NoTrapHandler { .. }
+            | StateError::CatchpointHit { .. }
+            | StateError::Argument(_) => None,
         }
     }
 
@@ -46,7 +66,11 @@ impl StateError {
                 BusError::ProgramExit(ec) => Some(*ec),
                 _ => None,
             },
-            StateError::BreakpointHit(_) | StateError::UnimplementedCsr { .. } => None,
+            StateError::BreakpointHit(_)
+            | StateError::UnimplementedCsr { .. }
+            | StateError::NoTrapHandler { .. }
+            | StateError::CatchpointHit { .. }
+            | StateError::Argument(_) => None,
         }
     }
 
@@ -54,7 +78,54 @@ impl StateError {
     pub fn breakpoint_pc(&self) -> Option<u32> {
         match self {
             StateError::BreakpointHit(pc) => Some(*pc),
-            StateError::BusError(_) | StateError::UnimplementedCsr { .. } => None,
+            StateError::BusError(_)
+            | StateError::UnimplementedCsr { .. }
+            | StateError::NoTrapHandler { .. }
+            | StateError::CatchpointHit { .. }
+            | StateError::Argument(_) => None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn catchpoint(&self) -> Option<(Mcause, u32)> {
+        match self {
+            StateError::CatchpointHit { cause, epc } => Some((*cause, *epc)),
+            StateError::BusError(_)
+            | StateError::BreakpointHit(_)
+            | StateError::UnimplementedCsr { .. }
+            | StateError::NoTrapHandler { .. }
+            | StateError::Argument(_) => None,
         }
     }
+
+    #[inline(always)]
+    pub fn is_reboot(&self) -> bool {
+        matches!(self, StateError::BusError(b) if matches!(b.as_ref(), BusError::Reboot))
+    }
+
+    /// Kind and address of an access the memory attributes forbid.
+    #[inline(always)]
+    pub fn access_fault(&self) -> Option<(AccessKind, usize)> {
+        match self {
+            StateError::BusError(b) => match b.as_ref() {
+                BusError::AccessFault { kind, addr, .. } => Some((*kind, *addr)),
+                _ => None,
+            },
+            StateError::BreakpointHit(_)
+            | StateError::UnimplementedCsr { .. }
+            | StateError::NoTrapHandler { .. }
+            | StateError::CatchpointHit { .. }
+            | StateError::Argument(_) => None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_watchdog_timeout(&self) -> bool {
+        matches!(self, StateError::BusError(b) if matches!(b.as_ref(), BusError::WatchdogTimeout))
+    }
+
+    #[inline(always)]
+    pub fn is_escape_key(&self) -> bool {
+        matches!(self, StateError::BusError(b) if matches!(b.as_ref(), BusError::EscapeKey))
+    }
 }
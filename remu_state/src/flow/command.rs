@@ -1,3 +1,5 @@
+use remu_fmt::{ExprContext, ExprError};
+
 use crate::{bus::BusCmd, reg::RegCmd};
 
 #[derive(Debug, clap::Subcommand)]
@@ -14,3 +16,14 @@ pub enum StateCmd {
         subcmd: BusCmd,
     },
 }
+
+impl StateCmd {
+    /// Evaluate the numeric arguments (see [`remu_fmt::NumArg`]); done by whoever parsed the
+    /// command, right before it runs.
+    pub fn resolve_args(&mut self, ctx: &mut dyn ExprContext) -> Result<(), ExprError> {
+        match self {
+            StateCmd::Reg { subcmd } => subcmd.resolve_args(ctx),
+            StateCmd::Bus { subcmd } => subcmd.resolve_args(ctx),
+        }
+    }
+}
@@ -6,7 +6,7 @@ use crate::{bus::Bus, reg::riscv::RiscvReg};
 
 remu_macro::mod_pub!(reg, bus);
 remu_macro::mod_pub_flat!(prelude, flow);
-remu_macro::mod_flat!(error);
+remu_macro::mod_flat!(error, snapshot, checkpoint_file);
 
 pub struct State<P: StatePolicy> {
     pub bus: Bus<P::ISA, P::Observer>,
@@ -17,8 +17,8 @@ pub struct State<P: StatePolicy> {
 impl<P: StatePolicy> State<P> {
     pub fn new(opt: StateOption, tracer: remu_types::TracerDyn, is_dut: bool) -> Self {
         Self {
+            reg: RiscvReg::new(opt.reg, opt.bus.dtb, tracer.clone()),
             bus: Bus::new(opt.bus, tracer.clone(), is_dut),
-            reg: RiscvReg::new(opt.reg, tracer.clone()),
             _marker: PhantomData,
         }
     }
@@ -26,7 +26,7 @@ impl<P: StatePolicy> State<P> {
     pub fn execute(&mut self, subcmd: &StateCmd) -> Result<(), StateError> {
         match subcmd {
             StateCmd::Bus { subcmd } => self.bus.execute(subcmd)?,
-            StateCmd::Reg { subcmd } => self.reg.execute(subcmd),
+            StateCmd::Reg { subcmd } => self.reg.execute(subcmd)?,
         }
         Ok(())
     }
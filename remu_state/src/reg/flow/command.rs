@@ -1,6 +1,6 @@
 use std::ops::Range;
 
-use remu_fmt::{parse_byte_vec, parse_prefixed_uint};
+use remu_fmt::{ExprContext, ExprError, NumArg, parse_byte_vec, parse_prefixed_uint};
 use remu_isa::isa::reg::{Csr as CsrReg, Fpr, Gpr};
 
 fn parse_vr_index(s: &str) -> Result<usize, String> {
@@ -77,8 +77,8 @@ pub enum GprRegCmd {
         #[arg()]
         index: Gpr,
 
-        #[arg(value_parser = parse_prefixed_uint::<u32>)]
-        value: u32,
+        #[arg(value_parser = NumArg::<u32>::parse)]
+        value: NumArg<u32>,
     },
 }
 
@@ -99,8 +99,8 @@ pub enum FprRegCmd {
         #[arg()]
         index: Fpr,
 
-        #[arg(value_parser = parse_prefixed_uint::<u32>)]
-        value: u32,
+        #[arg(value_parser = NumArg::<u32>::parse)]
+        value: NumArg<u32>,
     },
 }
 
@@ -137,8 +137,8 @@ pub enum CsrRegCmd {
         #[arg()]
         index: CsrReg,
 
-        #[arg(value_parser = parse_prefixed_uint::<u32>)]
-        value: u32,
+        #[arg(value_parser = NumArg::<u32>::parse)]
+        value: NumArg<u32>,
     },
 }
 
@@ -147,7 +147,28 @@ pub enum PcRegCmd {
     Read,
 
     Write {
-        #[arg(value_parser = parse_prefixed_uint::<u32>)]
-        value: u32,
+        #[arg(value_parser = NumArg::<u32>::parse)]
+        value: NumArg<u32>,
     },
 }
+
+impl RegCmd {
+    /// Evaluate the numeric arguments (see [`NumArg`]).
+    pub fn resolve_args(&mut self, ctx: &mut dyn ExprContext) -> Result<(), ExprError> {
+        match self {
+            RegCmd::Gpr {
+                subcmd: GprRegCmd::Write { value, .. },
+            }
+            | RegCmd::Fpr {
+                subcmd: FprRegCmd::Write { value, .. },
+            }
+            | RegCmd::Csr {
+                subcmd: CsrRegCmd::Write { value, .. },
+            }
+            | RegCmd::Pc {
+                subcmd: PcRegCmd::Write { value },
+            } => value.resolve(ctx),
+            _ => Ok(()),
+        }
+    }
+}
@@ -63,6 +63,9 @@ impl<C: CsrConfig> Csr<C> {
     const MSTATUS_SD: u32 = 1 << 31;
     const MSTATUS_MPP_MASK: u32 = 3 << 11;
     const MSTATUS_MPP_MACHINE: u32 = 3 << 11;
+    // --- mip/mie bits ---
+    /// Machine external interrupt, driven by the PLIC.
+    const MIP_MEIP: u32 = 1 << 11;
 
     #[inline(always)]
     pub fn mstatus_mie(&self) -> bool {
@@ -110,6 +113,30 @@ impl<C: CsrConfig> Csr<C> {
         self.set_mstatus_mpp(Self::MSTATUS_MPP_MACHINE >> 11);
     }
 
+    /// `mret`: MIE <- MPIE, MPIE <- 1, MPP <- M (only M-mode is implemented).
+    #[inline(always)]
+    pub fn mstatus_apply_trap_return(&mut self) {
+        let mpie = self.mstatus_mpie();
+        self.set_mstatus_mie(mpie);
+        self.set_mstatus_mpie(true);
+        self.set_mstatus_mpp(Self::MSTATUS_MPP_MACHINE >> 11);
+    }
+
+    #[inline(always)]
+    pub fn set_mip_meip(&mut self, v: bool) {
+        if v {
+            self.mip |= Self::MIP_MEIP;
+        } else {
+            self.mip &= !Self::MIP_MEIP;
+        }
+    }
+
+    /// Machine external interrupt pending, enabled in `mie` and globally (`mstatus.MIE`).
+    #[inline(always)]
+    pub fn external_interrupt_ready(&self) -> bool {
+        self.mstatus_mie() && self.mip & self.mie & Self::MIP_MEIP != 0
+    }
+
     /// `mstatus.VS` field (0=Off, 1=Initial, 2=Clean, 3=Dirty).
     #[inline(always)]
     pub fn mstatus_vs(&self) -> u32 {
@@ -148,6 +175,17 @@ impl<C: CsrConfig> Csr<C> {
         self.mtvec & !3u32
     }
 
+    /// Handler address for `cause`: vectored mode (`mtvec[1:0] = 1`) offsets interrupts by 4 * code.
+    #[inline(always)]
+    pub fn mtvec_target(&self, cause: u32) -> u32 {
+        let is_interrupt = (cause >> 31) != 0;
+        if is_interrupt && (self.mtvec & 3) == 1 {
+            self.mtvec_base().wrapping_add((cause & 0x7FFF_FFFF) << 2)
+        } else {
+            self.mtvec_base()
+        }
+    }
+
     pub fn read(&self, reg: CsrKind) -> u32 {
         match reg {
             CsrKind::Mstatus => self.mstatus,
@@ -1,3 +1,4 @@
+use remu_fmt::ExprError;
 use remu_isa::isa::extension_v::VExtensionConfig;
 use remu_isa::isa::reg::{Csr as CsrKind, Gpr, VrState as VrStateTrait};
 use remu_isa::isa::{RvIsa, reg::RegAccess};
@@ -15,10 +16,17 @@ pub struct RiscvReg<I: RvIsa> {
 }
 
 impl<I: RvIsa> RiscvReg<I> {
-    pub(crate) fn new(opt: RegOption, tracer: remu_types::TracerDyn) -> Self {
+    /// With a device tree at `dtb`, the hart starts as the boot protocol has it: `a0` holds
+    /// the hart ID and `a1` the device tree's address.
+    pub(crate) fn new(opt: RegOption, dtb: Option<usize>, tracer: remu_types::TracerDyn) -> Self {
+        let mut gpr = I::GprState::default();
+        if let Some(dtb) = dtb {
+            gpr.raw_write(Gpr::A0.idx(), 0);
+            gpr.raw_write(Gpr::A1.idx(), dtb as u32);
+        }
         Self {
             pc: opt.init_pc.into(),
-            gpr: Default::default(),
+            gpr,
             fpr: Default::default(),
             vr: Default::default(),
             csr: Csr::default(),
@@ -36,27 +44,29 @@ impl<I: RvIsa> RiscvReg<I> {
         }
     }
 
-    pub(crate) fn execute(&mut self, cmd: &RegCmd) {
+    pub(crate) fn execute(&mut self, cmd: &RegCmd) -> Result<(), ExprError> {
         match cmd {
-            RegCmd::Pc { subcmd } => self.execute_pc(subcmd),
+            RegCmd::Pc { subcmd } => self.execute_pc(subcmd)?,
             RegCmd::Vr { subcmd } => self.execute_vr(subcmd),
-            RegCmd::Csr { subcmd } => self.execute_csr(subcmd),
-            RegCmd::Gpr { subcmd } => self.execute_gpr(subcmd),
-            RegCmd::Fpr { subcmd } => self.execute_fpr(subcmd),
+            RegCmd::Csr { subcmd } => self.execute_csr(subcmd)?,
+            RegCmd::Gpr { subcmd } => self.execute_gpr(subcmd)?,
+            RegCmd::Fpr { subcmd } => self.execute_fpr(subcmd)?,
         }
+        Ok(())
     }
-    fn execute_pc(&mut self, cmd: &PcRegCmd) {
+    fn execute_pc(&mut self, cmd: &PcRegCmd) -> Result<(), ExprError> {
         match cmd {
             PcRegCmd::Read => {
                 self.tracer.borrow().reg_show_pc(*self.pc);
             }
             PcRegCmd::Write { value } => {
-                *self.pc = (*value).into();
+                *self.pc = value.get()?;
             }
         }
+        Ok(())
     }
 
-    fn execute_csr(&mut self, cmd: &CsrRegCmd) {
+    fn execute_csr(&mut self, cmd: &CsrRegCmd) -> Result<(), ExprError> {
         match cmd {
             CsrRegCmd::Read { index } => {
                 let value = self.read_csr(*index);
@@ -65,12 +75,13 @@ impl<I: RvIsa> RiscvReg<I> {
                     .print(&format!("{} = {:#010x}", index, value));
             }
             CsrRegCmd::Write { index, value } => {
-                self.csr.write(*index, *value);
+                self.csr.write(*index, value.get()?);
             }
         }
+        Ok(())
     }
 
-    fn execute_gpr(&mut self, cmd: &GprRegCmd) {
+    fn execute_gpr(&mut self, cmd: &GprRegCmd) -> Result<(), ExprError> {
         match cmd {
             GprRegCmd::Read { index } => {
                 self.tracer
@@ -85,12 +96,13 @@ impl<I: RvIsa> RiscvReg<I> {
                 self.tracer.borrow().reg_print(&regs, range.clone());
             }
             GprRegCmd::Write { index, value } => {
-                self.gpr.raw_write(index.idx(), *value);
+                self.gpr.raw_write(index.idx(), value.get()?);
             }
         }
+        Ok(())
     }
 
-    fn execute_fpr(&mut self, cmd: &FprRegCmd) {
+    fn execute_fpr(&mut self, cmd: &FprRegCmd) -> Result<(), ExprError> {
         match cmd {
             FprRegCmd::Read { index } => {
                 let i = index.idx();
@@ -103,9 +115,10 @@ impl<I: RvIsa> RiscvReg<I> {
                 self.tracer.borrow().reg_print_fpr(&regs, range.clone());
             }
             FprRegCmd::Write { index, value } => {
-                self.fpr.raw_write(index.idx(), *value);
+                self.fpr.raw_write(index.idx(), value.get()?);
             }
         }
+        Ok(())
     }
 
     fn execute_vr(&mut self, cmd: &VrRegCmd) {
@@ -0,0 +1,55 @@
+//! Whole-state snapshots: every register group plus the bus (see [`BusSnapshot`]).
+
+use remu_isa::isa::RvIsa;
+use remu_isa::isa::extension_v::VExtensionConfig;
+
+use crate::bus::BusSnapshot;
+use crate::reg::riscv::Csr;
+use crate::{State, StatePolicy};
+
+pub struct StateSnapshot<I: RvIsa> {
+    pub pc: I::PcState,
+    pub gpr: I::GprState,
+    pub fpr: I::FprState,
+    pub vr: <I::VConfig as VExtensionConfig>::VrState,
+    pub csr: Csr<I::VConfig>,
+    pub bus: BusSnapshot,
+}
+
+impl<I: RvIsa> Clone for StateSnapshot<I> {
+    fn clone(&self) -> Self {
+        Self {
+            pc: self.pc,
+            gpr: self.gpr,
+            fpr: self.fpr,
+            vr: self.vr.clone(),
+            csr: self.csr.clone(),
+            bus: self.bus.clone(),
+        }
+    }
+}
+
+impl<P: StatePolicy> State<P> {
+    /// Capture the state; memory pages unchanged since `prev` are shared with it.
+    pub fn snapshot(&self, prev: Option<&StateSnapshot<P::ISA>>) -> StateSnapshot<P::ISA> {
+        let reg = &self.reg;
+        StateSnapshot {
+            pc: reg.pc,
+            gpr: reg.gpr,
+            fpr: reg.fpr,
+            vr: reg.vr.clone(),
+            csr: reg.csr.clone(),
+            bus: self.bus.snapshot(prev.map(|p| &p.bus)),
+        }
+    }
+
+    pub fn restore(&mut self, snap: &StateSnapshot<P::ISA>) {
+        let reg = &mut self.reg;
+        reg.pc = snap.pc;
+        reg.gpr = snap.gpr;
+        reg.fpr = snap.fpr;
+        reg.vr = snap.vr.clone();
+        reg.csr = snap.csr.clone();
+        self.bus.restore(&snap.bus);
+    }
+}
@@ -0,0 +1,18 @@
+//! Breakpoint objects as listed by `breakpoint print`.
+
+/// A user breakpoint. Backends only patch addresses; id, condition and counters are kept by
+/// the debugger, so every backend stops under the same rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub id: u32,
+    pub addr: u32,
+    pub enabled: bool,
+    /// One-shot (`tbreak`): deleted the first time it stops.
+    pub temporary: bool,
+    /// Condition source; the breakpoint only counts a hit while it evaluates non-zero.
+    pub condition: Option<String>,
+    /// Remaining hits to pass over before stopping.
+    pub ignore: u32,
+    /// Hits with the condition true, ignored ones included.
+    pub hits: u64,
+}
@@ -1,4 +1,4 @@
-//! Program exit semantics: GOOD (code 0) vs BAD (non-zero).
+//! Program exit semantics: GOOD (status 0) vs BAD (a status chosen by the guest).
 
 use std::fmt;
 
@@ -7,15 +7,51 @@ use std::fmt;
 pub enum ExitCode {
     /// Success.
     Good,
-    /// Failure.
-    Bad,
+    /// Failure, with the guest's status (`code` of a `(code << 16) | 0x3333` finisher write).
+    Bad(u16),
+}
+
+/// Largest guest status a failure reports unchanged: 1 and the codes above it are left to the
+/// runner (e.g. `--batch` uses 1 for an output mismatch and 122..=130 for its own failures).
+pub const GUEST_STATUS_MAX: u8 = 120;
+
+/// Process status of a failure whose guest status is 0, 1 or above [`GUEST_STATUS_MAX`].
+pub const GUEST_STATUS_OTHER: u8 = GUEST_STATUS_MAX + 1;
+
+impl ExitCode {
+    /// Exit status for the host process: 0 on success; on failure the guest's status when it
+    /// is in `2..=GUEST_STATUS_MAX`, else [`GUEST_STATUS_OTHER`].
+    pub fn process_status(self) -> u8 {
+        match self {
+            ExitCode::Good => 0,
+            ExitCode::Bad(status) => match u8::try_from(status) {
+                Ok(status @ 2..=GUEST_STATUS_MAX) => status,
+                _ => GUEST_STATUS_OTHER,
+            },
+        }
+    }
 }
 
 impl fmt::Display for ExitCode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ExitCode::Good => write!(f, "good"),
-            ExitCode::Bad => write!(f, "bad"),
+            ExitCode::Bad(status) => write!(f, "bad (status {status})"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn process_status_avoids_reserved_codes() {
+        assert_eq!(ExitCode::Good.process_status(), 0);
+        assert_eq!(ExitCode::Bad(2).process_status(), 2);
+        assert_eq!(ExitCode::Bad(120).process_status(), 120);
+        for status in [0, 1, 121, 124, 130, 255, 0x100, 0x102, u16::MAX] {
+            assert_eq!(ExitCode::Bad(status).process_status(), GUEST_STATUS_OTHER);
         }
     }
 }
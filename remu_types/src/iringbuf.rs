@@ -0,0 +1,122 @@
+//! Instruction ring buffer (iringbuf): the last N instructions run on the DUT, dumped on failure.
+
+/// One instruction record. Disassembly is produced by the tracer at dump time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IringbufEntry {
+    pub pc: u32,
+    pub inst: u32,
+    /// The instruction completed; `false` for the one a failure stopped on.
+    pub retired: bool,
+    /// GPR the instruction wrote (x0 excluded) and its value after retiring, as the simulator
+    /// decoded it; `None` if it did not retire or writes no GPR.
+    pub writeback: Option<(u8, u32)>,
+}
+
+/// Fixed-capacity ring of [`IringbufEntry`]; pushing past capacity overwrites the oldest entry.
+#[derive(Debug, Clone)]
+pub struct Iringbuf {
+    entries: Box<[IringbufEntry]>,
+    /// Next slot to write.
+    head: usize,
+    len: usize,
+}
+
+impl Iringbuf {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: vec![IringbufEntry::default(); capacity].into_boxed_slice(),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline(always)]
+    pub fn push(&mut self, entry: IringbufEntry) {
+        let cap = self.entries.len();
+        if cap == 0 {
+            return;
+        }
+        self.entries[self.head] = entry;
+        self.head = if self.head + 1 == cap {
+            0
+        } else {
+            self.head + 1
+        };
+        if self.len < cap {
+            self.len += 1;
+        }
+    }
+
+    /// Most recently pushed entry.
+    #[inline(always)]
+    pub fn last_mut(&mut self) -> Option<&mut IringbufEntry> {
+        if self.len == 0 {
+            return None;
+        }
+        let cap = self.entries.len();
+        let idx = if self.head == 0 {
+            cap - 1
+        } else {
+            self.head - 1
+        };
+        Some(&mut self.entries[idx])
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Entries from oldest to newest.
+    pub fn to_vec(&self) -> Vec<IringbufEntry> {
+        let cap = self.entries.len();
+        let start = (self.head + cap - self.len) % cap.max(1);
+        (0..self.len)
+            .map(|i| self.entries[(start + i) % cap])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pc: u32) -> IringbufEntry {
+        IringbufEntry {
+            pc,
+            inst: 0x0000_0013,
+            retired: true,
+            writeback: None,
+        }
+    }
+
+    #[test]
+    fn wraps_and_keeps_order() {
+        let mut ring = Iringbuf::new(3);
+        for pc in 0..5 {
+            ring.push(entry(pc * 4));
+        }
+        let pcs: Vec<u32> = ring.to_vec().iter().map(|e| e.pc).collect();
+        assert_eq!(pcs, vec![8, 12, 16]);
+        assert_eq!(ring.last_mut().map(|e| e.pc), Some(16));
+    }
+
+    #[test]
+    fn zero_capacity_is_noop() {
+        let mut ring = Iringbuf::new(0);
+        ring.push(entry(0));
+        assert!(ring.is_empty());
+        assert!(ring.to_vec().is_empty());
+        assert!(ring.last_mut().is_none());
+    }
+}
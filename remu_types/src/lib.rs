@@ -1,12 +1,23 @@
 remu_macro::mod_pub!(prelude);
-remu_macro::mod_flat!(difftest, exit_code, platform, trace_flags);
+remu_macro::mod_flat!(
+    breakpoint,
+    difftest,
+    exit_code,
+    iringbuf,
+    platform,
+    source,
+    stack_frame,
+    trace_flags,
+    trap,
+    watch
+);
 
 // Re-export from remu_isa (backward compat; new code should use remu_isa directly)
 pub use remu_isa::{AllUsize, Xlen, isa};
 
 use std::{cell::RefCell, error::Error, ops::Range, rc::Rc};
 
-use remu_isa::isa::reg::Gpr;
+use remu_isa::isa::reg::{Gpr, Mcause};
 
 pub trait DynDiagError: Error {}
 impl<T> DynDiagError for T where T: Error {}
@@ -40,11 +51,54 @@ pub trait Tracer {
 
     fn disasm(&self, pc: u64, inst: u32);
 
-    fn breakpoint_print(&self, addrs: &[u32]) {
-        let _ = addrs;
+    fn breakpoint_print(&self, breakpoints: &[Breakpoint]) {
+        let _ = breakpoints;
     }
 
     fn stat_print(&self, _entries: &[(String, String)]) {}
+
+    /// Exception trace (etrace): one line per trap entry / `mret`.
+    fn trap_print(&self, event: &TrapEvent) {
+        let _ = event;
+    }
+
+    fn catch_print(&self, causes: &[Mcause]) {
+        let _ = causes;
+    }
+
+    /// Instruction ring buffer dump, oldest first; the last entry is the most recent instruction.
+    fn iringbuf_print(&self, entries: &[IringbufEntry]) {
+        let _ = entries;
+    }
+
+    fn watch_print(&self, watches: &[(u32, WatchTarget)]) {
+        let _ = watches;
+    }
+
+    /// Line info for annotating [`Tracer::disasm`] with the source line of each new line.
+    fn set_source_lookup(&mut self, lookup: Rc<dyn SourceLookup>) {
+        let _ = lookup;
+    }
+
+    fn source_line(&self, line: &SourceLine) {
+        self.print(&line.to_string());
+    }
+
+    /// Guest call stack, innermost frame first.
+    fn backtrace_print(&self, frames: &[StackFrame]) {
+        for (i, frame) in frames.iter().enumerate() {
+            self.print(&format!("#{i:<2} {frame}"));
+        }
+    }
+
+    /// `list` output: numbered lines of `file`, `current` marks the line of the PC.
+    fn source_list(&self, file: &str, lines: &[(u32, String)], current: Option<u32>) {
+        self.print(file);
+        for (n, text) in lines {
+            let marker = if Some(*n) == current { "=>" } else { "  " };
+            self.print(&format!("{marker} {n:>5}  {text}"));
+        }
+    }
 }
 
 pub type TracerDyn = Rc<RefCell<dyn Tracer>>;
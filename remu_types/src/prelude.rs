@@ -3,9 +3,16 @@
 //! Rule: a symbol goes here iff it is imported by 2+ downstream crates,
 //! or is the main entry-point type of this crate.
 
+pub use crate::breakpoint::Breakpoint;
 pub use crate::difftest::DifftestMismatchItem;
 pub use crate::exit_code::ExitCode;
+pub use crate::iringbuf::{Iringbuf, IringbufEntry};
 pub use crate::platform::Platform;
+pub use crate::source::{SourceLine, SourceLookup};
+pub use crate::stack_frame::StackFrame;
 pub use crate::trace_flags::{TraceFlags, TraceKind};
+pub use crate::trap::TrapEvent;
+pub use crate::watch::{WatchAccess, WatchEvent, WatchHit, WatchTarget};
 pub use crate::{AllUsize, DifftestRef, RegGroup, TracerDyn};
 pub use remu_isa::Xlen;
+pub use remu_isa::isa::reg::Mcause;
@@ -0,0 +1,32 @@
+//! Source positions from DWARF line info, for annotating instruction traces and stops.
+
+use std::fmt;
+
+/// The source line an address belongs to (the innermost inlined position).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLine {
+    /// Innermost function, inlined ones included (demangled).
+    pub function: Option<String>,
+    pub file: String,
+    pub line: u32,
+    /// Line text, when the source file is readable.
+    pub text: Option<String>,
+}
+
+impl fmt::Display for SourceLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(function) = &self.function {
+            write!(f, "{function} at ")?;
+        }
+        write!(f, "{}:{}", self.file, self.line)?;
+        if let Some(text) = &self.text {
+            write!(f, "  {}", text.trim())?;
+        }
+        Ok(())
+    }
+}
+
+/// Address to source line lookup, provided by the debugger when the ELF carries line info.
+pub trait SourceLookup {
+    fn line_at(&self, pc: u64) -> Option<SourceLine>;
+}
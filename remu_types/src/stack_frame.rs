@@ -0,0 +1,53 @@
+//! Guest call stack frames, as printed by `backtrace`.
+
+use std::fmt;
+
+use crate::SourceLine;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    /// PC of the innermost frame, return address of the others.
+    pub pc: u32,
+    /// Enclosing ELF symbol and the offset of `pc` into it.
+    pub symbol: Option<(String, u32)>,
+    /// Source line of `pc` (of the call, for caller frames).
+    pub line: Option<SourceLine>,
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:08x} in ", self.pc)?;
+        match &self.symbol {
+            Some((name, offset)) => write!(f, "{name}+0x{offset:x}")?,
+            None => write!(f, "??")?,
+        }
+        if let Some(line) = &self.line {
+            write!(f, " at {}:{}", line.file, line.line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_shows_symbol_offset_and_line() {
+        let mut frame = StackFrame {
+            pc: 0x8000_0108,
+            symbol: Some(("main".into(), 8)),
+            line: None,
+        };
+        assert_eq!(frame.to_string(), "0x80000108 in main+0x8");
+        frame.line = Some(SourceLine {
+            function: None,
+            file: "src/main.rs".into(),
+            line: 12,
+            text: None,
+        });
+        assert_eq!(frame.to_string(), "0x80000108 in main+0x8 at src/main.rs:12");
+        frame.symbol = None;
+        assert_eq!(frame.to_string(), "0x80000108 in ?? at src/main.rs:12");
+    }
+}
@@ -8,6 +8,10 @@ pub enum TraceKind {
     Instruction = 0,
     /// Bit 1: waveform trace
     Wavetrace = 1,
+    /// Bit 2: exception trace (trap entry / return)
+    Exception = 2,
+    /// Bit 3: instruction ring buffer
+    Iringbuf = 3,
 }
 
 impl TraceKind {
@@ -22,7 +26,11 @@ impl TraceKind {
 /// Bit layout:
 /// - 0: Instruction trace (disassembly)
 /// - 1: Wave trace (waveform)
-/// - 2..: Reserved
+/// - 2: Exception trace (etrace). Traps are rare, so this bit is checked at runtime by the
+///   simulator (via `on_trace_change`) and is not part of the monomorphized `TRACE` set.
+/// - 3: Instruction ring buffer (iringbuf), recorded by the harness per step
+/// - 4: Watchpoint checks. Not a user trace: the harness sets it while any watchpoint exists.
+/// - 5..: Reserved
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(transparent)]
 pub struct TraceFlags(pub u64);
@@ -30,6 +38,12 @@ pub struct TraceFlags(pub u64);
 impl TraceFlags {
     pub const INSTRUCTION: u64 = 1 << 0;
     pub const WAVEFORM: u64 = 1 << 1;
+    pub const EXCEPTION: u64 = 1 << 2;
+    pub const IRINGBUF: u64 = 1 << 3;
+    pub const WATCH: u64 = 1 << 4;
+
+    /// Bits that select a `run_steps_impl::<TRACE>` instantiation.
+    pub const STEP_MASK: u64 = Self::INSTRUCTION | Self::WAVEFORM | Self::IRINGBUF | Self::WATCH;
 
     #[inline(always)]
     pub const fn new() -> Self {
@@ -58,6 +72,24 @@ impl TraceFlags {
         (flags & Self::WAVEFORM) != 0
     }
 
+    /// Bit 2: exception trace
+    #[inline(always)]
+    pub const fn exception(flags: u64) -> bool {
+        (flags & Self::EXCEPTION) != 0
+    }
+
+    /// Bit 3: instruction ring buffer
+    #[inline(always)]
+    pub const fn iringbuf(flags: u64) -> bool {
+        (flags & Self::IRINGBUF) != 0
+    }
+
+    /// Bit 4: watchpoint checks
+    #[inline(always)]
+    pub const fn watch(flags: u64) -> bool {
+        (flags & Self::WATCH) != 0
+    }
+
     #[inline(always)]
     pub fn set_instruction(&mut self, enable: bool) {
         if enable {
@@ -75,4 +107,31 @@ impl TraceFlags {
             self.0 &= !Self::WAVEFORM;
         }
     }
+
+    #[inline(always)]
+    pub fn set_exception(&mut self, enable: bool) {
+        if enable {
+            self.0 |= Self::EXCEPTION;
+        } else {
+            self.0 &= !Self::EXCEPTION;
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_iringbuf(&mut self, enable: bool) {
+        if enable {
+            self.0 |= Self::IRINGBUF;
+        } else {
+            self.0 &= !Self::IRINGBUF;
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_watch(&mut self, enable: bool) {
+        if enable {
+            self.0 |= Self::WATCH;
+        } else {
+            self.0 &= !Self::WATCH;
+        }
+    }
 }
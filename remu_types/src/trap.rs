@@ -0,0 +1,55 @@
+//! Trap entry / return records, reported to the tracer when exception trace (etrace) is on.
+
+use std::fmt;
+
+use remu_isa::isa::reg::Mcause;
+
+/// One trap event on the hart (M-mode only).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapEvent {
+    /// Trap taken: CSRs are already updated and PC points at `handler`.
+    Entry {
+        /// Raw `mcause` value written on entry.
+        cause: u32,
+        /// `mepc`: PC of the trapping instruction (or the interrupted one).
+        epc: u32,
+        /// `mtval`
+        tval: u32,
+        /// Handler address taken from `mtvec`.
+        handler: u32,
+    },
+    /// `mret` executed at `pc`, resuming at `mepc`.
+    Return {
+        /// Raw `mcause` value at the time of return.
+        cause: u32,
+        pc: u32,
+        epc: u32,
+    },
+}
+
+impl fmt::Display for TrapEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |cause: u32| {
+            Mcause::from_u32(cause)
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| "unknown".to_string())
+        };
+        match *self {
+            TrapEvent::Entry {
+                cause,
+                epc,
+                tval,
+                handler,
+            } => write!(
+                f,
+                "trap {} (mcause=0x{cause:08x}) mepc=0x{epc:08x} mtval=0x{tval:08x} -> handler 0x{handler:08x}",
+                name(cause)
+            ),
+            TrapEvent::Return { cause, pc, epc } => write!(
+                f,
+                "mret {} (mcause=0x{cause:08x}) at 0x{pc:08x} -> mepc 0x{epc:08x}",
+                name(cause)
+            ),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Data watchpoints: what is watched and what a hit looks like. Shared by the bus, harness and tracer.
+
+use std::fmt;
+
+use remu_isa::isa::reg::{Csr, Gpr};
+use strum::{Display, EnumString};
+
+/// Which memory accesses trigger a memory watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, Display)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum WatchAccess {
+    Read,
+    #[default]
+    Write,
+    /// Read or write.
+    Access,
+}
+
+impl WatchAccess {
+    #[inline(always)]
+    pub fn covers(self, is_write: bool) -> bool {
+        match self {
+            WatchAccess::Read => !is_write,
+            WatchAccess::Write => is_write,
+            WatchAccess::Access => true,
+        }
+    }
+}
+
+/// What a watchpoint is attached to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchTarget {
+    /// Byte range `start..start + len` on the bus.
+    Mem {
+        start: usize,
+        len: usize,
+        access: WatchAccess,
+    },
+    /// Stop when the GPR changes value.
+    Gpr(Gpr),
+    /// Stop when the CSR changes value.
+    Csr(Csr),
+}
+
+impl fmt::Display for WatchTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            WatchTarget::Mem { start, len, access } => {
+                write!(f, "{access} 0x{start:08x}..0x{:08x}", start + len)
+            }
+            WatchTarget::Gpr(reg) => write!(f, "gpr {reg}"),
+            WatchTarget::Csr(reg) => write!(f, "csr {reg}"),
+        }
+    }
+}
+
+/// The access or change that triggered a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchEvent {
+    Read {
+        addr: usize,
+        size: usize,
+        value: u128,
+    },
+    Write {
+        addr: usize,
+        size: usize,
+        /// `None` for MMIO writes (device registers are not read back).
+        old: Option<u128>,
+        new: u128,
+    },
+    /// Register value changed.
+    Changed { old: u32, new: u32 },
+}
+
+/// Watchpoint hit: the instruction at `pc` has retired.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchHit {
+    pub id: u32,
+    pub target: WatchTarget,
+    pub pc: u32,
+    pub event: WatchEvent,
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = |v: u128, size: usize| format!("0x{v:0width$x}", width = size * 2);
+        write!(f, "watchpoint {} ({}): ", self.id, self.target)?;
+        match self.event {
+            WatchEvent::Read { addr, size, value } => {
+                write!(f, "read 0x{addr:08x} = {}", hex(value, size))?
+            }
+            WatchEvent::Write {
+                addr,
+                size,
+                old,
+                new,
+            } => {
+                let old = old.map_or_else(|| "?".to_string(), |v| hex(v, size));
+                write!(f, "write 0x{addr:08x}: {old} -> {}", hex(new, size))?
+            }
+            WatchEvent::Changed { old, new } => write!(f, "0x{old:08x} -> 0x{new:08x}")?,
+        }
+        write!(f, " by pc 0x{:08x}", self.pc)
+    }
+}